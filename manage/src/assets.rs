@@ -0,0 +1,246 @@
+//! Handling of the local assets referenced by chapters.
+//!
+//! Images, downloadable snippets... referenced from a chapter are copied to an `assets`
+//! directory under content-hashed names, and references are rewritten accordingly.
+
+use std::{collections::BTreeMap, ops::Range};
+
+prelude!();
+
+/// Name of the asset directory, relative to the output directory.
+pub const ASSETS_DIR: &str = "assets";
+
+/// FNV-1a 64-bit hash, stable across platforms and compiler versions.
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// True if a reference is a local asset.
+///
+/// Excludes URLs, fragments, and references without an extension or with a `md`/`html` extension,
+/// which are pages.
+pub fn is_local_asset(reference: &str) -> bool {
+    if reference.is_empty()
+        || reference.contains("://")
+        || reference.starts_with('#')
+        || reference.starts_with("mailto:")
+    {
+        return false;
+    }
+    let path = Path::new(strip_suffix(reference));
+    match path.extension() {
+        Some(ext) => ext != "md" && ext != "html",
+        None => false,
+    }
+}
+
+/// Removes the fragment and query parts of a reference.
+fn strip_suffix(reference: &str) -> &str {
+    match reference.find(['#', '?']) {
+        Some(idx) => &reference[..idx],
+        None => reference,
+    }
+}
+
+/// Byte ranges of the references in a markdown line.
+///
+/// Looks for inline links and images `[...](<ref>)`, reference definitions `[...]: <ref>` and
+/// HTML `src="<ref>"` and `href="<ref>"` attributes, ignoring code spans.
+pub fn references(line: &str) -> Vec<Range<usize>> {
+    // Code spans, we ignore references inside.
+    let mut code_spans = vec![];
+    let mut ticks = line.match_indices('`').map(|(idx, _)| idx);
+    while let (Some(start), Some(end)) = (ticks.next(), ticks.next()) {
+        code_spans.push(start..end)
+    }
+    let in_code = |idx: usize| code_spans.iter().any(|span| span.contains(&idx));
+
+    let mut res = vec![];
+
+    // Reference definition.
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('[') && !trimmed.starts_with("[^") {
+        if let Some(end) = trimmed.find("]: ") {
+            let offset = line.len() - trimmed.len() + end + "]: ".len();
+            let tail = &line[offset..];
+            let start = offset + (tail.len() - tail.trim_start().len());
+            let len = line[start..]
+                .find(char::is_whitespace)
+                .unwrap_or(line.len() - start);
+            if len > 0 {
+                res.push(start..start + len)
+            }
+        }
+    }
+
+    // Inline links and images.
+    for (idx, _) in line.match_indices("](") {
+        if in_code(idx) {
+            continue;
+        }
+        let start = idx + "](".len();
+        let len = line[start..]
+            .find(|c: char| c == ')' || c.is_whitespace())
+            .unwrap_or(line.len() - start);
+        if len > 0 {
+            res.push(start..start + len)
+        }
+    }
+
+    // HTML attributes.
+    for pat in &["src=\"", "href=\""] {
+        for (idx, _) in line.match_indices(pat) {
+            if in_code(idx) {
+                continue;
+            }
+            let start = idx + pat.len();
+            if let Some(len) = line[start..].find('"') {
+                res.push(start..start + len)
+            }
+        }
+    }
+
+    res.sort_by_key(|range| range.start);
+    res
+}
+
+/// Asset handler, copies assets to the asset directory of a target directory.
+pub struct Assets {
+    /// Asset directory.
+    dir: PathBuf,
+    /// Maps source paths to the name of their copy.
+    copied: BTreeMap<PathBuf, String>,
+}
+impl Assets {
+    /// Constructor, `target` is the output directory.
+    pub fn new(target: impl AsRef<Path>) -> Self {
+        Self {
+            dir: target.as_ref().join(ASSETS_DIR),
+            copied: BTreeMap::new(),
+        }
+    }
+
+    /// Copies an asset, returns the name of the copy.
+    pub fn copy(&mut self, path: impl AsRef<Path>) -> Res<String> {
+        let path = path.as_ref();
+        if let Some(name) = self.copied.get(path) {
+            return Ok(name.clone());
+        }
+        if !path.is_file() {
+            bail!("asset `{}` does not exist", path.display())
+        }
+
+        let content =
+            fs::read(path).chain_err(|| format!("while reading asset `{}`", path.display()))?;
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{:016x}.{}", stem, hash(&content), ext.to_string_lossy()),
+            None => format!("{}-{:016x}", stem, hash(&content)),
+        };
+
+        fs::create_dir_all(&self.dir).chain_err(|| {
+            format!(
+                "during (recursive) folder creation for `{}`",
+                self.dir.display()
+            )
+        })?;
+        let tgt = self.dir.join(&name);
+        // Names are content-hashed, an existing copy is up to date.
+        if !tgt.is_file() {
+            log::trace!("copying asset `{}` to `{}`", path.display(), tgt.display());
+            fs::write(&tgt, &content)
+                .chain_err(|| format!("while copying asset `{}`", path.display()))?;
+        }
+
+        self.copied.insert(path.to_path_buf(), name.clone());
+        Ok(name)
+    }
+
+    /// Copies the assets referenced in a markdown line and rewrites the references.
+    ///
+    /// - `md_dir`: directory of the markdown file the line is from.
+    pub fn rewrite_line(&mut self, md_dir: impl AsRef<Path>, line: &str) -> Res<String> {
+        let md_dir = md_dir.as_ref();
+        let mut res = line.to_string();
+        // Back to front so that ranges stay valid.
+        for range in references(line).into_iter().rev() {
+            let reference = &line[range.clone()];
+            if !is_local_asset(reference) {
+                continue;
+            }
+            let path = strip_suffix(reference);
+            let suffix = &reference[path.len()..];
+            let name = self.copy(md_dir.join(path))?;
+            res.replace_range(range, &format!("{}/{}{}", ASSETS_DIR, name, suffix));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_references() {
+        let line = "![pic](rsc/pic.png \"title\") and `[not](this.png)`, <img src=\"a.svg\"/>";
+        let refs: Vec<&str> = references(line)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect();
+        assert_eq!(refs, vec!["rsc/pic.png", "a.svg"]);
+
+        let line = "[bmc short]: ../induction (Induction chapter)";
+        let refs: Vec<&str> = references(line)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect();
+        assert_eq!(refs, vec!["../induction"]);
+
+        assert!(is_local_asset("rsc/pic.png"));
+        assert!(is_local_asset("code/sw_1.mkn#anchor"));
+        assert!(!is_local_asset("../induction"));
+        assert!(!is_local_asset("../bmc/readme.md#version-1"));
+        assert!(!is_local_asset("https://ocamlpro.com/logo.png"));
+        assert!(!is_local_asset("#version-1"));
+    }
+
+    #[test]
+    fn rewrite() {
+        let target = std::env::temp_dir().join(format!("manage_api_assets_{}", std::process::id()));
+        // Fixture, a chapter directory with a picture and a system.
+        let chapter = target.join("chapter");
+        fs::create_dir_all(&chapter).unwrap();
+        fs::write(chapter.join("pic.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        fs::write(chapter.join("sys.mkn"), "svars { x: int }\n").unwrap();
+
+        let mut assets = Assets::new(&target);
+        let line = "See ![picture](pic.png) and [the system](sys.mkn#sys).";
+        let rewritten = assets.rewrite_line(&chapter, line).unwrap();
+
+        let png = assets.copy(chapter.join("pic.png")).unwrap();
+        let mkn = assets.copy(chapter.join("sys.mkn")).unwrap();
+        assert!(png.starts_with("pic-") && png.ends_with(".png"));
+        assert_eq!(
+            rewritten,
+            format!(
+                "See ![picture](assets/{}) and [the system](assets/{}#sys).",
+                png, mkn
+            )
+        );
+        assert!(target.join(ASSETS_DIR).join(&png).is_file());
+
+        assert!(assets
+            .rewrite_line(&chapter, "![missing](nope.png)")
+            .is_err());
+        fs::remove_dir_all(&target).unwrap();
+    }
+}
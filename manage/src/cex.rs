@@ -0,0 +1,483 @@
+//! Parser for mikino's BMC and induction outputs.
+//!
+//! Turns the text mikino prints into verdicts per candidate, the overall conclusion, and the
+//! counterexample traces, so that outputs can be compared semantically and exported as JSON.
+//!
+//! Counterexamples look like
+//!
+//! ```text
+//! found a falsification at depth 0:
+//! - `cnt ≤ 2` = (<= cnt 2)
+//!   |=| Step 0
+//!   |         cnt = 3
+//!   | is_counting = false
+//!   |=|
+//! ```
+//!
+//! or, for the step case of induction, with `Step k` and `Step k + 1`. Verdicts come from the
+//! `|===| Bmc result` and `|===| Induction attempt result` summaries.
+
+use std::collections::BTreeMap;
+
+use crate::trace::{State, Trace, Value};
+
+prelude!();
+
+/// Kind of analysis an output comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Bmc,
+    Induction,
+}
+impl Kind {
+    /// Name used in JSON.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bmc => "bmc",
+            Self::Induction => "induction",
+        }
+    }
+}
+
+/// Verdict for a candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// BMC found a falsification.
+    Falsified,
+    /// BMC could not find a falsification.
+    Unfalsified,
+    /// Does not hold in the initial state(s).
+    BaseFalsified,
+    /// Holds in the initial state(s) but is not inductive.
+    NotInductive,
+    /// Holds in the initial state(s) and is inductive.
+    Proved,
+}
+impl Verdict {
+    /// Name used in JSON.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Falsified => "falsified",
+            Self::Unfalsified => "unfalsified",
+            Self::BaseFalsified => "base_falsified",
+            Self::NotInductive => "not_inductive",
+            Self::Proved => "proved",
+        }
+    }
+
+    /// Inverse of [`Self::name`].
+    pub fn of_name(name: &str) -> Option<Self> {
+        [
+            Self::Falsified,
+            Self::Unfalsified,
+            Self::BaseFalsified,
+            Self::NotInductive,
+            Self::Proved,
+        ]
+        .iter()
+        .copied()
+        .find(|verdict| verdict.name() == name)
+    }
+
+    /// Verdict corresponding to a summary header, if any.
+    fn of_header(header: &str) -> Option<Self> {
+        if header.starts_with("could not find falsifications") {
+            Some(Self::Unfalsified)
+        } else if header.starts_with("found a falsification") {
+            Some(Self::Falsified)
+        } else if header.contains("are not inductive") {
+            Some(Self::NotInductive)
+        } else if header.contains("and are inductive") {
+            Some(Self::Proved)
+        } else if header.contains("initial state") && header.contains("not") {
+            Some(Self::BaseFalsified)
+        } else {
+            None
+        }
+    }
+}
+
+/// Overall conclusion of an analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Safety {
+    /// All reachable states verify the candidates.
+    Safe,
+    /// Some candidates are falsified.
+    Unsafe,
+    /// Some candidates are not inductive.
+    Unknown,
+}
+impl Safety {
+    /// Name used in JSON.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Safe => "safe",
+            Self::Unsafe => "unsafe",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// A counterexample for a candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cex {
+    /// Candidate falsified.
+    pub candidate: String,
+    /// Candidate's definition, as printed by mikino.
+    pub term: String,
+    /// Falsification depth, `None` for step counterexamples (`Step k`, `Step k + 1`).
+    pub depth: Option<usize>,
+    pub trace: Trace,
+}
+impl Cex {
+    /// JSON version.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "candidate": self.candidate,
+            "term": self.term,
+            "depth": self.depth,
+            "step": self.depth.is_none(),
+            "trace": self.trace.to_json(),
+        })
+    }
+}
+
+/// Result of one analysis: its counterexamples and its summary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Kind of analysis, `None` if the counterexamples are not followed by a summary.
+    pub kind: Option<Kind>,
+    /// Verdict of each candidate listed in the summary.
+    pub verdicts: BTreeMap<String, Verdict>,
+    pub safety: Option<Safety>,
+    /// Counterexamples, in order of appearance.
+    pub cexs: Vec<Cex>,
+}
+impl Analysis {
+    /// Empty analysis.
+    fn new(cexs: Vec<Cex>) -> Self {
+        Self {
+            kind: None,
+            verdicts: BTreeMap::new(),
+            safety: None,
+            cexs,
+        }
+    }
+
+    /// Verdict of a candidate.
+    ///
+    /// When induction proves all the candidates, mikino does not list them: any candidate not in
+    /// the summary of a safe induction analysis is proved.
+    pub fn verdict(&self, candidate: &str) -> Option<Verdict> {
+        match self.verdicts.get(candidate) {
+            Some(verdict) => Some(*verdict),
+            None if self.kind == Some(Kind::Induction) && self.safety == Some(Safety::Safe) => {
+                Some(Verdict::Proved)
+            }
+            None => None,
+        }
+    }
+
+    /// Counterexample for a candidate, the last one if there are several.
+    pub fn cex_of(&self, candidate: &str) -> Option<&Cex> {
+        self.cexs
+            .iter()
+            .rev()
+            .find(|cex| cex.candidate == candidate)
+    }
+
+    /// True if two analyses have the same kind, verdicts, conclusion and counterexamples.
+    ///
+    /// Counterexamples are compared regardless of their order.
+    pub fn same_as(&self, other: &Self) -> bool {
+        fn sorted(cexs: &[Cex]) -> Vec<(&str, Option<usize>, &str, String)> {
+            let mut cexs: Vec<_> = cexs
+                .iter()
+                .map(|cex| {
+                    (
+                        cex.candidate.as_str(),
+                        cex.depth,
+                        cex.term.as_str(),
+                        cex.trace.to_string(),
+                    )
+                })
+                .collect();
+            cexs.sort();
+            cexs
+        }
+        self.kind == other.kind
+            && self.verdicts == other.verdicts
+            && self.safety == other.safety
+            && sorted(&self.cexs) == sorted(&other.cexs)
+    }
+
+    /// JSON version.
+    pub fn to_json(&self) -> serde_json::Value {
+        let verdicts: serde_json::Map<String, serde_json::Value> = self
+            .verdicts
+            .iter()
+            .map(|(candidate, verdict)| (candidate.clone(), verdict.name().into()))
+            .collect();
+        serde_json::json!({
+            "kind": self.kind.map(Kind::name),
+            "verdicts": verdicts,
+            "safety": self.safety.map(Safety::name),
+            "cexs": self.cexs.iter().map(Cex::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Extracts the verdicts and the conclusion of a summary block.
+    fn summary(&mut self, block: &[String]) {
+        let mut verdict = None;
+        for line in block {
+            if let Some(header) = line.strip_prefix(" - ") {
+                verdict = Verdict::of_header(header);
+                if header.starts_with("system is safe") {
+                    self.safety = Some(Safety::Safe)
+                } else if header.starts_with("system is unsafe") {
+                    self.safety = Some(Safety::Unsafe)
+                } else if header.starts_with("system might be unsafe") {
+                    self.safety = Some(Safety::Unknown)
+                }
+            } else if let Some(candidate) = line
+                .trim()
+                .strip_prefix('`')
+                .and_then(|rest| rest.strip_suffix('`'))
+            {
+                if let Some(verdict) = verdict {
+                    self.verdicts.insert(candidate.into(), verdict);
+                }
+            }
+        }
+    }
+}
+
+/// A structured mikino output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Output {
+    /// Analyses, in order of appearance: `mikino check --bmc` runs induction, then BMC.
+    pub analyses: Vec<Analysis>,
+    /// Error blocks, one string per block.
+    pub errors: Vec<String>,
+}
+impl Output {
+    /// True if the output contains nothing mikino-specific.
+    pub fn is_empty(&self) -> bool {
+        self.analyses.is_empty() && self.errors.is_empty()
+    }
+
+    /// Analysis of some kind, the last one if there are several.
+    pub fn analysis(&self, kind: Kind) -> Option<&Analysis> {
+        self.analyses
+            .iter()
+            .rev()
+            .find(|analysis| analysis.kind == Some(kind))
+    }
+
+    /// True if two outputs have the same analyses and errors, see [`Analysis::same_as`].
+    pub fn same_as(&self, other: &Self) -> bool {
+        self.errors == other.errors
+            && self.analyses.len() == other.analyses.len()
+            && self
+                .analyses
+                .iter()
+                .zip(&other.analyses)
+                .all(|(lft, rgt)| lft.same_as(rgt))
+    }
+
+    /// JSON version.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "analyses": self.analyses.iter().map(Analysis::to_json).collect::<Vec<_>>(),
+            "errors": self.errors,
+        })
+    }
+}
+
+/// Parses a value in a trace, as printed by mikino: `true`, `7`, `(- 1)`, `(/ 1 2)`...
+fn value(text: &str) -> Option<Value> {
+    Value::parse(text).or_else(|| match crate::smt2::parse(text) {
+        (sexps, errors) if errors.is_empty() => match sexps.as_slice() {
+            [sexp] => Value::of_sexp(sexp),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Parses the output of mikino.
+///
+/// Lines that are not part of a counterexample, a summary or an error block are ignored.
+pub fn parse(text: &str) -> Res<Output> {
+    let mut res = Output::default();
+    let mut lines = text.lines().enumerate().peekable();
+    // Counterexamples not attached to a summary yet.
+    let mut cexs = vec![];
+    // Depth of the counterexamples that follow, `None` for step counterexamples.
+    let mut depth = None;
+
+    while let Some((idx, line)) = lines.next() {
+        let err = || format!("line {}", idx + 1);
+        if let Some(rest) = line.strip_prefix("found a falsification at depth ") {
+            let rest = rest.trim_end_matches(':');
+            depth = Some(
+                rest.parse()
+                    .chain_err(|| format!("illegal depth `{}`", rest))
+                    .chain_err(err)?,
+            );
+        } else if line.starts_with("failed:") {
+            depth = if line.contains("not inductive") {
+                None
+            } else {
+                Some(0)
+            };
+        } else if let Some(rest) = line.strip_prefix("- `") {
+            let (candidate, term) = match rest.split_once("` = ") {
+                Some(pair) => pair,
+                None => bail!("{}: illegal counterexample header `{}`", err(), line),
+            };
+            let mut trace = vec![];
+            while let Some((idx, line)) = lines.next_if(|(_, line)| line.starts_with("  |")) {
+                let line = line["  |".len()..].trim_end();
+                if let Some(step) = line.strip_prefix("=| ") {
+                    if step.starts_with("Step") {
+                        trace.push(State::new())
+                    } else {
+                        // Unexpected values section, ignored.
+                        while lines
+                            .next_if(|(_, line)| line.starts_with("  | "))
+                            .is_some()
+                        {}
+                    }
+                } else if line == "=|" {
+                    break;
+                } else {
+                    let (var, val) = match line.split_once(" = ") {
+                        Some(pair) => pair,
+                        None => bail!("line {}: illegal trace line `{}`", idx + 1, line),
+                    };
+                    let val = value(val)
+                        .ok_or_else(|| format!("line {}: illegal value `{}`", idx + 1, val))?;
+                    match trace.last_mut() {
+                        Some(state) => {
+                            state.insert(var.trim().to_string(), val);
+                        }
+                        None => bail!("line {}: value outside of a step", idx + 1),
+                    }
+                }
+            }
+            cexs.push(Cex {
+                candidate: candidate.into(),
+                term: term.into(),
+                depth,
+                trace: Trace::new(trace),
+            });
+        } else if let Some(title) = line.strip_prefix("|===| ") {
+            let mut block = vec![];
+            while let Some((_, line)) = lines.next_if(|(_, line)| *line != "|===|") {
+                block.push(line.strip_prefix('|').unwrap_or(line).to_string())
+            }
+            lines.next();
+            let kind = match title {
+                "Bmc result" => Kind::Bmc,
+                "Induction attempt result" => Kind::Induction,
+                "Error" => {
+                    res.errors.push(block.join("\n"));
+                    continue;
+                }
+                _ => continue,
+            };
+            let mut analysis = Analysis::new(std::mem::take(&mut cexs));
+            analysis.kind = Some(kind);
+            analysis.summary(&block);
+            res.analyses.push(analysis);
+        }
+    }
+    if !cexs.is_empty() {
+        res.analyses.push(Analysis::new(cexs))
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn induction() {
+        let out = parse(&load_file("../src/mikino_induction/code/sw_2.mkn.out").unwrap()).unwrap();
+        assert_eq!(out.analyses.len(), 2);
+        assert_eq!(out.analyses[1].kind, Some(Kind::Bmc));
+        let out = &out.analyses[0];
+        assert_eq!(out.kind, Some(Kind::Induction));
+        assert_eq!(out.safety, Some(Safety::Unknown));
+        assert_eq!(out.verdicts.len(), 3);
+        assert_eq!(out.verdicts["cnt is positive"], Verdict::Proved);
+        assert_eq!(out.verdicts["cnt ≤ 4"], Verdict::NotInductive);
+        let cex = out.cex_of("cnt ≤ 2").unwrap();
+        assert_eq!(cex.depth, None);
+        assert_eq!(cex.term, "(<= cnt 2)");
+        assert_eq!(cex.trace.states.len(), 2);
+        assert_eq!(cex.trace.states[1]["cnt"], Value::Int(3));
+        assert_eq!(cex.trace.states[1]["start_stop"], Value::Bool(true));
+    }
+
+    #[test]
+    fn bmc() {
+        let output =
+            parse(&load_file("../src/strength/code/split_cex_0.mkn.out").unwrap()).unwrap();
+        let out = output.analysis(Kind::Bmc).unwrap();
+        assert_eq!(out.safety, Some(Safety::Unsafe));
+        assert_eq!(out.verdicts["candidate"], Verdict::Falsified);
+        let cex = out.cex_of("candidate").unwrap();
+        assert_eq!(cex.depth, Some(2));
+        assert_eq!(cex.trace.depth(), Some(2));
+        assert_eq!(cex.trace.states[2]["i"], Value::Int(8));
+        assert!(!cex.trace.states[2].contains_key("div0 ((x!0 int) (x!1 int)) int"));
+
+        let json = output.to_json();
+        assert_eq!(json["analyses"][0]["verdicts"]["candidate"], "falsified");
+        assert_eq!(json["analyses"][0]["cexs"][0]["trace"][2]["done"], true);
+    }
+
+    #[test]
+    fn semantic_comparison() {
+        let text = load_file("../src/mikino_bmc/code/sw_1.mkn.out").unwrap();
+        let out = parse(&text).unwrap();
+        assert_eq!(out.analyses[0].cexs.len(), 2);
+        // Reordering counterexamples and realigning traces does not change anything.
+        let found = "found a falsification at depth";
+        let (fst, snd) = (text.find(found).unwrap(), text.rfind(found).unwrap());
+        let end = text.find("checking for falsifications at depth 1").unwrap();
+        let swapped = format!(
+            "{}{}{}{}",
+            &text[..fst],
+            &text[snd..end],
+            &text[fst..snd],
+            &text[end..]
+        )
+        .replace("|         cnt", "| cnt");
+        assert!(parse(&swapped).unwrap().same_as(&out));
+        let changed = text.replace("cnt = 5", "cnt = 6");
+        assert!(!parse(&changed).unwrap().same_as(&out));
+
+        let out = parse(&load_file("../src/strength/code/split_1.mkn.out").unwrap()).unwrap();
+        assert_eq!(out.analyses, vec![]);
+        assert_eq!(out.errors.len(), 1);
+    }
+
+    #[test]
+    fn book_outputs() {
+        for snippet in test::snippets("../src", "out").unwrap() {
+            if snippet.to_string_lossy().ends_with(".mkn.out") {
+                // Skip snippets run by the manager's own engine.
+                let source = load_file(snippet.with_extension("")).unwrap();
+                if !source.starts_with("// CMD: mikino") {
+                    continue;
+                }
+                let out = parse(&load_file(&snippet).unwrap()).unwrap();
+                assert!(!out.is_empty(), "{}", snippet.display());
+            }
+        }
+    }
+}
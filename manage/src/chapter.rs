@@ -0,0 +1,239 @@
+//! Chapters of the book, as listed in `SUMMARY.md`.
+//!
+//! Tests are grouped and ordered by chapter. A chapter owns the directory of its markdown files:
+//! `src/bmc` for `./bmc/readme.md`, and the directory of the introduction, `src`, owns the files
+//! no other chapter does. Summary entries in the same directory, such as `smt/readme.md` and
+//! `smt/mikino.md`, form a single chapter named after the first one.
+//!
+//! Chapters can be selected by directory name (`bmc`) or by title (`Induction`, case
+//! insensitive), see [`crate::Conf::set_chapters`].
+
+use std::{fmt, time::Duration};
+
+prelude!();
+
+/// Summary file, relative to the book's `src` directory.
+pub const SUMMARY: &str = "SUMMARY.md";
+
+/// A chapter: the titles of its summary entries and its directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// Titles of the summary entries in the chapter's directory, in summary order.
+    pub titles: Vec<String>,
+    /// Directory, relative to the book's `src` directory.
+    pub dir: PathBuf,
+}
+impl Chapter {
+    /// Title of the chapter's first entry.
+    pub fn title(&self) -> &str {
+        &self.titles[0]
+    }
+
+    /// True if `name` is the name of the chapter's directory or one of its titles.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.dir.file_name().is_some_and(|dir| dir == name)
+            || self
+                .titles
+                .iter()
+                .any(|title| title.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Parses the chapters of a summary, in order.
+pub fn parse(summary: &str) -> Res<Vec<Chapter>> {
+    let mut res: Vec<Chapter> = vec![];
+    for (idx, line) in summary.lines().enumerate() {
+        let line = line.trim();
+        if !line.starts_with("- [") {
+            continue;
+        }
+        let err = || {
+            format!(
+                "line {} is illegal, expected `- [<TITLE>](<PATH>)`",
+                idx + 1
+            )
+        };
+        let (title, tail) = line[3..].split_once("](").ok_or_else(err)?;
+        let path = tail.strip_suffix(')').ok_or_else(err)?;
+        let dir = Path::new(path.trim_start_matches("./"))
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        match res.iter_mut().find(|chapter| chapter.dir == dir) {
+            Some(chapter) => chapter.titles.push(title.into()),
+            None => res.push(Chapter {
+                titles: vec![title.into()],
+                dir,
+            }),
+        }
+    }
+    Ok(res)
+}
+
+/// The chapters of a book, and the ones a configuration selects.
+#[derive(Debug, Clone)]
+pub struct Chapters {
+    src: PathBuf,
+    all: Vec<Chapter>,
+    selected: Vec<bool>,
+}
+impl Chapters {
+    /// Loads the summary of the book in `src`, fails if a chapter `conf` selects does not exist.
+    pub fn load(conf: &Conf, src: impl AsRef<Path>) -> Res<Self> {
+        let src = src.as_ref();
+        let summary = src.join(SUMMARY);
+        let all =
+            parse(&load_file(&summary)?).chain_err(|| format!("in `{}`", summary.display()))?;
+        let mut selected = vec![conf.chapters.is_empty(); all.len()];
+        for name in &conf.chapters {
+            let mut found = false;
+            for (chapter, selected) in all.iter().zip(selected.iter_mut()) {
+                if chapter.is_named(name) {
+                    *selected = true;
+                    found = true
+                }
+            }
+            if !found {
+                let names: Vec<String> = all
+                    .iter()
+                    .map(|chapter| match chapter.dir.to_str() {
+                        Some("") => format!("`{}`", chapter.title()),
+                        _ => format!("`{}` ({})", chapter.title(), chapter.dir.display()),
+                    })
+                    .collect();
+                bail!(
+                    "unknown chapter `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            }
+        }
+        Ok(Self {
+            src: src.into(),
+            all,
+            selected,
+        })
+    }
+
+    /// All chapters, in summary order.
+    pub fn all(&self) -> &[Chapter] {
+        &self.all
+    }
+    /// Selected chapters, in summary order.
+    pub fn selected(&self) -> impl Iterator<Item = &Chapter> {
+        self.all
+            .iter()
+            .zip(&self.selected)
+            .filter(|(_, selected)| **selected)
+            .map(|(chapter, _)| chapter)
+    }
+
+    /// Index of the chapter owning a path, the one with the deepest directory containing it.
+    ///
+    /// `path` is either relative to the book's `src` directory or starts with it.
+    pub fn index_of(&self, path: impl AsRef<Path>) -> Option<usize> {
+        let path = path.as_ref();
+        let path = path.strip_prefix(&self.src).unwrap_or(path);
+        self.all
+            .iter()
+            .enumerate()
+            .filter(|(_, chapter)| path.starts_with(&chapter.dir))
+            .max_by_key(|(_, chapter)| chapter.dir.components().count())
+            .map(|(idx, _)| idx)
+    }
+    /// True if the chapter owning a path is selected.
+    pub fn is_selected(&self, path: impl AsRef<Path>) -> bool {
+        self.index_of(path).is_some_and(|idx| self.selected[idx])
+    }
+}
+
+/// Results of the snippets of a chapter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Results {
+    /// Title of the chapter.
+    pub title: String,
+    /// Snippets with an output file.
+    pub snippets: usize,
+    pub ok: usize,
+    pub failed: usize,
+    /// Snippets not checked because their tool is deactivated.
+    pub skipped: usize,
+    pub time: Duration,
+}
+impl Results {
+    /// Empty results for a chapter.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+}
+impl fmt::Display for Results {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{}: {} snippet(s), {} ok, {} failed, {} skipped, {:.2}s",
+            self.title,
+            self.snippets,
+            self.ok,
+            self.failed,
+            self.skipped,
+            self.time.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let chapters = parse(&load_file("../src/SUMMARY.md").unwrap()).unwrap();
+        let dirs: Vec<String> = chapters
+            .iter()
+            .map(|chapter| chapter.dir.display().to_string())
+            .collect();
+        assert_eq!(
+            dirs,
+            vec![
+                "",
+                "preface",
+                "smt",
+                "trans",
+                "trans_smt",
+                "bmc",
+                "mikino_bmc",
+                "induction",
+                "mikino_induction",
+                "strength",
+                "conclusion",
+                "mikino_install",
+            ]
+        );
+        assert_eq!(
+            chapters[2].titles,
+            vec!["SMT Solvers", "SMT Scripts: Mikino"]
+        );
+        assert!(chapters[2].is_named("smt scripts: mikino"));
+        assert!(chapters[7].is_named("Induction"));
+        assert!(!chapters[8].is_named("Induction"));
+    }
+
+    #[test]
+    fn selection() {
+        let conf = Conf::default().set_chapters(vec!["bmc".into(), "Induction".into()]);
+        let chapters = Chapters::load(&conf, "../src").unwrap();
+        let selected: Vec<&str> = chapters.selected().map(Chapter::title).collect();
+        assert_eq!(selected, vec!["Unrolling and BMC", "Induction"]);
+        assert!(chapters.is_selected("../src/bmc/code"));
+        assert!(!chapters.is_selected("../src/mikino_bmc/code"));
+        assert!(!chapters.is_selected("../src/strength/exercises/1"));
+        assert_eq!(chapters.index_of("other/code"), Some(0));
+
+        let conf = Conf::default().set_chapters(vec!["nope".into()]);
+        let err = Chapters::load(&conf, "../src").unwrap_err();
+        assert!(err.to_string().starts_with("unknown chapter `nope`"));
+    }
+}
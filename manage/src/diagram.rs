@@ -0,0 +1,426 @@
+//! State-machine diagrams of `.mkn` systems, as Graphviz `dot`.
+//!
+//! The diagram is the reachable part of an abstraction of the system. Abstract states are
+//! valuations of some *atoms*: boolean state variables (all of them by default) and predicates over
+//! the state variables, such as `cnt = 0`. Z3 enumerates the abstract initial states, *i.e.* the
+//! valuations of the atoms compatible with `init`, and the abstract successors of each abstract
+//! state `A`, *i.e.* the valuations of the atoms in `s'` compatible with `A(s) ∧ trans(s, s')`.
+//!
+//! This is an over-approximation: an edge means *some* concrete state of the source can reach
+//! *some* concrete state of the target, not all of them.
+
+use crate::{
+    engine::{Solver, Sort},
+    mkn::{parse_expr, Expr, Mkn},
+    trace::Value,
+};
+
+prelude!();
+
+/// Generates the state-machine diagram of a system.
+pub struct Diagram<'s> {
+    source: &'s str,
+    bools: Option<Vec<&'s str>>,
+    preds: Vec<&'s str>,
+    output: Option<&'s str>,
+    check: bool,
+    z3_cmd: &'s str,
+}
+impl<'s> Diagram<'s> {
+    /// Constructor, the abstraction is all the boolean state variables.
+    pub fn new(source: &'s str) -> Self {
+        Self {
+            source,
+            bools: None,
+            preds: vec![],
+            output: None,
+            check: false,
+            z3_cmd: "z3",
+        }
+    }
+
+    /// Sets the boolean state variables of the abstraction, all of them if `None`.
+    pub fn set_bools(mut self, bools: Option<Vec<&'s str>>) -> Self {
+        self.bools = bools;
+        self
+    }
+    /// Sets the predicates of the abstraction, in mikino syntax.
+    pub fn set_preds(mut self, preds: Vec<&'s str>) -> Self {
+        self.preds = preds;
+        self
+    }
+    /// Sets the output `dot` file, stdout if none.
+    pub fn set_output(mut self, output: Option<&'s str>) -> Self {
+        self.output = output;
+        self
+    }
+    /// Only checks the output file is up to date.
+    pub fn set_check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+    /// Sets the Z3 command.
+    pub fn set_z3_cmd(mut self, z3_cmd: &'s str) -> Self {
+        self.z3_cmd = z3_cmd;
+        self
+    }
+
+    /// System source.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// Diagram of the system, as `dot`.
+    pub fn dot(&self) -> Res<String> {
+        let mkn = Mkn::load(self.source)?;
+        let atoms = atoms(&mkn, self.bools.as_deref(), &self.preds)?;
+        let mut solver = Solver::new(self.z3_cmd)?;
+        let graph = explore(&mut solver, &mkn, &atoms)
+            .chain_err(|| format!("while exploring `{}`", self.source))?;
+        let name = Path::new(self.source)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "system".into());
+        Ok(graph.to_dot(&name, &atoms))
+    }
+
+    /// Generates the diagram, or checks it is up to date.
+    pub fn run(&self) -> Res<()> {
+        let dot = self.dot()?;
+        let output = match (self.output, self.check) {
+            (Some(output), _) => output,
+            (None, false) => {
+                print!("{}", dot);
+                return Ok(());
+            }
+            (None, true) => bail!("checking a diagram requires an output file"),
+        };
+
+        if self.check {
+            let current = if Path::new(output).exists() {
+                Some(load_file(output)?)
+            } else {
+                None
+            };
+            if current.as_ref() != Some(&dot) {
+                bail!(
+                    "`{}` is out of date with `{}`, regenerate it",
+                    output,
+                    self.source
+                )
+            }
+            log::info!("`{}` is up to date", output);
+            return Ok(());
+        }
+
+        {
+            let mut file = open_write(output)?;
+            io::Write::write_all(&mut file, dot.as_bytes())
+                .chain_err(|| format!("while writing `{}`", output))?;
+        }
+        svg(Path::new(output))
+    }
+}
+
+/// Renders a `dot` file as SVG next to it, if Graphviz is installed.
+fn svg(dot: &Path) -> Res<()> {
+    let svg = dot.with_extension("svg");
+    let status = match std::process::Command::new("dot")
+        .arg("-Tsvg")
+        .arg(dot)
+        .arg("-o")
+        .arg(&svg)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            log::warn!("`dot` is not available, skipping SVG generation");
+            return Ok(());
+        }
+        Err(e) => return Err(e).chain_err(|| "while running `dot`"),
+    };
+    if !status.success() {
+        bail!("`dot` failed on `{}`", dot.display())
+    }
+    log::info!("generated `{}`", svg.display());
+    Ok(())
+}
+
+/// Atoms of an abstraction: some boolean state variables (all if `None`) and some predicates.
+pub fn atoms(mkn: &Mkn, bools: Option<&[&str]>, preds: &[&str]) -> Res<Vec<Expr>> {
+    let mut atoms = vec![];
+    let is_bool = |var: &str| {
+        mkn.vars
+            .iter()
+            .any(|(name, sort)| name == var && *sort == Sort::Bool)
+    };
+    let all_bools: Vec<&str>;
+    let bools = match bools {
+        Some(bools) => bools,
+        None => {
+            all_bools = mkn
+                .vars
+                .iter()
+                .filter(|(_, sort)| *sort == Sort::Bool)
+                .map(|(var, _)| var.as_str())
+                .collect();
+            &all_bools
+        }
+    };
+    for var in bools {
+        if !is_bool(var) {
+            bail!("`{}` is not a boolean state variable", var)
+        }
+        atoms.push(Expr::Var {
+            name: var.to_string(),
+            next: false,
+        })
+    }
+    for pred in preds {
+        let expr = parse_expr(mkn, pred).chain_err(|| format!("in predicate `{}`", pred))?;
+        if expr.sort(&mkn.vars) != Sort::Bool {
+            bail!("predicate `{}` is not boolean", pred)
+        }
+        atoms.push(expr)
+    }
+    if atoms.is_empty() {
+        bail!("the abstraction has no boolean state variable nor predicate")
+    }
+    Ok(atoms)
+}
+
+/// Reachable abstract state space.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    /// Abstract states, the value of each atom.
+    pub states: Vec<Vec<bool>>,
+    /// Indices of the initial abstract states.
+    pub init: Vec<usize>,
+    /// Transitions between abstract states.
+    pub edges: Vec<(usize, usize)>,
+}
+impl Graph {
+    /// Graphviz version.
+    pub fn to_dot(&self, name: &str, atoms: &[Expr]) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut res = format!("digraph \"{}\" {{\n", escape(name));
+        res.push_str("    node [shape = box, style = rounded, fontname = \"monospace\"];\n");
+        res.push_str("    init [shape = point];\n");
+        for (idx, state) in self.states.iter().enumerate() {
+            let label: Vec<String> = atoms
+                .iter()
+                .zip(state)
+                .map(|(atom, value)| {
+                    if *value {
+                        escape(&atom.to_string())
+                    } else {
+                        escape(&Expr::Not(Box::new(atom.clone())).to_string())
+                    }
+                })
+                .collect();
+            res.push_str(&format!(
+                "    s{} [label = \"{}\"];\n",
+                idx,
+                label.join("\\n")
+            ));
+        }
+        for idx in &self.init {
+            res.push_str(&format!("    init -> s{};\n", idx));
+        }
+        for (src, tgt) in &self.edges {
+            res.push_str(&format!("    s{} -> s{};\n", src, tgt));
+        }
+        res.push_str("}\n");
+        res
+    }
+}
+
+/// Explores the reachable abstract state space of a system, breadth-first.
+///
+/// Abstract states are numbered in the order they are discovered, successors in the order of their
+/// valuations, so that the result does not depend on the models Z3 produces.
+pub fn explore(solver: &mut Solver, mkn: &Mkn, atoms: &[Expr]) -> Res<Graph> {
+    let system = mkn.to_system();
+    let params: Vec<String> = mkn
+        .vars
+        .iter()
+        .map(|(var, sort)| format!("(|s.{}| {})", var, sort))
+        .collect();
+    solver.commands(system.definitions())?;
+    for (idx, atom) in atoms.iter().enumerate() {
+        solver.command(&format!(
+            "(define-fun atom_{} ({}) Bool {})",
+            idx,
+            params.join(" "),
+            atom.to_smt2(&mkn.vars)
+        ))?
+    }
+    solver.commands(system.declare_state(0))?;
+    solver.commands(system.declare_state(1))?;
+    let terms = |k: usize| -> Vec<String> {
+        (0..atoms.len())
+            .map(|idx| system.apply(&format!("atom_{}", idx), &[k]))
+            .collect()
+    };
+    let (current, next) = (terms(0), terms(1));
+
+    let mut graph = Graph::default();
+    let mut index = std::collections::BTreeMap::new();
+    let mut id_of = |graph: &mut Graph, state: Vec<bool>| -> usize {
+        *index.entry(state.clone()).or_insert_with(|| {
+            graph.states.push(state);
+            graph.states.len() - 1
+        })
+    };
+
+    solver.command("(push 1)")?;
+    solver.command(&format!("(assert {})", system.apply("init", &[0])))?;
+    for state in all_sat(solver, &current)? {
+        let id = id_of(&mut graph, state);
+        graph.init.push(id)
+    }
+    solver.command("(pop 1)")?;
+
+    let mut idx = 0;
+    while idx < graph.states.len() {
+        solver.command("(push 1)")?;
+        solver.command(&format!("(assert {})", cube(&current, &graph.states[idx])))?;
+        solver.command(&format!("(assert {})", system.apply("trans", &[0, 1])))?;
+        for state in all_sat(solver, &next)? {
+            let id = id_of(&mut graph, state);
+            graph.edges.push((idx, id))
+        }
+        solver.command("(pop 1)")?;
+        idx += 1
+    }
+    Ok(graph)
+}
+
+/// Conjunction of some atoms or their negation.
+fn cube(terms: &[String], values: &[bool]) -> String {
+    let lits: Vec<String> = terms
+        .iter()
+        .zip(values)
+        .map(|(term, value)| {
+            if *value {
+                term.clone()
+            } else {
+                format!("(not {})", term)
+            }
+        })
+        .collect();
+    match lits.as_slice() {
+        [lit] => lit.clone(),
+        lits => format!("(and {})", lits.join(" ")),
+    }
+}
+
+/// All the valuations of some boolean terms compatible with the current assertions, sorted.
+fn all_sat(solver: &mut Solver, terms: &[String]) -> Res<Vec<Vec<bool>>> {
+    let mut res = vec![];
+    solver.command("(push 1)")?;
+    while solver.check_sat_assuming(&[])? {
+        let mut values = vec![];
+        for value in solver.get_values(terms)? {
+            match value {
+                Value::Bool(b) => values.push(b),
+                value => bail!("[internal] non-boolean atom value `{}`", value),
+            }
+        }
+        solver.command(&format!("(assert (not {}))", cube(terms, &values)))?;
+        res.push(values)
+    }
+    solver.command("(pop 1)")?;
+    res.sort();
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_z3(f: impl FnOnce(&mut Solver) -> Res<()>) {
+        match Solver::new("z3").and_then(|mut solver| f(&mut solver)) {
+            Ok(()) => (),
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping")
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn abstraction() {
+        let mkn = Mkn::load("../systems/stopwatch.mkn").unwrap();
+        let atoms = atoms(&mkn, None, &["cnt = 0"]).unwrap();
+        let atoms: Vec<String> = atoms.iter().map(Expr::to_string).collect();
+        assert_eq!(atoms, vec!["start_stop", "reset", "is_counting", "cnt = 0"]);
+        let err = |bools: Option<&[&str]>, preds: &[&str]| {
+            super::atoms(&mkn, bools, preds).unwrap_err().to_string()
+        };
+        assert_eq!(
+            err(Some(&["cnt"]), &[]),
+            "`cnt` is not a boolean state variable"
+        );
+        assert_eq!(
+            err(None, &["cnt + 1"]),
+            "predicate `cnt + 1` is not boolean"
+        );
+        assert_eq!(
+            err(Some(&[]), &[]),
+            "the abstraction has no boolean state variable nor predicate"
+        );
+    }
+
+    #[test]
+    fn counter() {
+        with_z3(|solver| {
+            let mkn = Mkn::load("../paper/rsc/stopwatch_1.mkn")?;
+            let atoms = atoms(&mkn, None, &["count = 0"])?;
+            let graph = explore(solver, &mkn, &atoms)?;
+            // Nothing prevents `count` from being `-1` when it is not `0` in the abstraction.
+            assert_eq!(
+                graph,
+                Graph {
+                    states: vec![vec![false, false], vec![false, true], vec![true, true]],
+                    init: vec![0, 1, 2],
+                    edges: vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 2)],
+                }
+            );
+            let dot = graph.to_dot("stopwatch_1", &atoms);
+            assert!(dot.contains("    s0 [label = \"¬reset\\n¬(count = 0)\"];\n"));
+            assert!(dot.contains("    init -> s2;\n    s0 -> s0;\n"));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn up_to_date() {
+        // Same as `make diagram_check`.
+        let diagrams = [
+            ("../systems/stopwatch.mkn", vec!["cnt = 0"]),
+            ("../paper/rsc/stopwatch_1.mkn", vec!["count = 0"]),
+            (
+                "../paper/rsc/stopwatch_2.mkn",
+                vec!["count = 0", "count ≥ 0"],
+            ),
+            ("../paper/rsc/stopwatch_3.mkn", vec!["count = 5"]),
+        ];
+        for (source, preds) in diagrams {
+            let output = match source {
+                "../systems/stopwatch.mkn" => "../systems/gen/stopwatch.dot".to_string(),
+                _ => source.replace(".mkn", ".dot"),
+            };
+            let diagram = Diagram::new(source)
+                .set_preds(preds)
+                .set_output(Some(&output))
+                .set_check(true);
+            match diagram.run() {
+                Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                    eprintln!("z3 is not available, skipping")
+                }
+                res => res.unwrap(),
+            }
+        }
+    }
+}
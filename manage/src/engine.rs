@@ -0,0 +1,661 @@
+//! Reference BMC and induction engine.
+//!
+//! Implements exactly what the book teaches, over a Z3 child process driven through its standard
+//! input and output. The transition system is encoded as `define-fun`s `init`, `trans` and one per
+//! candidate, over the `|s.<var>|` (and `|s'.<var>|` for `trans`) variables; state `k` is declared
+//! as constants `<var>_<k>`, just like in the `bmc` and `induction` chapters.
+//!
+//! - BMC unrolls the system from an initial state. At each depth, each candidate not falsified yet
+//!   is checked under an activation literal, which is deactivated right after the check.
+//! - Induction checks that the candidates hold in the initial states (base case), and that each
+//!   candidate is preserved by the transition relation (step case): a state verifying it cannot
+//!   reach a state falsifying it in one transition.
+//! - k-induction generalizes induction: the base case is BMC up to depth `k - 1`, and the step case
+//!   checks that `k` consecutive states verifying a candidate cannot reach a state falsifying it.
+//!   The states of the step unrolling are pairwise distinct (*path compression*), otherwise
+//!   unreachable loops of states verifying the candidate could unroll forever.
+//!
+//! Counterexamples come with a [`Trace`].
+
+use std::io::{BufRead, Write};
+
+use crate::{
+    smt2::{self, Sexp},
+    trace::{State, Trace, Value},
+};
+
+prelude!();
+
+/// Sorts of state variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Bool,
+    Int,
+    Real,
+}
+impl std::fmt::Display for Sort {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Bool => "Bool".fmt(fmt),
+            Self::Int => "Int".fmt(fmt),
+            Self::Real => "Real".fmt(fmt),
+        }
+    }
+}
+
+/// A transition system with candidate invariants.
+///
+/// Terms are SMT-LIB 2 terms over the `|s.<var>|` variables, and also `|s'.<var>|` for `trans`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct System {
+    pub vars: Vec<(String, Sort)>,
+    pub init: String,
+    pub trans: String,
+    /// Candidates, with their name.
+    pub candidates: Vec<(String, String)>,
+}
+impl System {
+    /// Constructor, `init` and `trans` are `true` and there is no candidate.
+    pub fn new() -> Self {
+        Self {
+            vars: vec![],
+            init: "true".into(),
+            trans: "true".into(),
+            candidates: vec![],
+        }
+    }
+
+    /// Adds a state variable.
+    pub fn add_var(mut self, var: impl Into<String>, sort: Sort) -> Self {
+        self.vars.push((var.into(), sort));
+        self
+    }
+    /// Sets the initial predicate.
+    pub fn set_init(mut self, init: impl Into<String>) -> Self {
+        self.init = init.into();
+        self
+    }
+    /// Sets the transition relation.
+    pub fn set_trans(mut self, trans: impl Into<String>) -> Self {
+        self.trans = trans.into();
+        self
+    }
+    /// Adds a candidate invariant.
+    pub fn add_candidate(mut self, name: impl Into<String>, term: impl Into<String>) -> Self {
+        self.candidates.push((name.into(), term.into()));
+        self
+    }
+
+    /// Formal parameters over some state prefix, `s` or `s'`.
+    fn params(&self, prefix: &str) -> String {
+        let params: Vec<String> = self
+            .vars
+            .iter()
+            .map(|(var, sort)| format!("(|{}.{}| {})", prefix, var, sort))
+            .collect();
+        params.join(" ")
+    }
+    /// Definitions of `init`, `trans` and the candidates, `cand_<idx>`.
+    pub fn definitions(&self) -> Vec<String> {
+        let state = self.params("s");
+        let mut res = vec![
+            format!("(define-fun init ({}) Bool {})", state, self.init),
+            format!(
+                "(define-fun trans ({} {}) Bool {})",
+                state,
+                self.params("s'"),
+                self.trans
+            ),
+        ];
+        for (idx, (_, term)) in self.candidates.iter().enumerate() {
+            res.push(format!(
+                "(define-fun cand_{} ({}) Bool {})",
+                idx, state, term
+            ))
+        }
+        res
+    }
+
+    /// Name of variable `var` in state `k`.
+    pub fn state_var(var: &str, k: usize) -> String {
+        format!("{}_{}", var, k)
+    }
+    /// Declarations of the variables of state `k`.
+    pub fn declare_state(&self, k: usize) -> Vec<String> {
+        self.vars
+            .iter()
+            .map(|(var, sort)| format!("(declare-const {} {})", Self::state_var(var, k), sort))
+            .collect()
+    }
+    /// Constraint stating that states `i` and `j` are different.
+    pub fn distinct(&self, i: usize, j: usize) -> String {
+        if self.vars.is_empty() {
+            return "false".into();
+        }
+        let eqs: Vec<String> = self
+            .vars
+            .iter()
+            .map(|(var, _)| {
+                format!(
+                    "(= {} {})",
+                    Self::state_var(var, i),
+                    Self::state_var(var, j)
+                )
+            })
+            .collect();
+        format!("(not (and {}))", eqs.join(" "))
+    }
+    /// Applies a function defined in [`Self::definitions`] to some states.
+    pub fn apply(&self, fun: &str, states: &[usize]) -> String {
+        let mut res = format!("({}", fun);
+        for k in states {
+            for (var, _) in &self.vars {
+                res.push(' ');
+                res.push_str(&Self::state_var(var, *k))
+            }
+        }
+        res.push(')');
+        res
+    }
+}
+
+/// A Z3 child process.
+pub struct Solver {
+    child: std::process::Child,
+    stdin: std::process::ChildStdin,
+    stdout: io::BufReader<std::process::ChildStdout>,
+}
+impl Solver {
+    /// Spawns `<z3_cmd> -in`.
+    ///
+    /// Fails with [`err::ErrorKind::ToolUnavailable`] if the command cannot be found.
+    pub fn new(z3_cmd: &str) -> Res<Self> {
+        use std::process::{Command, Stdio};
+        let mut child = match Command::new(z3_cmd)
+            .arg("-in")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                bail!(err::ErrorKind::ToolUnavailable(z3_cmd.into()))
+            }
+            Err(e) => return Err(e).chain_err(|| format!("while spawning `{} -in`", z3_cmd)),
+        };
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = io::BufReader::new(child.stdout.take().expect("piped stdout"));
+        let mut solver = Self {
+            child,
+            stdin,
+            stdout,
+        };
+        // Every command answers something, errors cannot go unnoticed.
+        solver.command("(set-option :print-success true)")?;
+        Ok(solver)
+    }
+
+    /// Sends some text to the solver.
+    fn send(&mut self, text: &str) -> Res<()> {
+        log::trace!("z3 <- {}", text);
+        writeln!(self.stdin, "{}", text)
+            .and_then(|()| self.stdin.flush())
+            .chain_err(|| "while writing to z3")
+    }
+    /// Reads an answer, a symbol or an s-expression possibly spanning several lines.
+    fn answer(&mut self) -> Res<String> {
+        let mut answer = String::new();
+        loop {
+            let len = answer.len();
+            let read = self
+                .stdout
+                .read_line(&mut answer)
+                .chain_err(|| "while reading from z3")?;
+            if read == 0 {
+                bail!("z3 exited unexpectedly")
+            }
+            if answer[len..].trim().is_empty() && answer.trim().is_empty() {
+                answer.clear();
+                continue;
+            }
+            let (_, errors) = smt2::parse(&answer);
+            if errors.is_empty() {
+                break;
+            }
+        }
+        let answer = answer.trim().to_string();
+        log::trace!("z3 -> {}", answer);
+        if answer.starts_with("(error") {
+            bail!("z3 error: {}", answer)
+        }
+        Ok(answer)
+    }
+
+    /// Sends a command expecting `success`.
+    pub fn command(&mut self, command: &str) -> Res<()> {
+        self.send(command)?;
+        match self.answer()?.as_str() {
+            "success" => Ok(()),
+            answer => bail!("unexpected z3 answer `{}` to `{}`", answer, command),
+        }
+    }
+    /// Sends some commands expecting `success`.
+    pub fn commands(&mut self, commands: impl IntoIterator<Item = String>) -> Res<()> {
+        for command in commands {
+            self.command(&command)?
+        }
+        Ok(())
+    }
+
+    /// Checks satisfiability under some assumptions, fails on `unknown`.
+    pub fn check_sat_assuming(&mut self, assumptions: &[&str]) -> Res<bool> {
+        self.send(&format!("(check-sat-assuming ({}))", assumptions.join(" ")))?;
+        match self.answer()?.as_str() {
+            "sat" => Ok(true),
+            "unsat" => Ok(false),
+            answer => bail!("unexpected z3 answer `{}` to check-sat", answer),
+        }
+    }
+
+    /// Values of some terms in the current model.
+    pub fn get_values(&mut self, terms: &[String]) -> Res<Vec<Value>> {
+        self.send(&format!("(get-value ({}))", terms.join(" ")))?;
+        let answer = self.answer()?;
+        let (sexps, _) = smt2::parse(&answer);
+        let pairs = match sexps.as_slice() {
+            [Sexp::List { elems, .. }] => elems,
+            _ => bail!("unexpected z3 answer `{}` to get-value", answer),
+        };
+        let mut res = Vec::with_capacity(terms.len());
+        for pair in pairs {
+            match pair.list() {
+                Some([_, value]) => res.push(Value::of_sexp(value).ok_or_else(|| {
+                    format!("unexpected value in z3 answer `{}` to get-value", answer)
+                })?),
+                _ => bail!("unexpected z3 answer `{}` to get-value", answer),
+            }
+        }
+        if res.len() != terms.len() {
+            bail!("unexpected z3 answer `{}` to get-value", answer)
+        }
+        Ok(res)
+    }
+}
+impl Drop for Solver {
+    fn drop(&mut self) {
+        // Z3 may still be busy with a check, do not wait for it to exit on its own.
+        let _ = self.send("(exit)");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A counterexample for a candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cex {
+    /// Candidate falsified.
+    pub candidate: String,
+    /// Trace falsifying the candidate in its last state.
+    pub trace: Trace,
+}
+
+/// Result of a BMC run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bmc {
+    /// Candidates falsified, by increasing depth.
+    pub falsified: Vec<Cex>,
+    /// Candidates not falsified up to the maximum depth.
+    pub unfalsified: Vec<String>,
+}
+
+/// Result of an induction attempt.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Induction {
+    /// Candidates falsified by an initial state.
+    pub base: Vec<Cex>,
+    /// Candidates that are not inductive, with a two-state counterexample.
+    pub step: Vec<Cex>,
+    /// Candidates that hold in the initial states and are inductive.
+    pub invariants: Vec<String>,
+}
+
+/// Result of a k-induction run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KInduction {
+    /// Candidates falsified, by increasing depth.
+    pub falsified: Vec<Cex>,
+    /// Candidates proved, with the minimal `k` at which they are k-inductive, by increasing `k`.
+    pub proved: Vec<(String, usize)>,
+    /// Candidates that are not k-inductive for any `k` up to the maximum, with the step
+    /// counterexample for the maximum `k`.
+    pub unknown: Vec<Cex>,
+}
+
+/// BMC and induction over a system.
+pub struct Engine<'s> {
+    z3_cmd: &'s str,
+    system: &'s System,
+}
+impl<'s> Engine<'s> {
+    /// Constructor.
+    pub fn new(z3_cmd: &'s str, system: &'s System) -> Self {
+        Self { z3_cmd, system }
+    }
+
+    /// Solver with the system's definitions and states `0` to `k` declared.
+    fn solver(&self, k: usize) -> Res<Solver> {
+        let mut solver = Solver::new(self.z3_cmd)?;
+        solver.commands(self.system.definitions())?;
+        for k in 0..=k {
+            solver.commands(self.system.declare_state(k))?;
+        }
+        Ok(solver)
+    }
+
+    /// Trace of states `0` to `k` in the current model.
+    fn trace(&self, solver: &mut Solver, k: usize) -> Res<Trace> {
+        let vars = &self.system.vars;
+        let mut terms = Vec::with_capacity(vars.len() * (k + 1));
+        for k in 0..=k {
+            terms.extend(vars.iter().map(|(var, _)| System::state_var(var, k)));
+        }
+        let mut values = solver.get_values(&terms)?.into_iter();
+        let mut states = Vec::with_capacity(k + 1);
+        for _ in 0..=k {
+            let state: State = vars
+                .iter()
+                .map(|(var, _)| var.clone())
+                .zip(values.by_ref())
+                .collect();
+            states.push(state)
+        }
+        Ok(Trace::new(states))
+    }
+
+    /// Checks each candidate of `candidates` in state `k` under an activation literal.
+    ///
+    /// The candidate checked is assumed to hold in the states of `assume`. Returns the
+    /// counterexamples, the trace going from state `0` to `k`. Falsified candidates are removed
+    /// from `candidates`.
+    fn falsify(
+        &self,
+        solver: &mut Solver,
+        candidates: &mut Vec<usize>,
+        k: usize,
+        assume: std::ops::Range<usize>,
+        actlits: &mut usize,
+    ) -> Res<Vec<Cex>> {
+        let mut cexs = vec![];
+        let mut idx = 0;
+        while idx < candidates.len() {
+            let candidate = candidates[idx];
+            let actlit = format!("actlit_{}", actlits);
+            *actlits += 1;
+            solver.command(&format!("(declare-const {} Bool)", actlit))?;
+            let cand = format!("cand_{}", candidate);
+            let mut conj: Vec<String> = assume
+                .clone()
+                .map(|j| self.system.apply(&cand, &[j]))
+                .collect();
+            conj.push(format!("(not {})", self.system.apply(&cand, &[k])));
+            solver.command(&format!(
+                "(assert (=> {} (and {})))",
+                actlit,
+                conj.join(" ")
+            ))?;
+            let sat = solver.check_sat_assuming(&[&actlit])?;
+            if sat {
+                cexs.push(Cex {
+                    candidate: self.system.candidates[candidate].0.clone(),
+                    trace: self.trace(solver, k)?,
+                });
+                candidates.remove(idx);
+            } else {
+                idx += 1
+            }
+            solver.command(&format!("(assert (not {}))", actlit))?;
+        }
+        Ok(cexs)
+    }
+
+    /// Looks for falsifications up to depth `max`, included.
+    pub fn bmc(&self, max: usize) -> Res<Bmc> {
+        let mut solver = self.solver(0)?;
+        solver.command(&format!("(assert {})", self.system.apply("init", &[0])))?;
+        let mut candidates: Vec<usize> = (0..self.system.candidates.len()).collect();
+        let mut actlits = 0;
+        let mut res = Bmc::default();
+
+        for k in 0..=max {
+            if candidates.is_empty() {
+                break;
+            }
+            log::debug!("bmc: checking for falsifications at depth {}", k);
+            if k > 0 {
+                solver.commands(self.system.declare_state(k))?;
+                solver.command(&format!(
+                    "(assert {})",
+                    self.system.apply("trans", &[k - 1, k])
+                ))?;
+            }
+            let cexs = self.falsify(&mut solver, &mut candidates, k, 0..0, &mut actlits)?;
+            res.falsified.extend(cexs);
+        }
+
+        res.unfalsified = candidates
+            .into_iter()
+            .map(|idx| self.system.candidates[idx].0.clone())
+            .collect();
+        Ok(res)
+    }
+
+    /// Attempts to prove the candidates by induction.
+    pub fn induction(&self) -> Res<Induction> {
+        let all: Vec<usize> = (0..self.system.candidates.len()).collect();
+        let mut res = Induction::default();
+
+        log::debug!("induction: checking base case");
+        let mut base = self.solver(0)?;
+        base.command(&format!("(assert {})", self.system.apply("init", &[0])))?;
+        let mut base_ok = all.clone();
+        res.base = self.falsify(&mut base, &mut base_ok, 0, 0..0, &mut 0)?;
+        drop(base);
+
+        log::debug!("induction: checking step case");
+        let mut step = self.solver(1)?;
+        step.command(&format!("(assert {})", self.system.apply("trans", &[0, 1])))?;
+        let mut step_ok = all;
+        res.step = self.falsify(&mut step, &mut step_ok, 1, 0..1, &mut 0)?;
+
+        res.invariants = step_ok
+            .into_iter()
+            .filter(|idx| base_ok.contains(idx))
+            .map(|idx| self.system.candidates[idx].0.clone())
+            .collect();
+        Ok(res)
+    }
+
+    /// Attempts to prove the candidates by k-induction, for `k` from `1` to `max`.
+    ///
+    /// Candidates are checked separately, each one only assumes itself in the step case.
+    pub fn k_induction(&self, max: usize) -> Res<KInduction> {
+        if max == 0 {
+            bail!("k-induction needs a maximum `k` of at least 1")
+        }
+        let mut candidates: Vec<usize> = (0..self.system.candidates.len()).collect();
+        let mut res = KInduction::default();
+
+        let mut base = self.solver(0)?;
+        base.command(&format!("(assert {})", self.system.apply("init", &[0])))?;
+        let mut step = self.solver(0)?;
+        let (mut base_actlits, mut step_actlits) = (0, 0);
+
+        for k in 1..=max {
+            if candidates.is_empty() {
+                break;
+            }
+            log::debug!("k-induction: checking base case at depth {}", k - 1);
+            if k > 1 {
+                base.commands(self.system.declare_state(k - 1))?;
+                base.command(&format!(
+                    "(assert {})",
+                    self.system.apply("trans", &[k - 2, k - 1])
+                ))?;
+            }
+            let cexs = self.falsify(&mut base, &mut candidates, k - 1, 0..0, &mut base_actlits)?;
+            res.falsified.extend(cexs);
+
+            log::debug!("k-induction: checking step case for k = {}", k);
+            step.commands(self.system.declare_state(k))?;
+            step.command(&format!(
+                "(assert {})",
+                self.system.apply("trans", &[k - 1, k])
+            ))?;
+            for j in 0..k {
+                step.command(&format!("(assert {})", self.system.distinct(j, k)))?;
+            }
+            let mut inductive = candidates.clone();
+            let cexs = self.falsify(&mut step, &mut inductive, k, 0..k, &mut step_actlits)?;
+            candidates.retain(|idx| !inductive.contains(idx));
+            res.proved.extend(
+                inductive
+                    .into_iter()
+                    .map(|idx| (self.system.candidates[idx].0.clone(), k)),
+            );
+            if k == max {
+                res.unknown = cexs
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The stopwatch from the `bmc` chapter, with the candidates from `mikino_bmc/code/sw_1.mkn`.
+    fn stopwatch() -> System {
+        System::new()
+            .add_var("start_stop", Sort::Bool)
+            .add_var("reset", Sort::Bool)
+            .add_var("is_counting", Sort::Bool)
+            .add_var("cnt", Sort::Int)
+            .set_init(
+                "(and (= |s.is_counting| |s.start_stop|) (>= |s.cnt| 0) (=> |s.reset| (= |s.cnt| 0)))",
+            )
+            .set_trans(
+                "(and \
+                    (= |s'.is_counting| (ite |s'.start_stop| (not |s.is_counting|) |s.is_counting|)) \
+                    (= |s'.cnt| (ite |s'.reset| 0 (ite |s'.is_counting| (+ |s.cnt| 1) |s.cnt|))) \
+                )",
+            )
+            .add_candidate("cnt is positive", "(>= |s.cnt| 0)")
+            .add_candidate("cnt ≤ 2", "(<= |s.cnt| 2)")
+            .add_candidate("cnt ≤ 4", "(<= |s.cnt| 4)")
+    }
+
+    #[test]
+    fn encoding() {
+        let system = stopwatch();
+        let definitions = system.definitions();
+        assert_eq!(definitions.len(), 5);
+        assert!(definitions[0].starts_with(
+            "(define-fun init ((|s.start_stop| Bool) (|s.reset| Bool) (|s.is_counting| Bool) \
+            (|s.cnt| Int)) Bool"
+        ));
+        assert!(definitions[4].starts_with("(define-fun cand_2 ("));
+        assert_eq!(
+            system.apply("trans", &[0, 1]),
+            "(trans start_stop_0 reset_0 is_counting_0 cnt_0 \
+            start_stop_1 reset_1 is_counting_1 cnt_1)"
+        );
+        for definition in definitions {
+            let (_, errors) = smt2::parse(&definition);
+            assert!(errors.is_empty(), "{}", definition)
+        }
+    }
+
+    /// Runs `f` on the default Z3 command, skips if Z3 is not available.
+    fn with_z3(f: impl FnOnce(&str) -> Res<()>) {
+        match f("z3") {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping")
+            }
+            res => res.unwrap(),
+        }
+    }
+
+    #[test]
+    fn bmc() {
+        let system = stopwatch();
+        with_z3(|z3| {
+            let bmc = Engine::new(z3, &system).bmc(10)?;
+            assert_eq!(bmc.unfalsified, vec!["cnt is positive".to_string()]);
+            let falsified: Vec<(&str, Option<usize>)> = bmc
+                .falsified
+                .iter()
+                .map(|cex| (cex.candidate.as_str(), cex.trace.depth()))
+                .collect();
+            assert_eq!(falsified, vec![("cnt ≤ 2", Some(0)), ("cnt ≤ 4", Some(0))]);
+            let last = bmc.falsified[0].trace.states.last().unwrap();
+            assert!(matches!(last["cnt"], Value::Int(cnt) if cnt > 2));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn induction() {
+        let system = stopwatch();
+        with_z3(|z3| {
+            let induction = Engine::new(z3, &system).induction()?;
+            let candidates = |cexs: &[Cex]| -> Vec<String> {
+                cexs.iter().map(|cex| cex.candidate.clone()).collect()
+            };
+            // `init` only says `cnt` is positive.
+            assert_eq!(candidates(&induction.base), vec!["cnt ≤ 2", "cnt ≤ 4"]);
+            assert_eq!(candidates(&induction.step), vec!["cnt ≤ 2", "cnt ≤ 4"]);
+            assert_eq!(induction.invariants, vec!["cnt is positive".to_string()]);
+            let trace = &induction.step[0].trace;
+            assert_eq!(trace.depth(), Some(1));
+            assert!(matches!(trace.states[1]["cnt"], Value::Int(cnt) if cnt > 2));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn k_induction() {
+        // Counts from 0 to 3 and wraps. Above 3, `x` stays where it is or jumps to -1 on `go`: this
+        // unreachable loop is why `x ≥ 0` needs path compression. States include the input `go`,
+        // so the loop can still be taken once with `go` differing: `x ≥ 0` is 3-inductive.
+        let system = System::new()
+            .add_var("go", Sort::Bool)
+            .add_var("x", Sort::Int)
+            .set_init("(= |s.x| 0)")
+            .set_trans(
+                "(= |s'.x| (ite (< |s.x| 3) (+ |s.x| 1) (ite (= |s.x| 3) 0 \
+                    (ite |s'.go| (- 1) |s.x|))))",
+            )
+            .add_candidate("x ≥ 0", "(>= |s.x| 0)")
+            .add_candidate("x ≤ 3", "(<= |s.x| 3)")
+            .add_candidate("x ≤ 2", "(<= |s.x| 2)")
+            .add_candidate("x ≠ -2", "(not (= |s.x| (- 2)))");
+        with_z3(|z3| {
+            let engine = Engine::new(z3, &system);
+            assert!(engine.k_induction(0).is_err());
+            let res = engine.k_induction(5)?;
+            assert_eq!(
+                res.proved,
+                vec![("x ≤ 3".to_string(), 1), ("x ≥ 0".to_string(), 3)]
+            );
+            assert_eq!(res.falsified.len(), 1);
+            assert_eq!(res.falsified[0].candidate, "x ≤ 2");
+            assert_eq!(res.falsified[0].trace.depth(), Some(3));
+            // Decreasing from -7 to -2 is a simple path, for any length.
+            assert_eq!(res.unknown.len(), 1);
+            assert_eq!(res.unknown[0].candidate, "x ≠ -2");
+            assert_eq!(res.unknown[0].trace.depth(), Some(5));
+            Ok(())
+        })
+    }
+}
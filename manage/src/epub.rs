@@ -0,0 +1,879 @@
+//! EPUB 3 export of the book.
+//!
+//! Chapters are inlined like in [`Vanilla`] and rendered to XHTML, then packaged with an OPF
+//! manifest, a nav document and a stylesheet in a zip archive.
+
+use std::collections::BTreeMap;
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+
+prelude!();
+
+/// Book configuration file, for metadata.
+const BOOK_TOML: &str = "book.toml";
+/// Directory of the content files in the archive.
+const OEBPS: &str = "OEBPS";
+/// Name of the OPF package document.
+const OPF: &str = "content.opf";
+/// Name of the nav document.
+const NAV: &str = "nav.xhtml";
+/// Name of the stylesheet.
+const CSS: &str = "style.css";
+
+/// Stylesheet of the book.
+const STYLE: &str = "body {
+    font-family: serif;
+    line-height: 1.4;
+}
+code, pre {
+    font-family: monospace;
+}
+pre {
+    background-color: #f6f7f6;
+    border-left: 3px solid #e0e0e0;
+    padding: 0.5em;
+    white-space: pre-wrap;
+    font-size: 0.85em;
+}
+blockquote {
+    border-left: 3px solid #e0e0e0;
+    margin-left: 0;
+    padding-left: 1em;
+    color: #555555;
+}
+table {
+    border-collapse: collapse;
+    margin: 1em auto;
+}
+th, td {
+    border: 1px solid #c0c0c0;
+    padding: 0.2em 0.5em;
+}
+summary {
+    font-style: italic;
+}
+";
+
+/// Book metadata, extracted from `book.toml`.
+#[derive(Debug, Clone)]
+pub struct Meta {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub language: String,
+}
+impl Meta {
+    /// Loads the metadata from the `[book]` section of a `book.toml` file.
+    ///
+    /// The language defaults to `en`.
+    pub fn load(path: impl AsRef<Path>) -> Res<Self> {
+        let path = path.as_ref();
+        let config = mdbook::Config::from_disk(path).map_err(|e| {
+            let msg: Vec<String> = e.chain().map(|e| e.to_string()).collect();
+            err::Error::from(msg.join("\n"))
+                .chain_err(|| format!("while loading `{}`", path.display()))
+        })?;
+        let book = config.book;
+        let title = book
+            .title
+            .ok_or_else(|| format!("no book title in `{}`", path.display()))?;
+        Ok(Self {
+            title,
+            authors: book.authors,
+            language: book.language.unwrap_or_else(|| "en".into()),
+        })
+    }
+}
+
+/// A chapter, ready to be packaged.
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    /// File name in the archive, without extension.
+    pub stem: String,
+    /// Title of the chapter.
+    pub title: String,
+    /// XHTML body of the chapter.
+    pub body: String,
+}
+
+/// EPUB generator.
+pub struct Epub<'s> {
+    /// Configuration for running the snippets of `{{ #run }}` directives.
+    conf: Conf<'s>,
+    target: &'s str,
+}
+impl<'s> Epub<'s> {
+    /// Constructor.
+    pub fn new(conf: Conf<'s>, target: &'s str) -> Self {
+        Self { conf, target }
+    }
+    /// Target accessor.
+    pub fn target(&self) -> &'s str {
+        self.target
+    }
+
+    /// Runs EPUB generation.
+    pub fn run(&self) -> Res<()> {
+        let meta = Meta::load(BOOK_TOML)?;
+        let top_level = Vanilla::top_level_md(Vanilla::SRC)?;
+        log::info!("generating EPUB for {} chapter(s)", top_level.len());
+
+        // Chapter markdown paths to archive file names, for link rewriting.
+        let files: BTreeMap<PathBuf, String> = top_level
+            .iter()
+            .map(|file| (normalize(file.path()), format!("{}.xhtml", file.slug())))
+            .collect();
+
+        let mut chapters = Vec::with_capacity(top_level.len());
+        for file in &top_level {
+            let src_path = PathBuf::from(Vanilla::SRC).join(file.path());
+            log::debug!(
+                "generating XHTML for `{}` from `{}`",
+                file.title(),
+                src_path.display(),
+            );
+            let md = Vanilla::inline_md(&self.conf, &src_path)?;
+            let body = xhtml_body(file.path(), &md, &files);
+            chapters.push(Chapter {
+                stem: file.slug(),
+                title: file.title().into(),
+                body,
+            })
+        }
+
+        let bytes = build(&meta, &chapters)?;
+        check(&bytes).chain_err(|| "generated EPUB is not valid")?;
+
+        if let Some(parent) = Path::new(self.target).parent() {
+            fs::create_dir_all(parent).chain_err(|| {
+                format!(
+                    "during (recursive) folder creation for `{}`",
+                    parent.display()
+                )
+            })?;
+        }
+        let mut file = open_write(self.target)?;
+        io::Write::write_all(&mut file, &bytes)
+            .chain_err(|| format!("while writing `{}`", self.target))?;
+
+        log::info!("done with EPUB generation");
+        Ok(())
+    }
+}
+
+/// Normalizes a relative path, removing `.` and resolving `..` components.
+fn normalize(path: impl AsRef<Path>) -> PathBuf {
+    use std::path::Component;
+    let mut res = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                res.pop();
+            }
+            component => res.push(component),
+        }
+    }
+    res
+}
+
+/// Renders the inlined markdown of a chapter to an XHTML body.
+///
+/// - `md_path` is the path of the chapter relative to the book's `src` directory,
+/// - `files` maps chapter markdown files to their XHTML counterpart, links to chapters are
+///   rewritten accordingly.
+pub fn xhtml_body(md_path: &str, md: &str, files: &BTreeMap<PathBuf, String>) -> String {
+    // Lone `\` lines are used as vertical spacing in the book.
+    let md = md
+        .lines()
+        .map(|line| if line == "\\" { "<br />" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let md_dir = normalize(md_path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let mut in_rust_code = false;
+    let events = Parser::new_ext(&md, Options::ENABLE_TABLES).map(|event| match event {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+            in_rust_code = info.split([',', ' ']).next() == Some("rust");
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+        }
+        Event::End(TagEnd::CodeBlock) => {
+            in_rust_code = false;
+            event
+        }
+        Event::Text(text) if in_rust_code => {
+            let text: String = text
+                .split_inclusive('\n')
+                .filter(|line| !crate::is_hidden_rust_line(line.trim_end_matches('\n')))
+                .collect();
+            Event::Text(text.into())
+        }
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            let dest_url = rewrite_link(&md_dir, dest_url, files);
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            })
+        }
+        event => event,
+    });
+
+    let mut body = String::with_capacity(md.len());
+    pulldown_cmark::html::push_html(&mut body, events);
+    body
+}
+
+/// Rewrites a link to a chapter of the book to the chapter's XHTML file.
+fn rewrite_link<'a>(
+    md_dir: &Path,
+    dest: CowStr<'a>,
+    files: &BTreeMap<PathBuf, String>,
+) -> CowStr<'a> {
+    if dest.contains("://") || dest.starts_with("mailto:") {
+        return dest;
+    }
+    let (path, anchor) = match dest.find('#') {
+        Some(idx) => (&dest[..idx], &dest[idx..]),
+        None => (dest.as_ref(), ""),
+    };
+    if !path.ends_with(".md") {
+        return dest;
+    }
+    match files.get(&normalize(md_dir.join(path))) {
+        Some(file) => format!("{}{}", file, anchor).into(),
+        None => dest,
+    }
+}
+
+/// Escapes XML special characters.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// Wraps an XHTML body in a full document.
+fn xhtml_document(lang: &str, title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+<meta charset="UTF-8" />
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="{css}" />
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = escape(lang),
+        title = escape(title),
+        css = CSS,
+        body = body,
+    )
+}
+
+/// Current UTC date and time, formatted as `CCYY-MM-DDThh:mm:ssZ`.
+fn utc_now() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Civil date from days since epoch, see <http://howardhinnant.github.io/date_algorithms.html>.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:0>4}-{:0>2}-{:0>2}T{:0>2}:{:0>2}:{:0>2}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// Builds the EPUB archive for some chapters.
+pub fn build(meta: &Meta, chapters: &[Chapter]) -> Res<Vec<u8>> {
+    let mut zip = zip::Writer::new();
+
+    // Must be first and uncompressed.
+    zip.add("mimetype", b"application/epub+zip")?;
+    zip.add(
+        "META-INF/container.xml",
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="{}/{}" media-type="application/oebps-package+xml" />
+</rootfiles>
+</container>
+"#,
+            OEBPS, OPF
+        )
+        .as_bytes(),
+    )?;
+
+    // Package document.
+    let mut manifest = format!(
+        "<item id=\"nav\" href=\"{}\" media-type=\"application/xhtml+xml\" properties=\"nav\" />\n\
+         <item id=\"style\" href=\"{}\" media-type=\"text/css\" />\n",
+        NAV, CSS
+    );
+    let mut spine = String::new();
+    for chapter in chapters {
+        manifest.push_str(&format!(
+            "<item id=\"ch_{stem}\" href=\"{stem}.xhtml\" media-type=\"application/xhtml+xml\" />\n",
+            stem = escape(&chapter.stem),
+        ));
+        spine.push_str(&format!(
+            "<itemref idref=\"ch_{}\" />\n",
+            escape(&chapter.stem)
+        ));
+    }
+    let creators: String = meta
+        .authors
+        .iter()
+        .map(|author| format!("<dc:creator>{}</dc:creator>\n", escape(author)))
+        .collect();
+    let identifier: String = meta
+        .title
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let opf = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{lang}">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:identifier id="book-id">urn:book:{identifier}</dc:identifier>
+<dc:title>{title}</dc:title>
+<dc:language>{lang}</dc:language>
+{creators}<meta property="dcterms:modified">{modified}</meta>
+</metadata>
+<manifest>
+{manifest}</manifest>
+<spine>
+{spine}</spine>
+</package>
+"#,
+        lang = escape(&meta.language),
+        identifier = identifier,
+        title = escape(&meta.title),
+        creators = creators,
+        modified = utc_now(),
+        manifest = manifest,
+        spine = spine,
+    );
+    zip.add(&format!("{}/{}", OEBPS, OPF), opf.as_bytes())?;
+
+    // Navigation document.
+    let mut toc = String::new();
+    for chapter in chapters {
+        toc.push_str(&format!(
+            "<li><a href=\"{}.xhtml\">{}</a></li>\n",
+            escape(&chapter.stem),
+            escape(&chapter.title)
+        ));
+    }
+    let nav = xhtml_document(
+        &meta.language,
+        &meta.title,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>",
+            escape(&meta.title),
+            toc
+        ),
+    );
+    zip.add(&format!("{}/{}", OEBPS, NAV), nav.as_bytes())?;
+    zip.add(&format!("{}/{}", OEBPS, CSS), STYLE.as_bytes())?;
+
+    for chapter in chapters {
+        let doc = xhtml_document(&meta.language, &chapter.title, &chapter.body);
+        zip.add(&format!("{}/{}.xhtml", OEBPS, chapter.stem), doc.as_bytes())?;
+    }
+
+    Ok(zip.finish())
+}
+
+/// Checks the structure of an EPUB archive.
+///
+/// - the first entry is an uncompressed `mimetype` file,
+/// - the container points to an existing package document,
+/// - all manifest items exist, all spine items are in the manifest, and there is a nav document,
+/// - all XHTML and XML files are well-formed.
+pub fn check(bytes: &[u8]) -> Res<()> {
+    let entries = zip::read(bytes)?;
+    let get = |name: &str| -> Res<&str> {
+        let (_, data) = entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .ok_or_else(|| format!("archive has no `{}` file", name))?;
+        std::str::from_utf8(data)
+            .map_err(|_| format!("archive file `{}` is not UTF-8", name).into())
+    };
+
+    match entries.first() {
+        Some((name, data)) if name == "mimetype" && data == b"application/epub+zip" => (),
+        Some((name, _)) if name == "mimetype" => bail!("illegal `mimetype` content"),
+        _ => bail!("first archive file should be `mimetype`"),
+    }
+
+    for (name, data) in &entries {
+        if name.ends_with(".xhtml") || name.ends_with(".xml") || name.ends_with(".opf") {
+            let content = std::str::from_utf8(data)
+                .map_err(|_| format!("archive file `{}` is not UTF-8", name))?;
+            check_xml(content).chain_err(|| format!("in archive file `{}`", name))?;
+        }
+    }
+
+    let container = get("META-INF/container.xml")?;
+    let opf_path = tags(container, "rootfile")
+        .first()
+        .and_then(|tag| attr(tag, "full-path"))
+        .ok_or("container has no rootfile")?;
+    let opf = get(opf_path)?;
+    let opf_dir = match opf_path.rfind('/') {
+        Some(idx) => &opf_path[..=idx],
+        None => "",
+    };
+
+    let mut ids = vec![];
+    let mut has_nav = false;
+    for item in tags(opf, "item") {
+        let id = attr(item, "id").ok_or("manifest item has no `id`")?;
+        let href = attr(item, "href").ok_or("manifest item has no `href`")?;
+        attr(item, "media-type").ok_or("manifest item has no `media-type`")?;
+        get(&format!("{}{}", opf_dir, href)).chain_err(|| format!("on manifest item `{}`", id))?;
+        has_nav = has_nav || attr(item, "properties") == Some("nav");
+        ids.push(id);
+    }
+    if !has_nav {
+        bail!("manifest has no nav document")
+    }
+    let spine = tags(opf, "itemref");
+    if spine.is_empty() {
+        bail!("spine is empty")
+    }
+    for itemref in spine {
+        let idref = attr(itemref, "idref").ok_or("spine item has no `idref`")?;
+        if !ids.contains(&idref) {
+            bail!("spine item `{}` is not in the manifest", idref)
+        }
+    }
+
+    Ok(())
+}
+
+/// All the tags with some name in some XML content.
+fn tags<'a>(content: &'a str, name: &str) -> Vec<&'a str> {
+    let pat = format!("<{} ", name);
+    let mut res = vec![];
+    let mut rest = content;
+    while let Some(start) = rest.find(&pat) {
+        rest = &rest[start..];
+        let end = rest.find('>').map(|idx| idx + 1).unwrap_or(rest.len());
+        res.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    res
+}
+
+/// Value of an attribute in a tag.
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pat = format!(" {}=\"", name);
+    let start = tag.find(&pat)? + pat.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+/// Checks that some XML content is well-formed: tags are balanced and entities are legal.
+fn check_xml(content: &str) -> Res<()> {
+    let mut stack: Vec<&str> = vec![];
+    let mut rest = content;
+
+    while let Some(idx) = rest.find(['<', '&']) {
+        rest = &rest[idx..];
+        let offset = content.len() - rest.len();
+        let line = || content[..offset].lines().count().max(1);
+
+        if rest.starts_with('&') {
+            let end = rest
+                .find(';')
+                .ok_or_else(|| format!("unterminated entity on line {}", line()))?;
+            let entity = &rest[1..end];
+            let legal = matches!(entity, "amp" | "lt" | "gt" | "quot" | "apos")
+                || entity
+                    .strip_prefix("#x")
+                    .map(|hex| !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                    .unwrap_or(false)
+                || entity
+                    .strip_prefix('#')
+                    .map(|dec| !dec.is_empty() && dec.chars().all(|c| c.is_ascii_digit()))
+                    .unwrap_or(false);
+            if !legal {
+                bail!("illegal entity `&{};` on line {}", entity, line())
+            }
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        let (skip_to, closing) = if rest.starts_with("<?") {
+            (Some("?>"), None)
+        } else if rest.starts_with("<!--") {
+            (Some("-->"), None)
+        } else if rest.starts_with("<!") {
+            (Some(">"), None)
+        } else {
+            (None, Some(rest.starts_with("</")))
+        };
+        if let Some(end) = skip_to {
+            let idx = rest
+                .find(end)
+                .ok_or_else(|| format!("unterminated markup on line {}", line()))?;
+            rest = &rest[idx + end.len()..];
+            continue;
+        }
+        let closing = closing.unwrap_or(false);
+
+        // Find the end of the tag, ignoring `>` in attribute values.
+        let mut quote = None;
+        let mut end = None;
+        for (idx, c) in rest.char_indices() {
+            match (quote, c) {
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                (None, '>') => {
+                    end = Some(idx);
+                    break;
+                }
+                _ => (),
+            }
+        }
+        let end = end.ok_or_else(|| format!("unterminated tag on line {}", line()))?;
+        let tag = &rest[..end];
+        let name_start = if closing { 2 } else { 1 };
+        let name_end = tag[name_start..]
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .map(|idx| idx + name_start)
+            .unwrap_or(tag.len());
+        let name = &tag[name_start..name_end];
+        if name.is_empty() {
+            bail!("illegal tag `{}>` on line {}", tag, line())
+        }
+
+        if closing {
+            match stack.pop() {
+                Some(open) if open == name => (),
+                Some(open) => bail!(
+                    "closing tag `{}` on line {} does not match opening tag `{}`",
+                    name,
+                    line(),
+                    open
+                ),
+                None => bail!("unexpected closing tag `{}` on line {}", name, line()),
+            }
+        } else if !tag.ends_with('/') {
+            stack.push(name)
+        }
+        rest = &rest[end + 1..];
+    }
+
+    if let Some(open) = stack.pop() {
+        bail!("tag `{}` is never closed", open)
+    }
+    Ok(())
+}
+
+/// Minimal zip archive handling, uncompressed entries only.
+mod zip {
+    prelude!();
+
+    const LOCAL_SIG: u32 = 0x0403_4b50;
+    const CENTRAL_SIG: u32 = 0x0201_4b50;
+    const END_SIG: u32 = 0x0605_4b50;
+    /// MS-DOS date for 1980-01-01, keeps archives reproducible.
+    const DOS_DATE: u16 = 0x21;
+
+    /// CRC-32 of some bytes, as used by zip.
+    pub fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+        !crc
+    }
+
+    /// Writes an archive in memory.
+    pub struct Writer {
+        buf: Vec<u8>,
+        central: Vec<u8>,
+        count: u16,
+    }
+    impl Writer {
+        /// Constructor.
+        pub fn new() -> Self {
+            Self {
+                buf: vec![],
+                central: vec![],
+                count: 0,
+            }
+        }
+
+        /// Adds a file to the archive.
+        pub fn add(&mut self, name: &str, data: &[u8]) -> Res<()> {
+            let offset = self.buf.len() as u32;
+            let (crc, len) = (crc32(data), data.len() as u32);
+            let name_len = name.len() as u16;
+
+            // Local header.
+            put_u32(&mut self.buf, LOCAL_SIG);
+            self.common_header(true, crc, len, name_len);
+            self.buf.extend_from_slice(name.as_bytes());
+            self.buf.extend_from_slice(data);
+
+            // Central directory header.
+            put_u32(&mut self.central, CENTRAL_SIG);
+            put_u16(&mut self.central, 20);
+            self.common_header(false, crc, len, name_len);
+            // comment length, disk number, internal and external attributes
+            put_u16(&mut self.central, 0);
+            put_u16(&mut self.central, 0);
+            put_u16(&mut self.central, 0);
+            put_u32(&mut self.central, 0);
+            put_u32(&mut self.central, offset);
+            self.central.extend_from_slice(name.as_bytes());
+
+            self.count = self
+                .count
+                .checked_add(1)
+                .ok_or("too many files for a zip archive")?;
+            Ok(())
+        }
+
+        /// Header fields shared by local and central headers.
+        fn common_header(&mut self, local: bool, crc: u32, len: u32, name_len: u16) {
+            let buf = if local {
+                &mut self.buf
+            } else {
+                &mut self.central
+            };
+            // version needed, flags, method (stored), time, date
+            put_u16(buf, 20);
+            put_u16(buf, 0);
+            put_u16(buf, 0);
+            put_u16(buf, 0);
+            put_u16(buf, DOS_DATE);
+            put_u32(buf, crc);
+            put_u32(buf, len);
+            put_u32(buf, len);
+            put_u16(buf, name_len);
+            // extra field length
+            put_u16(buf, 0);
+        }
+
+        /// Finalizes the archive.
+        pub fn finish(mut self) -> Vec<u8> {
+            let (central_offset, central_len) = (self.buf.len() as u32, self.central.len() as u32);
+            self.buf.append(&mut self.central);
+            put_u32(&mut self.buf, END_SIG);
+            // disk numbers
+            put_u16(&mut self.buf, 0);
+            put_u16(&mut self.buf, 0);
+            put_u16(&mut self.buf, self.count);
+            put_u16(&mut self.buf, self.count);
+            put_u32(&mut self.buf, central_len);
+            put_u32(&mut self.buf, central_offset);
+            // comment length
+            put_u16(&mut self.buf, 0);
+            self.buf
+        }
+    }
+
+    fn put_u16(buf: &mut Vec<u8>, n: u16) {
+        buf.extend_from_slice(&n.to_le_bytes())
+    }
+    fn put_u32(buf: &mut Vec<u8>, n: u32) {
+        buf.extend_from_slice(&n.to_le_bytes())
+    }
+
+    fn get_u16(bytes: &[u8], at: usize) -> Res<u16> {
+        bytes
+            .get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| "unexpected end of zip archive".into())
+    }
+    fn get_u32(bytes: &[u8], at: usize) -> Res<u32> {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "unexpected end of zip archive".into())
+    }
+
+    /// Reads the files of an archive, in the order they appear in.
+    ///
+    /// Fails on compressed entries and CRC mismatches.
+    pub fn read(bytes: &[u8]) -> Res<Vec<(String, Vec<u8>)>> {
+        let mut res = vec![];
+        let mut at = 0;
+        while get_u32(bytes, at)? == LOCAL_SIG {
+            let method = get_u16(bytes, at + 8)?;
+            let crc = get_u32(bytes, at + 14)?;
+            let len = get_u32(bytes, at + 18)? as usize;
+            let name_len = get_u16(bytes, at + 26)? as usize;
+            let extra_len = get_u16(bytes, at + 28)? as usize;
+            let name_start = at + 30;
+            let data_start = name_start + name_len + extra_len;
+            let name = bytes
+                .get(name_start..name_start + name_len)
+                .ok_or("unexpected end of zip archive")?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            if method != 0 {
+                bail!("zip entry `{}` is compressed", name)
+            }
+            let data = bytes
+                .get(data_start..data_start + len)
+                .ok_or("unexpected end of zip archive")?;
+            if crc32(data) != crc {
+                bail!("CRC mismatch on zip entry `{}`", name)
+            }
+            res.push((name, data.to_vec()));
+            at = data_start + len;
+        }
+        if get_u32(bytes, at)? != CENTRAL_SIG && get_u32(bytes, at)? != END_SIG {
+            bail!("illegal zip archive, unexpected data at offset {}", at)
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> Meta {
+        Meta {
+            title: "Test & Book".into(),
+            authors: vec!["Someone".into()],
+            language: "en".into(),
+        }
+    }
+
+    fn chapters() -> Vec<Chapter> {
+        let files: BTreeMap<PathBuf, String> = vec![
+            (normalize("./a/readme.md"), "a.xhtml".to_string()),
+            (normalize("./b/readme.md"), "b.xhtml".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        vec![
+            Chapter {
+                stem: "a".into(),
+                title: "A".into(),
+                body: xhtml_body(
+                    "./a/readme.md",
+                    "# A\n\nSee [B](../b/readme.md#top).\n\n\\\n\\\n\n```rust ,no_run\n# hidden\nfn main() {}\n```\n",
+                    &files,
+                ),
+            },
+            Chapter {
+                stem: "b".into(),
+                title: "B".into(),
+                body: xhtml_body(
+                    "./b/readme.md",
+                    "# B\n\n<details>\n\t<summary>More</summary>\n\n```text\n(check-sat)\n```\n</details>\n",
+                    &files,
+                ),
+            },
+        ]
+    }
+
+    #[test]
+    fn book_meta() {
+        let meta = Meta::load("../book.toml").unwrap();
+        assert!(meta.title.starts_with("Verification For Dummies"));
+        assert_eq!(meta.authors, vec!["Adrien Champion"]);
+        assert_eq!(meta.language, "en");
+    }
+
+    #[test]
+    fn valid_epub() {
+        let chapters = chapters();
+        let body = &chapters[0].body;
+        assert!(body.contains("<a href=\"b.xhtml#top\">B</a>"));
+        assert!(body.contains("<code class=\"language-rust\">fn main() {}\n</code>"));
+
+        let bytes = build(&meta(), &chapters).unwrap();
+        if let Err(e) = check(&bytes) {
+            e.pretty_eprint("| ");
+            panic!("generated EPUB is not valid")
+        }
+
+        let entries = zip::read(&bytes).unwrap();
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/content.opf",
+                "OEBPS/nav.xhtml",
+                "OEBPS/style.css",
+                "OEBPS/a.xhtml",
+                "OEBPS/b.xhtml",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_epub() {
+        let mut chapters = chapters();
+        chapters[1].body.push_str("<p>unclosed");
+        assert!(check(&build(&meta(), &chapters).unwrap()).is_err());
+
+        let mut zip = zip::Writer::new();
+        zip.add("META-INF/container.xml", b"<container></container>")
+            .unwrap();
+        zip.add("mimetype", b"application/epub+zip").unwrap();
+        assert!(check(&zip.finish()).is_err());
+    }
+}
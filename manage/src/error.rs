@@ -8,6 +8,14 @@ error_chain::error_chain! {
     foreign_links {
         Io(std::io::Error);
     }
+
+    errors {
+        /// A tool needed to run a snippet could not be found.
+        ToolUnavailable(tool: String) {
+            description("tool unavailable")
+            display("tool `{}` is not available", tool)
+        }
+    }
 }
 
 impl Error {
@@ -0,0 +1,544 @@
+//! Exercises for readers, graded by running mikino.
+//!
+//! Exercise `<chapter>/<name>` lives in `src/<chapter>/exercises/<name>`, next to the chapter's
+//! `code` directory, and consists of
+//!
+//! - `template.mkn`, the system readers start from;
+//! - `solution.mkn`, a reference solution;
+//! - `exercise.txt`, a description followed by the expected verdicts and the hints.
+//!
+//! The template and the solution are usually snippets of the chapter, `exercise.txt` can point to
+//! them with `template:` and `solution:` lines, relative to the exercise's directory.
+//!
+//! ```text
+//! Add candidates so that `i ≥ 0` is proved.
+//!
+//! template: ../../code/split_0_1.mkn
+//! solution: ../../code/split_0.mkn
+//!
+//! expect proved: i ≥ 0
+//! expect not_inductive: done then i = len
+//!
+//! hint: Look at the step counterexample for `i ≥ 0`.
+//! hint: ...
+//! ```
+//!
+//! Verdicts are named as in [`Verdict::name`]. Checking a reader's file runs mikino on it with the
+//! command of its `// CMD:` line and compares the verdicts it reports with the expected ones. By
+//! default, the file is the reader's working copy of the template, `target/exercises/<id>.mkn`
+//! under the book's root, created on the first check so that the template itself is never edited.
+//! Hints are staged: each failed attempt reveals one more hint, attempts are counted in
+//! `target/exercises` too. [`verify`] checks that the reference solutions meet their expectations
+//! and that the templates do not.
+
+use std::collections::BTreeMap;
+
+use crate::cex::{self, Verdict};
+
+prelude!();
+
+/// Directory of a chapter containing its exercises.
+pub const EXERCISES_DIR: &str = "exercises";
+/// Definition of an exercise.
+pub const DEFINITION_FILE: &str = "exercise.txt";
+/// Default template of an exercise.
+pub const TEMPLATE_FILE: &str = "template.mkn";
+/// Default reference solution of an exercise.
+pub const SOLUTION_FILE: &str = "solution.mkn";
+/// Directory where failed attempts are counted, and where readers' working copies live, relative to
+/// the book's root.
+pub const ATTEMPTS_DIR: &str = "target/exercises";
+
+/// An exercise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exercise {
+    /// Identifier, `<chapter>/<name>`.
+    pub id: String,
+    /// Directory of the exercise.
+    pub dir: PathBuf,
+    /// Description.
+    pub description: String,
+    /// Template readers start from.
+    pub template: PathBuf,
+    /// Reference solution.
+    pub solution: PathBuf,
+    /// Expected verdict for some candidates.
+    pub expected: BTreeMap<String, Verdict>,
+    /// Hints, in the order they are revealed.
+    pub hints: Vec<String>,
+    /// Directory of the attempts and working copies, [`ATTEMPTS_DIR`] under the book's root.
+    pub attempts: PathBuf,
+}
+impl Exercise {
+    /// Loads exercise `<chapter>/<name>` from the book's `src` directory.
+    pub fn load(src: impl AsRef<Path>, id: &str) -> Res<Self> {
+        let (chapter, name) = match id.split_once('/') {
+            Some(pair) => pair,
+            None => bail!("illegal exercise `{}`, expected `<chapter>/<exercise>`", id),
+        };
+        let dir = src.as_ref().join(chapter).join(EXERCISES_DIR).join(name);
+        if !dir.is_dir() {
+            bail!(
+                "unknown exercise `{}`, `{}` does not exist",
+                id,
+                dir.display()
+            )
+        }
+        let path = dir.join(DEFINITION_FILE);
+        let mut res = Self::parse(id, &dir, &load_file(&path)?)
+            .chain_err(|| format!("in `{}`", path.display()))?;
+        let root = src.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        res.attempts = root.join(ATTEMPTS_DIR);
+        Ok(res)
+    }
+
+    /// Parses the definition of an exercise.
+    ///
+    /// Attempts are in [`ATTEMPTS_DIR`], relative to the current directory.
+    pub fn parse(id: &str, dir: &Path, text: &str) -> Res<Self> {
+        let mut res = Self {
+            id: id.into(),
+            dir: dir.into(),
+            description: String::new(),
+            template: dir.join(TEMPLATE_FILE),
+            solution: dir.join(SOLUTION_FILE),
+            expected: BTreeMap::new(),
+            hints: vec![],
+            attempts: ATTEMPTS_DIR.into(),
+        };
+        // True once the description is over.
+        let mut described = false;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if ["expect ", "hint:", "template:", "solution:"]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+            {
+                described = true
+            }
+            if let Some(rest) = line.strip_prefix("expect ") {
+                let (verdict, candidate) = match rest.split_once(':') {
+                    Some((verdict, candidate)) => (verdict.trim(), candidate.trim()),
+                    None => bail!(
+                        "{}: expected `expect <verdict>: <candidate>`, found `{}`",
+                        idx + 1,
+                        line
+                    ),
+                };
+                let verdict = Verdict::of_name(verdict)
+                    .ok_or_else(|| format!("{}: unknown verdict `{}`", idx + 1, verdict))?;
+                if res.expected.insert(candidate.into(), verdict).is_some() {
+                    bail!("{}: candidate `{}` is expected twice", idx + 1, candidate)
+                }
+            } else if let Some(hint) = line.strip_prefix("hint:") {
+                res.hints.push(hint.trim().into())
+            } else if let Some(template) = line.strip_prefix("template:") {
+                res.template = dir.join(template.trim())
+            } else if let Some(solution) = line.strip_prefix("solution:") {
+                res.solution = dir.join(solution.trim())
+            } else if line.is_empty() {
+                if !res.description.is_empty() && !res.description.ends_with("\n\n") {
+                    res.description.push('\n')
+                }
+            } else if !described {
+                res.description.push_str(line);
+                res.description.push('\n')
+            } else {
+                bail!(
+                    "{}: expected `expect`, `hint:`, `template:` or `solution:` after the \
+                    description, found `{}`",
+                    idx + 1,
+                    line
+                )
+            }
+        }
+        res.description.truncate(res.description.trim_end().len());
+        if res.expected.is_empty() {
+            bail!("exercise `{}` expects nothing", id)
+        }
+        Ok(res)
+    }
+
+    /// First sentence of the description.
+    pub fn summary(&self) -> String {
+        let description = self.description.replace('\n', " ");
+        match description.split_once(". ") {
+            Some((first, _)) => format!("{}.", first),
+            None => description,
+        }
+    }
+
+    /// Path to the reader's working copy of the template, `<attempts>/<id>.mkn`.
+    pub fn work_file(&self) -> PathBuf {
+        self.attempts.join(format!("{}.mkn", self.id))
+    }
+
+    /// Creates the reader's working copy of the template if it does not exist, returns its path.
+    ///
+    /// The copy does not contain the template's `// ANCHOR` markers.
+    pub fn working_copy(&self) -> Res<PathBuf> {
+        let path = self.work_file();
+        if path.exists() {
+            return Ok(path);
+        }
+        let template: String = load_file(&self.template)?
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("// ANCHOR"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .chain_err(|| format!("while creating directory `{}`", dir.display()))?
+        }
+        fs::write(&path, template).chain_err(|| format!("while writing `{}`", path.display()))?;
+        println!(
+            "copied the template of exercise `{}` to `{}`, edit this file to solve it",
+            self.id,
+            path.display()
+        );
+        Ok(path)
+    }
+
+    /// Compares the verdicts of a mikino output with the expected ones.
+    ///
+    /// Returns the explanation of each mismatch, no mismatch means the exercise is solved. A
+    /// candidate meets its expectation if any analysis of the output, induction or BMC, gives it
+    /// the expected verdict, see [`cex::Analysis::verdict`].
+    pub fn grade(&self, output: &str) -> Res<Vec<String>> {
+        let output = cex::parse(output).chain_err(|| "while parsing mikino's output")?;
+        let failed = !output.errors.is_empty();
+        let mut res: Vec<String> = output
+            .errors
+            .iter()
+            .map(|error| format!("mikino failed:\n{}", error))
+            .collect();
+        for (candidate, expected) in &self.expected {
+            let verdicts: Vec<Verdict> = output
+                .analyses
+                .iter()
+                .filter_map(|analysis| analysis.verdict(candidate))
+                .collect();
+            if verdicts.contains(expected) {
+                continue;
+            }
+            res.push(match verdicts.as_slice() {
+                // Candidates are missing anyway if mikino failed.
+                [] if failed => continue,
+                [] => format!("candidate `{}` is missing", candidate),
+                verdicts => format!(
+                    "candidate `{}` is {}, expected {}",
+                    candidate,
+                    verdicts
+                        .iter()
+                        .map(|verdict| verdict.name())
+                        .collect::<Vec<_>>()
+                        .join(" and "),
+                    expected.name()
+                ),
+            })
+        }
+        Ok(res)
+    }
+
+    /// Runs mikino on a file and grades its output, see [`Self::grade`].
+    pub fn check(&self, conf: &Conf, file: &Path) -> Res<Vec<String>> {
+        let output = match test::snippet_output(conf, file)? {
+            Some(output) => output,
+            None => bail!(
+                "mikino checking is deactivated, cannot check `{}`",
+                file.display()
+            ),
+        };
+        self.grade(&output)
+            .chain_err(|| format!("while grading `{}`", file.display()))
+    }
+}
+
+/// Loads all the exercises of the book's `src` directory, sorted by identifier.
+pub fn all(src: impl AsRef<Path>) -> Res<Vec<Exercise>> {
+    let src = src.as_ref();
+    let read_dir = |dir: &Path| -> Res<Vec<PathBuf>> {
+        let mut res = vec![];
+        for entry in dir
+            .read_dir()
+            .chain_err(|| format!("while reading directory `{}`", dir.display()))?
+        {
+            let path = entry
+                .chain_err(|| format!("while reading directory `{}`", dir.display()))?
+                .path();
+            if path.is_dir() {
+                res.push(path)
+            }
+        }
+        Ok(res)
+    };
+    let mut res = vec![];
+    for chapter in read_dir(src)? {
+        let dir = chapter.join(EXERCISES_DIR);
+        if !dir.is_dir() {
+            continue;
+        }
+        for exercise in read_dir(&dir)? {
+            let name = |path: &Path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            res.push(Exercise::load(
+                src,
+                &format!("{}/{}", name(&chapter), name(&exercise)),
+            )?)
+        }
+    }
+    res.sort_by(|lft, rgt| lft.id.cmp(&rgt.id));
+    Ok(res)
+}
+
+/// Checks the reference solutions of all exercises meet their expectations.
+///
+/// Also warns about templates that already meet them.
+pub fn verify(conf: &Conf, src: impl AsRef<Path>, report: &mut Report) -> Res<()> {
+    if !conf.get_mikino()?.0 {
+        log::warn!("mikino checking deactivated, skipping exercises");
+        return Ok(());
+    }
+    let chapters = crate::chapter::Chapters::load(conf, &src)?;
+    for exercise in all(&src)? {
+        if !chapters.is_selected(&exercise.dir) {
+            continue;
+        }
+        log::debug!("verifying exercise `{}`", exercise.id);
+        for (path, is_solution) in [
+            (exercise.solution.clone(), true),
+            (exercise.template.clone(), false),
+        ] {
+            let mismatches = match exercise.check(conf, &path) {
+                Ok(mismatches) => mismatches,
+                Err(e) => {
+                    report.push(Diagnostic::of_error("exercise", &e).set_file(&path));
+                    continue;
+                }
+            };
+            if is_solution {
+                for mismatch in mismatches {
+                    report.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            "exercise",
+                            format!("reference solution of `{}`: {}", exercise.id, mismatch),
+                        )
+                        .set_file(&path),
+                    )
+                }
+            } else if mismatches.is_empty() {
+                report.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        "exercise",
+                        format!(
+                            "the template of `{}` already meets the expectations",
+                            exercise.id
+                        ),
+                    )
+                    .set_file(&path),
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs [`verify`] and reports the result, fails if there are errors.
+pub fn run_verify(conf: &Conf, src: impl AsRef<Path>) -> Res<()> {
+    let mut report = Report::new();
+    verify(conf, src, &mut report)?;
+    if !report.diagnostics.is_empty() {
+        eprintln!("|===| Report:");
+        report.pretty_eprint("| ");
+        eprintln!("|===|");
+    }
+    let errors = report.count(Severity::Error);
+    if errors > 0 {
+        bail!(
+            "{} error(s), {} warning(s)",
+            errors,
+            report.count(Severity::Warning)
+        )
+    }
+    Ok(())
+}
+
+/// Checks a reader's attempt at an exercise.
+pub struct Check<'s> {
+    conf: &'s Conf<'s>,
+    src: &'s str,
+    id: &'s str,
+    file: Option<&'s str>,
+}
+impl<'s> Check<'s> {
+    /// Constructor.
+    pub fn new(conf: &'s Conf<'s>, src: &'s str, id: &'s str) -> Self {
+        Self {
+            conf,
+            src,
+            id,
+            file: None,
+        }
+    }
+
+    /// Sets the file to check, the reader's working copy of the template if none.
+    pub fn set_file(mut self, file: Option<&'s str>) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Exercise identifier.
+    pub fn id(&self) -> &str {
+        self.id
+    }
+
+    /// Checks the attempt, reveals one more hint if it fails.
+    pub fn run(&self) -> Res<()> {
+        let exercise = Exercise::load(self.src, self.id)?;
+        let file = match self.file {
+            Some(file) => PathBuf::from(file),
+            None => exercise.working_copy()?,
+        };
+        let mismatches = exercise.check(self.conf, &file)?;
+        let attempts = exercise.attempts.join(self.id);
+
+        if mismatches.is_empty() {
+            println!("exercise `{}` solved 🎉", self.id);
+            if attempts.exists() {
+                fs::remove_file(&attempts)
+                    .chain_err(|| format!("while removing `{}`", attempts.display()))?
+            }
+            return Ok(());
+        }
+
+        println!("|===| Exercise `{}`", self.id);
+        for line in exercise.description.lines() {
+            println!("| {}", line)
+        }
+        println!("|");
+        for mismatch in &mismatches {
+            for (idx, line) in mismatch.lines().enumerate() {
+                println!("| {} {}", if idx == 0 { "-" } else { " " }, line)
+            }
+        }
+        let count = match load_file(&attempts) {
+            Ok(text) => text.trim().parse::<usize>().unwrap_or(0) + 1,
+            Err(_) => 1,
+        };
+        if let Some(dir) = attempts.parent() {
+            fs::create_dir_all(dir)
+                .chain_err(|| format!("while creating directory `{}`", dir.display()))?
+        }
+        fs::write(&attempts, count.to_string())
+            .chain_err(|| format!("while writing `{}`", attempts.display()))?;
+        let total = exercise.hints.len();
+        for (idx, hint) in exercise.hints.iter().take(count).enumerate() {
+            println!("|");
+            println!("| hint {}/{}: {}", idx + 1, total, hint)
+        }
+        println!("|===|");
+        bail!("exercise `{}` is not solved yet", self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text of a system without its `ANCHOR` markers.
+    fn without_anchors(path: impl AsRef<Path>) -> String {
+        load_file(path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with("// ANCHOR"))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    #[test]
+    fn definitions() {
+        let exercise = Exercise::parse(
+            "ch/1",
+            "dir".as_ref(),
+            "Prove\nthings.\n\nSecond paragraph.\n\nexpect proved: a\nhint: one\n\nhint: two\n",
+        )
+        .unwrap();
+        assert_eq!(exercise.description, "Prove\nthings.\n\nSecond paragraph.");
+        assert_eq!(exercise.summary(), "Prove things.");
+        assert_eq!(exercise.expected["a"], Verdict::Proved);
+        assert_eq!(exercise.hints, vec!["one", "two"]);
+
+        let err = |text| {
+            Exercise::parse("ch/1", "dir".as_ref(), text)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(err("expect done: a\n"), "1: unknown verdict `done`");
+        assert_eq!(
+            err("expect proved: a\nmore text\n"),
+            "2: expected `expect`, `hint:`, `template:` or `solution:` after the description, \
+            found `more text`"
+        );
+        assert_eq!(err("no expectation\n"), "exercise `ch/1` expects nothing");
+    }
+
+    /// Solutions and templates are the chapter's snippets, whose outputs are known.
+    #[test]
+    fn strength() {
+        let exercises = all("../src").unwrap();
+        let ids: Vec<&str> = exercises.iter().map(|ex| ex.id.as_str()).collect();
+        assert_eq!(ids, vec!["strength/1", "strength/2"]);
+
+        let code = Path::new("../src/strength/code");
+        let snippets = [("split_0_1", "split_0"), ("split_0", "split_2")];
+        for (exercise, (template, solution)) in exercises.iter().zip(snippets) {
+            let template = code.join(format!("{}.mkn", template));
+            let solution = code.join(format!("{}.mkn", solution));
+            let canonical = |path: &Path| path.canonicalize().unwrap();
+            assert_eq!(canonical(&exercise.template), canonical(&template));
+            assert_eq!(canonical(&exercise.solution), canonical(&solution));
+            let output = |path: &Path| load_file(path.with_extension("mkn.out")).unwrap();
+            assert!(exercise.grade(&output(&solution)).unwrap().is_empty());
+            assert!(!exercise.grade(&output(&template)).unwrap().is_empty());
+        }
+
+        // Readers edit a copy of the template, under the book's root.
+        assert_eq!(
+            exercises[0].work_file(),
+            Path::new("../target/exercises/strength/1.mkn")
+        );
+        let mut exercise = exercises[0].clone();
+        exercise.attempts =
+            std::env::temp_dir().join(format!("manage_exercises_{}", std::process::id()));
+        let work_file = exercise.working_copy().unwrap();
+        assert_eq!(work_file, exercise.attempts.join("strength/1.mkn"));
+        assert_eq!(
+            load_file(&work_file).unwrap(),
+            without_anchors(code.join("split_0_1.mkn"))
+        );
+        fs::remove_dir_all(&exercise.attempts).unwrap();
+
+        let mismatches = exercises[1]
+            .grade(&load_file(code.join("split_0_1.mkn.out")).unwrap())
+            .unwrap();
+        assert_eq!(
+            mismatches,
+            vec![
+                "candidate `done then i = len` is not_inductive, expected proved",
+                "candidate `grouping > 0` is missing",
+                "candidate `i ≥ 0` is not_inductive, expected proved",
+                "candidate `len grouping related` is missing",
+            ]
+        );
+        let mismatches = exercises[1]
+            .grade(&load_file(code.join("split_1.mkn.out")).unwrap())
+            .unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].starts_with("mikino failed:\n"));
+    }
+}
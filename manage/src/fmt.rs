@@ -0,0 +1,565 @@
+//! Canonical formatter for SMT-LIB 2 files.
+//!
+//! The formatter keeps the layout of the file: elements that are on the same line stay on the same
+//! line, and an element starting a line still does. What it normalizes is the spacing, a single
+//! space between elements and none inside parentheses, and the indentation: a line is indented
+//! four more spaces than the line opening its innermost unclosed list, and a line starting with a
+//! closing parenthesis is indented like the line that opened its list.
+//!
+//! A list written on a single line that does not fit in the width is broken: its head, and the
+//! first argument of some commands and operators (`define-fun`, `=>`, `ite`...), stay on the first
+//! line, the other elements go on the following lines with four more spaces of indentation, and
+//! the closing parenthesis gets its own line. Consecutive atoms are packed on the same line as long
+//! as they fit. Lists with a single atom argument, such as `echo` commands, are never broken.
+//!
+//! Comments, `; ANCHOR:` markers included, are kept verbatim. Own-line comments are indented like
+//! the elements around them, except for comments such as `; ^~~~ here` pointing at the line above
+//! them: they move along with what they point at. Blank lines are kept, collapsed to a single one.
+
+use crate::smt2::{self, Pos, Token, TokenKind};
+
+prelude!();
+
+/// Default maximum line width.
+pub const DEFAULT_WIDTH: usize = 80;
+/// Indentation step.
+const INDENT: usize = 4;
+
+/// Heads after which the first argument stays on the head's line.
+const ONE_HEADER: &[&str] = &[
+    "declare-const",
+    "declare-fun",
+    "define-fun",
+    "define-fun-rec",
+    "declare-sort",
+    "define-sort",
+    "declare-datatype",
+    "=>",
+    "=",
+    "ite",
+    "let",
+    "forall",
+    "exists",
+    "match",
+    "!",
+];
+
+/// Formats SMT-LIB 2 files in place, or checks that they are formatted.
+pub struct Fmt<'s> {
+    targets: Vec<&'s str>,
+    check: bool,
+    width: usize,
+}
+impl<'s> Fmt<'s> {
+    /// Constructor.
+    pub fn new(targets: Vec<&'s str>) -> Self {
+        Self {
+            targets,
+            check: false,
+            width: DEFAULT_WIDTH,
+        }
+    }
+
+    /// If true, only checks files are formatted and fails if some are not.
+    pub fn set_check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+    /// Sets the maximum line width, [`DEFAULT_WIDTH`] by default.
+    pub fn set_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+    /// Targets accessor.
+    pub fn targets(&self) -> &[&'s str] {
+        &self.targets
+    }
+
+    /// Formats or checks the targets.
+    pub fn run(&self) -> Res<()> {
+        let mut files = vec![];
+        for target in &self.targets {
+            smt2::collect_files(Path::new(target), &mut files)?;
+        }
+        files.sort();
+
+        let mut unformatted = vec![];
+        for file in &files {
+            let text = load_file(file)?;
+            let formatted = format(&text, self.width)
+                .chain_err(|| format!("while formatting `{}`", file.display()))?;
+            if formatted == text {
+                continue;
+            }
+            if self.check {
+                log::warn!("`{}` is not formatted", file.display());
+                unformatted.push(file)
+            } else {
+                log::info!("formatting `{}`", file.display());
+                fs::write(file, formatted)
+                    .chain_err(|| format!("while writing `{}`", file.display()))?;
+            }
+        }
+
+        if !unformatted.is_empty() {
+            bail!(
+                "{} of {} file(s) not formatted, run `cargo run -- fmt` to format them",
+                unformatted.len(),
+                files.len()
+            )
+        }
+        log::info!("{} file(s) formatted", files.len());
+        Ok(())
+    }
+}
+
+/// A node of the syntax tree, with its comments.
+#[derive(Debug, Clone)]
+enum Node<'a> {
+    Atom(&'a str),
+    List(Vec<Item<'a>>),
+    /// Comment that is not at the end of an item's line.
+    Comment(&'a str),
+}
+
+/// An element of a list, or a top-level element.
+#[derive(Debug, Clone)]
+struct Item<'a> {
+    node: Node<'a>,
+    /// Position of the item's first token in the source.
+    pos: Pos,
+    /// Line of the item's last token in the source, its closing parenthesis for lists.
+    end: usize,
+    /// True if there is a blank line before the item in the source.
+    blank_before: bool,
+    /// Comment at the end of the item's last line.
+    trailing: Option<&'a str>,
+}
+impl<'a> Item<'a> {
+    /// Single-line version of the item, if it contains no comments.
+    ///
+    /// The item's own trailing comment is not part of the result.
+    fn flat(&self) -> Option<String> {
+        match &self.node {
+            Node::Atom(atom) => Some(atom.to_string()),
+            Node::Comment(_) => None,
+            Node::List(items) => {
+                let mut res = "(".to_string();
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        res.push(' ')
+                    }
+                    if item.trailing.is_some() {
+                        return None;
+                    }
+                    res.push_str(&item.flat()?)
+                }
+                res.push(')');
+                Some(res)
+            }
+        }
+    }
+
+    /// True if the item is a list whose only argument is an atom, such as `(echo "...")`.
+    ///
+    /// Breaking such a list does not make it narrower in any meaningful way.
+    fn unbreakable(&self) -> bool {
+        match &self.node {
+            Node::List(items) => {
+                items.len() <= 2 && items.iter().all(|item| matches!(item.node, Node::Atom(_)))
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Line on which a token ends.
+fn end_line(token: &Token) -> usize {
+    token.pos.line + token.text.matches('\n').count()
+}
+
+/// Builds the syntax tree of a text.
+fn tree(text: &str) -> Res<Vec<Item<'_>>> {
+    let (tokens, errors) = smt2::tokenize(text);
+    if let Some(error) = errors.first() {
+        bail!("syntax error at {}: {}", error.pos, error.msg)
+    }
+
+    // Open lists with the blank line flag and position of their opening parenthesis, the
+    // top-level at the bottom.
+    let mut stack: Vec<(bool, Pos, Vec<Item>)> = vec![(false, Pos::new(1, 1), vec![])];
+    // Last line of the previous token, `0` if none.
+    let mut last_line = 0;
+    // True if the previous token closed or completed an item that can get a trailing comment.
+    let mut can_trail = false;
+
+    for token in &tokens {
+        let blank_before = last_line > 0 && token.pos.line > last_line + 1;
+        match token.kind {
+            TokenKind::Comment if can_trail && token.pos.line == last_line => {
+                let (_, _, items) = stack.last_mut().expect("non-empty stack");
+                let item = items.last_mut().expect("`can_trail` implies an item");
+                item.trailing = Some(token.text);
+                can_trail = false;
+            }
+            TokenKind::Open => {
+                stack.push((blank_before, token.pos, vec![]));
+                can_trail = false;
+            }
+            TokenKind::Close => {
+                let (blank_before, pos, items) = stack.pop().expect("non-empty stack");
+                let parent = match stack.last_mut() {
+                    Some((_, _, parent)) => parent,
+                    None => bail!("unexpected closing parenthesis at {}", token.pos),
+                };
+                parent.push(Item {
+                    node: Node::List(items),
+                    pos,
+                    end: token.pos.line,
+                    blank_before,
+                    trailing: None,
+                });
+                can_trail = true;
+            }
+            kind => {
+                let node = if kind == TokenKind::Comment {
+                    Node::Comment(token.text)
+                } else {
+                    Node::Atom(token.text)
+                };
+                stack.last_mut().expect("non-empty stack").2.push(Item {
+                    node,
+                    pos: token.pos,
+                    end: end_line(token),
+                    blank_before,
+                    trailing: None,
+                });
+                can_trail = kind != TokenKind::Comment;
+            }
+        }
+        last_line = end_line(token);
+    }
+
+    if stack.len() != 1 {
+        bail!("unclosed parenthesis")
+    }
+    Ok(stack.pop().expect("non-empty stack").2)
+}
+
+/// Formats an SMT-LIB 2 text.
+pub fn format(text: &str, width: usize) -> Res<String> {
+    let items = tree(text)?;
+    let mut printer = Printer {
+        out: String::with_capacity(text.len()),
+        width,
+        printed: vec![],
+    };
+    printer.elems(&items, 0, 0);
+    if !printer.out.is_empty() {
+        printer.out.push('\n')
+    }
+
+    // Sanity check, formatting only changes whitespace.
+    let significant = |text: &str| -> Vec<String> {
+        smt2::tokenize(text)
+            .0
+            .iter()
+            .map(|token| token.text.to_string())
+            .collect()
+    };
+    if significant(text) != significant(&printer.out) {
+        bail!("[internal] formatting changed the content of the script")
+    }
+
+    Ok(printer.out)
+}
+
+/// Pretty-printer state.
+struct Printer {
+    out: String,
+    width: usize,
+    /// Source line, source column, length and output column of the atoms and opening parentheses
+    /// printed so far, used to move pointer comments along with what they point at.
+    printed: Vec<(usize, usize, usize, usize)>,
+}
+impl Printer {
+    /// Start of the last line of the output.
+    fn line_start(&self) -> usize {
+        self.out.rfind('\n').map(|idx| idx + 1).unwrap_or(0)
+    }
+    /// Column of the end of the output.
+    fn col(&self) -> usize {
+        self.out[self.line_start()..].chars().count()
+    }
+    /// Indentation of the last line of the output.
+    fn line_indent(&self) -> usize {
+        let line = &self.out[self.line_start()..];
+        line.len() - line.trim_start_matches(' ').len()
+    }
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+    }
+    fn trailing(&mut self, item: &Item) {
+        if let Some(comment) = item.trailing {
+            self.out.push(' ');
+            self.out.push_str(comment);
+        }
+    }
+    /// Prints some text from the source at `pos`.
+    fn token(&mut self, pos: Pos, text: &str) {
+        self.printed
+            .push((pos.line, pos.col, text.chars().count(), self.col() + 1));
+        self.out.push_str(text)
+    }
+
+    /// Prints the elements of a list or the top-level items, `line` is the source line of what
+    /// precedes them.
+    ///
+    /// Elements that are on the same line in the source stay on the same line, the others start a
+    /// new line with `indent` spaces.
+    fn elems(&mut self, items: &[Item], indent: usize, mut line: usize) -> usize {
+        for item in items {
+            if item.pos.line == line {
+                if !self.out.ends_with('(') {
+                    self.out.push(' ')
+                }
+            } else if !self.out.is_empty() {
+                if item.blank_before {
+                    self.out.push('\n')
+                }
+                self.newline(indent);
+            }
+            self.item(item, indent);
+            line = item.end;
+        }
+        line
+    }
+
+    /// Prints an item, keeping its line breaks, `indent` is the indentation of its own-line
+    /// comments.
+    fn item(&mut self, item: &Item, indent: usize) {
+        let items = match &item.node {
+            Node::Atom(atom) => {
+                self.token(item.pos, atom);
+                return self.trailing(item);
+            }
+            Node::Comment(comment) => return self.comment(item, comment, indent),
+            Node::List(items) => items,
+        };
+        if item.pos.line == item.end {
+            if let Some(flat) = item.flat() {
+                if self.col() + flat.chars().count() > self.width && !item.unbreakable() {
+                    self.canonical(item)
+                } else {
+                    self.flat(item);
+                    self.trailing(item)
+                }
+                return;
+            }
+        }
+
+        let indent = self.line_indent();
+        self.token(item.pos, "(");
+        let line = self.elems(items, indent + INDENT, item.pos.line);
+        if line != item.end {
+            self.newline(indent)
+        }
+        self.out.push(')');
+        self.trailing(item);
+    }
+
+    /// Prints an own-line comment at `indent`, or below what it points at for pointer comments
+    /// such as `; ^~~~ here`.
+    fn comment(&mut self, item: &Item, comment: &str, indent: usize) {
+        let line_start = self.line_start();
+        if !self.out[line_start..].trim().is_empty() {
+            return self.out.push_str(comment);
+        }
+        let col = self.pointer_col(item, comment).unwrap_or(indent + 1);
+        self.out.truncate(line_start);
+        self.out.extend(std::iter::repeat_n(' ', col - 1));
+        self.out.push_str(comment)
+    }
+
+    /// Output column of a pointer comment, `None` if it is not one.
+    ///
+    /// A pointer comment contains a `^` that is under an atom or parenthesis of the line above.
+    fn pointer_col(&self, item: &Item, comment: &str) -> Option<usize> {
+        let caret = item.pos.col + comment[..comment.find('^')?].chars().count();
+        let (_, col, _, out_col) = self.printed.iter().rev().find(|(line, col, len, _)| {
+            line + 1 == item.pos.line && *col <= caret && caret < col + len
+        })?;
+        let shifted = item.pos.col + out_col + caret - col;
+        shifted.checked_sub(caret).filter(|col| *col > 0)
+    }
+
+    /// Prints an item on a single line, it must not contain comments.
+    fn flat(&mut self, item: &Item) {
+        match &item.node {
+            Node::Atom(atom) => self.token(item.pos, atom),
+            Node::Comment(_) => unreachable!("flat items have no comments"),
+            Node::List(items) => {
+                self.token(item.pos, "(");
+                for (idx, elem) in items.iter().enumerate() {
+                    if idx > 0 {
+                        self.out.push(' ')
+                    }
+                    self.flat(elem)
+                }
+                self.out.push(')')
+            }
+        }
+    }
+
+    /// Prints an item in the canonical style, breaking lists that do not fit in the width; it
+    /// must not contain comments.
+    fn canonical(&mut self, item: &Item) {
+        let items = match &item.node {
+            Node::List(items) => items,
+            _ => {
+                self.flat(item);
+                return self.trailing(item);
+            }
+        };
+        if let Some(flat) = item.flat() {
+            if self.col() + flat.chars().count() <= self.width || item.unbreakable() {
+                self.flat(item);
+                return self.trailing(item);
+            }
+        }
+
+        let indent = self.line_indent();
+        self.token(item.pos, "(");
+        let body_indent = indent + INDENT;
+        let mut rest = items.iter().peekable();
+
+        // First line: head and header argument, if any.
+        if let Some(head) = rest.peek().cloned() {
+            if let Node::Atom(atom) = head.node {
+                self.token(head.pos, atom);
+                rest.next();
+                if ONE_HEADER.contains(&atom) {
+                    if let Some(flat) = rest.peek().and_then(|arg| arg.flat()) {
+                        if self.col() + 1 + flat.chars().count() <= self.width {
+                            self.out.push(' ');
+                            self.flat(rest.next().expect("peeked"));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Body, atoms are packed.
+        let mut packing = false;
+        for elem in rest {
+            let packable = matches!(elem.node, Node::Atom(_));
+            let fits = packable
+                && self.col() + 1 + elem.flat().map(|f| f.len()).unwrap_or(0) <= self.width;
+            if packing && fits {
+                self.out.push(' ');
+            } else {
+                self.newline(body_indent);
+            }
+            self.canonical(elem);
+            packing = packable;
+        }
+
+        self.newline(indent);
+        self.out.push(')');
+        self.trailing(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let text = "\
+; ANCHOR: all
+(declare-const x Int) ; trailing
+(assert   (> x 7))
+   ;      ^ pointer
+
+
+(define-fun trans ( (a Int) (b Int) ) Bool
+	(and (= b (+ a 1))
+	(>= a 0) (>= b 0)
+  )
+)
+(assert (=> actlit
+        ; own-line comment
+    (not (> x 7))
+))
+; ANCHOR_END: all";
+        let expected = "\
+; ANCHOR: all
+(declare-const x Int) ; trailing
+(assert (> x 7))
+ ;      ^ pointer
+
+(define-fun trans ((a Int) (b Int)) Bool
+    (and (= b (+ a 1))
+        (>= a 0) (>= b 0)
+    )
+)
+(assert (=> actlit
+    ; own-line comment
+    (not (> x 7))
+))
+; ANCHOR_END: all
+";
+        let formatted = format(text, 80).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted, 80).unwrap(), formatted);
+    }
+
+    #[test]
+    fn width() {
+        let text =
+            "(assert (trans start_stop_0 reset_0 is_counting_0 cnt_0 start_stop_1 reset_1))\n";
+        let expected = "\
+(assert
+    (trans
+        start_stop_0 reset_0 is_counting_0
+        cnt_0 start_stop_1 reset_1
+    )
+)
+";
+        let formatted = format(text, 44).unwrap();
+        assert_eq!(formatted, expected);
+        assert!(formatted.lines().all(|line| line.len() <= 44));
+        assert_eq!(format(&formatted, 44).unwrap(), formatted);
+
+        // Lists with a single atom argument are not broken.
+        let text = "(echo \"; info: this line is a bit too long for the width\")\n";
+        assert_eq!(format(text, 44).unwrap(), text);
+
+        assert!(format("(assert (> x 7)", 80).is_err());
+    }
+
+    #[test]
+    fn book_snippets() {
+        let mut files = vec![];
+        smt2::collect_files(Path::new("../src"), &mut files).unwrap();
+        for file in files {
+            let text = load_file(&file).unwrap();
+            let formatted = format(&text, DEFAULT_WIDTH).unwrap();
+            assert_eq!(
+                formatted,
+                text,
+                "`{}` is not formatted, run `cargo run -- fmt`",
+                file.display()
+            );
+            let anchors = |text: &str| -> Vec<String> {
+                text.lines()
+                    .filter(|line| line.contains("ANCHOR"))
+                    .map(|line| line.trim().to_string())
+                    .collect()
+            };
+            assert_eq!(anchors(&text), anchors(&formatted));
+        }
+    }
+}
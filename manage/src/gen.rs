@@ -0,0 +1,1269 @@
+//! Generates the SMT-LIB 2 encodings of a transition system from a `.mkn` description.
+//!
+//! From `<dir>/<name>.mkn`, generates in the target directory
+//!
+//! - `<name>_bmc.smt2`: definitions of `init`, `trans` and the candidates, and BMC checks from
+//!   depth `0` to `k` using activation literals;
+//! - `<name>_base.smt2`: the base case of the induction check;
+//! - `<name>_step.smt2`: the step case of the induction check.
+//!
+//! Scripts follow the book's conventions: `|s.<var>|`/`|s'.<var>|` formal parameters, `<var>_<k>`
+//! for the variables of state `k`, and `ANCHOR` markers around each part.
+//!
+//! In check mode, nothing is written. Instead, fails if the generated scripts differ from the ones
+//! in the target directory, or if a book snippet declaring the system as its source is not
+//! consistent with it. Snippets declare their source with a `SYSTEM: <path>` comment, the path
+//! being relative to the book's root, in their header:
+//!
+//! ```text
+//! ; SYSTEM: systems/stopwatch.mkn
+//! ; ANCHOR: all
+//! ...
+//! ```
+//!
+//! The `init` and `trans` definitions of such `.smt2` snippets must be equivalent to the system's,
+//! which is checked with z3. Their satisfiability checks must also unroll the system the way one of
+//! the generated scripts does, see [`Check`]. The `SYSTEM` comment can list the generated scripts,
+//! among `bmc`, `base` and `step`, whose checks the snippet performs, up to the depth it unrolls to:
+//!
+//! ```text
+//! ; SYSTEM: systems/stopwatch.mkn base step
+//! ```
+//!
+//! For `.mkn` snippets, the `SYSTEM` comment must come after the `// CMD: ...` line, and state
+//! variables, initial predicate and transition relation must be the same as the system's.
+//!
+//! Checking `.smt2` snippets requires z3, check mode fails if it is not available.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    engine::{Solver, Sort, System},
+    mkn::Mkn,
+    smt2::{Pos, Sexp},
+};
+
+prelude!();
+
+/// Default maximum BMC depth.
+pub const DEFAULT_DEPTH: usize = 3;
+
+/// Prefix of the comment declaring the source of a snippet.
+const SYSTEM_PREF: &str = "SYSTEM:";
+
+/// Generates scripts for a `.mkn` system.
+pub struct Gen<'s> {
+    source: &'s str,
+    target: &'s str,
+    depth: usize,
+    check: bool,
+    /// Book source directory, for check mode.
+    src: &'s str,
+    z3_cmd: &'s str,
+}
+impl<'s> Gen<'s> {
+    /// Constructor, generates up to depth [`DEFAULT_DEPTH`].
+    pub fn new(source: &'s str, target: &'s str) -> Self {
+        Self {
+            source,
+            target,
+            depth: DEFAULT_DEPTH,
+            check: false,
+            src: "src",
+            z3_cmd: "z3",
+        }
+    }
+
+    /// Sets the maximum BMC depth.
+    pub fn set_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+    /// Sets check mode.
+    pub fn set_check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+    /// Sets the book source directory, for check mode.
+    pub fn set_src(mut self, src: &'s str) -> Self {
+        self.src = src;
+        self
+    }
+    /// Sets the z3 command, for check mode.
+    pub fn set_z3_cmd(mut self, z3_cmd: &'s str) -> Self {
+        self.z3_cmd = z3_cmd;
+        self
+    }
+
+    /// Source system.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+    /// Target directory.
+    pub fn target(&self) -> &str {
+        self.target
+    }
+
+    /// Generated files, with their content.
+    pub fn scripts(&self) -> Res<Vec<(PathBuf, String)>> {
+        let path = Path::new(self.source);
+        let mkn = Mkn::load(path)?;
+        let system = mkn.to_system();
+        let name = match path.file_stem() {
+            Some(name) => name.to_string_lossy(),
+            None => bail!("illegal system path `{}`", self.source),
+        };
+        let source = self.source_from_root();
+        let target = Path::new(self.target);
+        Ok(vec![
+            (
+                target.join(format!("{}_bmc.smt2", name)),
+                bmc(&system, &source, self.depth)?,
+            ),
+            (
+                target.join(format!("{}_base.smt2", name)),
+                base(&system, &source)?,
+            ),
+            (
+                target.join(format!("{}_step.smt2", name)),
+                step(&system, &source)?,
+            ),
+        ])
+    }
+
+    /// Root directory of the book, parent of the source directory.
+    fn root(&self) -> &Path {
+        Path::new(self.src)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+    }
+    /// Path of the source system relative to the book's root, as it appears in snippet headers.
+    fn source_from_root(&self) -> String {
+        let source = Path::new(self.source);
+        source
+            .strip_prefix(self.root())
+            .unwrap_or(source)
+            .display()
+            .to_string()
+    }
+
+    /// Runs generation, or checks.
+    pub fn run(&self) -> Res<()> {
+        let scripts = self.scripts()?;
+        if !self.check {
+            fs::create_dir_all(self.target)
+                .chain_err(|| format!("while creating directory `{}`", self.target))?;
+            for (path, content) in &scripts {
+                log::debug!("writing `{}`", path.display());
+                let mut file = open_write(path)?;
+                io::Write::write_all(&mut file, content.as_bytes())
+                    .chain_err(|| format!("while writing `{}`", path.display()))?;
+            }
+            log::info!("generated {} script(s)", scripts.len());
+            return Ok(());
+        }
+
+        let mut report = Report::new();
+        for (path, content) in &scripts {
+            let current = if path.exists() {
+                Some(load_file(path)?)
+            } else {
+                None
+            };
+            if current.as_ref() != Some(content) {
+                report.push(
+                    Diagnostic::new(
+                        Severity::Error,
+                        "gen",
+                        format!("out of date with `{}`, regenerate it", self.source),
+                    )
+                    .set_file(path),
+                )
+            }
+        }
+        for diagnostic in self.check_snippets(&scripts)? {
+            report.push(diagnostic)
+        }
+
+        if !report.diagnostics.is_empty() {
+            eprintln!("|===| Report:");
+            report.pretty_eprint("| ");
+            eprintln!("|===|");
+        }
+        if report.has_errors() {
+            bail!(
+                "{} snippet(s) do not match `{}`",
+                report.count(Severity::Error),
+                self.source
+            )
+        }
+        log::info!("all snippets match `{}`", self.source);
+        Ok(())
+    }
+
+    /// Checks the book snippets declaring the system as their source.
+    ///
+    /// - `scripts`: the generated scripts, with their content.
+    fn check_snippets(&self, scripts: &[(PathBuf, String)]) -> Res<Vec<Diagnostic>> {
+        let mkn = Mkn::load(self.source)?;
+        let system = mkn.to_system();
+        let source = self.source_from_root();
+        let is_source = |snippet: &Path| -> Res<bool> {
+            let text = load_file(snippet)?;
+            Ok(system_of(&text) == Some(source.as_str()))
+        };
+
+        let mut res = vec![];
+        let mut solver = None;
+        let mut generated = vec![];
+        for snippet in test::snippets(self.src, "smt2")? {
+            if !is_source(&snippet)? {
+                continue;
+            }
+            if solver.is_none() {
+                let mut new = Solver::new(self.z3_cmd)?;
+                for (path, content) in scripts {
+                    let (checks, errors) = checks(&mut new, &system, content)
+                        .chain_err(|| format!("while checking `{}`", path.display()))?;
+                    if let Some(error) = errors.first() {
+                        bail!("[internal] `{}`, {}", path.display(), error)
+                    }
+                    generated.push((script_name(path), path.clone(), checks))
+                }
+                solver = Some(new)
+            }
+            let solver = solver.as_mut().expect("solver is running");
+            let text = load_file(&snippet)?;
+            let (snippet_checks, mut msgs) = checks(solver, &system, &text)
+                .chain_err(|| format!("while checking `{}`", snippet.display()))?;
+            msgs.extend(
+                check_smt2(solver, &system, &text)
+                    .chain_err(|| format!("while checking `{}`", snippet.display()))?,
+            );
+            msgs.extend(check_checks(
+                &snippet_checks,
+                &generated,
+                &scripts_of(&text),
+            ));
+            for msg in msgs {
+                res.push(Diagnostic::new(Severity::Error, "gen", msg).set_file(&snippet))
+            }
+        }
+        for snippet in test::snippets(self.src, "mkn")? {
+            if !is_source(&snippet)? {
+                continue;
+            }
+            let other = Mkn::load(&snippet)?;
+            for msg in check_mkn(&mkn, &other) {
+                res.push(Diagnostic::new(Severity::Error, "gen", msg).set_file(&snippet))
+            }
+        }
+
+        // The `SYSTEM` comment is outside anchors, whole-file includes would show it.
+        let mut md_files = vec![];
+        markdown_files(self.src, &mut md_files)?;
+        for md_file in md_files {
+            for (idx, line) in load_file(&md_file)?.lines().enumerate() {
+                if !line.contains("#include") {
+                    continue;
+                }
+                let include = Include::parse(&md_file, line)?;
+                if include.anchor.is_none() && include.path.is_file() && is_source(&include.path)? {
+                    res.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            "gen",
+                            format!(
+                                "`{}` is included whole, include its `all` anchor to hide its \
+                                `SYSTEM` comment",
+                                include.path.display()
+                            ),
+                        )
+                        .set_file(&md_file)
+                        .set_line(idx + 1),
+                    )
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// `SYSTEM` comment in the header of a snippet, without its prefix.
+///
+/// The header is the leading block of comment lines.
+fn system_comment(text: &str) -> Option<&str> {
+    for line in text.lines() {
+        let line = line.trim();
+        let comment = match line.strip_prefix("//").or_else(|| line.strip_prefix(';')) {
+            Some(comment) => comment.trim_start_matches(['/', ';']).trim(),
+            None => return None,
+        };
+        if let Some(system) = comment.strip_prefix(SYSTEM_PREF) {
+            return Some(system.trim());
+        }
+    }
+    None
+}
+
+/// Markdown files in a directory, recursively.
+fn markdown_files(dir: impl AsRef<Path>, res: &mut Vec<PathBuf>) -> Res<()> {
+    let dir = dir.as_ref();
+    let mut entries = vec![];
+    for entry in dir
+        .read_dir()
+        .chain_err(|| format!("while reading directory `{}`", dir.display()))?
+    {
+        entries.push(
+            entry
+                .chain_err(|| format!("while reading directory `{}`", dir.display()))?
+                .path(),
+        )
+    }
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            markdown_files(&path, res)?
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            res.push(path)
+        }
+    }
+    Ok(())
+}
+
+/// Source system declared in the header of a snippet, if any.
+pub fn system_of(text: &str) -> Option<&str> {
+    system_comment(text).and_then(|system| system.split_whitespace().next())
+}
+
+/// Generated scripts whose checks a snippet performs, as listed after its source system.
+pub fn scripts_of(text: &str) -> Vec<&str> {
+    system_comment(text)
+        .map(|system| system.split_whitespace().skip(1).collect())
+        .unwrap_or_default()
+}
+
+/// Name of a generated script, `bmc` for `<name>_bmc.smt2`.
+fn script_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    match stem.rsplit_once('_') {
+        Some((_, name)) => name.into(),
+        None => stem.into(),
+    }
+}
+
+/// Header of all generated scripts.
+fn header(source: &str) -> String {
+    format!(
+        "; Generated from `{}` by `manage gen`, do not edit.\n; ANCHOR: all\n",
+        source
+    )
+}
+
+/// Formal parameters for state prefix `s` or `s'`, one per line.
+fn params(system: &System, prefix: &str, res: &mut String) {
+    for (var, sort) in &system.vars {
+        res.push_str(&format!("        (|{}.{}| {})\n", prefix, var, sort))
+    }
+}
+
+/// Definitions of `init`, `trans` and the candidates.
+fn definitions(system: &System, res: &mut String) {
+    res.push_str("; ANCHOR: init_def\n; Initial predicate.\n(define-fun init\n    (\n");
+    params(system, "s", res);
+    res.push_str(&format!(
+        "    )\n    Bool\n\n    {}\n)\n; ANCHOR_END: init_def\n\n",
+        system.init
+    ));
+
+    res.push_str("; ANCHOR: trans_def\n; Transition relation.\n(define-fun trans\n    (\n");
+    res.push_str("        ; \"Previous\" state.\n");
+    params(system, "s", res);
+    res.push_str("        ; \"Next\" state.\n");
+    params(system, "s'", res);
+    res.push_str(&format!(
+        "    )\n    Bool\n\n    {}\n)\n; ANCHOR_END: trans_def\n\n",
+        system.trans
+    ));
+
+    if system.candidates.is_empty() {
+        return;
+    }
+    res.push_str("; ANCHOR: cand_defs\n");
+    for (idx, (name, term)) in system.candidates.iter().enumerate() {
+        res.push_str(&format!(
+            "; Candidate `{}`.\n(define-fun cand_{}\n    (\n",
+            name, idx
+        ));
+        params(system, "s", res);
+        res.push_str(&format!("    )\n    Bool\n    {}\n)\n", term));
+    }
+    res.push_str("; ANCHOR_END: cand_defs\n\n");
+}
+
+/// Declarations of states `0` to `k`.
+fn states(system: &System, k: usize, res: &mut String) {
+    res.push_str("; ANCHOR: states_def\n");
+    for state in 0..=k {
+        res.push_str(&format!("; State {}.\n", state));
+        for decl in system.declare_state(state) {
+            res.push_str(&decl);
+            res.push('\n')
+        }
+    }
+    res.push_str("; ANCHOR_END: states_def\n\n");
+}
+
+/// Conjunction of the candidates on state `k`.
+fn candidates(system: &System, k: usize) -> String {
+    let cands: Vec<String> = (0..system.candidates.len())
+        .map(|idx| system.apply(&format!("cand_{}", idx), &[k]))
+        .collect();
+    match cands.as_slice() {
+        [] => "true".into(),
+        [cand] => cand.clone(),
+        _ => format!("(and {})", cands.join(" ")),
+    }
+}
+
+/// Asserts that state `0` is initial.
+fn assert_init(system: &System, res: &mut String) {
+    res.push_str(&format!(
+        "; ANCHOR: assert_init\n; State 0 is initial.\n(assert {})\n; ANCHOR_END: assert_init\n\n",
+        system.apply("init", &[0])
+    ));
+}
+
+/// Formats a script and closes its `all` anchor.
+fn finish(mut script: String) -> Res<String> {
+    script.push_str("; ANCHOR_END: all\n");
+    crate::fmt::format(&script, crate::fmt::DEFAULT_WIDTH)
+        .chain_err(|| "[internal] while formatting a generated script")
+}
+
+/// BMC script, falsifies the candidates at depths `0` to `depth`.
+pub fn bmc(system: &System, source: &str, depth: usize) -> Res<String> {
+    let mut res = header(source);
+    definitions(system, &mut res);
+    states(system, depth, &mut res);
+    assert_init(system, &mut res);
+    for k in 0..=depth {
+        res.push_str(&format!("; ANCHOR: depth_{}\n", k));
+        if k > 0 {
+            res.push_str(&format!(
+                "; Unrolling from {} to {}.\n(assert {})\n",
+                k - 1,
+                k,
+                system.apply("trans", &[k - 1, k])
+            ));
+        }
+        res.push_str(&format!(
+            "; Falsification at depth {k}.\n\
+            (declare-const actlit_{k} Bool)\n\
+            (assert (=> actlit_{k} (not {cands})))\n\
+            (echo \"; info: falsification at depth {k}?\")\n\
+            (check-sat-assuming (actlit_{k}))\n\
+            (assert (not actlit_{k}))\n\
+            ; ANCHOR_END: depth_{k}\n\n",
+            k = k,
+            cands = candidates(system, k),
+        ));
+    }
+    finish(res)
+}
+
+/// Base case of the induction check.
+pub fn base(system: &System, source: &str) -> Res<String> {
+    let mut res = header(source);
+    definitions(system, &mut res);
+    states(system, 0, &mut res);
+    assert_init(system, &mut res);
+    res.push_str(&format!(
+        "; ANCHOR: check\n\
+        ; Can an initial state falsify the candidates?\n\
+        (assert (not {}))\n\
+        (check-sat)\n\
+        ; ANCHOR_END: check\n",
+        candidates(system, 0)
+    ));
+    finish(res)
+}
+
+/// Step case of the induction check.
+pub fn step(system: &System, source: &str) -> Res<String> {
+    let mut res = header(source);
+    definitions(system, &mut res);
+    states(system, 1, &mut res);
+    res.push_str(&format!(
+        "; ANCHOR: check\n\
+        ; Can a state verifying the candidates reach a state falsifying them in one transition?\n\
+        (assert {})\n\
+        (assert {})\n\
+        (assert (not {}))\n\
+        (check-sat)\n\
+        ; ANCHOR_END: check\n",
+        candidates(system, 0),
+        system.apply("trans", &[0, 1]),
+        candidates(system, 1)
+    ));
+    finish(res)
+}
+
+/// Text of an s-expression, on one line.
+fn sexp_text(sexp: &Sexp) -> String {
+    match sexp {
+        Sexp::Atom(token) => token.text.to_string(),
+        Sexp::List { elems, .. } => {
+            let elems: Vec<String> = elems.iter().map(sexp_text).collect();
+            format!("({})", elems.join(" "))
+        }
+    }
+}
+
+/// Checks the `init` and `trans` definitions of an `.smt2` snippet are equivalent to the system's.
+///
+/// Returns a message for each inconsistency.
+pub fn check_smt2(solver: &mut Solver, system: &System, text: &str) -> Res<Vec<String>> {
+    let (sexps, errors) = crate::smt2::parse(text);
+    if let Some(error) = errors.first() {
+        bail!("{}: {}", error.pos, error.msg)
+    }
+    let sort_of = |var: &str, prefix: &str| {
+        system
+            .vars
+            .iter()
+            .find(|(v, _)| format!("|{}.{}|", prefix, v) == var)
+            .map(|(_, sort)| sort.to_string())
+    };
+
+    let mut res = vec![];
+    for sexp in &sexps {
+        let elems = match sexp.list() {
+            Some(elems) => elems,
+            None => continue,
+        };
+        match (sexp.head(), elems) {
+            (Some("define-fun"), [_, name, params, _, body]) => {
+                let (name, expected) = match name.symbol() {
+                    Some("init") => ("init", &system.init),
+                    Some("trans") => ("trans", &system.trans),
+                    _ => continue,
+                };
+                let mut consistent = true;
+                for param in params.list().unwrap_or(&[]) {
+                    if let Some([var, sort]) = param.list() {
+                        let (var, sort) = (sexp_text(var), sexp_text(sort));
+                        let expected = sort_of(&var, "s").or_else(|| sort_of(&var, "s'"));
+                        if expected.as_ref() != Some(&sort) {
+                            res.push(format!(
+                                "{}: parameter `{}` of `{}` is not a state variable of sort {}",
+                                param.pos(),
+                                var,
+                                name,
+                                sort
+                            ));
+                            consistent = false
+                        }
+                    }
+                }
+                let decls = ["s", "s'"].iter().flat_map(|prefix| {
+                    system.vars.iter().map(move |(var, sort)| {
+                        format!("(declare-const |{}.{}| {})", prefix, var, sort)
+                    })
+                });
+                if consistent && !equivalent(solver, decls, expected, &sexp_text(body))? {
+                    res.push(format!(
+                        "{}: `{}` is not equivalent to the system's",
+                        sexp.pos(),
+                        name
+                    ))
+                }
+            }
+            (Some("declare-const"), [_, name, sort]) => {
+                let (name, sort) = (sexp_text(name), sexp_text(sort));
+                let var = system.vars.iter().find(|(var, _)| {
+                    name.strip_prefix(var.as_str())
+                        .and_then(|rest| rest.strip_prefix('_'))
+                        .map(|k| k.parse::<usize>().is_ok())
+                        .unwrap_or(false)
+                });
+                if let Some((var, expected)) = var {
+                    if expected.to_string() != sort {
+                        res.push(format!(
+                            "{}: `{}` has sort {}, but state variable `{}` has sort {}",
+                            sexp.pos(),
+                            name,
+                            sort,
+                            var,
+                            expected
+                        ))
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(res)
+}
+
+/// True if two terms are equivalent, given the commands declaring their symbols.
+fn equivalent(
+    solver: &mut Solver,
+    decls: impl IntoIterator<Item = String>,
+    lft: &str,
+    rgt: &str,
+) -> Res<bool> {
+    solver.command("(push 1)")?;
+    solver.commands(decls)?;
+    solver.command(&format!("(assert (not (= {} {})))", lft, rgt))?;
+    let sat = solver.check_sat_assuming(&[])?;
+    solver.command("(pop 1)")?;
+    Ok(!sat)
+}
+
+/// A satisfiability check of a script: what is asserted about which state.
+///
+/// Assertions guarded by an activation literal, `(=> <actlit> <term>)`, are active when the check
+/// assumes the literal. Active conjunctions are split, and each term is either
+/// - an application of `init` or `trans` to state variables,
+/// - equivalent to the system's candidates on a state, or their negation,
+/// - anything else, which only snippets do (to constrain a trace for instance).
+///
+/// Terms without state variables are activation literal bookkeeping and are ignored. Two checks are
+/// the same if renaming their states makes them equal: the book sometimes unrolls backwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// Position of the check command.
+    pub pos: Pos,
+    /// States asserted initial.
+    pub inits: BTreeSet<usize>,
+    /// Pairs of states asserted to be in the transition relation.
+    pub transs: BTreeSet<(usize, usize)>,
+    /// States asserted to verify the candidates.
+    pub cands: BTreeSet<usize>,
+    /// States asserted to falsify the candidates.
+    pub not_cands: BTreeSet<usize>,
+    /// True if something else is asserted.
+    pub other: bool,
+}
+impl Check {
+    /// Constructor.
+    fn new(pos: Pos) -> Self {
+        Self {
+            pos,
+            inits: BTreeSet::new(),
+            transs: BTreeSet::new(),
+            cands: BTreeSet::new(),
+            not_cands: BTreeSet::new(),
+            other: false,
+        }
+    }
+
+    /// States the check mentions.
+    fn states(&self) -> BTreeSet<usize> {
+        let mut res: BTreeSet<usize> = self.inits.union(&self.cands).copied().collect();
+        res.extend(&self.not_cands);
+        res.extend(self.transs.iter().flat_map(|(pre, post)| [*pre, *post]));
+        res
+    }
+
+    /// Renames the states of the check.
+    fn renamed(&self, map: &BTreeMap<usize, usize>) -> Self {
+        let rename = |states: &BTreeSet<usize>| states.iter().map(|k| map[k]).collect();
+        Self {
+            pos: self.pos,
+            inits: rename(&self.inits),
+            transs: self
+                .transs
+                .iter()
+                .map(|(pre, post)| (map[pre], map[post]))
+                .collect(),
+            cands: rename(&self.cands),
+            not_cands: rename(&self.not_cands),
+            other: self.other,
+        }
+    }
+
+    /// True if some renaming of the states of `self` makes `pred(renamed, other)` true.
+    fn up_to_renaming(&self, other: &Self, pred: impl Fn(&Self, &Self) -> bool) -> bool {
+        let states: Vec<usize> = self.states().into_iter().collect();
+        let targets: Vec<usize> = other.states().into_iter().collect();
+        if states.len() != targets.len() {
+            return false;
+        }
+        permutations(&targets).into_iter().any(|targets| {
+            let map = states.iter().copied().zip(targets).collect();
+            pred(&self.renamed(&map), other)
+        })
+    }
+
+    /// True if both checks assert the same things, up to a renaming of the states.
+    pub fn same_as(&self, other: &Self) -> bool {
+        !self.other
+            && !other.other
+            && self.up_to_renaming(other, |lft, rgt| {
+                (&lft.inits, &lft.transs, &lft.cands, &lft.not_cands)
+                    == (&rgt.inits, &rgt.transs, &rgt.cands, &rgt.not_cands)
+            })
+    }
+    /// True if both checks unroll the transition relation the same way, up to a renaming of the
+    /// states.
+    pub fn same_unrolling_as(&self, other: &Self) -> bool {
+        let unrolling = |check: &Self| Self {
+            transs: check.transs.clone(),
+            ..Self::new(check.pos)
+        };
+        unrolling(self).up_to_renaming(&unrolling(other), |lft, rgt| lft.transs == rgt.transs)
+    }
+}
+
+/// All the permutations of some elements.
+fn permutations(elems: &[usize]) -> Vec<Vec<usize>> {
+    if elems.is_empty() {
+        return vec![vec![]];
+    }
+    let mut res = vec![];
+    for (idx, elem) in elems.iter().enumerate() {
+        let mut rest = elems.to_vec();
+        rest.remove(idx);
+        for mut perm in permutations(&rest) {
+            perm.insert(0, *elem);
+            res.push(perm)
+        }
+    }
+    res
+}
+
+/// Definitions of a script's functions but `init` and `trans`: formal parameters and body.
+type Defs<'t, 'a> = BTreeMap<&'a str, (Vec<String>, &'t Sexp<'a>)>;
+
+/// Text of a term, with the applications of functions in `defs` expanded.
+fn expand(term: &Sexp, defs: &Defs, bindings: &BTreeMap<String, String>) -> String {
+    match term {
+        Sexp::Atom(token) => bindings
+            .get(token.text)
+            .cloned()
+            .unwrap_or_else(|| token.text.to_string()),
+        Sexp::List { elems, .. } => {
+            let elems: Vec<String> = elems
+                .iter()
+                .map(|elem| expand(elem, defs, bindings))
+                .collect();
+            match term.head().and_then(|head| defs.get(head)) {
+                Some((params, body)) => {
+                    let bindings = params.iter().cloned().zip(elems[1..].to_vec()).collect();
+                    expand(body, defs, &bindings)
+                }
+                None => format!("({})", elems.join(" ")),
+            }
+        }
+    }
+}
+
+/// State variable and state of a constant `<var>_<k>`, if any.
+fn state_of<'s>(system: &'s System, name: &str) -> Option<(&'s str, usize)> {
+    system.vars.iter().find_map(|(var, _)| {
+        name.strip_prefix(var.as_str())
+            .and_then(|rest| rest.strip_prefix('_'))
+            .and_then(|k| k.parse().ok())
+            .map(|k| (var.as_str(), k))
+    })
+}
+
+/// States of some arguments, if they are the variables of each state in the system's order.
+fn states_of_args(system: &System, args: &[Sexp]) -> Option<Vec<usize>> {
+    if system.vars.is_empty() || !args.len().is_multiple_of(system.vars.len()) {
+        return None;
+    }
+    let mut res = vec![];
+    for state in args.chunks(system.vars.len()) {
+        let mut k = None;
+        for ((var, _), arg) in system.vars.iter().zip(state) {
+            match arg.symbol().and_then(|name| state_of(system, name)) {
+                Some((arg_var, arg_k)) if arg_var == var && k.unwrap_or(arg_k) == arg_k => {
+                    k = Some(arg_k)
+                }
+                _ => return None,
+            }
+        }
+        res.extend(k)
+    }
+    Some(res)
+}
+
+/// States of the state variables appearing in a term.
+fn states_in(system: &System, term: &str) -> BTreeSet<usize> {
+    fn walk(system: &System, sexp: &Sexp, res: &mut BTreeSet<usize>) {
+        match sexp {
+            Sexp::Atom(_) => res.extend(
+                sexp.symbol()
+                    .and_then(|name| state_of(system, name))
+                    .map(|(_, k)| k),
+            ),
+            Sexp::List { elems, .. } => {
+                for elem in elems {
+                    walk(system, elem, res)
+                }
+            }
+        }
+    }
+    let mut res = BTreeSet::new();
+    for sexp in &crate::smt2::parse(term).0 {
+        walk(system, sexp, &mut res)
+    }
+    res
+}
+
+/// Adds an active assertion to a check, pushes a message if it is ill-formed.
+fn fact(
+    solver: &mut Solver,
+    system: &System,
+    defs: &Defs,
+    term: &Sexp,
+    check: &mut Check,
+    errors: &mut Vec<String>,
+) -> Res<()> {
+    let (head, args) = match term.list().and_then(|elems| elems.split_first()) {
+        Some((head, args)) => (head.symbol(), args),
+        None => (None, &[][..]),
+    };
+    match head {
+        Some("and") => {
+            for arg in args {
+                fact(solver, system, defs, arg, check, errors)?
+            }
+            return Ok(());
+        }
+        Some(fun @ ("init" | "trans")) => {
+            match (fun, states_of_args(system, args).as_deref()) {
+                ("init", Some([k])) => {
+                    check.inits.insert(*k);
+                }
+                ("trans", Some([pre, post])) => {
+                    check.transs.insert((*pre, *post));
+                }
+                _ => errors.push(format!(
+                    "{}: `{}` is not applied to the variables of {} state(s)",
+                    term.pos(),
+                    fun,
+                    if fun == "init" { 1 } else { 2 }
+                )),
+            }
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    let term = expand(term, defs, &BTreeMap::new());
+    let states: Vec<usize> = states_in(system, &term).into_iter().collect();
+    if let [k] = states.as_slice() {
+        let decls = || {
+            system
+                .definitions()
+                .into_iter()
+                .chain(system.declare_state(*k))
+        };
+        let cands = candidates(system, *k);
+        if equivalent(solver, decls(), &term, &cands)? {
+            check.cands.insert(*k);
+            return Ok(());
+        }
+        if equivalent(solver, decls(), &term, &format!("(not {})", cands))? {
+            check.not_cands.insert(*k);
+            return Ok(());
+        }
+    }
+    check.other = check.other || !states.is_empty();
+    Ok(())
+}
+
+/// Satisfiability checks of an `.smt2` script, see [`Check`].
+///
+/// Also returns a message for each ill-formed application of `init` or `trans`.
+pub fn checks(solver: &mut Solver, system: &System, text: &str) -> Res<(Vec<Check>, Vec<String>)> {
+    let (sexps, errors) = crate::smt2::parse(text);
+    if let Some(error) = errors.first() {
+        bail!("{}: {}", error.pos, error.msg)
+    }
+    let mut defs = Defs::new();
+    // Assertions, by assertion level.
+    let mut levels: Vec<Vec<&Sexp>> = vec![vec![]];
+    let mut res = vec![];
+    let mut errors = vec![];
+    for sexp in &sexps {
+        let elems = match sexp.list() {
+            Some(elems) => elems,
+            None => continue,
+        };
+        // Argument of `push` and `pop`.
+        let count = || match elems.get(1) {
+            Some(Sexp::Atom(token)) => token.text.parse().unwrap_or(1),
+            _ => 1,
+        };
+        match (sexp.head(), elems) {
+            (Some("define-fun"), [_, name, params, _, body]) => match name.symbol() {
+                Some("init" | "trans") | None => (),
+                Some(name) => {
+                    let params = params
+                        .list()
+                        .unwrap_or(&[])
+                        .iter()
+                        .filter_map(|param| param.list().and_then(|param| param.first()))
+                        .map(sexp_text)
+                        .collect();
+                    defs.insert(name, (params, body));
+                }
+            },
+            (Some("assert"), [_, term]) => levels.last_mut().expect("assertion level").push(term),
+            (Some("push"), _) => levels.extend((0..count()).map(|_| vec![])),
+            (Some("pop"), _) => {
+                let len = levels.len().saturating_sub(count()).max(1);
+                levels.truncate(len)
+            }
+            (Some(cmd @ ("check-sat" | "check-sat-assuming")), _) => {
+                let assumptions: BTreeSet<String> = match cmd {
+                    "check-sat" => BTreeSet::new(),
+                    _ => elems
+                        .get(1)
+                        .and_then(Sexp::list)
+                        .unwrap_or(&[])
+                        .iter()
+                        .map(sexp_text)
+                        .collect(),
+                };
+                let mut check = Check::new(sexp.pos());
+                for term in levels.iter().flatten() {
+                    let term = match term.list() {
+                        Some([imp, guard, term])
+                            if imp.symbol() == Some("=>")
+                                && guard
+                                    .symbol()
+                                    .is_some_and(|g| state_of(system, g).is_none()) =>
+                        {
+                            if !assumptions.contains(&sexp_text(guard)) {
+                                continue;
+                            }
+                            term
+                        }
+                        _ => term,
+                    };
+                    fact(solver, system, &defs, term, &mut check, &mut errors)?
+                }
+                res.push(check)
+            }
+            _ => (),
+        }
+    }
+    Ok((res, errors))
+}
+
+/// Compares the checks of a snippet to the ones of the generated scripts.
+///
+/// Each check must unroll the transition relation like some generated check. For each script in
+/// `scripts`, each of its checks that does not unroll deeper than the snippet must be performed by
+/// the snippet.
+///
+/// - `generated`: name, path and checks of the generated scripts.
+///
+/// Returns a message for each inconsistency.
+pub fn check_checks(
+    checks: &[Check],
+    generated: &[(String, PathBuf, Vec<Check>)],
+    scripts: &[&str],
+) -> Vec<String> {
+    let mut res = vec![];
+    for check in checks {
+        let known = generated
+            .iter()
+            .flat_map(|(_, _, checks)| checks)
+            .any(|other| check.same_unrolling_as(other));
+        if !known {
+            res.push(format!(
+                "{}: the unrolling of this check does not match any generated script's",
+                check.pos
+            ))
+        }
+    }
+
+    let depth = checks
+        .iter()
+        .map(|check| check.transs.len())
+        .max()
+        .unwrap_or(0);
+    for script in scripts {
+        let (path, expected) = match generated.iter().find(|(name, _, _)| name == script) {
+            Some((_, path, checks)) => (path, checks),
+            None => {
+                let names: Vec<String> = generated
+                    .iter()
+                    .map(|(name, _, _)| format!("`{}`", name))
+                    .collect();
+                res.push(format!(
+                    "unknown script `{}` in the `SYSTEM` comment, expected one of {}",
+                    script,
+                    names.join(", ")
+                ));
+                continue;
+            }
+        };
+        for other in expected {
+            if other.transs.len() <= depth && !checks.iter().any(|check| check.same_as(other)) {
+                res.push(format!(
+                    "no check matches the one of `{}` at {}",
+                    path.display(),
+                    other.pos
+                ))
+            }
+        }
+    }
+    res
+}
+
+/// Checks an `.mkn` snippet has the same state variables, initial predicate and transition
+/// relation as a system.
+///
+/// Returns a message for each inconsistency.
+pub fn check_mkn(system: &Mkn, other: &Mkn) -> Vec<String> {
+    let mut res = vec![];
+    if system.vars != other.vars {
+        let vars = |mkn: &Mkn| {
+            let vars: Vec<String> = mkn
+                .vars
+                .iter()
+                .map(|(var, sort)| format!("{}: {}", var, sort_name(*sort)))
+                .collect();
+            vars.join(", ")
+        };
+        res.push(format!(
+            "state variables `{}` differ from the system's, `{}`",
+            vars(other),
+            vars(system)
+        ))
+    }
+    if system.init != other.init {
+        res.push("initial predicate differs from the system's".into())
+    }
+    if system.trans != other.trans {
+        res.push("transition relation differs from the system's".into())
+    }
+    res
+}
+
+/// Mikino name of a sort.
+fn sort_name(sort: Sort) -> &'static str {
+    match sort {
+        Sort::Bool => "bool",
+        Sort::Int => "int",
+        Sort::Real => "real",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter() -> Mkn {
+        crate::mkn::parse(
+            "svars { reset: bool, cnt: int }\n\
+            init { cnt = 0 }\n\
+            trans { 'cnt = if 'reset { 0 } else { cnt + 1 } }\n\
+            candidates { \"positive\": cnt ≥ 0 }",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn scripts() {
+        let system = counter().to_system();
+        let bmc = bmc(&system, "counter.mkn", 2).unwrap();
+        assert_eq!(
+            crate::fmt::format(&bmc, crate::fmt::DEFAULT_WIDTH).unwrap(),
+            bmc
+        );
+        for anchor in ["init_def", "trans_def", "states_def", "depth_2"] {
+            assert!(
+                bmc.contains(&format!("; ANCHOR: {}\n", anchor)),
+                "{}",
+                anchor
+            );
+            assert!(
+                bmc.contains(&format!("; ANCHOR_END: {}\n", anchor)),
+                "{}",
+                anchor
+            );
+        }
+        assert!(bmc.contains("(assert (trans reset_1 cnt_1 reset_2 cnt_2))"));
+        assert!(bmc.contains("(assert (=> actlit_2 (not (cand_0 reset_2 cnt_2))))"));
+        assert!(!bmc.contains("cnt_3"));
+
+        let step = step(&system, "counter.mkn").unwrap();
+        assert!(step.contains("(assert (not (cand_0 reset_1 cnt_1)))"));
+        assert!(!step.contains("(init"));
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(
+            system_of("; SYSTEM: systems/sw.mkn\n; ANCHOR: all\n(check-sat)"),
+            Some("systems/sw.mkn")
+        );
+        assert_eq!(
+            system_of("// CMD: mikino check <file>\n// SYSTEM: systems/sw.mkn\nsvars {}"),
+            Some("systems/sw.mkn")
+        );
+        assert_eq!(system_of("(check-sat)\n; SYSTEM: systems/sw.mkn"), None);
+        assert_eq!(
+            scripts_of("; SYSTEM: systems/sw.mkn base step\n(check-sat)"),
+            vec!["base", "step"]
+        );
+        assert!(scripts_of("; SYSTEM: systems/sw.mkn\n(check-sat)").is_empty());
+    }
+
+    #[test]
+    fn consistency() {
+        let mkn = counter();
+        let other = crate::mkn::parse(
+            "svars { reset: bool, cnt: int }\n\
+            init { cnt = 1 }\n\
+            trans { 'cnt = if 'reset { 0 } else { cnt + 1 } }",
+        )
+        .unwrap();
+        assert_eq!(check_mkn(&mkn, &mkn), Vec::<String>::new());
+        assert_eq!(
+            check_mkn(&mkn, &other),
+            vec!["initial predicate differs from the system's".to_string()]
+        );
+
+        let system = mkn.to_system();
+        let snippet = "\
+(define-fun init ((|s.reset| Bool) (|s.cnt| Int)) Bool (= 0 |s.cnt|))
+(define-fun trans ((|s.reset| Bool) (|s.cnt| Int) (|s'.reset| Bool) (|s'.cnt| Int)) Bool
+    (= |s'.cnt| (ite |s'.reset| 0 (+ |s.cnt| 2)))
+)
+(declare-const cnt_0 Bool)
+";
+        let res =
+            Solver::new("z3").and_then(|mut solver| check_smt2(&mut solver, &system, snippet));
+        match res {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping");
+            }
+            res => assert_eq!(
+                res.unwrap(),
+                vec![
+                    "2:1: `trans` is not equivalent to the system's".to_string(),
+                    "5:1: `cnt_0` has sort Bool, but state variable `cnt` has sort Int".to_string(),
+                ]
+            ),
+        }
+    }
+
+    #[test]
+    fn unrollings() {
+        let system = counter().to_system();
+        let res = Solver::new("z3").and_then(|mut solver| {
+            let mut generated = vec![];
+            for (name, script) in [
+                ("bmc", bmc(&system, "counter.mkn", 2)?),
+                ("step", step(&system, "counter.mkn")?),
+            ] {
+                let (checks, errors) = checks(&mut solver, &system, &script)?;
+                assert!(errors.is_empty(), "{:?}", errors);
+                generated.push((name.to_string(), PathBuf::from(name), checks))
+            }
+            // Backward step check with an inline candidate.
+            let step = "\
+(declare-const reset_0 Bool)
+(declare-const cnt_0 Int)
+(declare-const reset_1 Bool)
+(declare-const cnt_1 Int)
+(define-fun cand ((|s.cnt| Int)) Bool (<= 0 |s.cnt|))
+(assert (not (cand cnt_0)))
+(declare-const act Bool)
+(assert (=> act (init reset_0 cnt_0)))
+(check-sat-assuming (act))
+(assert (and (trans reset_1 cnt_1 reset_0 cnt_0) (cand cnt_1)))
+(check-sat)
+";
+            let (step_checks, errors) = checks(&mut solver, &system, step)?;
+            assert!(errors.is_empty(), "{:?}", errors);
+            let bad = "\
+(declare-const reset_0 Bool)
+(declare-const cnt_0 Int)
+(declare-const reset_1 Bool)
+(declare-const cnt_1 Int)
+(declare-const reset_2 Bool)
+(declare-const cnt_2 Int)
+(assert (trans reset_0 cnt_0 reset_1 cnt_1))
+(assert (trans reset_0 cnt_0 reset_2 cnt_2))
+(assert (trans reset_0 cnt_2 reset_2 cnt_0))
+(check-sat)
+";
+            let (bad_checks, bad_errors) = checks(&mut solver, &system, bad)?;
+            Ok((generated, step_checks, bad_checks, bad_errors))
+        });
+        let (generated, step_checks, bad_checks, bad_errors) = match res {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping");
+                return;
+            }
+            res => res.unwrap(),
+        };
+
+        assert_eq!(generated[0].2.len(), 3);
+        assert_eq!(generated[1].2[0].cands, [0].iter().copied().collect());
+        assert_eq!(generated[1].2[0].not_cands, [1].iter().copied().collect());
+        assert_eq!(step_checks[1].transs, [(1, 0)].iter().copied().collect());
+        assert!(step_checks[1].same_as(&generated[1].2[0]));
+        assert_eq!(
+            check_checks(&step_checks, &generated, &["step"]),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            check_checks(&step_checks, &generated, &["bmc"]),
+            vec![format!(
+                "no check matches the one of `bmc` at {}",
+                generated[0].2[1].pos
+            )]
+        );
+
+        assert_eq!(
+            bad_errors,
+            vec!["9:9: `trans` is not applied to the variables of 2 state(s)".to_string()]
+        );
+        assert_eq!(
+            check_checks(&bad_checks, &generated, &[]),
+            vec![
+                "10:1: the unrolling of this check does not match any generated script's"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn book_snippets() {
+        let res = Gen::new("../systems/stopwatch.mkn", "../systems/gen")
+            .set_src("../src")
+            .set_check(true)
+            .run();
+        match res {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping");
+            }
+            res => res.unwrap(),
+        }
+
+        let res = Gen::new("../systems/stopwatch.mkn", "../systems/gen")
+            .set_src("../src")
+            .set_check(true)
+            .set_z3_cmd("manage_no_such_z3")
+            .run();
+        assert!(matches!(
+            res,
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _))
+        ));
+    }
+}
@@ -0,0 +1,457 @@
+//! LaTeX export of the book.
+//!
+//! Produces one `.tex` file per top-level chapter and a `main.tex` that can be built with
+//! `tectonic`, just like the paper in `paper/`.
+
+use std::io::Write;
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+prelude!();
+
+/// Rust listings package, shared with the paper.
+const RUST_STY: &str = "paper/src/listings-rust.sty";
+/// Name of the generated listings package for SMT-LIB 2 and mikino.
+const BOOK_STY: &str = "listings-book";
+
+/// Preamble of the generated `main.tex`.
+const PREAMBLE: &str = r#"\documentclass{report}
+
+\usepackage{graphicx, xcolor, listings, hyperref}
+\usepackage{listings-rust}
+\usepackage{listings-book}
+
+\newcommand{\code}[1]{\textcolor{orange!75!black}{\texttt{#1}}}
+
+\lstset{%
+  basicstyle=\ttfamily\small%
+, breaklines=true%
+, tabsize=4%
+, columns=fullflexible%
+, keepspaces=true%
+}
+"#;
+
+/// Content of the generated listings package.
+const BOOK_STY_CONTENT: &str = r#"\NeedsTeXFormat{LaTeX2e}[1994/06/01]
+\ProvidesPackage{listings-book}[2021/11/01 Custom Package]
+
+\RequirePackage{color}
+\RequirePackage{listings}
+
+\lstdefinelanguage{SMT-LIB2}{%
+  sensitive%
+, morecomment=[l]{;}%
+, morestring=[b]{"}%
+, alsoletter={-}%
+, morekeywords={%
+    declare-const, declare-fun, define-fun, define-const, assert, check-sat,%
+    check-sat-assuming, get-model, get-value, get-unsat-core, set-option, set-logic, push, pop,%
+    exit, echo, reset%
+  }%
+, morekeywords=[2]{and, or, not, ite, =>, =, distinct, let, forall, exists}%
+, morekeywords=[3]{Bool, Int, Real}%
+, morekeywords=[4]{true, false}%
+}%
+
+\lstdefinelanguage{mikino}{%
+  sensitive%
+, morecomment=[l]{//}%
+, morecomment=[s]{/*}{*/}%
+, morestring=[b]{"}%
+, morekeywords={svars, vars, init, trans, candidates, assert, check_sat, get_model, echo, let}%
+, morekeywords=[2]{if, else, and, or, not}%
+, morekeywords=[3]{bool, int, rat}%
+, morekeywords=[4]{true, false}%
+}%
+
+\lstdefinestyle{colouredSmt}%
+{ basicstyle=\ttfamily\small%
+, commentstyle=\color[gray]{0.4}%
+, stringstyle=\color[rgb]{0, 0, 0.5}%
+, keywordstyle=\bfseries%
+, keywordstyle=[2]\color[rgb]{0.75, 0, 0}%
+, keywordstyle=[3]\color[rgb]{0, 0.5, 0}%
+, keywordstyle=[4]\color[rgb]{0, 0.5, 0}%
+, columns=fullflexible%
+, keepspaces=true%
+, showstringspaces=false%
+}%
+
+\lstdefinestyle{colouredMikino}%
+{ style=colouredSmt%
+}%
+
+\lstdefinestyle{plain}%
+{ basicstyle=\ttfamily\small%
+, columns=fullflexible%
+, keepspaces=true%
+, backgroundcolor=\color[gray]{0.95}%
+}%
+"#;
+
+/// LaTeX book generator.
+pub struct Latex<'s> {
+    /// Configuration for running the snippets of `{{ #run }}` directives.
+    conf: Conf<'s>,
+    target: &'s str,
+}
+impl<'s> Latex<'s> {
+    /// Constructor.
+    pub fn new(conf: Conf<'s>, target: &'s str) -> Self {
+        Self { conf, target }
+    }
+    /// Target accessor.
+    pub fn target(&self) -> &'s str {
+        self.target
+    }
+
+    /// Runs LaTeX generation.
+    pub fn run(&self) -> Res<()> {
+        fs::create_dir_all(self.target)
+            .chain_err(|| format!("during (recursive) folder creation for `{}`", self.target))?;
+        let top_level = Vanilla::top_level_md(Vanilla::SRC)?;
+        log::info!("generating LaTeX for {} chapter(s)", top_level.len());
+
+        let mut chapters = Vec::with_capacity(top_level.len());
+        for file in top_level {
+            let src_path = PathBuf::from(Vanilla::SRC).join(file.path());
+            let stem = file.slug();
+            let tgt_path = self.tgt_dir().join(format!("{}.tex", stem));
+            log::debug!(
+                "generating LaTeX for `{}` from `{}`",
+                file.title(),
+                src_path.display(),
+            );
+
+            // Raw HTML is dropped, timing diagrams are inlined as text.
+            let content = crate::preprocess::Manage::new(self.conf.clone())
+                .set_timing_svg(false)
+                .expand(&src_path, &load_file(&src_path)?)
+                .chain_err(|| {
+                    format!("while expanding the directives of `{}`", src_path.display())
+                })?;
+            let mut tgt_file = open_write(&tgt_path)?;
+            chapter(&src_path, &content, &mut tgt_file)
+                .chain_err(|| format!("while generating LaTeX for `{}`", src_path.display()))?;
+            chapters.push(stem);
+        }
+
+        self.write_main(&chapters)?;
+        self.write_styles()?;
+
+        log::info!("done with LaTeX generation");
+        Ok(())
+    }
+
+    pub fn tgt_dir(&self) -> PathBuf {
+        PathBuf::from(self.target)
+    }
+
+    /// Writes `main.tex`, which inputs all the chapters.
+    fn write_main(&self, chapters: &[String]) -> Res<()> {
+        let path = self.tgt_dir().join("main.tex");
+        let mut file = open_write(&path)?;
+        let err = || format!("while writing `{}`", path.display());
+
+        write!(file, "{}", PREAMBLE).chain_err(err)?;
+        writeln!(file, "\n\\begin{{document}}\n").chain_err(err)?;
+        writeln!(file, "\\tableofcontents\n").chain_err(err)?;
+        for chapter in chapters {
+            writeln!(file, "\\input{{{}}}", chapter).chain_err(err)?;
+        }
+        writeln!(file, "\n\\end{{document}}").chain_err(err)?;
+        Ok(())
+    }
+
+    /// Copies the paper's Rust listings package and writes the book's listings package.
+    fn write_styles(&self) -> Res<()> {
+        let rust_sty = self.tgt_dir().join("listings-rust.sty");
+        fs::copy(RUST_STY, &rust_sty)
+            .chain_err(|| format!("while copying `{}` to `{}`", RUST_STY, rust_sty.display()))?;
+
+        let book_sty = self.tgt_dir().join(format!("{}.sty", BOOK_STY));
+        let mut file = open_write(&book_sty)?;
+        write!(file, "{}", BOOK_STY_CONTENT)
+            .chain_err(|| format!("while writing `{}`", book_sty.display()))?;
+        Ok(())
+    }
+}
+
+/// Escapes LaTeX special characters.
+pub fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => res.push_str(r"\textbackslash{}"),
+            '~' => res.push_str(r"\textasciitilde{}"),
+            '^' => res.push_str(r"\textasciicircum{}"),
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                res.push('\\');
+                res.push(c)
+            }
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// Listings language and style for a code block.
+///
+/// Uses the code block's info string first, and the extension of the file it includes otherwise.
+pub fn listing_lang(info: &str, include_ext: Option<&str>) -> Option<(&'static str, &'static str)> {
+    let lang = info.split([',', ' ']).next().unwrap_or("");
+    match lang {
+        "rust" => return Some(("Rust", "colouredRust")),
+        "smt2" | "smt" | "lisp" => return Some(("SMT-LIB2", "colouredSmt")),
+        "mikino" | "mkn" | "hsmt" => return Some(("mikino", "colouredMikino")),
+        _ => (),
+    }
+    match include_ext {
+        Some("rs") => Some(("Rust", "colouredRust")),
+        Some("smt2") => Some(("SMT-LIB2", "colouredSmt")),
+        Some("mkn") | Some("hsmt") => Some(("mikino", "colouredMikino")),
+        _ => None,
+    }
+}
+
+/// Converts the markdown `content` of chapter `md_path` to LaTeX.
+pub fn chapter(md_path: impl AsRef<Path>, content: &str, target: &mut impl Write) -> Res<()> {
+    let md_path = md_path.as_ref();
+
+    /// Code block being read.
+    struct Code {
+        info: String,
+        content: String,
+    }
+    let mut code: Option<Code> = None;
+    // `true` for ordered lists.
+    let mut lists: Vec<bool> = vec![];
+    // `true` for links rendered as `\href`.
+    let mut links: Vec<bool> = vec![];
+    let mut first_cell = true;
+
+    let parser = Parser::new_ext(content, Options::ENABLE_TABLES);
+
+    for event in parser {
+        if let Some(code) = code.as_mut() {
+            match event {
+                Event::Text(text) => {
+                    code.content.push_str(&text);
+                    continue;
+                }
+                Event::End(TagEnd::CodeBlock) => (),
+                event => bail!("unexpected markdown event in code block: {:?}", event),
+            }
+        }
+
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Paragraph => (),
+                Tag::Heading { level, .. } => {
+                    let cmd = match level {
+                        HeadingLevel::H1 => "chapter",
+                        HeadingLevel::H2 => "section",
+                        HeadingLevel::H3 => "subsection",
+                        HeadingLevel::H4 => "subsubsection",
+                        HeadingLevel::H5 | HeadingLevel::H6 => "paragraph",
+                    };
+                    write!(target, "\\{}{{", cmd)?
+                }
+                Tag::BlockQuote => writeln!(target, "\\begin{{quote}}")?,
+                Tag::CodeBlock(kind) => {
+                    let info = match kind {
+                        CodeBlockKind::Fenced(info) => info.to_string(),
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    code = Some(Code {
+                        info,
+                        content: String::new(),
+                    })
+                }
+                Tag::HtmlBlock => (),
+                Tag::List(None) => {
+                    lists.push(false);
+                    writeln!(target, "\\begin{{itemize}}")?
+                }
+                Tag::List(Some(start)) => {
+                    lists.push(true);
+                    writeln!(target, "\\begin{{enumerate}}")?;
+                    if start != 1 {
+                        writeln!(target, "\\setcounter{{enumi}}{{{}}}", start - 1)?
+                    }
+                }
+                Tag::Item => write!(target, "\\item ")?,
+                Tag::Table(aligns) => {
+                    let cols: String = aligns
+                        .iter()
+                        .map(|align| match align {
+                            Alignment::Left => "l|",
+                            Alignment::Right => "r|",
+                            Alignment::Center | Alignment::None => "c|",
+                        })
+                        .collect();
+                    writeln!(target, "\\begin{{center}}")?;
+                    writeln!(target, "\\begin{{tabular}}{{|{}}}", cols)?;
+                    writeln!(target, "\\hline")?
+                }
+                Tag::TableHead | Tag::TableRow => first_cell = true,
+                Tag::TableCell => {
+                    if !first_cell {
+                        write!(target, " & ")?
+                    }
+                    first_cell = false
+                }
+                Tag::Emphasis => write!(target, "\\emph{{")?,
+                Tag::Strong => write!(target, "\\textbf{{")?,
+                Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                    if dest_url.starts_with("http") || dest_url.starts_with("mailto:") {
+                        write!(target, "\\href{{{}}}{{", escape_url(&dest_url))?;
+                        links.push(true)
+                    } else {
+                        links.push(false)
+                    }
+                }
+                tag @ Tag::FootnoteDefinition(_)
+                | tag @ Tag::Strikethrough
+                | tag @ Tag::MetadataBlock(_) => {
+                    bail!("unsupported markdown element {:?}", tag)
+                }
+            },
+
+            Event::End(tag) => match tag {
+                TagEnd::Paragraph => write!(target, "\n\n")?,
+                TagEnd::Heading(_) => write!(target, "}}\n\n")?,
+                TagEnd::BlockQuote => write!(target, "\\end{{quote}}\n\n")?,
+                TagEnd::CodeBlock => {
+                    let Code { info, content } = code.take().ok_or_else(|| {
+                        err::Error::from("[internal] code block end without code block start")
+                    })?;
+                    code_block(md_path, &info, &content, target)?
+                }
+                TagEnd::HtmlBlock => (),
+                TagEnd::List(_) => {
+                    let ordered = lists.pop().unwrap_or(false);
+                    let env = if ordered { "enumerate" } else { "itemize" };
+                    write!(target, "\\end{{{}}}\n\n", env)?
+                }
+                TagEnd::Item => writeln!(target)?,
+                TagEnd::Table => write!(target, "\\end{{tabular}}\n\\end{{center}}\n\n")?,
+                TagEnd::TableHead | TagEnd::TableRow => writeln!(target, " \\\\ \\hline")?,
+                TagEnd::TableCell => (),
+                TagEnd::Emphasis | TagEnd::Strong => write!(target, "}}")?,
+                TagEnd::Link | TagEnd::Image => {
+                    if links.pop().unwrap_or(false) {
+                        write!(target, "}}")?
+                    }
+                }
+                TagEnd::FootnoteDefinition | TagEnd::Strikethrough | TagEnd::MetadataBlock(_) => (),
+            },
+
+            // Lone `\` lines are used as vertical spacing in the book.
+            Event::Text(text) if text.as_ref() == "\\" => write!(target, "\\medskip{{}}")?,
+            Event::Text(text) => write!(target, "{}", escape(&text))?,
+            Event::Code(text) => write!(target, "\\code{{{}}}", escape(&text))?,
+            Event::Html(html) | Event::InlineHtml(html) => {
+                // Only keep the text of `<summary>` elements.
+                if let Some(start) = html.find("<summary>") {
+                    let summary = &html[start + "<summary>".len()..];
+                    let summary = summary
+                        .find("</summary>")
+                        .map(|end| &summary[..end])
+                        .unwrap_or(summary);
+                    write!(target, "\\textit{{{}}}\n\n", escape(summary.trim()))?
+                }
+            }
+            Event::SoftBreak => writeln!(target)?,
+            Event::HardBreak => writeln!(target, "\\medskip{{}}")?,
+            Event::Rule => write!(target, "\\noindent\\rule{{\\textwidth}}{{0.4pt}}\n\n")?,
+            event @ Event::FootnoteReference(_) | event @ Event::TaskListMarker(_) => {
+                bail!("unsupported markdown element {:?}", event)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a code block as an `lstlisting` environment, expanding includes.
+fn code_block(md_path: &Path, info: &str, content: &str, target: &mut impl Write) -> Res<()> {
+    let (content, ext) = Include::expand_all(md_path, content)?;
+    let (lang, style) = match listing_lang(info, ext.as_deref()) {
+        Some((lang, style)) => (Some(lang), style),
+        None => (None, "plain"),
+    };
+    match lang {
+        Some(lang) => writeln!(
+            target,
+            "\\begin{{lstlisting}}[language={}, style={}]",
+            lang, style
+        )?,
+        None => writeln!(target, "\\begin{{lstlisting}}[style={}]", style)?,
+    }
+    for line in content.lines() {
+        // Hidden lines in Rust code blocks.
+        if lang == Some("Rust") && crate::is_hidden_rust_line(line) {
+            continue;
+        }
+        writeln!(target, "{}", line)?;
+    }
+    write!(target, "\\end{{lstlisting}}\n\n")?;
+    Ok(())
+}
+
+/// Escapes the characters of a URL that are special in `\href`.
+fn escape_url(url: &str) -> String {
+    url.replace('%', "\\%").replace('#', "\\#")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_latex(md: &str) -> String {
+        let mut out = vec![];
+        chapter("src/readme.md", md, &mut out).expect("LaTeX conversion failed");
+        String::from_utf8(out).expect("LaTeX output is not UTF-8")
+    }
+
+    #[test]
+    fn structure() {
+        let tex = to_latex(
+            "# Title\n\nSome *emph*, **strong** and `code_1`, [link](https://ocamlpro.com).\n\n\
+             ## Section\n\n- a\n- b\n",
+        );
+        assert!(tex.starts_with("\\chapter{Title}\n"));
+        assert!(tex.contains("\\emph{emph}"));
+        assert!(tex.contains("\\textbf{strong}"));
+        assert!(tex.contains("\\code{code\\_1}"));
+        assert!(tex.contains("\\href{https://ocamlpro.com}{link}"));
+        assert!(tex.contains("\\section{Section}"));
+        assert!(tex.contains("\\begin{itemize}\n\\item a\n\\item b\n\\end{itemize}"));
+    }
+
+    #[test]
+    fn code_languages() {
+        assert_eq!(
+            listing_lang("rust ,compile_fail", None),
+            Some(("Rust", "colouredRust"))
+        );
+        assert_eq!(
+            listing_lang("text", Some("smt2")),
+            Some(("SMT-LIB2", "colouredSmt"))
+        );
+        assert_eq!(
+            listing_lang("text", Some("mkn")),
+            Some(("mikino", "colouredMikino"))
+        );
+        assert_eq!(listing_lang("text", Some("out")), None);
+
+        let tex = to_latex("```rust\n# fn hidden() {}\nfn main() {}\n```\n");
+        assert_eq!(
+            tex,
+            "\\begin{lstlisting}[language=Rust, style=colouredRust]\nfn main() {}\n\
+             \\end{lstlisting}\n\n"
+        );
+    }
+}
@@ -18,8 +18,13 @@ pub mod prelude {
     pub use log;
 
     pub use crate::{
+        assets::Assets,
+        epub::Epub,
+        latex::Latex,
         prelude::err::{Res, ResExt},
-        test, Conf, Vanilla,
+        report::{Diagnostic, Report, Severity},
+        slides::Slides,
+        test, Conf, Include, TopLevelMd, Vanilla,
     };
 
     pub mod err {
@@ -63,17 +68,48 @@ pub mod prelude {
 
 prelude!();
 
+pub mod assets;
+pub mod cex;
+pub mod chapter;
+pub mod diagram;
+pub mod engine;
+pub mod epub;
+pub mod exercise;
+pub mod fmt;
+pub mod gen;
+pub mod latex;
+pub mod lint;
+pub mod mkn;
+pub mod model;
+pub mod pair;
+pub mod portability;
+pub mod preprocess;
+pub mod prove;
+pub mod render;
+pub mod replay;
+pub mod report;
+pub mod simulate;
+pub mod slides;
+pub mod smt2;
+pub mod strengthen;
+pub mod timing;
+pub mod trace;
+
 /// Test configuration.
 #[derive(Clone, Debug)]
 pub struct Conf<'s> {
     check_smt2: Option<(bool, &'s str)>,
     check_mikino: Option<(bool, &'s str)>,
+    validate_models: bool,
+    chapters: Vec<String>,
 }
 impl Default for Conf<'static> {
     fn default() -> Self {
         Self {
             check_smt2: Some((true, "z3")),
             check_mikino: Some((true, "mikino")),
+            validate_models: false,
+            chapters: vec![],
         }
     }
 }
@@ -83,6 +119,8 @@ impl<'s> Conf<'s> {
         Self {
             check_smt2: None,
             check_mikino: None,
+            validate_models: false,
+            chapters: vec![],
         }
     }
 
@@ -94,16 +132,24 @@ impl<'s> Conf<'s> {
         self.check_mikino = Some((check, command));
         self
     }
+    /// Validates the models printed in `.smt2` outputs, see [`crate::model`].
+    pub fn set_validate_models(mut self, validate: bool) -> Self {
+        self.validate_models = validate;
+        self
+    }
+    /// Only checks some chapters, by directory name or title, see [`crate::chapter`].
+    pub fn set_chapters(mut self, chapters: Vec<String>) -> Self {
+        self.chapters = chapters;
+        self
+    }
 
     fn get_smt2(&self) -> Res<(bool, &'s str)> {
-        self.check_smt2.ok_or_else(|| {
-            format!("[internal] no information provided for SMT file checking").into()
-        })
+        self.check_smt2
+            .ok_or_else(|| "[internal] no information provided for SMT file checking".into())
     }
     fn get_mikino(&self) -> Res<(bool, &'s str)> {
-        self.check_mikino.ok_or_else(|| {
-            format!("[internal] no information provided for mikino file checking").into()
-        })
+        self.check_mikino
+            .ok_or_else(|| "[internal] no information provided for mikino file checking".into())
     }
 
     /// Runs the actual checks.
@@ -119,10 +165,10 @@ pub mod test {
     prelude!();
 
     #[test]
-    fn test_all() -> () {
-        simple_logger::SimpleLogger::new()
-            .with_level(log::LevelFilter::Trace)
-            .init()
+    fn test_all() {
+        let logger = simple_logger::SimpleLogger::new().with_level(log::LevelFilter::Trace);
+        crate::report::Capture::new(logger)
+            .init(log::LevelFilter::Trace)
             .expect("failed to initialize logger");
         let out = std::process::Command::new("pwd").output().unwrap();
         println!("pwd: {}", String::from_utf8_lossy(&out.stdout));
@@ -138,65 +184,441 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn vanilla_names_and_manifest() {
+        let file = crate::TopLevelMd {
+            path: "./mikino_induction/readme.md".into(),
+            title: "Induction: Mikino and Step Cex-s".into(),
+        };
+        assert_eq!(file.slug(), "induction_mikino_and_step_cex_s");
+
+        let manifest = crate::Manifest {
+            entries: vec![crate::ManifestEntry {
+                file: file.slug() + ".md",
+                hash: crate::assets::hash(b"content"),
+                src: file.path,
+            }],
+        };
+        let parsed = crate::Manifest::parse(&manifest.to_string()).unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(crate::Manifest::parse("0\tfile.md\tnot_hex\tsrc.md").is_err());
+        assert_eq!(
+            crate::Manifest::parse("1\tfile.md\t0\tsrc.md")
+                .unwrap_err()
+                .to_string(),
+            "illegal manifest line 0: expected order 0, found 1"
+        );
+    }
+
+    #[test]
+    fn doctest_output() {
+        let stdout = "running 2 tests\n\
+            test ch/readme.md - Title::Sub (line 11) ... FAILED\n\
+            test ch/readme.md - Title::Sub (line 7) ... FAILED\n\n\
+            failures:\n\n\
+            ---- ch/readme.md - Title::Sub (line 11) stdout ----\n\
+            Test executable failed (exit status: 101).\n\n\
+            ---- ch/readme.md - Title::Sub (line 7) stdout ----\n\
+            error[E0308]: mismatched types\n\
+            Couldn't compile the test.\n\n\
+            failures:\n    ch/readme.md - Title::Sub (line 11)\n";
+        assert_eq!(
+            doctest_failures(stdout),
+            vec![
+                (11, "Test executable failed (exit status: 101).".into()),
+                (
+                    7,
+                    "error[E0308]: mismatched types\nCouldn't compile the test.".into()
+                ),
+            ]
+        );
+
+        let md_path = Path::new("../src/trans/readme.md");
+        let content = "```rust\n{{ #include code/sw_1.rs }}\n```\n";
+        let (expanded, line_map) = expand_includes(md_path, content).unwrap();
+        assert_eq!(expanded.lines().count(), line_map.len());
+        assert_eq!(line_map.first(), Some(&0));
+        assert_eq!(line_map.last(), Some(&2));
+        assert!(line_map[1..line_map.len() - 1].iter().all(|idx| *idx == 1));
+    }
+
+    #[test]
+    fn semantic_output_match() {
+        let snippet = "../src/mikino_bmc/code/sw_1.mkn";
+        let out = "../src/mikino_bmc/code/sw_1.mkn.out";
+        let expected = load_file(out).unwrap();
+        let mut report = Report::new();
+        output_same_as_file_content(snippet, &expected, out, &mut report).unwrap();
+        assert!(report.diagnostics.is_empty());
+
+        // Same counterexamples, in a different order.
+        let lines: Vec<&str> = expected.lines().collect();
+        let mut swapped = lines[..2].to_vec();
+        swapped.extend(&lines[10..18]);
+        swapped.extend(&lines[2..10]);
+        swapped.extend(&lines[18..]);
+        let output = format!("{}\n", swapped.join("\n"));
+        output_same_as_file_content(snippet, &output, out, &mut report).unwrap();
+        assert_eq!(report.count(Severity::Warning), 1);
+        assert_eq!(
+            report.diagnostics[0].file.as_deref(),
+            Some(Path::new(snippet))
+        );
+        assert!(report.diagnostics[0].message.contains(out));
+    }
+
+    #[test]
+    fn max_variants() {
+        let code = Path::new("../src/conclusion/code");
+        let snippet = code.join("wrap.mkn");
+        assert_eq!(
+            max_variant(&code.join("wrap.mkn.max_1")),
+            (snippet.clone(), Some(1))
+        );
+        assert_eq!(max_variant(&snippet), (snippet.clone(), None));
+        assert!(has_max_variants(code, "wrap.mkn".as_ref()).unwrap());
+
+        let conf = Conf::new().set_smt2(true, "z3").set_mikino(false, "mikino");
+        for max in [1, 5] {
+            let out = code.join(format!("wrap.mkn.max_{}.out", max));
+            match snippet_output_max(&conf, &snippet, Some(max)) {
+                Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                    eprintln!("z3 is not available, skipping")
+                }
+                res => assert_eq!(res.unwrap(), Some(load_file(out).unwrap())),
+            }
+        }
+        let sw = "../src/mikino_bmc/code/sw_1.mkn";
+        assert!(snippet_output_max(&conf, sw, Some(1)).is_err());
+    }
+
     /// Runs all the tests.
+    ///
+    /// Building the book, its doctests and the code snippets all report to the same [`Report`],
+    /// displayed at the end. Fails if the report contains errors.
     pub fn run(conf: &Conf, path: impl AsRef<Path>) -> Res<()> {
         let path = path.as_ref();
+        let mut report = Report::new();
 
         log::info!("testing book...");
-        test::book(path)?;
+        test::book(conf, path, &mut report)?;
 
         log::info!("testing code snippets");
         let mut src_path = path.to_path_buf();
         src_path.push("src");
-        test::code_out(conf, src_path)?;
+        let results = test::code_out(conf, &src_path, &mut report)?;
+
+        log::info!("testing exercises");
+        crate::exercise::verify(conf, &src_path, &mut report)?;
+
+        eprintln!("|===| Snippets by chapter:");
+        for results in &results {
+            eprintln!("| {}", results)
+        }
+        eprintln!("|===|");
 
+        if !report.diagnostics.is_empty() {
+            eprintln!("|===| Report:");
+            report.pretty_eprint("| ");
+            eprintln!("|===|");
+        }
+        let errors = report.count(Severity::Error);
+        if errors > 0 {
+            bail!(
+                "{} error(s), {} warning(s)",
+                errors,
+                report.count(Severity::Warning)
+            )
+        }
         log::info!("everything okay");
         Ok(())
     }
 
-    /// Tests the book itself.
-    pub fn book(path: impl AsRef<Path>) -> Res<()> {
-        use std::process::Command;
+    /// Tests the book itself, builds it and runs its doctests in-process.
+    ///
+    /// The book's own preprocessor and vanilla renderer run in-process too, instead of through
+    /// the `cargo run` commands of `book.toml`.
+    pub fn book(conf: &Conf, path: impl AsRef<Path>, report: &mut Report) -> Res<()> {
+        use mdbook::{errors::Error as MdError, Config, MDBook};
+        let md_err = |e: MdError| {
+            let msg: Vec<String> = e.chain().map(|e| e.to_string()).collect();
+            err::Error::from(msg.join("\n"))
+        };
+        let path = path.as_ref();
+
+        let mut config = Config::from_disk(path.join("book.toml")).map_err(|e| {
+            md_err(e).chain_err(|| format!("while loading `{}`", path.join("book.toml").display()))
+        })?;
+        if let Some(table) = config
+            .get_mut("preprocessor")
+            .and_then(|table| table.as_table_mut())
+        {
+            table.remove(crate::preprocess::NAME);
+        }
+        if let Some(table) = config
+            .get_mut("output")
+            .and_then(|table| table.as_table_mut())
+        {
+            table.remove(crate::render::NAME);
+        }
+        let mut book = MDBook::load_with_config(path, config)
+            .map_err(|e| md_err(e).chain_err(|| "while loading book"))?;
+        book.with_renderer(crate::render::Render);
+
+        log::info!("testing with `rustdoc`");
+        doctests(conf, &book, report)?;
 
         log::info!("building with `mdbook`");
-        let status = Command::new("mdbook")
-            .arg("build")
-            .arg("--")
-            .arg(path.as_ref())
-            .status()
-            .chain_err(|| "failed to run `mdbook test`")?;
-        if !status.success() {
-            bail!("`mdbook build` returned with an error")
+        // Same as running the preprocessor before mdbook's own preprocessors.
+        let manage = crate::preprocess::Manage::new(conf.clone());
+        for (file, e) in manage.book(&book.source_dir(), &mut book.book) {
+            report.push(Diagnostic::of_error("preprocess", &e).set_file(file))
         }
+        let (res, captured) = crate::report::capture(|| book.build());
+        for diagnostic in captured {
+            report.push(diagnostic)
+        }
+        if let Err(e) = res {
+            report.push(Diagnostic::of_error("mdbook", &md_err(e)))
+        }
+        Ok(())
+    }
 
-        log::info!("testing with `mdbook`");
-        let status = Command::new("mdbook")
-            .arg("test")
-            .arg("--")
-            .arg(path.as_ref())
-            .status()
-            .chain_err(|| "failed to run `mdbook test`")?;
-        if !status.success() {
-            bail!("`mdbook test` returned with an error")
+    /// Runs the doctests of the selected chapters of a book with `rustdoc`.
+    ///
+    /// Failures are reported at the line of the test's code block in the chapter.
+    fn doctests(conf: &Conf, book: &mdbook::MDBook, report: &mut Report) -> Res<()> {
+        use mdbook::{book::BookItem, config::RustEdition};
+
+        let tmp_dir = std::env::temp_dir().join(format!("manage_doctests_{}", std::process::id()));
+        let src_dir = book.source_dir();
+        let chapters = crate::chapter::Chapters::load(conf, &src_dir)?;
+        let edition = book.config.rust.edition.map(|edition| match edition {
+            RustEdition::E2015 => "2015",
+            RustEdition::E2018 => "2018",
+            RustEdition::E2021 => "2021",
+            RustEdition::E2024 => "2024",
+        });
+
+        for item in book.iter() {
+            let (chapter, path) = match item {
+                BookItem::Chapter(chapter) => match chapter.path.as_ref() {
+                    Some(path) if !path.as_os_str().is_empty() => (chapter, path),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if !chapters.is_selected(path) {
+                continue;
+            }
+            let md_path = src_dir.join(path);
+            log::debug!("testing chapter `{}`", md_path.display());
+
+            let (content, line_map) = match expand_includes(&md_path, &chapter.content) {
+                Ok(res) => res,
+                Err(e) => {
+                    report.push(Diagnostic::of_error("doctest", &e).set_file(md_path));
+                    continue;
+                }
+            };
+            let tmp_path = tmp_dir.join(path);
+            if let Some(parent) = tmp_path.parent() {
+                fs::create_dir_all(parent).chain_err(|| {
+                    format!(
+                        "during (recursive) folder creation for `{}`",
+                        parent.display()
+                    )
+                })?;
+            }
+            fs::write(&tmp_path, &content)
+                .chain_err(|| format!("while writing `{}`", tmp_path.display()))?;
+
+            let mut cmd = std::process::Command::new("rustdoc");
+            cmd.current_dir(&tmp_dir).arg(path).arg("--test");
+            if let Some(edition) = edition {
+                cmd.arg("--edition").arg(edition);
+            }
+            let output = match cmd.output() {
+                Ok(output) => output,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    bail!(err::ErrorKind::ToolUnavailable("rustdoc".into()))
+                }
+                Err(e) => return Err(e).chain_err(|| format!("running command {:?}", cmd)),
+            };
+            if output.status.success() {
+                continue;
+            }
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let failures = doctest_failures(&stdout);
+            if failures.is_empty() {
+                report.push(
+                    Diagnostic::new(
+                        Severity::Error,
+                        "doctest",
+                        format!(
+                            "`rustdoc --test` failed\n{}",
+                            String::from_utf8_lossy(&output.stderr)
+                        ),
+                    )
+                    .set_file(&md_path),
+                )
+            }
+            for (line, message) in failures {
+                let mut diagnostic =
+                    Diagnostic::new(Severity::Error, "doctest", format!("failed\n{}", message))
+                        .set_file(&md_path);
+                if let Some(line) = line_map.get(line.saturating_sub(1)) {
+                    diagnostic = diagnostic.set_line(line + 1)
+                }
+                report.push(diagnostic)
+            }
         }
+
+        let _ = fs::remove_dir_all(&tmp_dir);
         Ok(())
     }
 
+    /// Expands the include lines of a chapter.
+    ///
+    /// Also returns, for each line of the result, the index of the line it comes from in
+    /// `content`.
+    fn expand_includes(md_path: &Path, content: &str) -> Res<(String, Vec<usize>)> {
+        let (mut res, mut line_map) = (String::with_capacity(content.len()), vec![]);
+        for (idx, line) in content.lines().enumerate() {
+            let err = || format!("while inlining line {}: {}", idx + 1, line.trim());
+            let is_include = crate::preprocess::Directive::all_in(line)
+                .iter()
+                .any(|directive| directive.name == "include");
+            let lines = if is_include {
+                Include::parse(md_path, line)
+                    .chain_err(err)?
+                    .lines()
+                    .chain_err(err)?
+            } else {
+                vec![line.to_string()]
+            };
+            for line in lines {
+                res.push_str(&line);
+                res.push('\n');
+                line_map.push(idx);
+            }
+        }
+        Ok((res, line_map))
+    }
+
+    /// Extracts the failures from the output of `rustdoc --test` on a markdown file.
+    ///
+    /// Returns the line of each failing test, and its output.
+    fn doctest_failures(stdout: &str) -> Vec<(usize, String)> {
+        let mut res: Vec<(usize, String)> = vec![];
+        let mut current: Option<(usize, String)> = None;
+        for line in stdout.lines() {
+            if let Some(name) = line
+                .strip_prefix("---- ")
+                .and_then(|line| line.strip_suffix(" stdout ----"))
+            {
+                res.extend(current.take());
+                let test_line = name
+                    .rsplit_once("(line ")
+                    .and_then(|(_, tail)| tail.strip_suffix(')'))
+                    .and_then(|line| line.parse().ok());
+                if let Some(test_line) = test_line {
+                    current = Some((test_line, String::new()))
+                }
+            } else if line == "failures:" {
+                res.extend(current.take());
+            } else if let Some((_, output)) = current.as_mut() {
+                if !(output.is_empty() && line.trim().is_empty()) {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+        }
+        res.extend(current);
+        for (_, output) in res.iter_mut() {
+            output.truncate(output.trim_end().len());
+        }
+        res
+    }
+
     macro_rules! dir_read_err {
         { $dir:expr } => {
             || format!("while reading directory `{}`", $dir)
         };
     }
 
-    /// Tests the code snippets that have a `.out` file.
-    pub fn code_out(conf: &Conf, path: impl AsRef<Path>) -> Res<()> {
-        code_out_in(conf, path)
+    /// Tests the code snippets that have a `.out` file, returns the results of each chapter.
+    pub fn code_out(
+        conf: &Conf,
+        path: impl AsRef<Path>,
+        report: &mut Report,
+    ) -> Res<Vec<crate::chapter::Results>> {
+        code_out_in(conf, path, report)
+    }
+    /// Checks the `code` directories in `src` of the selected chapters, in summary order.
+    ///
+    /// Chapters without `code` directories have no results.
+    fn code_out_in(
+        conf: &Conf,
+        src: impl AsRef<Path>,
+        report: &mut Report,
+    ) -> Res<Vec<crate::chapter::Results>> {
+        let src = src.as_ref();
+        let chapters = crate::chapter::Chapters::load(conf, src)?;
+        let dirs = code_dirs(src)?;
+        let mut res = vec![];
+        for (idx, chapter) in chapters.all().iter().enumerate() {
+            if !chapters.selected().any(|selected| selected == chapter) {
+                continue;
+            }
+            let dirs: Vec<&PathBuf> = dirs
+                .iter()
+                .filter(|dir| chapters.index_of(dir) == Some(idx))
+                .collect();
+            if dirs.is_empty() {
+                continue;
+            }
+            log::info!("testing the code snippets of `{}`", chapter.title());
+            let mut results = crate::chapter::Results::new(chapter.title());
+            let start = std::time::Instant::now();
+            for dir in dirs {
+                code_out_check(conf, dir, report, &mut results)
+                    .chain_err(|| format!("while checking code snippets in `{}`", dir.display()))?
+            }
+            results.time = start.elapsed();
+            res.push(results)
+        }
+        Ok(res)
+    }
+
+    /// Collects the `code` directories in `src`, recursively, sorted.
+    pub fn code_dirs(src: impl AsRef<Path>) -> Res<Vec<PathBuf>> {
+        let mut dirs = vec![];
+        code_dirs_in(src, &mut dirs)?;
+        dirs.sort();
+        Ok(dirs)
+    }
+    /// Collects the snippets with some extension in the `code` directories in `src`, sorted.
+    pub fn snippets(src: impl AsRef<Path>, ext: &str) -> Res<Vec<PathBuf>> {
+        let mut snippets = vec![];
+        for dir in code_dirs(src)? {
+            for entry_res in dir.read_dir().chain_err(dir_read_err!(dir.display()))? {
+                let entry_path = entry_res.chain_err(dir_read_err!(dir.display()))?.path();
+                if entry_path.is_file() && entry_path.extension().map(|e| e == ext).unwrap_or(false)
+                {
+                    snippets.push(entry_path)
+                }
+            }
+        }
+        snippets.sort();
+        Ok(snippets)
     }
     /// Searches for `code` directories in `src`, recursively.
-    fn code_out_in(conf: &Conf, src: impl AsRef<Path>) -> Res<()> {
+    fn code_dirs_in(src: impl AsRef<Path>, dirs: &mut Vec<PathBuf>) -> Res<()> {
         const CODE_DIR: &str = "code";
         let src = src.as_ref().to_path_buf();
-        log::trace!("code_out_in({})", src.display());
+        log::trace!("code_dirs_in({})", src.display());
 
         if !(src.exists() && src.is_dir()) {
             bail!("expected directory path, got `{}`", src.display())
@@ -209,21 +631,19 @@ pub mod test {
                 continue 'sub_dirs;
             }
 
-            log::trace!("code_out_in: looking at `{}`", entry_path.display());
+            log::trace!("code_dirs_in: looking at `{}`", entry_path.display());
 
-            // `code` directory, check what's inside
+            // `code` directory, remember it
             if entry_path
                 .file_name()
                 .map(|name| name == CODE_DIR)
                 .unwrap_or(false)
             {
-                code_out_check(conf, &entry_path).chain_err(|| {
-                    format!("while checking code snippets in `{}`", entry_path.display())
-                })?
+                dirs.push(entry_path.clone())
             }
 
             // just a sub-directory, go down
-            code_out_in(conf, entry_path)?
+            code_dirs_in(entry_path, dirs)?
         }
 
         Ok(())
@@ -236,14 +656,25 @@ pub mod test {
     ///
     /// For instance, `<name>.smt2` file's corresponding tool is Z3 and the output file contains
     /// the output of `z3 <name>.smt2`.
-    fn code_out_check(conf: &Conf, path: impl AsRef<Path>) -> Res<()> {
+    ///
+    /// Snippets that fail their check are reported in `report`, and counted in `results`.
+    fn code_out_check(
+        conf: &Conf,
+        path: impl AsRef<Path>,
+        report: &mut Report,
+        results: &mut crate::chapter::Results,
+    ) -> Res<()> {
         const OUT_SUFF: &str = "out";
         let path = path.as_ref();
         log::trace!("code_out_check({})", path.display());
 
-        'out_files: for entry_res in path.read_dir().chain_err(dir_read_err!(path.display()))? {
-            let entry = entry_res.chain_err(dir_read_err!(path.display()))?;
-            let entry_path = entry.path();
+        let mut entries = vec![];
+        for entry_res in path.read_dir().chain_err(dir_read_err!(path.display()))? {
+            entries.push(entry_res.chain_err(dir_read_err!(path.display()))?.path())
+        }
+        entries.sort();
+
+        'out_files: for entry_path in entries {
             if entry_path.is_dir() {
                 continue 'out_files;
             }
@@ -259,12 +690,12 @@ pub mod test {
 
             if !is_out_file {
                 log::trace!("not an `out` file");
-                warn_if_not_tested(path, entry_path)?;
+                warn_if_not_tested(path, entry_path, report)?;
                 continue 'out_files;
             }
 
             let out_path = entry_path;
-            let snippet_path = {
+            let (snippet_path, max) = max_variant(&{
                 let mut path = out_path.clone();
                 let stem = path
                     .file_stem()
@@ -278,7 +709,7 @@ pub mod test {
                 }
                 path.push(stem);
                 path
-            };
+            });
 
             log::trace!(
                 "out: {}, snippet: {}",
@@ -286,16 +717,6 @@ pub mod test {
                 snippet_path.display()
             );
 
-            let ext = snippet_path
-                .extension()
-                .ok_or_else(|| {
-                    format!(
-                        "could not retrieve extension for `{}`",
-                        snippet_path.display()
-                    )
-                })?
-                .to_string_lossy();
-
             let err = || {
                 format!(
                     "while checking `{}` with out file `{}`",
@@ -304,21 +725,27 @@ pub mod test {
                 )
             };
 
-            let actually_okay = if ext == "smt2" {
-                code_out_check_smt2(conf, &out_path, &snippet_path).chain_err(err)?
-            } else if ext == "hsmt" {
-                code_out_check_hsmt(conf, &out_path, &snippet_path).chain_err(err)?
-            } else if ext == "mkn" {
-                code_out_check_mkn(conf, &out_path, &snippet_path).chain_err(err)?
-            } else if ext == "rs" {
-                code_out_check_rs(conf, &out_path, &snippet_path).chain_err(err)?
-            } else {
-                bail!(
-                    "unknown extension `{}` for code snippet `{}` with out file `{}`",
-                    ext,
-                    snippet_path.display(),
-                    out_path.display()
-                )
+            let check = |report: &mut Report| -> Res<bool> {
+                match snippet_output_max(conf, &snippet_path, max)? {
+                    Some(output) => {
+                        output_same_as_file_content(&snippet_path, &output, &out_path, report)?;
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            };
+            results.snippets += 1;
+            let errors = report.count(Severity::Error);
+            let actually_okay = match check(report).chain_err(err) {
+                Ok(true) => true,
+                Ok(false) => {
+                    results.skipped += 1;
+                    continue 'out_files;
+                }
+                Err(e) => {
+                    report.push(Diagnostic::of_error("snippet", &e).set_file(&snippet_path));
+                    false
+                }
             };
 
             if actually_okay {
@@ -327,9 +754,77 @@ pub mod test {
                     snippet_path.display(),
                     out_path.display()
                 );
+                if conf.validate_models && snippet_path.extension().is_some_and(|e| e == "smt2") {
+                    validate_models(conf, &snippet_path, &out_path, report).chain_err(err)?
+                }
+            }
+            if report.count(Severity::Error) > errors {
+                results.failed += 1
+            } else {
+                results.ok += 1
             }
         }
 
+        cross_check_pairs(conf, path, report)
+    }
+
+    /// Cross-checks the mikino scripts in `path` with their SMT-LIB 2 counterparts, see
+    /// [`crate::pair`].
+    ///
+    /// Models are cross-checked too when model validation is active.
+    fn cross_check_pairs(conf: &Conf, path: &Path, report: &mut Report) -> Res<()> {
+        let z3_cmd = if conf.validate_models {
+            Some(conf.get_smt2()?.1)
+        } else {
+            None
+        };
+        for hsmt in crate::pair::pairs(path)? {
+            let divergences = match crate::pair::cross_check(z3_cmd, &hsmt) {
+                Ok(divergences) => divergences,
+                Err(e) => {
+                    report.push(Diagnostic::of_error("pair", &e).set_file(&hsmt));
+                    continue;
+                }
+            };
+            for divergence in divergences {
+                let mut diag = Diagnostic::new(Severity::Error, "pair", divergence.to_string())
+                    .set_file(&hsmt);
+                if let Some(pos) = divergence.pos {
+                    diag = diag.set_line(pos.line).set_col(pos.col)
+                }
+                report.push(diag)
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the models of an `.smt2` output file, see [`crate::model`].
+    fn validate_models(
+        conf: &Conf,
+        snippet_path: &Path,
+        out_path: &Path,
+        report: &mut Report,
+    ) -> Res<()> {
+        let (_, z3_cmd) = conf.get_smt2()?;
+        let violations = match crate::model::validate(
+            z3_cmd,
+            &load_file(snippet_path)?,
+            &load_file(out_path)?,
+        ) {
+            Ok(violations) => violations,
+            Err(e) => {
+                report.push(Diagnostic::of_error("model", &e).set_file(out_path));
+                return Ok(());
+            }
+        };
+        for violation in violations {
+            report.push(
+                Diagnostic::new(Severity::Error, "model", violation.to_string())
+                    .set_file(snippet_path)
+                    .set_line(violation.pos.line)
+                    .set_col(violation.pos.col),
+            )
+        }
         Ok(())
     }
 
@@ -342,7 +837,11 @@ pub mod test {
     ///
     /// Otherwise we have non-Rust file with no output file, meaning the file is not tested against
     /// anything. We assume the author forgot the output file and issue a warning.
-    fn warn_if_not_tested(parent: impl AsRef<Path>, snippet_path: impl AsRef<Path>) -> Res<()> {
+    fn warn_if_not_tested(
+        parent: impl AsRef<Path>,
+        snippet_path: impl AsRef<Path>,
+        report: &mut Report,
+    ) -> Res<()> {
         let (parent, snippet) = (parent.as_ref(), snippet_path.as_ref());
 
         // Rust files are tested by `mdbook`, no need for output file.
@@ -359,33 +858,140 @@ pub mod test {
             parent.push(format!("{}.out", file_name.to_string_lossy()));
             parent
         };
-        if !out_file.exists() {
-            log::warn!(
-                "file `{}` has no output file, no way to test it",
-                snippet.display()
+        if !out_file.exists() && !has_max_variants(parent, file_name)? {
+            report.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    "snippet",
+                    "no output file, no way to test it",
+                )
+                .set_file(snippet),
             );
         }
         if out_file.is_dir() {
-            log::warn!(
-                "file `{}` has no output 'file', `{}` exists but is a directory",
-                snippet.display(),
-                out_file.display()
+            report.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    "snippet",
+                    format!(
+                        "no output 'file', `{}` exists but is a directory",
+                        out_file.display()
+                    ),
+                )
+                .set_file(snippet),
             );
         }
 
         Ok(())
     }
 
-    /// Compares the output of a command to the content of a file.
-    fn cmd_output_same_as_file_content(
-        cmd: &mut std::process::Command,
+    /// Extension prefix of the output files of a `manage prove` snippet for a given `--max`.
+    ///
+    /// Output file `<name>.max_<k>.out` contains the output of snippet `<name>` with `--max <k>`.
+    const MAX_VARIANT_PREF: &str = "max_";
+
+    /// Splits `<name>.max_<k>` into `<name>` and `k`, see [`MAX_VARIANT_PREF`].
+    fn max_variant(path: &Path) -> (PathBuf, Option<usize>) {
+        let max = path
+            .extension()
+            .and_then(|ext| ext.to_str()?.strip_prefix(MAX_VARIANT_PREF)?.parse().ok());
+        match max {
+            Some(max) => (path.with_extension(""), Some(max)),
+            None => (path.to_path_buf(), None),
+        }
+    }
+
+    /// True if snippet `file_name` in `parent` has `<file_name>.max_<k>.out` output files.
+    fn has_max_variants(parent: &Path, file_name: &std::ffi::OsStr) -> Res<bool> {
+        let pref = format!("{}.{}", file_name.to_string_lossy(), MAX_VARIANT_PREF);
+        for entry in parent
+            .read_dir()
+            .chain_err(dir_read_err!(parent.display()))?
+        {
+            let name = entry
+                .chain_err(dir_read_err!(parent.display()))?
+                .file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with(&pref) && name.ends_with(".out") {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Runs a code snippet with the tool corresponding to its extension, returns its output.
+    ///
+    /// Returns `None` if checking this kind of snippet is deactivated in `conf`. Fails with
+    /// [`err::ErrorKind::ToolUnavailable`] if the tool cannot be found.
+    pub fn snippet_output(conf: &Conf, snippet_path: impl AsRef<Path>) -> Res<Option<String>> {
+        snippet_output_max(conf, snippet_path, None)
+    }
+
+    /// Same as [`snippet_output`], but `max` overrides the `--max` of a `manage prove` snippet.
+    ///
+    /// Fails if `max` is given and the snippet's `// CMD:` line is not `manage prove`.
+    pub fn snippet_output_max(
+        conf: &Conf,
+        snippet_path: impl AsRef<Path>,
+        max: Option<usize>,
+    ) -> Res<Option<String>> {
+        let snippet_path = snippet_path.as_ref();
+        let ext = snippet_path
+            .extension()
+            .ok_or_else(|| {
+                format!(
+                    "could not retrieve extension for `{}`",
+                    snippet_path.display()
+                )
+            })?
+            .to_string_lossy();
+
+        if ext == "hsmt" || ext == "mkn" {
+            snippet_output_mkn(conf, snippet_path, max)
+        } else if max.is_some() {
+            bail!(
+                "only `manage prove` snippets can have `.{}<k>.out` output files, `{}` is not one",
+                MAX_VARIANT_PREF,
+                snippet_path.display()
+            )
+        } else if ext == "smt2" {
+            snippet_output_smt2(conf, snippet_path)
+        } else if ext == "rs" {
+            snippet_output_rs(conf, snippet_path)
+        } else {
+            bail!(
+                "unknown extension `{}` for code snippet `{}`",
+                ext,
+                snippet_path.display(),
+            )
+        }
+    }
+
+    /// Runs a command, returns its output.
+    ///
+    /// Fails with [`err::ErrorKind::ToolUnavailable`] if the command's program cannot be found.
+    fn cmd_output(cmd: &mut std::process::Command) -> Res<String> {
+        let output = match cmd.output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => bail!(
+                err::ErrorKind::ToolUnavailable(cmd.get_program().to_string_lossy().into_owned())
+            ),
+            Err(e) => return Err(e).chain_err(|| format!("running command {:?}", cmd)),
+        };
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Compares the output of a snippet to the content of a file.
+    ///
+    /// Outputs of `.mkn` snippets that only match semantically are accepted, with a warning in
+    /// `report` so that the output file gets regenerated.
+    fn output_same_as_file_content(
+        snippet_path: impl AsRef<Path>,
+        output: &str,
         path: impl AsRef<Path>,
+        report: &mut Report,
     ) -> Res<()> {
         let path = path.as_ref();
-        let output = cmd
-            .output()
-            .chain_err(|| format!("running command {:?}", cmd))?;
-        let stdout = String::from_utf8_lossy(&output.stdout);
         let expected = {
             use std::{fs::OpenOptions, io::BufReader};
             let mut file = BufReader::new(
@@ -399,103 +1005,160 @@ pub mod test {
                 .chain_err(|| format!("while reading `{}`", path.display()))?;
             buf
         };
-        if stdout != expected {
+        if output == expected {
+            Ok(())
+        } else if same_mikino_output(&snippet_path, output, &expected) {
+            report.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    "snippet",
+                    format!(
+                        "output differs from `{}`, but verdicts and cex-s are the same, \
+                        consider regenerating it",
+                        path.display()
+                    ),
+                )
+                .set_file(snippet_path.as_ref()),
+            );
+            Ok(())
+        } else {
             eprintln!("|===| expected");
             for line in expected.lines() {
                 eprintln!("| `{}`", line);
             }
             eprintln!("|===| got");
-            for line in stdout.lines() {
+            for line in output.lines() {
                 eprintln!("| `{}`", line);
             }
             eprintln!("|===|");
-            bail!("unexpected output for `{:?}`", cmd)
-        } else {
-            Ok(())
+            bail!(
+                "unexpected output for `{}`",
+                snippet_path.as_ref().display()
+            )
         }
     }
 
-    /// Checks a single `.smt2` file `snippet_path` against its output file `out_path`.
-    fn code_out_check_smt2(
-        conf: &Conf,
-        out_path: impl AsRef<Path>,
-        snippet_path: impl AsRef<Path>,
-    ) -> Res<bool> {
-        let (out_path, snippet_path) = (out_path.as_ref(), snippet_path.as_ref());
+    /// True if `snippet_path` is a `.mkn` snippet and both outputs have the same verdicts and
+    /// counterexamples, see [`crate::cex::Output::same_as`].
+    fn same_mikino_output(snippet_path: impl AsRef<Path>, output: &str, expected: &str) -> bool {
+        let is_mkn = snippet_path
+            .as_ref()
+            .extension()
+            .map(|ext| ext == "mkn")
+            .unwrap_or(false);
+        if !is_mkn {
+            return false;
+        }
+        match (crate::cex::parse(output), crate::cex::parse(expected)) {
+            (Ok(output), Ok(expected)) => !expected.is_empty() && output.same_as(&expected),
+            _ => false,
+        }
+    }
+
+    /// Runs a single `.smt2` file `snippet_path`.
+    fn snippet_output_smt2(conf: &Conf, snippet_path: impl AsRef<Path>) -> Res<Option<String>> {
+        let snippet_path = snippet_path.as_ref();
         let (check_smt2, z3_cmd) = conf.get_smt2()?;
         if !check_smt2 {
             log::warn!(
-                "SMT2 checking deactivated, skipping `{}` (`{}`)",
+                "SMT2 checking deactivated, skipping `{}`",
                 snippet_path.display(),
-                out_path.display()
             );
-            return Ok(false);
+            return Ok(None);
         }
         let mut cmd = std::process::Command::new(z3_cmd);
         cmd.arg("-T:5").arg(snippet_path);
-        let () = cmd_output_same_as_file_content(&mut cmd, out_path)?;
-
-        Ok(true)
+        cmd_output(&mut cmd).map(Some)
     }
 
-    /// Checks a single `.hsmt` file `snippet_path` against its output file `out_path`.
-    fn code_out_check_hsmt(
+    /// Runs a single `.mkn` or `.hsmt` file `snippet_path`.
+    ///
+    /// Uses mikino, unless the `// CMD:` line is a `manage` command. `max` overrides the `--max`
+    /// of `manage prove`.
+    fn snippet_output_mkn(
         conf: &Conf,
-        out_path: impl AsRef<Path>,
         snippet_path: impl AsRef<Path>,
-    ) -> Res<bool> {
-        let (out_path, snippet_path) = (out_path.as_ref(), snippet_path.as_ref());
+        max: Option<usize>,
+    ) -> Res<Option<String>> {
+        let snippet_path = snippet_path.as_ref();
+        if let Ok(cmd_line) = cmd_line_of(snippet_path, "//") {
+            if let Some(("manage", args)) = cmd_line.split_first().map(|(c, a)| (c.as_str(), a)) {
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                return snippet_output_manage(conf, snippet_path, &args, max);
+            }
+        }
+        if max.is_some() {
+            bail!(
+                "only `manage prove` snippets can have `.{}<k>.out` output files, `{}` is not one",
+                MAX_VARIANT_PREF,
+                snippet_path.display()
+            )
+        }
         let (check_mikino, mikino_cmd) = conf.get_mikino()?;
         if !check_mikino {
             log::warn!(
-                "mikino checking deactivated, skipping `{}` (`{}`)",
+                "mikino checking deactivated, skipping `{}`",
                 snippet_path.display(),
-                out_path.display()
             );
-            return Ok(false);
+            return Ok(None);
         }
 
         let (_, z3_cmd) = conf.get_smt2()?;
         let mut cmd = retrieve_mkn_cmd(mikino_cmd, z3_cmd, snippet_path, "//")?;
-        cmd_output_same_as_file_content(&mut cmd, out_path)?;
-
-        Ok(true)
+        cmd_output(&mut cmd).map(Some)
     }
 
-    /// Checks a single `.mkn` file `snippet_path` against its output file `out_path`.
-    fn code_out_check_mkn(
+    /// Runs a `.mkn` file `snippet_path` with the reference engine, see [`crate::prove`].
+    ///
+    /// `args` are the arguments following `manage` on the `// CMD:` line, `max` overrides their
+    /// `--max`.
+    fn snippet_output_manage(
         conf: &Conf,
-        out_path: impl AsRef<Path>,
-        snippet_path: impl AsRef<Path>,
-    ) -> Res<bool> {
-        let (out_path, snippet_path) = (out_path.as_ref(), snippet_path.as_ref());
-        let (check_mikino, mikino_cmd) = conf.get_mikino()?;
-        if !check_mikino {
+        snippet_path: &Path,
+        args: &[&str],
+        max: Option<usize>,
+    ) -> Res<Option<String>> {
+        let (check_smt2, z3_cmd) = conf.get_smt2()?;
+        if !check_smt2 {
             log::warn!(
-                "mikino checking deactivated, skipping `{}` (`{}`)",
+                "SMT2 checking deactivated, skipping `{}`",
                 snippet_path.display(),
-                out_path.display()
             );
-            return Ok(false);
+            return Ok(None);
         }
-
-        let (_, z3_cmd) = conf.get_smt2()?;
-        let mut cmd = retrieve_mkn_cmd(mikino_cmd, z3_cmd, snippet_path, "//")?;
-        cmd_output_same_as_file_content(&mut cmd, out_path)?;
-
-        Ok(true)
+        let source = snippet_path.to_string_lossy();
+        let mut prove = crate::prove::Prove::new(&source).set_z3_cmd(z3_cmd);
+        let mut args = args.iter();
+        match args.next() {
+            Some(&"prove") => (),
+            Some(arg) => bail!("unexpected `manage` subcommand `{}`, expected `prove`", arg),
+            None => bail!("expected `prove` after `manage`"),
+        }
+        while let Some(arg) = args.next() {
+            match *arg {
+                "<file>" => (),
+                "--max" => {
+                    let max = args.next().ok_or("expected integer after `--max`")?;
+                    let max = max
+                        .parse()
+                        .map_err(|_| format!("expected integer after `--max`, got `{}`", max))?;
+                    prove = prove.set_max(max)
+                }
+                arg => bail!("unexpected `manage prove` argument `{}`", arg),
+            }
+        }
+        if let Some(max) = max {
+            prove = prove.set_max(max)
+        }
+        prove.output().map(Some)
     }
 
-    fn retrieve_mkn_cmd(
-        mikino_cmd: &str,
-        z3_cmd: &str,
-        path: impl AsRef<Path>,
-        pref: &str,
-    ) -> Res<std::process::Command> {
+    /// Elements of the `<pref> CMD: ...` first line of a file.
+    fn cmd_line_of(path: impl AsRef<Path>, pref: &str) -> Res<Vec<String>> {
         let path = path.as_ref();
         // Mikino files are expected to start with a special line specifying the command to run.
         let cmd_line = first_line_of(path)?
-            .ok_or_else(|| "first line of `mkn` files must specify a `mikino` command")?;
+            .ok_or("first line of `mkn` files must specify a `mikino` command")?;
         const CMD_PREF: &str = " CMD: ";
         if !cmd_line.starts_with(pref) || !cmd_line[pref.len()..].starts_with(CMD_PREF) {
             bail!(
@@ -506,8 +1169,21 @@ pub mod test {
         }
 
         let start = pref.len() + CMD_PREF.len();
-        let cmd_line = &cmd_line[start..];
-        let mut elems = cmd_line.split_whitespace();
+        Ok(cmd_line[start..]
+            .split_whitespace()
+            .map(String::from)
+            .collect())
+    }
+
+    fn retrieve_mkn_cmd(
+        mikino_cmd: &str,
+        z3_cmd: &str,
+        path: impl AsRef<Path>,
+        pref: &str,
+    ) -> Res<std::process::Command> {
+        let path = path.as_ref();
+        let cmd_line = cmd_line_of(path, pref)?;
+        let mut elems = cmd_line.iter().map(String::as_str);
 
         match elems.next() {
             Some("mikino") => (),
@@ -519,7 +1195,7 @@ pub mod test {
         }
 
         let mut cmd = std::process::Command::new(mikino_cmd);
-        cmd.arg("--z3_cmd").arg(&format!("{} -T:5", z3_cmd));
+        cmd.arg("--z3_cmd").arg(format!("{} -T:5", z3_cmd));
 
         for arg in elems {
             if arg == "<file>" {
@@ -532,21 +1208,21 @@ pub mod test {
         Ok(cmd)
     }
 
-    /// Checks a single `.mkn` file `snippet_path` against its output file `out_path`.
-    fn code_out_check_rs(
-        _conf: &Conf,
-        out_path: impl AsRef<Path>,
-        snippet_path: impl AsRef<Path>,
-    ) -> Res<bool> {
-        let (out_path, snippet_path) = (out_path.as_ref(), snippet_path.as_ref());
+    /// Compiles and runs a single `.rs` file `snippet_path`.
+    fn snippet_output_rs(_conf: &Conf, snippet_path: impl AsRef<Path>) -> Res<Option<String>> {
+        let snippet_path = snippet_path.as_ref();
 
         let tmpfile = PathBuf::from("./dont_exist_please_CI_does_not_like_tempfile");
         let mut cmd = std::process::Command::new("rustc");
         cmd.arg("-o").arg(&tmpfile).arg(snippet_path);
         let cmd_string = || format!("rustc -o {} {}", tmpfile.display(), snippet_path.display());
-        let status = cmd
-            .status()
-            .chain_err(|| format!("while running {}", cmd_string()))?;
+        let status = match cmd.status() {
+            Ok(status) => status,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                bail!(err::ErrorKind::ToolUnavailable("rustc".into()))
+            }
+            Err(e) => return Err(e).chain_err(|| format!("while running {}", cmd_string())),
+        };
         if !status.success() {
             bail!(
                 "command `{}` was not successful, exit code {}",
@@ -558,13 +1234,13 @@ pub mod test {
             )
         }
         let mut cmd = std::process::Command::new(&tmpfile);
-        cmd_output_same_as_file_content(&mut cmd, out_path)?;
+        let output = cmd_output(&mut cmd)?;
 
         // Delete temporary file.
         std::fs::remove_file(&tmpfile)
             .chain_err(|| format!("while deleting temp file `{}`", tmpfile.display()))?;
 
-        Ok(true)
+        Ok(Some(output))
     }
 
     /// Retrieves the first line of a file.
@@ -604,67 +1280,206 @@ pub struct TopLevelMd {
     path: String,
     title: String,
 }
+impl TopLevelMd {
+    /// Constructor, `path` is relative to the book's `src` directory.
+    pub fn new(title: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            path: path.into(),
+        }
+    }
+
+    /// True if a chapter of the summary is a top-level markdown file.
+    ///
+    /// Top-level files are `readme.md` files, except the introduction.
+    pub fn is_top_level(title: &str, path: &str) -> bool {
+        path.contains("readme.md") && !title.contains("Introduction")
+    }
+
+    /// Path of the markdown file, relative to the book's `src` directory.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Title of the chapter.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Slug of the chapter's title, stable as long as the title does not change.
+    pub fn slug(&self) -> String {
+        let mut slug = String::with_capacity(self.title.len());
+        for c in self.title.chars() {
+            if c.is_alphanumeric() {
+                slug.extend(c.to_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with('_') {
+                slug.push('_');
+            }
+        }
+        while slug.ends_with('_') {
+            slug.pop();
+        }
+        slug
+    }
+}
 
 /// Vanilla markdown generator.
 pub struct Vanilla<'s> {
     target: &'s str,
-    #[allow(dead_code)]
+    /// Configuration for running the snippets of `{{ #run }}` directives.
     conf: Conf<'s>,
+    prefix: bool,
+    src: PathBuf,
+    preprocessed: bool,
 }
 impl<'s> Vanilla<'s> {
     /// Constructor.
     pub fn new(conf: Conf<'s>, target: &'s str) -> Self {
         Self {
             conf,
-            target: target.into(),
+            target,
+            prefix: false,
+            src: Self::SRC.into(),
+            preprocessed: false,
         }
     }
+
+    /// Sets the book's source directory, [`Self::SRC`] by default.
+    pub fn set_src(mut self, src: impl Into<PathBuf>) -> Self {
+        self.src = src.into();
+        self
+    }
+    /// If true, output file names are prefixed by the chapter's position in the summary.
+    pub fn set_prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+    /// If true, the markdown given to [`Self::write_chapters`] is already preprocessed.
+    ///
+    /// Its directives and includes are then not expanded again, which is the case of the `mdbook`
+    /// renderer (see [`crate::render`]).
+    pub fn set_preprocessed(mut self, preprocessed: bool) -> Self {
+        self.preprocessed = preprocessed;
+        self
+    }
     /// Target accessor.
     pub fn target(&self) -> &'s str {
         self.target
     }
 
     /// Runs vanilla markdown generation.
+    ///
+    /// Only rewrites the files whose content changed since the last run, as recorded in the
+    /// target's [`Manifest`]. Files from the previous run that are not generated anymore are
+    /// deleted.
     pub fn run(&self) -> Res<()> {
+        let top_level = Self::top_level_md(&self.src)?;
+        self.write_chapters(top_level, |file| {
+            load_file(self.src_dir().join(file.path()))
+        })
+    }
+
+    /// Generates and writes the vanilla markdown for some top-level files.
+    ///
+    /// Shared by [`Self::run`] and the `mdbook` renderer (see [`crate::render`]), `content`
+    /// yields the markdown of a top-level file, preprocessed or not (see
+    /// [`Self::set_preprocessed`]).
+    pub fn write_chapters(
+        &self,
+        top_level: Vec<TopLevelMd>,
+        mut content: impl FnMut(&TopLevelMd) -> Res<String>,
+    ) -> Res<()> {
         std::fs::create_dir_all(self.target)
             .chain_err(|| format!("during (recursive) folder creation for `{}`", self.target))?;
-        let top_level = Self::top_level_md()?;
         log::info!(
             "working on vanilla versions for {} markdown file(s)",
             top_level.len()
         );
+        let old_manifest = Manifest::load(self.target)?;
+        let mut manifest = Manifest::default();
+        let mut assets = Assets::new(self.target);
+        let mut written = 0;
+
         for (idx, file) in top_level.into_iter().enumerate() {
+            let slug = file.slug();
+            let name = if self.prefix {
+                format!("{:0>2}_{}.md", idx, slug)
+            } else {
+                format!("{}.md", slug)
+            };
+            if manifest.get(&name).is_some() {
+                bail!(
+                    "chapter `{}` would overwrite vanilla file `{}` of a previous chapter",
+                    file.title,
+                    name
+                )
+            }
+
             log::debug!(
                 "generating vanilla markdown for `{}` from `{}`",
                 file.title,
                 file.path,
             );
-            self.work_one(idx, file)?;
+            let content = content(&file)?;
+            let content = self.work_one(&file, &content, &mut assets)?;
+            let hash = assets::hash(content.as_bytes());
+            let tgt_path = self.tgt_dir().join(&name);
+
+            let up_to_date = old_manifest
+                .get(&name)
+                .map(|entry| entry.hash == hash)
+                .unwrap_or(false)
+                && tgt_path.is_file();
+            if up_to_date {
+                log::debug!("`{}` is up to date", tgt_path.display());
+            } else {
+                log::debug!("writing `{}`", tgt_path.display());
+                let mut tgt_file = open_write(&tgt_path)?;
+                io::Write::write_all(&mut tgt_file, content.as_bytes())
+                    .chain_err(|| format!("while writing `{}`", tgt_path.display()))?;
+                written += 1;
+            }
+
+            manifest.entries.push(ManifestEntry {
+                file: name,
+                hash,
+                src: file.path,
+            });
+        }
+
+        for entry in &old_manifest.entries {
+            if manifest.get(&entry.file).is_none() {
+                let path = self.tgt_dir().join(&entry.file);
+                if path.is_file() {
+                    log::debug!("removing stale file `{}`", path.display());
+                    fs::remove_file(&path)
+                        .chain_err(|| format!("while removing stale file `{}`", path.display()))?;
+                }
+            }
         }
+        manifest.write(self.target)?;
 
-        log::info!("done with vanilla markdown generation");
+        log::info!(
+            "done with vanilla markdown generation, {} file(s) (re)written",
+            written
+        );
         Ok(())
     }
 
-    const SRC: &'static str = "src";
-    const SUMMARY: &'static str = "src/SUMMARY.md";
+    /// Book source directory.
+    pub const SRC: &'static str = "src";
 
-    /// Vector of the top-level markdown files.
-    pub fn top_level_md() -> Res<Vec<TopLevelMd>> {
+    /// Vector of the top-level markdown files of the book in `src`.
+    pub fn top_level_md(src: impl AsRef<Path>) -> Res<Vec<TopLevelMd>> {
         let mut res = vec![];
 
-        let content =
-            load_file(Self::SUMMARY).chain_err(|| format!("on top-level summary file"))?;
+        let summary = src.as_ref().join(crate::chapter::SUMMARY);
+        let content = load_file(&summary).chain_err(|| "on top-level summary file")?;
 
         for (idx, line) in content.lines().enumerate() {
             // skip lines that refer no markdown
             if !line.contains("readme.md") {
                 continue;
             }
-            // skip intro
-            if line.contains("Introduction") {
-                continue;
-            }
 
             // Expecting a line of shape `- [<NAME>](<PATH>)`
             let err = || {
@@ -672,110 +1487,161 @@ impl<'s> Vanilla<'s> {
                     "line {} is illegal, expected `- [<TITLE>](<PATH>)`",
                     idx
                 ))
-                .chain_err(|| format!("in summary file `{}`", Self::SUMMARY))
+                .chain_err(|| format!("in summary file `{}`", summary.display()))
             };
             let title_start = line.find('[').ok_or_else(err)? + 1;
             let title_end = line.find(']').ok_or_else(err)?;
             let path_start = line.find('(').ok_or_else(err)? + 1;
             let path_end = line.find(')').ok_or_else(err)?;
 
-            let title = line[title_start..title_end].into();
-            let path = line[path_start..path_end].into();
+            let title = &line[title_start..title_end];
+            let path = &line[path_start..path_end];
 
-            res.push(TopLevelMd { title, path })
+            // skip intro
+            if !TopLevelMd::is_top_level(title, path) {
+                continue;
+            }
+
+            res.push(TopLevelMd::new(title, path))
         }
 
         Ok(res)
     }
 
     pub fn src_dir(&self) -> PathBuf {
-        PathBuf::from(Self::SRC)
+        self.src.clone()
     }
     pub fn tgt_dir(&self) -> PathBuf {
         PathBuf::from(self.target)
     }
 
-    /// Works on a single top-level file.
-    pub fn work_one(&self, idx: usize, file: TopLevelMd) -> Res<()> {
+    /// Generates the vanilla markdown for a single top-level file with markdown `src_content`.
+    ///
+    /// Unless [`Self::set_preprocessed`], expands the [`crate::preprocess`] directives and inlines
+    /// the `#include`s first. Local assets referenced outside of code blocks are copied by
+    /// `assets`.
+    pub fn work_one(
+        &self,
+        file: &TopLevelMd,
+        src_content: &str,
+        assets: &mut Assets,
+    ) -> Res<String> {
+        use io::Write;
+
         let src_path = {
             let mut src_path = self.src_dir();
             src_path.push(&file.path);
             src_path
         };
 
-        let tgt_path = {
-            let mut tgt_path = self.tgt_dir();
-            let mut tgt_file = format!("{:0>2}_", idx);
-            let mut last_is_underscore = true;
-            for c in file.title.chars() {
-                if c.is_alphanumeric() {
-                    tgt_file.push(c);
-                    last_is_underscore = false;
-                } else if !last_is_underscore {
-                    tgt_file.push('_');
-                    last_is_underscore = true;
-                }
-            }
-            tgt_file.push_str(".md");
-            tgt_path.push(&tgt_file);
-            tgt_path
-        };
+        log::trace!("src_path: {}", src_path.display());
 
-        log::trace!(
-            "src_path: {}, tgt_path: {}",
-            src_path.display(),
-            tgt_path.display()
-        );
+        let expanded;
+        let src_content = if self.preprocessed {
+            src_content
+        } else {
+            expanded = crate::preprocess::Manage::new(self.conf.clone())
+                .expand(&src_path, src_content)
+                .chain_err(|| {
+                    format!("while expanding the directives of `{}`", src_path.display())
+                })?;
+            &expanded
+        };
 
-        let src_content = load_file(&src_path)?;
-        let mut tgt_file = open_write(&tgt_path)?;
+        let mut tgt = Vec::with_capacity(src_content.len());
+        let src_dir = src_path.parent().unwrap_or_else(|| Path::new(""));
+        let mut in_code = false;
 
         for (idx, line) in src_content.lines().enumerate() {
-            if line.contains("#include") {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+            }
+            if !self.preprocessed && line.contains("#include") {
                 log::trace!(
                     "inlining line {} of `{}`: {}",
                     idx,
                     src_path.display(),
                     line.trim()
                 );
-                self.inline_block(&src_path, line, &mut tgt_file)
-                    .chain_err(|| {
-                        format!(
-                            "while inlining code block line {} of `{}`",
-                            idx,
-                            src_path.display()
-                        )
-                    })?;
-            } else {
-                use io::Write;
-                let line = if line == "\\" { "<br>" } else { line };
-                writeln!(&mut tgt_file, "{}", line).chain_err(|| {
+                self.inline_block(&src_path, line, &mut tgt).chain_err(|| {
                     format!(
-                        "while writing line {} from `{}` to `{}`",
+                        "while inlining code block line {} of `{}`",
                         idx,
-                        src_path.display(),
-                        tgt_path.display()
+                        src_path.display()
                     )
                 })?;
+            } else {
+                let line = if line == "\\" {
+                    "<br>".into()
+                } else if in_code {
+                    line.into()
+                } else {
+                    assets.rewrite_line(src_dir, line).chain_err(|| {
+                        format!(
+                            "while handling assets line {} of `{}`",
+                            idx,
+                            src_path.display()
+                        )
+                    })?
+                };
+                writeln!(&mut tgt, "{}", line)?;
             }
         }
 
-        Ok(())
+        String::from_utf8(tgt).chain_err(|| {
+            format!(
+                "vanilla markdown for `{}` is not legal UTF-8",
+                src_path.display()
+            )
+        })
     }
 
+    /// Loads a markdown file, expands its [`crate::preprocess`] directives and inlines all its code
+    /// blocks.
+    pub fn inline_md(conf: &Conf, md_path: impl AsRef<Path>) -> Res<String> {
+        let md_path = md_path.as_ref();
+        let content = crate::preprocess::Manage::new(conf.clone())
+            .expand(md_path, &load_file(md_path)?)
+            .chain_err(|| format!("while expanding the directives of `{}`", md_path.display()))?;
+        let (content, _) = Include::expand_all(md_path, &content)
+            .chain_err(|| format!("while inlining code blocks of `{}`", md_path.display()))?;
+        Ok(content)
+    }
+
+    /// Inlines the code block referred to by an `#include` line.
     pub fn inline_block(
         &self,
         md_path: impl AsRef<Path>,
         line: &str,
         target: &mut impl io::Write,
     ) -> Res<()> {
+        Include::parse(md_path, line)?.write(target)
+    }
+}
+
+/// True if `line`, from a Rust code block, is hidden by `mdbook`.
+pub fn is_hidden_rust_line(line: &str) -> bool {
+    line == "#" || line.starts_with("# ")
+}
+
+/// An `{{ #include <path>[:<anchor>] }}` directive.
+#[derive(Debug, Clone)]
+pub struct Include {
+    /// Path to the included file, relative to the book's root.
+    pub path: PathBuf,
+    /// Optional anchor, only the lines between the anchor's start and end are included.
+    pub anchor: Option<String>,
+}
+impl Include {
+    /// Parses an include line, `md_path` is the path of the markdown file the line comes from.
+    pub fn parse(md_path: impl AsRef<Path>, line: &str) -> Res<Self> {
         let pat = "#include ";
-        let err = || err::Error::from(format!("illegal code block include line"));
+        let err = || err::Error::from("illegal code block include line");
         let md_path = md_path.as_ref();
 
         let path_start = line.find(pat).ok_or_else(err)? + pat.len();
         let line = &line[path_start..];
-        let path_end = line.find(|c| c == ':' || c == ' ').ok_or_else(err)?;
+        let path_end = line.find([':', ' ', '}']).ok_or_else(err)?;
         let line_tail = &line[path_end..];
 
         let path = &line[..path_end];
@@ -790,27 +1656,69 @@ impl<'s> Vanilla<'s> {
             src_path
         };
 
-        let (anchor_start, anchor_end) = if line_tail.starts_with(':') {
-            let tail = &line[1..];
+        let anchor = if let Some(tail) = line_tail.strip_prefix(':') {
             let anchor_end = tail
                 .find(|c: char| c == '}' || c.is_whitespace())
                 .ok_or_else(err)?;
-            let anchor = &tail[..anchor_end];
-            (
-                Some(format!("ANCHOR: {}", anchor)),
-                Some(format!("ANCHOR_END: {}", anchor)),
-            )
+            Some(tail[..anchor_end].to_string())
         } else {
-            (None, None)
+            None
         };
 
         log::trace!(
-            "code path: {}, anchor: `{}` / `{}`",
+            "code path: {}, anchor: `{}`",
             code_path.display(),
-            anchor_start.as_deref().unwrap_or("none"),
-            anchor_end.as_deref().unwrap_or("none"),
+            anchor.as_deref().unwrap_or("none"),
         );
 
+        Ok(Self {
+            path: code_path,
+            anchor,
+        })
+    }
+
+    /// Expands all the include lines in some code, `md_path` is the markdown file the code is from.
+    ///
+    /// Also returns the extension of the first included file, if any.
+    pub fn expand_all(md_path: impl AsRef<Path>, code: &str) -> Res<(String, Option<String>)> {
+        let md_path = md_path.as_ref();
+        let (mut res, mut ext) = (String::with_capacity(code.len()), None);
+        for (idx, line) in code.lines().enumerate() {
+            if line.contains("#include") {
+                let err = || format!("while inlining code block line {}: {}", idx, line.trim());
+                let include = Self::parse(md_path, line).chain_err(err)?;
+                if ext.is_none() {
+                    ext = include.ext();
+                }
+                for line in include.lines().chain_err(err)? {
+                    res.push_str(&line);
+                    res.push('\n');
+                }
+            } else {
+                res.push_str(line);
+                res.push('\n');
+            }
+        }
+        Ok((res, ext))
+    }
+
+    /// Extension of the included file, if any.
+    pub fn ext(&self) -> Option<String> {
+        self.path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+    }
+
+    /// Loads the included lines.
+    pub fn lines(&self) -> Res<Vec<String>> {
+        let (anchor_start, anchor_end) = match self.anchor.as_ref() {
+            Some(anchor) => (
+                Some(format!("ANCHOR: {}", anchor)),
+                Some(format!("ANCHOR_END: {}", anchor)),
+            ),
+            None => (None, None),
+        };
+
         #[derive(Clone, Copy)]
         enum Mode<'s> {
             Skip { start: &'s str },
@@ -822,7 +1730,8 @@ impl<'s> Vanilla<'s> {
         } else {
             Mode::Copy { end: None }
         };
-        let code_content = load_file(&code_path)?;
+        let code_content = load_file(&self.path)?;
+        let mut res = vec![];
 
         for line in code_content.lines() {
             match mode {
@@ -844,12 +1753,121 @@ impl<'s> Vanilla<'s> {
                 {
                     continue;
                 }
-                Mode::Copy { .. } => {
-                    writeln!(target, "{}", line)?;
-                }
+                Mode::Copy { .. } => res.push(line.to_string()),
             }
         }
 
+        if let (Mode::Skip { start }, Some(anchor)) = (mode, self.anchor.as_ref()) {
+            bail!(
+                "anchor `{}` not found in `{}`, expected a line containing `{}`",
+                anchor,
+                self.path.display(),
+                start,
+            )
+        }
+
+        Ok(res)
+    }
+
+    /// Writes the included lines to `target`.
+    pub fn write(&self, target: &mut impl io::Write) -> Res<()> {
+        for line in self.lines()? {
+            writeln!(target, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// An entry of a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Name of the generated file.
+    pub file: String,
+    /// Hash of the content of the generated file.
+    pub hash: u64,
+    /// Path of the source markdown file, relative to the book's `src` directory.
+    pub src: String,
+}
+
+/// Files generated in an output directory, in chapter order.
+///
+/// One line per file: `<order> <file> <hash> <source>`, tab-separated. The order is the position of
+/// the file's chapter, lines are sorted by order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+impl Manifest {
+    /// Name of the manifest file in an output directory.
+    pub const NAME: &'static str = ".manifest";
+
+    /// Retrieves the entry for a file.
+    pub fn get(&self, file: &str) -> Option<&ManifestEntry> {
+        self.entries.iter().find(|entry| entry.file == file)
+    }
+
+    /// Loads the manifest of an output directory, empty if there is none.
+    pub fn load(dir: impl AsRef<Path>) -> Res<Self> {
+        let path = dir.as_ref().join(Self::NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = load_file(&path)?;
+        Self::parse(&content).chain_err(|| format!("in manifest `{}`", path.display()))
+    }
+
+    /// Parses the content of a manifest.
+    pub fn parse(content: &str) -> Res<Self> {
+        let mut entries = vec![];
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = || format!("illegal manifest line {}: `{}`", idx, line);
+            let mut elems = line.split('\t');
+            let (order, file, hash, src) =
+                match (elems.next(), elems.next(), elems.next(), elems.next()) {
+                    (Some(order), Some(file), Some(hash), Some(src)) => (order, file, hash, src),
+                    _ => bail!(err()),
+                };
+            let order: usize = order.parse().chain_err(err)?;
+            if order != entries.len() {
+                bail!(
+                    "illegal manifest line {}: expected order {}, found {}",
+                    idx,
+                    entries.len(),
+                    order
+                )
+            }
+            let hash = u64::from_str_radix(hash, 16).chain_err(err)?;
+            entries.push(ManifestEntry {
+                file: file.into(),
+                hash,
+                src: src.into(),
+            })
+        }
+        Ok(Self { entries })
+    }
+
+    /// Writes the manifest in an output directory.
+    pub fn write(&self, dir: impl AsRef<Path>) -> Res<()> {
+        use io::Write;
+        let path = dir.as_ref().join(Self::NAME);
+        let mut file = open_write(&path)?;
+        write!(file, "{}", self).chain_err(|| format!("while writing `{}`", path.display()))?;
+        Ok(())
+    }
+}
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(fmt, "# order\tfile\thash\tsource")?;
+        for (idx, entry) in self.entries.iter().enumerate() {
+            writeln!(
+                fmt,
+                "{}\t{}\t{:016x}\t{}",
+                idx, entry.file, entry.hash, entry.src
+            )?;
+        }
         Ok(())
     }
 }
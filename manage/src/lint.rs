@@ -0,0 +1,790 @@
+//! SMT-LIB 2 linter for the book's snippets.
+//!
+//! Besides syntax and scoping errors, enforces the book's rules: no `push`/`pop`, and activation
+//! literals guard assertions and are deactivated once they are not needed anymore (see
+//! `src/bmc/code/sw_actlit_1.smt2`). Activation literals are recognized by their use: boolean
+//! constants only used as the guard of an implication assertion, in a deactivation
+//! `(assert (not <symbol>))` or as a literal of `check-sat-assuming`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::smt2::{self, Pos, Sexp};
+
+prelude!();
+
+/// Name of the diagnostics' source.
+const SOURCE: &str = "lint";
+
+/// Function symbols from the core, integer, real and array theories, and some common extensions.
+pub const BUILTIN_FUNS: &[&str] = &[
+    "true", "false", "not", "=>", "and", "or", "xor", "=", "distinct", "ite", "+", "-", "*", "/",
+    "div", "mod", "abs", "<=", "<", ">=", ">", "to_real", "to_int", "is_int", "select", "store",
+    "concat", "extract", "bvnot", "bvand", "bvor", "bvneg", "bvadd", "bvmul", "bvudiv", "bvurem",
+    "bvshl", "bvlshr", "bvult", "bvule", "bvugt", "bvuge", "bvslt", "bvsle", "bvsgt", "bvsge",
+    "bvsub", "bvxor", "bvsdiv", "bvsrem", "bvashr",
+];
+/// Builtin sorts.
+pub const BUILTIN_SORTS: &[&str] = &["Bool", "Int", "Real", "Array", "BitVec", "String"];
+
+/// Lints all the `.smt2` files in some files and directories, recursively.
+pub struct Lint<'s> {
+    targets: Vec<&'s str>,
+}
+impl<'s> Lint<'s> {
+    /// Constructor.
+    pub fn new(targets: Vec<&'s str>) -> Self {
+        Self { targets }
+    }
+    /// Targets accessor.
+    pub fn targets(&self) -> &[&'s str] {
+        &self.targets
+    }
+
+    /// Lints the targets, fails if there are errors.
+    pub fn run(&self) -> Res<()> {
+        let mut files = vec![];
+        for target in &self.targets {
+            smt2::collect_files(Path::new(target), &mut files)?;
+        }
+        files.sort();
+        log::info!("linting {} SMT-LIB 2 file(s)", files.len());
+
+        let mut report = Report::new();
+        for file in &files {
+            log::debug!("linting `{}`", file.display());
+            let text = load_file(file)?;
+            for diagnostic in lint(file, &text) {
+                report.push(diagnostic)
+            }
+        }
+
+        if !report.diagnostics.is_empty() {
+            eprintln!("|===| Report:");
+            report.pretty_eprint("| ");
+            eprintln!("|===|");
+        }
+        let errors = report.count(Severity::Error);
+        if errors > 0 {
+            bail!(
+                "{} error(s), {} warning(s)",
+                errors,
+                report.count(Severity::Warning)
+            )
+        }
+        log::info!(
+            "done linting, {} warning(s)",
+            report.count(Severity::Warning)
+        );
+        Ok(())
+    }
+}
+
+/// Lints an SMT-LIB 2 script, `file` is only used to locate diagnostics.
+pub fn lint(file: &Path, text: &str) -> Vec<Diagnostic> {
+    let (commands, errors) = smt2::parse(text);
+    let mut linter = Linter::new(file);
+    for error in errors {
+        linter.error(error.pos, error.msg);
+    }
+    for command in &commands {
+        linter.command(command);
+    }
+    linter.finish()
+}
+
+/// Global symbols, with their declaration position.
+#[derive(Debug, Clone, Default)]
+struct Scope<'a> {
+    funs: BTreeMap<&'a str, Pos>,
+    sorts: BTreeMap<&'a str, Pos>,
+    /// Datatype constructors, also in `funs`.
+    ctors: BTreeSet<&'a str>,
+}
+
+/// Linter state for a script.
+struct Linter<'a, 'f> {
+    file: &'f Path,
+    diagnostics: Vec<Diagnostic>,
+    /// Current global scope.
+    scope: Scope<'a>,
+    /// Scopes saved by `push` commands.
+    pushed: Vec<Scope<'a>>,
+    /// Local term variables, innermost last.
+    locals: Vec<&'a str>,
+    /// Sort parameters, innermost last.
+    sort_params: Vec<&'a str>,
+    /// Boolean constants, potential activation literals.
+    bool_consts: BTreeMap<&'a str, Pos>,
+    /// Number of occurrences of each symbol in terms.
+    occurrences: BTreeMap<&'a str, usize>,
+    /// Number of occurrences of each symbol as an activation literal, among `occurrences`.
+    activations: BTreeMap<&'a str, usize>,
+    /// Symbols guarding an assertion: `(assert (=> <symbol> ...))`.
+    guards: BTreeSet<&'a str>,
+    /// Symbols deactivated by an assertion: `(assert (not <symbol>))`.
+    deactivated: BTreeSet<&'a str>,
+    /// True if there was a `check-sat` since the last change to the assertions.
+    checked: bool,
+}
+impl<'a, 'f> Linter<'a, 'f> {
+    fn new(file: &'f Path) -> Self {
+        Self {
+            file,
+            diagnostics: vec![],
+            scope: Scope::default(),
+            pushed: vec![],
+            locals: vec![],
+            sort_params: vec![],
+            bool_consts: BTreeMap::new(),
+            occurrences: BTreeMap::new(),
+            activations: BTreeMap::new(),
+            guards: BTreeSet::new(),
+            deactivated: BTreeSet::new(),
+            checked: false,
+        }
+    }
+
+    fn diagnostic(&mut self, severity: Severity, pos: Pos, msg: impl Into<String>) {
+        self.diagnostics.push(
+            Diagnostic::new(severity, SOURCE, msg)
+                .set_file(self.file)
+                .set_line(pos.line)
+                .set_col(pos.col),
+        )
+    }
+    fn error(&mut self, pos: Pos, msg: impl Into<String>) {
+        self.diagnostic(Severity::Error, pos, msg)
+    }
+    fn warning(&mut self, pos: Pos, msg: impl Into<String>) {
+        self.diagnostic(Severity::Warning, pos, msg)
+    }
+
+    /// Final checks, yields the diagnostics sorted by position.
+    fn finish(mut self) -> Vec<Diagnostic> {
+        for (name, pos) in std::mem::take(&mut self.bool_consts) {
+            // Activation literal if all its occurrences are activation ones.
+            let activations = self.activations.get(name).cloned().unwrap_or(0);
+            if activations == 0 || self.occurrences.get(name) != Some(&activations) {
+                continue;
+            }
+            let guards = self.guards.contains(name);
+            if !guards {
+                self.warning(
+                    pos,
+                    format!(
+                        "activation literal `{}` is declared but never asserted",
+                        name
+                    ),
+                )
+            }
+            if guards && !self.deactivated.contains(name) {
+                self.warning(
+                    pos,
+                    format!("activation literal `{}` is never deactivated", name),
+                )
+            }
+        }
+        self.diagnostics
+            .sort_by_key(|d| (d.line.unwrap_or(0), d.col.unwrap_or(0)));
+        self.diagnostics
+    }
+
+    fn is_fun(&self, name: &str) -> bool {
+        self.locals.contains(&name)
+            || self.scope.funs.contains_key(name)
+            || BUILTIN_FUNS.contains(&name)
+            // Z3-style tester `is-<constructor>`.
+            || name
+                .strip_prefix("is-")
+                .map(|ctor| self.scope.ctors.contains(ctor))
+                .unwrap_or(false)
+    }
+    fn is_sort(&self, name: &str) -> bool {
+        self.sort_params.contains(&name)
+            || self.scope.sorts.contains_key(name)
+            || BUILTIN_SORTS.contains(&name)
+    }
+
+    /// Declares a function symbol, reports redeclarations.
+    fn declare_fun(&mut self, sexp: &Sexp<'a>) {
+        let name = match sexp.symbol() {
+            Some(name) => name,
+            None => return self.error(sexp.pos(), "expected a symbol"),
+        };
+        let pos = sexp.pos();
+        if let Some(prev) = self.scope.funs.get(name).cloned() {
+            self.error(
+                pos,
+                format!(
+                    "redeclaration of `{}`, previously declared at {}",
+                    name, prev
+                ),
+            )
+        } else if BUILTIN_FUNS.contains(&name) {
+            self.error(pos, format!("redeclaration of builtin `{}`", name))
+        } else {
+            self.scope.funs.insert(name, pos);
+        }
+    }
+    /// Declares a sort symbol, reports redeclarations.
+    fn declare_sort(&mut self, sexp: &Sexp<'a>) {
+        let name = match sexp.symbol() {
+            Some(name) => name,
+            None => return self.error(sexp.pos(), "expected a symbol"),
+        };
+        let pos = sexp.pos();
+        if let Some(prev) = self.scope.sorts.get(name).cloned() {
+            self.error(
+                pos,
+                format!(
+                    "redeclaration of sort `{}`, previously declared at {}",
+                    name, prev
+                ),
+            )
+        } else if BUILTIN_SORTS.contains(&name) {
+            self.error(pos, format!("redeclaration of builtin sort `{}`", name))
+        } else {
+            self.scope.sorts.insert(name, pos);
+        }
+    }
+
+    fn sort(&mut self, sort: &Sexp<'a>) {
+        match sort {
+            Sexp::Atom(token) => match token.symbol() {
+                Some(name) if !self.is_sort(name) => {
+                    self.error(token.pos, format!("undeclared sort `{}`", name))
+                }
+                Some(_) => (),
+                None => self.error(token.pos, format!("illegal sort `{}`", token.text)),
+            },
+            Sexp::List { open, elems } => match elems.split_first() {
+                // Indexed sort, `(_ BitVec 32)`.
+                Some((head, _)) if head.symbol() == Some("_") => (),
+                Some((head, args)) => {
+                    self.sort(head);
+                    for arg in args {
+                        self.sort(arg)
+                    }
+                }
+                None => self.error(*open, "illegal empty sort"),
+            },
+        }
+    }
+
+    /// Sorted variables `((<symbol> <sort>)*)`, returns the variables.
+    fn sorted_vars(&mut self, vars: Option<&Sexp<'a>>, pos: Pos) -> Vec<&'a str> {
+        let mut res = vec![];
+        match vars.and_then(Sexp::list) {
+            Some(vars) => {
+                for var in vars {
+                    match var.list() {
+                        Some([name, sort]) if name.symbol().is_some() => {
+                            self.sort(sort);
+                            res.extend(name.symbol());
+                        }
+                        _ => {
+                            self.error(var.pos(), "expected a sorted variable `(<symbol> <sort>)`")
+                        }
+                    }
+                }
+            }
+            None => self.error(
+                vars.map(Sexp::pos).unwrap_or(pos),
+                "expected a list of sorted variables",
+            ),
+        }
+        res
+    }
+
+    fn term(&mut self, term: &Sexp<'a>) {
+        let (open, elems) = match term {
+            Sexp::Atom(token) => {
+                if let Some(name) = token.symbol() {
+                    if !self.is_fun(name) {
+                        self.error(token.pos, format!("undeclared symbol `{}`", name))
+                    }
+                    *self.occurrences.entry(name).or_insert(0) += 1;
+                }
+                return;
+            }
+            Sexp::List { open, elems } => (*open, elems),
+        };
+        let (head, args) = match elems.split_first() {
+            Some(split) => split,
+            None => return self.error(open, "illegal empty term"),
+        };
+        let locals = self.locals.len();
+
+        match head.symbol() {
+            Some("let") => {
+                let bindings = args.first().and_then(Sexp::list).unwrap_or(&[]);
+                let mut vars = vec![];
+                for binding in bindings {
+                    match binding.list() {
+                        Some([name, def]) if name.symbol().is_some() => {
+                            self.term(def);
+                            vars.extend(name.symbol())
+                        }
+                        _ => self.error(binding.pos(), "expected a binding `(<symbol> <term>)`"),
+                    }
+                }
+                self.locals.extend(vars);
+                self.terms(open, "let", &args[1.min(args.len())..], 1);
+            }
+            Some(binder @ ("forall" | "exists")) => {
+                let vars = self.sorted_vars(args.first(), open);
+                self.locals.extend(vars);
+                self.terms(open, binder, &args[1.min(args.len())..], 1);
+            }
+            Some("match") => {
+                self.terms(open, "match", &args[..1.min(args.len())], 1);
+                let cases = args.get(1).and_then(Sexp::list).unwrap_or(&[]);
+                for case in cases {
+                    match case.list() {
+                        Some([pattern, term]) => {
+                            // Pattern variables are the symbols that are not constructors.
+                            let vars: Vec<&Sexp<'a>> = match pattern.list() {
+                                Some(elems) => elems.iter().skip(1).collect(),
+                                None => vec![pattern],
+                            };
+                            for var in vars {
+                                if let Some(name) = var.symbol() {
+                                    if !self.is_fun(name) {
+                                        self.locals.push(name)
+                                    }
+                                }
+                            }
+                            self.term(term);
+                            self.locals.truncate(locals);
+                        }
+                        _ => self.error(case.pos(), "expected a match case `(<pattern> <term>)`"),
+                    }
+                }
+            }
+            Some("!") => {
+                self.terms(open, "!", &args[..1.min(args.len())], 1);
+                let mut attributes = args.iter().skip(1);
+                while let Some(attribute) = attributes.next() {
+                    if let Sexp::Atom(token) = attribute {
+                        if token.text == ":named" {
+                            if let Some(name) = attributes.next() {
+                                self.declare_fun(name)
+                            }
+                        }
+                    }
+                }
+            }
+            // Indexed identifier, `(_ bv5 32)`.
+            Some("_") => (),
+            // `(as const (Array Int Int))` or `(as <term> <sort>)`.
+            Some("as") => {
+                if let Some(term) = args.first() {
+                    if term.symbol() != Some("const") {
+                        self.term(term)
+                    }
+                }
+                if let Some(sort) = args.get(1) {
+                    self.sort(sort)
+                }
+            }
+            Some(_) => {
+                self.term(head);
+                for arg in args {
+                    self.term(arg)
+                }
+            }
+            None => {
+                // `((_ extract 3 0) x)`, `((as const (Array Int Int)) 0)`...
+                if !matches!(head.head(), Some("_") | Some("as")) {
+                    self.term(head)
+                }
+                for arg in args {
+                    self.term(arg)
+                }
+            }
+        }
+
+        self.locals.truncate(locals);
+    }
+
+    /// Checks the terms of a construct that expects exactly `count` of them.
+    fn terms(&mut self, pos: Pos, construct: &str, terms: &[Sexp<'a>], count: usize) {
+        if terms.len() != count {
+            self.error(
+                pos,
+                format!(
+                    "`{}` expects {} term(s), found {}",
+                    construct,
+                    count,
+                    terms.len()
+                ),
+            )
+        }
+        for term in terms {
+            self.term(term)
+        }
+    }
+
+    /// Datatype declaration `(par (<symbol>*) (<constructor>*))` or `(<constructor>*)`.
+    fn datatype(&mut self, decl: &Sexp<'a>) {
+        let params = self.sort_params.len();
+        let ctors = match decl.list() {
+            Some([par, params, ctors]) if par.symbol() == Some("par") => {
+                for param in params.list().unwrap_or(&[]) {
+                    self.sort_params.extend(param.symbol())
+                }
+                ctors.list()
+            }
+            ctors => ctors,
+        };
+        match ctors {
+            Some(ctors) => {
+                for ctor in ctors {
+                    let (name, selectors) = match ctor.list().and_then(<[_]>::split_first) {
+                        Some(split) => split,
+                        None => {
+                            self.error(ctor.pos(), "expected a constructor declaration");
+                            continue;
+                        }
+                    };
+                    self.declare_fun(name);
+                    self.scope.ctors.extend(name.symbol());
+                    for selector in selectors {
+                        match selector.list() {
+                            Some([name, sort]) => {
+                                self.declare_fun(name);
+                                self.sort(sort)
+                            }
+                            _ => self
+                                .error(selector.pos(), "expected a selector `(<symbol> <sort>)`"),
+                        }
+                    }
+                }
+            }
+            None => self.error(decl.pos(), "expected a datatype declaration"),
+        }
+        self.sort_params.truncate(params);
+    }
+
+    /// Function definition `<symbol> ((<symbol> <sort>)*) <sort> <term>`.
+    fn define_fun(&mut self, pos: Pos, args: &[Sexp<'a>], rec: bool) {
+        let (name, params, sort, body) = match args {
+            [name, params, sort, body] => (name, params, sort, body),
+            _ => {
+                return self.error(
+                    pos,
+                    "expected `<symbol> ((<symbol> <sort>)*) <sort> <term>`",
+                )
+            }
+        };
+        if rec {
+            self.declare_fun(name)
+        }
+        let vars = self.sorted_vars(Some(params), pos);
+        self.sort(sort);
+        let locals = self.locals.len();
+        self.locals.extend(vars);
+        self.term(body);
+        self.locals.truncate(locals);
+        if !rec {
+            self.declare_fun(name)
+        }
+    }
+
+    fn command(&mut self, command: &Sexp<'a>) {
+        let (open, elems) = match command {
+            Sexp::List { open, elems } => (*open, elems),
+            Sexp::Atom(token) => {
+                return self.error(
+                    token.pos,
+                    format!("expected a command, found `{}`", token.text),
+                )
+            }
+        };
+        let (name, args) = match elems.split_first() {
+            Some((name, args)) => match name.symbol() {
+                Some(name) => (name, args),
+                None => return self.error(name.pos(), "expected a command name"),
+            },
+            None => return self.error(open, "illegal empty command"),
+        };
+
+        match name {
+            "declare-const" => match args {
+                [name, sort] => {
+                    self.sort(sort);
+                    self.declare_fun(name);
+                    if let (Some(name), Some("Bool")) = (name.symbol(), sort.symbol()) {
+                        self.bool_consts.insert(name, command.pos());
+                    }
+                }
+                _ => self.error(open, "expected `(declare-const <symbol> <sort>)`"),
+            },
+            "declare-fun" => match args {
+                [name, sorts, sort] => {
+                    for arg_sort in sorts.list().unwrap_or(&[]) {
+                        self.sort(arg_sort)
+                    }
+                    self.sort(sort);
+                    self.declare_fun(name);
+                    if let (Some(name), Some([]), Some("Bool")) =
+                        (name.symbol(), sorts.list(), sort.symbol())
+                    {
+                        self.bool_consts.insert(name, command.pos());
+                    }
+                }
+                _ => self.error(open, "expected `(declare-fun <symbol> (<sort>*) <sort>)`"),
+            },
+            "define-fun" => self.define_fun(open, args, false),
+            "define-fun-rec" => self.define_fun(open, args, true),
+            "define-funs-rec" => match args {
+                [decls, bodies] => {
+                    let decls = decls.list().unwrap_or(&[]);
+                    for decl in decls {
+                        if let Some(name) = decl.list().and_then(<[_]>::first) {
+                            self.declare_fun(name)
+                        }
+                    }
+                    for (decl, body) in decls.iter().zip(bodies.list().unwrap_or(&[])) {
+                        if let Some([_, params, sort]) = decl.list() {
+                            let vars = self.sorted_vars(Some(params), decl.pos());
+                            self.sort(sort);
+                            self.locals.extend(vars);
+                            self.term(body);
+                            self.locals.clear();
+                        }
+                    }
+                }
+                _ => self.error(open, "expected `(define-funs-rec (<decl>*) (<term>*))`"),
+            },
+            "declare-sort" => match args {
+                [name] | [name, _] => self.declare_sort(name),
+                _ => self.error(open, "expected `(declare-sort <symbol> <numeral>)`"),
+            },
+            "define-sort" => match args {
+                [name, params, sort] => {
+                    for param in params.list().unwrap_or(&[]) {
+                        self.sort_params.extend(param.symbol())
+                    }
+                    self.sort(sort);
+                    self.sort_params.clear();
+                    self.declare_sort(name)
+                }
+                _ => self.error(open, "expected `(define-sort <symbol> (<symbol>*) <sort>)`"),
+            },
+            "declare-datatype" => match args {
+                [name, decl] => {
+                    self.declare_sort(name);
+                    self.datatype(decl)
+                }
+                _ => self.error(open, "expected `(declare-datatype <symbol> <datatype>)`"),
+            },
+            "declare-datatypes" => match args {
+                [sorts, decls] => {
+                    for sort in sorts.list().unwrap_or(&[]) {
+                        if let Some(name) = sort.list().and_then(<[_]>::first) {
+                            self.declare_sort(name)
+                        }
+                    }
+                    for decl in decls.list().unwrap_or(&[]) {
+                        self.datatype(decl)
+                    }
+                }
+                _ => self.error(
+                    open,
+                    "expected `(declare-datatypes ((<symbol> <numeral>)*) (<datatype>*))`",
+                ),
+            },
+            "assert" => match args {
+                [term] => {
+                    self.term(term);
+                    self.activation(term);
+                    self.checked = false;
+                }
+                _ => self.error(open, "expected `(assert <term>)`"),
+            },
+            "check-sat" => self.checked = true,
+            "check-sat-assuming" => {
+                match args {
+                    [lits] => match lits.list() {
+                        Some(lits) => {
+                            for lit in lits {
+                                self.term(lit);
+                                self.activation_use(lit)
+                            }
+                        }
+                        None => self.error(lits.pos(), "expected a list of literals"),
+                    },
+                    _ => self.error(open, "expected `(check-sat-assuming (<literal>*))`"),
+                }
+                self.checked = true
+            }
+            "get-model" => {
+                if !self.checked {
+                    self.error(open, "`get-model` without a preceding `check-sat`")
+                }
+            }
+            "get-value" => match args {
+                [terms] => {
+                    for term in terms.list().unwrap_or(&[]) {
+                        self.term(term)
+                    }
+                }
+                _ => self.error(open, "expected `(get-value (<term>+))`"),
+            },
+            "push" | "pop" => {
+                self.error(
+                    open,
+                    format!(
+                        "`{}` should not be used, prefer activation literals and `check-sat-assuming`",
+                        name
+                    ),
+                );
+                let count = match args {
+                    [] => 1,
+                    [Sexp::Atom(token)] => token.text.parse().unwrap_or(1),
+                    _ => 1,
+                };
+                for _ in 0..count {
+                    if name == "push" {
+                        self.pushed.push(self.scope.clone())
+                    } else if let Some(scope) = self.pushed.pop() {
+                        self.scope = scope
+                    } else {
+                        self.error(open, "`pop` without a matching `push`")
+                    }
+                }
+                self.checked = false
+            }
+            "reset" => {
+                self.scope = Scope::default();
+                self.pushed.clear();
+                self.checked = false
+            }
+            "reset-assertions" => self.checked = false,
+            // Z3 extensions.
+            "eval" | "simplify" => {
+                if let Some(term) = args.first() {
+                    self.term(term)
+                }
+            }
+            "echo"
+            | "exit"
+            | "get-assertions"
+            | "get-assignment"
+            | "get-info"
+            | "get-option"
+            | "get-proof"
+            | "get-unsat-assumptions"
+            | "get-unsat-core"
+            | "set-info"
+            | "set-logic"
+            | "set-option" => (),
+            _ => self.warning(open, format!("unknown command `{}`", name)),
+        }
+    }
+
+    /// Tracks the activation literals guarding or deactivated by an assertion.
+    fn activation(&mut self, term: &Sexp<'a>) {
+        let elems = term.list().unwrap_or(&[]);
+        match (term.head(), elems.get(1)) {
+            (Some("=>"), Some(guard)) => {
+                self.guards.extend(guard.symbol());
+                self.activation_use(guard)
+            }
+            (Some("not"), Some(lit)) if elems.len() == 2 => {
+                self.deactivated.extend(lit.symbol());
+                self.activation_use(lit)
+            }
+            _ => (),
+        }
+    }
+    /// Counts an occurrence of a symbol as an activation literal.
+    fn activation_use(&mut self, lit: &Sexp<'a>) {
+        if let Some(name) = lit.symbol() {
+            *self.activations.entry(name).or_insert(0) += 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(text: &str) -> Vec<String> {
+        lint(Path::new("test.smt2"), text)
+            .into_iter()
+            .map(|d| format!("{}:{} {}", d.line.unwrap(), d.col.unwrap(), d.message))
+            .collect()
+    }
+
+    #[test]
+    fn scoping() {
+        let text = "\
+(declare-const x Int)
+(declare-const x Int)
+(define-fun f ((a Int)) Bool (let ((b (+ a 1))) (> b y)))
+(assert (forall ((z Int)) (f z)))
+(declare-const s Sort)
+(get-model)
+(check-sat)
+(get-model)
+";
+        assert_eq!(
+            messages(text),
+            vec![
+                "2:16 redeclaration of `x`, previously declared at 1:16",
+                "3:54 undeclared symbol `y`",
+                "5:18 undeclared sort `Sort`",
+                "6:1 `get-model` without a preceding `check-sat`",
+            ]
+        );
+    }
+
+    #[test]
+    fn book_rules() {
+        let text = "\
+(declare-const actlit_0 Bool)
+(declare-const base Bool)
+(declare-const actlit_2 Bool)
+(declare-const b Bool)
+(declare-const c Bool)
+(assert (=> actlit_0 b))
+(assert (=> base b))
+(assert (=> c b))
+(check-sat-assuming (actlit_0 base actlit_2 b))
+(assert (not actlit_0))
+(assert (or c b))
+(push 1)
+(pop 1)
+(assert (and b)
+";
+        // `b` and `c` have other uses, they are not activation literals.
+        assert_eq!(
+            messages(text),
+            vec![
+                "2:1 activation literal `base` is never deactivated",
+                "3:1 activation literal `actlit_2` is declared but never asserted",
+                "12:1 `push` should not be used, prefer activation literals and `check-sat-assuming`",
+                "13:1 `pop` should not be used, prefer activation literals and `check-sat-assuming`",
+                "14:1 unclosed parenthesis",
+            ]
+        );
+    }
+
+    #[test]
+    fn book_snippets() {
+        let mut files = vec![];
+        smt2::collect_files(Path::new("../src"), &mut files).unwrap();
+        assert!(!files.is_empty());
+        for file in files {
+            let text = load_file(&file).unwrap();
+            // The book follows its own rules, no warnings either.
+            let diagnostics: Vec<String> = lint(&file, &text)
+                .into_iter()
+                .map(|d| d.to_string())
+                .collect();
+            assert!(diagnostics.is_empty(), "{:#?}", diagnostics);
+        }
+    }
+}
@@ -7,8 +7,71 @@ const CHECK_SMT2_KEY: &str = "CHECK_SMT2";
 const CHECK_MIKINO_KEY: &str = "CHECK_MIKINO";
 const Z3_CMD_KEY: &str = "Z3_CMD";
 const MIKINO_CMD_KEY: &str = "MIKINO_CMD";
+const VALIDATE_MODELS_KEY: &str = "VALIDATE_MODELS";
+const CHAPTER_KEY: &str = "CHAPTER";
 const VANILLA_MODE: &str = "vanilla";
 const VANILLA_TARGET_KEY: &str = "vanilla";
+const VANILLA_PREFIX_KEY: &str = "VANILLA_PREFIX";
+const LATEX_MODE: &str = "latex";
+const LATEX_TARGET_KEY: &str = "latex";
+const EPUB_MODE: &str = "epub";
+const EPUB_TARGET_KEY: &str = "epub";
+const SLIDES_MODE: &str = "slides";
+const SLIDES_TARGET_KEY: &str = "slides";
+const PREPROCESS_MODE: &str = "preprocess";
+const SUPPORTS_MODE: &str = "supports";
+const RENDERER_KEY: &str = "RENDERER";
+const RENDER_MODE: &str = "render";
+const LINT_MODE: &str = "lint";
+const LINT_TARGETS_KEY: &str = "LINT_TARGETS";
+const FMT_MODE: &str = "fmt";
+const FMT_CHECK_KEY: &str = "FMT_CHECK";
+const FMT_WIDTH_KEY: &str = "FMT_WIDTH";
+const FMT_TARGETS_KEY: &str = "FMT_TARGETS";
+const PORTABILITY_MODE: &str = "portability";
+const PORTABILITY_SRC_KEY: &str = "PORTABILITY_SRC";
+const CEX_MODE: &str = "cex";
+const CEX_OUTPUT_KEY: &str = "CEX_OUTPUT";
+const GEN_MODE: &str = "gen";
+const GEN_SOURCE_KEY: &str = "GEN_SOURCE";
+const GEN_TARGET_KEY: &str = "GEN_TARGET";
+const GEN_DEPTH_KEY: &str = "GEN_DEPTH";
+const GEN_CHECK_KEY: &str = "GEN_CHECK";
+const REPLAY_MODE: &str = "replay";
+const REPLAY_OUTPUT_KEY: &str = "REPLAY_OUTPUT";
+const REPLAY_RUST_KEY: &str = "REPLAY_RUST";
+const REPLAY_CANDIDATE_KEY: &str = "REPLAY_CANDIDATE";
+const SIMULATE_MODE: &str = "simulate";
+const SIMULATE_SOURCE_KEY: &str = "SIMULATE_SOURCE";
+const SIMULATE_DEPTH_KEY: &str = "SIMULATE_DEPTH";
+const SIMULATE_SEED_KEY: &str = "SIMULATE_SEED";
+const SIMULATE_BOUND_KEY: &str = "SIMULATE_BOUND";
+const SIMULATE_SCRIPT_KEY: &str = "SIMULATE_SCRIPT";
+const TIMING_MODE: &str = "timing";
+const TIMING_SOURCE_KEY: &str = "TIMING_SOURCE";
+const TIMING_CANDIDATE_KEY: &str = "TIMING_CANDIDATE";
+const TIMING_SCRIPT_KEY: &str = "TIMING_SCRIPT";
+const TIMING_OUTPUT_KEY: &str = "TIMING_OUTPUT";
+const DIAGRAM_MODE: &str = "diagram";
+const DIAGRAM_SOURCE_KEY: &str = "DIAGRAM_SOURCE";
+const DIAGRAM_BOOL_KEY: &str = "DIAGRAM_BOOL";
+const DIAGRAM_NO_BOOLS_KEY: &str = "DIAGRAM_NO_BOOLS";
+const DIAGRAM_PRED_KEY: &str = "DIAGRAM_PRED";
+const DIAGRAM_OUTPUT_KEY: &str = "DIAGRAM_OUTPUT";
+const DIAGRAM_CHECK_KEY: &str = "DIAGRAM_CHECK";
+const STRENGTHEN_MODE: &str = "strengthen";
+const STRENGTHEN_SOURCE_KEY: &str = "STRENGTHEN_SOURCE";
+const STRENGTHEN_CONST_KEY: &str = "STRENGTHEN_CONST";
+const PROVE_MODE: &str = "prove";
+const PROVE_SOURCE_KEY: &str = "PROVE_SOURCE";
+const PROVE_MAX_KEY: &str = "PROVE_MAX";
+const EXERCISE_MODE: &str = "exercise";
+const EXERCISE_SRC_KEY: &str = "EXERCISE_SRC";
+const EXERCISE_LIST_MODE: &str = "list";
+const EXERCISE_CHECK_MODE: &str = "check";
+const EXERCISE_ID_KEY: &str = "EXERCISE_ID";
+const EXERCISE_FILE_KEY: &str = "EXERCISE_FILE";
+const EXERCISE_VERIFY_MODE: &str = "verify";
 
 fn main() {
     let matches = {
@@ -41,6 +104,18 @@ fn main() {
                     .help("Command to run mikino to check `.mkn` files")
                     .takes_value(true)
                     .default_value("mikino"),
+                Arg::with_name(VALIDATE_MODELS_KEY)
+                    .long("validate_models")
+                    .help("(De)activates checking that `.smt2` models satisfy their assertions")
+                    .takes_value(true)
+                    .validator(|s| check_bool_arg(&s))
+                    .default_value("off"),
+                Arg::with_name(CHAPTER_KEY)
+                    .long("chapter")
+                    .help("Only tests a chapter, by directory name or title (all by default)")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
             ])
             .subcommand(
                 SubCommand::with_name(VANILLA_MODE)
@@ -51,6 +126,348 @@ fn main() {
                             .help("Output directory for vanilla markdown")
                             .takes_value(true)
                             .default_value("target/vanilla"),
+                    )
+                    .arg(
+                        Arg::with_name(VANILLA_PREFIX_KEY)
+                            .long("prefix")
+                            .help("Prefixes file names with the chapter's position in the summary")
+                            .takes_value(true)
+                            .validator(|s| check_bool_arg(&s))
+                            .default_value("off"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(LATEX_MODE)
+                    .about("generates LaTeX sources for the book, buildable with tectonic")
+                    .arg(
+                        Arg::with_name(LATEX_TARGET_KEY)
+                            .long("target")
+                            .help("Output directory for LaTeX sources")
+                            .takes_value(true)
+                            .default_value("target/latex"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(EPUB_MODE)
+                    .about("generates an EPUB version of the book")
+                    .arg(
+                        Arg::with_name(EPUB_TARGET_KEY)
+                            .help("Output EPUB file")
+                            .index(1)
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(SLIDES_MODE)
+                    .about("generates a Marp slide deck per chapter")
+                    .arg(
+                        Arg::with_name(SLIDES_TARGET_KEY)
+                            .long("target")
+                            .help("Output directory for slide decks")
+                            .takes_value(true)
+                            .default_value("target/slides"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(PREPROCESS_MODE)
+                    .about("runs as an mdbook preprocessor, reads the book on stdin")
+                    .subcommand(
+                        SubCommand::with_name(SUPPORTS_MODE)
+                            .about("checks whether a renderer is supported")
+                            .arg(
+                                Arg::with_name(RENDERER_KEY)
+                                    .help("Name of the renderer")
+                                    .index(1)
+                                    .required(true),
+                            ),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(RENDER_MODE).about(
+                    "runs as an mdbook renderer for vanilla markdown, reads the book on stdin",
+                ),
+            )
+            .subcommand(
+                SubCommand::with_name(LINT_MODE)
+                    .about("lints SMT-LIB 2 files")
+                    .arg(
+                        Arg::with_name(LINT_TARGETS_KEY)
+                            .help("Files and directories to lint, recursively")
+                            .multiple(true)
+                            .default_value("src"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(FMT_MODE)
+                    .about("formats SMT-LIB 2 files")
+                    .arg(
+                        Arg::with_name(FMT_CHECK_KEY)
+                            .long("check")
+                            .help("Only checks files are formatted, fails if some are not"),
+                    )
+                    .arg(
+                        Arg::with_name(FMT_WIDTH_KEY)
+                            .long("width")
+                            .help("Maximum line width")
+                            .takes_value(true)
+                            .validator(|s| check_usize_arg(&s))
+                            .default_value("80"),
+                    )
+                    .arg(
+                        Arg::with_name(FMT_TARGETS_KEY)
+                            .help("Files and directories to format, recursively")
+                            .multiple(true)
+                            .default_value("src"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(PORTABILITY_MODE)
+                    .about("checks SMT-LIB 2.6 compliance and infers the logic of `.smt2` snippets")
+                    .arg(
+                        Arg::with_name(PORTABILITY_SRC_KEY)
+                            .help("Source directory of the book")
+                            .default_value("src"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CEX_MODE)
+                    .about("parses a mikino BMC or induction output, prints it as JSON")
+                    .arg(
+                        Arg::with_name(CEX_OUTPUT_KEY)
+                            .help("Output of mikino, `-` for stdin")
+                            .default_value("-"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(GEN_MODE)
+                    .about("generates the SMT-LIB 2 encodings of a `.mkn` system")
+                    .arg(
+                        Arg::with_name(GEN_SOURCE_KEY)
+                            .help("System to encode")
+                            .default_value("systems/stopwatch.mkn"),
+                    )
+                    .arg(
+                        Arg::with_name(GEN_TARGET_KEY)
+                            .long("target")
+                            .help("Output directory for the generated scripts")
+                            .takes_value(true)
+                            .default_value("systems/gen"),
+                    )
+                    .arg(
+                        Arg::with_name(GEN_DEPTH_KEY)
+                            .long("depth")
+                            .help("Maximum BMC depth")
+                            .takes_value(true)
+                            .validator(|s| check_usize_arg(&s))
+                            .default_value("3"),
+                    )
+                    .arg(Arg::with_name(GEN_CHECK_KEY).long("check").help(
+                        "Only checks generated scripts and the snippets declaring \
+                        the system as their source are up to date, fails if some are not",
+                    )),
+            )
+            .subcommand(
+                SubCommand::with_name(REPLAY_MODE)
+                    .about("replays the counterexamples of a mikino output against a Rust implementation")
+                    .arg(
+                        Arg::with_name(REPLAY_OUTPUT_KEY)
+                            .help("Output of mikino")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name(REPLAY_RUST_KEY)
+                            .long("rust")
+                            .help("Rust implementation of the system")
+                            .takes_value(true)
+                            .default_value("src/trans/code/sw_1.rs"),
+                    )
+                    .arg(
+                        Arg::with_name(REPLAY_CANDIDATE_KEY)
+                            .long("candidate")
+                            .help("Only replays the counterexamples for this candidate")
+                            .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(SIMULATE_MODE)
+                    .about("simulates a `.mkn` system without a solver, prints the trace")
+                    .arg(
+                        Arg::with_name(SIMULATE_SOURCE_KEY)
+                            .help("System to simulate")
+                            .default_value("systems/stopwatch.mkn"),
+                    )
+                    .arg(
+                        Arg::with_name(SIMULATE_DEPTH_KEY)
+                            .long("depth")
+                            .help("Number of transitions")
+                            .takes_value(true)
+                            .validator(|s| check_usize_arg(&s))
+                            .default_value("5"),
+                    )
+                    .arg(
+                        Arg::with_name(SIMULATE_SEED_KEY)
+                            .long("seed")
+                            .help("Seed of the random choices")
+                            .takes_value(true)
+                            .validator(|s| check_usize_arg(&s))
+                            .default_value("0"),
+                    )
+                    .arg(
+                        Arg::with_name(SIMULATE_BOUND_KEY)
+                            .long("bound")
+                            .help("Random integers are between `-bound` and `bound`")
+                            .takes_value(true)
+                            .validator(|s| check_usize_arg(&s))
+                            .default_value("10"),
+                    )
+                    .arg(
+                        Arg::with_name(SIMULATE_SCRIPT_KEY)
+                            .long("script")
+                            .help("Script of choices, one line of `<var> = <value>, ...` per state")
+                            .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(TIMING_MODE)
+                    .about("renders the timing diagram of a trace as SVG")
+                    .arg(
+                        Arg::with_name(TIMING_SOURCE_KEY)
+                            .help("Mikino output (`.out`) or system to simulate (`.mkn`)")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name(TIMING_CANDIDATE_KEY)
+                            .long("candidate")
+                            .help(
+                                "Candidate whose counterexample to render, the first one by \
+                                default; for systems, the counterexample comes from the \
+                                reference engine",
+                            )
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name(TIMING_SCRIPT_KEY)
+                            .long("script")
+                            .help("Simulation script, for systems")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name(TIMING_OUTPUT_KEY)
+                            .long("output")
+                            .short("o")
+                            .help("Output file, stdout by default")
+                            .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(DIAGRAM_MODE)
+                    .about("generates the state-machine diagram of a `.mkn` system as Graphviz `dot`")
+                    .arg(
+                        Arg::with_name(DIAGRAM_SOURCE_KEY)
+                            .help("System to draw")
+                            .default_value("systems/stopwatch.mkn"),
+                    )
+                    .arg(
+                        Arg::with_name(DIAGRAM_BOOL_KEY)
+                            .long("bool")
+                            .help("Boolean state variable of the abstraction, all of them by default")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1),
+                    )
+                    .arg(
+                        Arg::with_name(DIAGRAM_NO_BOOLS_KEY)
+                            .long("no-bools")
+                            .help("Abstracts away all boolean state variables")
+                            .conflicts_with(DIAGRAM_BOOL_KEY),
+                    )
+                    .arg(
+                        Arg::with_name(DIAGRAM_PRED_KEY)
+                            .long("pred")
+                            .help("Predicate of the abstraction, in mikino syntax, e.g. `cnt = 0`")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1),
+                    )
+                    .arg(
+                        Arg::with_name(DIAGRAM_OUTPUT_KEY)
+                            .long("output")
+                            .short("o")
+                            .help("Output `dot` file, also rendered as SVG if `dot` is installed")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name(DIAGRAM_CHECK_KEY)
+                            .long("check")
+                            .requires(DIAGRAM_OUTPUT_KEY)
+                            .help("Only checks the output file is up to date, fails if it is not"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(STRENGTHEN_MODE)
+                    .about("finds invariants strengthening the candidates of a `.mkn` system")
+                    .arg(
+                        Arg::with_name(STRENGTHEN_SOURCE_KEY)
+                            .help("System to strengthen")
+                            .default_value("systems/stopwatch.mkn"),
+                    )
+                    .arg(
+                        Arg::with_name(STRENGTHEN_CONST_KEY)
+                            .long("const")
+                            .help("Additional constant for bounds and equalities")
+                            .takes_value(true)
+                            .allow_hyphen_values(true)
+                            .multiple(true)
+                            .number_of_values(1)
+                            .validator(|s| check_i64_arg(&s)),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(PROVE_MODE)
+                    .about("runs k-induction on a `.mkn` system with the reference engine")
+                    .arg(
+                        Arg::with_name(PROVE_SOURCE_KEY)
+                            .help("System to prove")
+                            .default_value("systems/stopwatch.mkn"),
+                    )
+                    .arg(
+                        Arg::with_name(PROVE_MAX_KEY)
+                            .long("max")
+                            .help("Maximum k")
+                            .takes_value(true)
+                            .validator(|s| check_usize_arg(&s))
+                            .default_value("5"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(EXERCISE_MODE)
+                    .about("checks readers' solutions to the book's exercises")
+                    .arg(
+                        Arg::with_name(EXERCISE_SRC_KEY)
+                            .long("src")
+                            .help("Source directory of the book")
+                            .takes_value(true)
+                            .default_value("src"),
+                    )
+                    .subcommand(
+                        SubCommand::with_name(EXERCISE_LIST_MODE).about("lists the exercises"),
+                    )
+                    .subcommand(
+                        SubCommand::with_name(EXERCISE_CHECK_MODE)
+                            .about("checks a solution, reveals a hint if it is wrong")
+                            .arg(
+                                Arg::with_name(EXERCISE_ID_KEY)
+                                    .help("Exercise to check, e.g. `strength/1`")
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::with_name(EXERCISE_FILE_KEY)
+                                    .help("Solution to check, a working copy of the exercise's template by default"),
+                            ),
+                    )
+                    .subcommand(
+                        SubCommand::with_name(EXERCISE_VERIFY_MODE)
+                            .about("checks the reference solutions meet their expectations"),
                     ),
             )
             .get_matches()
@@ -61,9 +478,9 @@ fn main() {
         _ => log::LevelFilter::Trace,
     };
 
-    simple_logger::SimpleLogger::new()
-        .with_level(log_level)
-        .init()
+    let logger = simple_logger::SimpleLogger::new().with_level(log_level);
+    manage_api::report::Capture::new(logger)
+        .init(log_level)
         .expect("failed to initialize logger");
 
     match run(&matches) {
@@ -97,18 +514,280 @@ fn run(matches: &clap::ArgMatches) -> Res<()> {
         .value_of(MIKINO_CMD_KEY)
         .expect("argument with default value");
 
+    let validate_models = bool_arg(
+        matches
+            .value_of(VALIDATE_MODELS_KEY)
+            .expect("argument with default value"),
+    )
+    .expect("already checked by validator");
+
     let conf = Conf::new()
         .set_smt2(test_smt2, z3_cmd)
-        .set_mikino(test_mikino, mikino_cmd);
+        .set_mikino(test_mikino, mikino_cmd)
+        .set_validate_models(validate_models)
+        .set_chapters(
+            matches
+                .values_of(CHAPTER_KEY)
+                .map(|chapters| chapters.map(String::from).collect())
+                .unwrap_or_default(),
+        );
 
     if let Some(matches) = matches.subcommand_matches(VANILLA_MODE) {
         let target = matches
             .value_of(VANILLA_TARGET_KEY)
             .expect("argument with default value");
-        let vanilla = Vanilla::new(conf, target);
+        let prefix = bool_arg(
+            matches
+                .value_of(VANILLA_PREFIX_KEY)
+                .expect("argument with default value"),
+        )
+        .expect("already checked by validator");
+        let vanilla = Vanilla::new(conf, target).set_prefix(prefix);
         log::info!("generating vanilla markdown to `{}`", vanilla.target());
 
         vanilla.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(LATEX_MODE) {
+        let target = matches
+            .value_of(LATEX_TARGET_KEY)
+            .expect("argument with default value");
+        let latex = Latex::new(conf, target);
+        log::info!("generating LaTeX to `{}`", latex.target());
+
+        latex.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(EPUB_MODE) {
+        let target = matches
+            .value_of(EPUB_TARGET_KEY)
+            .expect("required argument");
+        let epub = Epub::new(conf, target);
+        log::info!("generating EPUB to `{}`", epub.target());
+
+        epub.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(SLIDES_MODE) {
+        let target = matches
+            .value_of(SLIDES_TARGET_KEY)
+            .expect("argument with default value");
+        let slides = Slides::new(conf, target);
+        log::info!("generating slide decks to `{}`", slides.target());
+
+        slides.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(PREPROCESS_MODE) {
+        use mdbook::preprocess::Preprocessor;
+        let preprocessor = manage_api::preprocess::Manage::new(conf);
+        if let Some(matches) = matches.subcommand_matches(SUPPORTS_MODE) {
+            let renderer = matches.value_of(RENDERER_KEY).expect("required argument");
+            let code = if preprocessor.supports_renderer(renderer) {
+                0
+            } else {
+                1
+            };
+            std::process::exit(code)
+        }
+        preprocessor.run_cmd()?;
+    } else if let Some(matches) = matches.subcommand_matches(LINT_MODE) {
+        let targets = matches
+            .values_of(LINT_TARGETS_KEY)
+            .expect("argument with default value")
+            .collect();
+        let lint = manage_api::lint::Lint::new(targets);
+        log::info!("linting {}", lint.targets().join(", "));
+
+        lint.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(FMT_MODE) {
+        let targets = matches
+            .values_of(FMT_TARGETS_KEY)
+            .expect("argument with default value")
+            .collect();
+        let width = usize_arg(
+            matches
+                .value_of(FMT_WIDTH_KEY)
+                .expect("argument with default value"),
+        )
+        .expect("already checked by validator");
+        let fmt = manage_api::fmt::Fmt::new(targets)
+            .set_check(matches.is_present(FMT_CHECK_KEY))
+            .set_width(width);
+        log::info!("formatting {}", fmt.targets().join(", "));
+
+        fmt.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(PORTABILITY_MODE) {
+        let src = matches
+            .value_of(PORTABILITY_SRC_KEY)
+            .expect("argument with default value");
+        let portability = manage_api::portability::Portability::new(src);
+        log::info!(
+            "checking the portability of snippets in {}",
+            portability.src()
+        );
+
+        portability.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(CEX_MODE) {
+        let path = matches
+            .value_of(CEX_OUTPUT_KEY)
+            .expect("argument with default value");
+        let text = if path == "-" {
+            use io::Read;
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .chain_err(|| "while reading stdin")?;
+            text
+        } else {
+            load_file(path)?
+        };
+        let output = manage_api::cex::parse(&text)
+            .chain_err(|| format!("while parsing mikino output `{}`", path))?;
+        println!("{:#}", output.to_json());
+    } else if let Some(matches) = matches.subcommand_matches(GEN_MODE) {
+        let source = matches
+            .value_of(GEN_SOURCE_KEY)
+            .expect("argument with default value");
+        let target = matches
+            .value_of(GEN_TARGET_KEY)
+            .expect("argument with default value");
+        let depth = usize_arg(
+            matches
+                .value_of(GEN_DEPTH_KEY)
+                .expect("argument with default value"),
+        )
+        .expect("already checked by validator");
+        let gen = manage_api::gen::Gen::new(source, target)
+            .set_depth(depth)
+            .set_check(matches.is_present(GEN_CHECK_KEY))
+            .set_z3_cmd(z3_cmd);
+        log::info!(
+            "generating encodings of `{}` to `{}`",
+            gen.source(),
+            gen.target()
+        );
+
+        gen.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(REPLAY_MODE) {
+        let output = matches
+            .value_of(REPLAY_OUTPUT_KEY)
+            .expect("required argument");
+        let rust = matches
+            .value_of(REPLAY_RUST_KEY)
+            .expect("argument with default value");
+        let replay = manage_api::replay::Replay::new(output, rust)
+            .set_candidate(matches.value_of(REPLAY_CANDIDATE_KEY));
+        log::info!(
+            "replaying the counterexamples of `{}` against `{}`",
+            replay.output(),
+            replay.rust()
+        );
+
+        replay.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(SIMULATE_MODE) {
+        let source = matches
+            .value_of(SIMULATE_SOURCE_KEY)
+            .expect("argument with default value");
+        let usize_of = |key| {
+            usize_arg(matches.value_of(key).expect("argument with default value"))
+                .expect("already checked by validator")
+        };
+        let simulate = manage_api::simulate::Simulate::new(source)
+            .set_depth(usize_of(SIMULATE_DEPTH_KEY))
+            .set_seed(usize_of(SIMULATE_SEED_KEY) as u64)
+            .set_bound(usize_of(SIMULATE_BOUND_KEY) as i64)
+            .set_script(matches.value_of(SIMULATE_SCRIPT_KEY));
+        log::info!("simulating `{}`", simulate.source());
+
+        simulate.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(TIMING_MODE) {
+        let source = matches
+            .value_of(TIMING_SOURCE_KEY)
+            .expect("required argument");
+        let timing = manage_api::timing::Timing::new(source)
+            .set_candidate(matches.value_of(TIMING_CANDIDATE_KEY))
+            .set_script(matches.value_of(TIMING_SCRIPT_KEY))
+            .set_output(matches.value_of(TIMING_OUTPUT_KEY))
+            .set_z3_cmd(z3_cmd);
+        log::info!("rendering the timing diagram of `{}`", timing.source());
+
+        timing.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(DIAGRAM_MODE) {
+        let source = matches
+            .value_of(DIAGRAM_SOURCE_KEY)
+            .expect("argument with default value");
+        let bools = if matches.is_present(DIAGRAM_NO_BOOLS_KEY) {
+            Some(vec![])
+        } else {
+            matches
+                .values_of(DIAGRAM_BOOL_KEY)
+                .map(|bools| bools.collect())
+        };
+        let preds = matches
+            .values_of(DIAGRAM_PRED_KEY)
+            .map(|preds| preds.collect())
+            .unwrap_or_default();
+        let diagram = manage_api::diagram::Diagram::new(source)
+            .set_bools(bools)
+            .set_preds(preds)
+            .set_output(matches.value_of(DIAGRAM_OUTPUT_KEY))
+            .set_check(matches.is_present(DIAGRAM_CHECK_KEY))
+            .set_z3_cmd(z3_cmd);
+        log::info!(
+            "generating the state-machine diagram of `{}`",
+            diagram.source()
+        );
+
+        diagram.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(STRENGTHEN_MODE) {
+        let source = matches
+            .value_of(STRENGTHEN_SOURCE_KEY)
+            .expect("argument with default value");
+        let consts = matches
+            .values_of(STRENGTHEN_CONST_KEY)
+            .map(|consts| {
+                consts
+                    .map(|c| i64_arg(c).expect("already checked by validator"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let strengthen = manage_api::strengthen::Strengthen::new(source)
+            .set_consts(consts)
+            .set_z3_cmd(z3_cmd);
+        log::info!("strengthening the candidates of `{}`", strengthen.source());
+
+        strengthen.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(PROVE_MODE) {
+        let source = matches
+            .value_of(PROVE_SOURCE_KEY)
+            .expect("argument with default value");
+        let max = matches
+            .value_of(PROVE_MAX_KEY)
+            .map(|max| usize_arg(max).expect("already checked by validator"))
+            .expect("argument with default value");
+        let prove = manage_api::prove::Prove::new(source)
+            .set_max(max)
+            .set_z3_cmd(z3_cmd);
+        log::info!("running k-induction on `{}`", prove.source());
+
+        prove.run()?;
+    } else if let Some(matches) = matches.subcommand_matches(EXERCISE_MODE) {
+        let src = matches
+            .value_of(EXERCISE_SRC_KEY)
+            .expect("argument with default value");
+        if let Some(matches) = matches.subcommand_matches(EXERCISE_CHECK_MODE) {
+            let id = matches
+                .value_of(EXERCISE_ID_KEY)
+                .expect("required argument");
+            let check = manage_api::exercise::Check::new(&conf, src, id)
+                .set_file(matches.value_of(EXERCISE_FILE_KEY));
+            log::info!("checking exercise `{}`", check.id());
+
+            check.run()?;
+        } else if matches.subcommand_matches(EXERCISE_VERIFY_MODE).is_some() {
+            log::info!("verifying the exercises in `{}`", src);
+
+            manage_api::exercise::run_verify(&conf, src)?;
+        } else {
+            for exercise in manage_api::exercise::all(src)? {
+                println!("{}: {}", exercise.id, exercise.summary())
+            }
+        }
+    } else if matches.subcommand_matches(RENDER_MODE).is_some() {
+        manage_api::render::Render.run_cmd()?;
     } else {
         conf.check(".")?;
     }
@@ -129,3 +808,19 @@ fn bool_arg(arg: &str) -> Result<bool, String> {
         )),
     }
 }
+
+fn check_i64_arg(arg: &str) -> Result<(), String> {
+    i64_arg(arg).map(|_| ())
+}
+fn i64_arg(arg: &str) -> Result<i64, String> {
+    arg.parse()
+        .map_err(|_| format!("expected integer, got `{}`", arg))
+}
+
+fn check_usize_arg(arg: &str) -> Result<(), String> {
+    usize_arg(arg).map(|_| ())
+}
+fn usize_arg(arg: &str) -> Result<usize, String> {
+    arg.parse()
+        .map_err(|_| format!("expected integer, got `{}`", arg))
+}
@@ -0,0 +1,994 @@
+//! Parser for the subset of mikino's `.mkn` format the book uses.
+//!
+//! A system declares its state variables (`svars`), its initial predicate (`init`), its transition
+//! relation (`trans`) and its candidate invariants (`candidates`):
+//!
+//! ```text
+//! svars { start_stop reset: bool, cnt: int }
+//! init { cnt ≥ 0, reset ⇒ cnt = 0 }
+//! trans { 'cnt = if 'reset { 0 } else { cnt + 1 } }
+//! candidates { "cnt is positive": cnt ≥ 0 }
+//! ```
+//!
+//! Predicates are comma-separated lists of boolean expressions, `'x` is the *next* value of `x` and
+//! can only appear in `trans`. Operators have ASCII alternatives: `not`/`!`, `&&`, `||`, `=>`,
+//! `<=`, `>=`, `!=`.
+
+use crate::{
+    engine::{Sort, System},
+    smt2::Pos,
+    trace::Value,
+};
+
+prelude!();
+
+/// Binary and n-ary operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Implies,
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+impl Op {
+    /// Mikino notation.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Implies => "⇒",
+            Self::Or => "∨",
+            Self::And => "∧",
+            Self::Eq => "=",
+            Self::Ne => "≠",
+            Self::Lt => "<",
+            Self::Le => "≤",
+            Self::Gt => ">",
+            Self::Ge => "≥",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Mod => "%",
+        }
+    }
+}
+
+/// An expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Bool(bool),
+    Int(i64),
+    /// State variable, `next` is true for `'x`.
+    Var {
+        name: String,
+        next: bool,
+    },
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    /// Operator application, `and`, `or`, `+` and `*` can have more than two arguments.
+    App(Op, Vec<Expr>),
+    Ite(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+impl Expr {
+    /// Sort of the expression.
+    pub fn sort(&self, vars: &[(String, Sort)]) -> Sort {
+        match self {
+            Self::Bool(_) | Self::Not(_) => Sort::Bool,
+            Self::Int(_) => Sort::Int,
+            Self::Var { name, .. } => vars
+                .iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, sort)| *sort)
+                .unwrap_or(Sort::Int),
+            Self::Neg(arg) => arg.sort(vars),
+            Self::App(op, args) => match op {
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod => {
+                    if args.iter().any(|arg| arg.sort(vars) == Sort::Real) {
+                        Sort::Real
+                    } else {
+                        Sort::Int
+                    }
+                }
+                _ => Sort::Bool,
+            },
+            Self::Ite(_, thn, _) => thn.sort(vars),
+        }
+    }
+
+    /// SMT-LIB 2 version, `x` is `|s.x|` and `'x` is `|s'.x|`.
+    pub fn to_smt2(&self, vars: &[(String, Sort)]) -> String {
+        let app = |op: &str, args: &[Expr]| {
+            let mut res = format!("({}", op);
+            for arg in args {
+                res.push(' ');
+                res.push_str(&arg.to_smt2(vars))
+            }
+            res.push(')');
+            res
+        };
+        match self {
+            Self::Bool(b) => b.to_string(),
+            Self::Int(i) if *i < 0 => format!("(- {})", -i),
+            Self::Int(i) => i.to_string(),
+            Self::Var { name, next } => format!("|{}.{}|", if *next { "s'" } else { "s" }, name),
+            Self::Not(arg) => app("not", std::slice::from_ref(arg)),
+            Self::Neg(arg) => app("-", std::slice::from_ref(arg)),
+            Self::Ite(cnd, thn, els) => {
+                format!(
+                    "(ite {} {} {})",
+                    cnd.to_smt2(vars),
+                    thn.to_smt2(vars),
+                    els.to_smt2(vars)
+                )
+            }
+            Self::App(Op::Ne, args) => format!("(not {})", app("=", args)),
+            Self::App(Op::Div, args) if self.sort(vars) == Sort::Int => app("div", args),
+            Self::App(op, args) => {
+                let op = match op {
+                    Op::Implies => "=>",
+                    Op::Or => "or",
+                    Op::And => "and",
+                    Op::Eq => "=",
+                    Op::Ne => unreachable!(),
+                    Op::Lt => "<",
+                    Op::Le => "<=",
+                    Op::Gt => ">",
+                    Op::Ge => ">=",
+                    Op::Add => "+",
+                    Op::Sub => "-",
+                    Op::Mul => "*",
+                    Op::Div => "/",
+                    Op::Mod => "mod",
+                };
+                app(op, args)
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression, `None` if it depends on a variable `val` has no value for.
+    ///
+    /// `val(name, next)` is the value of `name`, or `'name` if `next`. Conjunctions, disjunctions,
+    /// implications and conditionals are lazy: `false ∧ x` is `false` even if `x` has no value.
+    /// Integer division and modulo are euclidean, as in SMT-LIB 2.
+    pub fn eval(&self, val: &impl Fn(&str, bool) -> Option<Value>) -> Res<Option<Value>> {
+        let boolean = |expr: &Expr| -> Res<Option<bool>> {
+            match expr.eval(val)? {
+                None => Ok(None),
+                Some(Value::Bool(b)) => Ok(Some(b)),
+                Some(value) => bail!("expected a boolean, found `{}` in `{}`", value, self),
+            }
+        };
+        match self {
+            Self::Bool(b) => return Ok(Some(Value::Bool(*b))),
+            Self::Int(i) => return Ok(Some(Value::Int(*i))),
+            Self::Var { name, next } => return Ok(val(name, *next)),
+            Self::Not(arg) => return Ok(boolean(arg)?.map(|b| Value::Bool(!b))),
+            Self::Ite(cnd, thn, els) => {
+                return match boolean(cnd)? {
+                    None => Ok(None),
+                    Some(true) => thn.eval(val),
+                    Some(false) => els.eval(val),
+                }
+            }
+            Self::App(op @ (Op::And | Op::Or), args) => {
+                // Value that decides the result on its own.
+                let absorbing = *op == Op::Or;
+                let mut known = true;
+                for arg in args {
+                    match boolean(arg)? {
+                        Some(b) if b == absorbing => return Ok(Some(Value::Bool(absorbing))),
+                        Some(_) => (),
+                        None => known = false,
+                    }
+                }
+                return Ok(if known {
+                    Some(Value::Bool(!absorbing))
+                } else {
+                    None
+                });
+            }
+            Self::App(Op::Implies, args) => {
+                let (lhs, rhs) = match args.as_slice() {
+                    [lhs, rhs] => (boolean(lhs)?, boolean(rhs)?),
+                    _ => bail!("implication expects two arguments in `{}`", self),
+                };
+                return Ok(match (lhs, rhs) {
+                    (Some(false), _) | (_, Some(true)) => Some(Value::Bool(true)),
+                    (Some(true), Some(false)) => Some(Value::Bool(false)),
+                    _ => None,
+                });
+            }
+            Self::Neg(_) | Self::App(..) => (),
+        }
+
+        let args: &[Expr] = match self {
+            Self::Neg(arg) => std::slice::from_ref(arg),
+            Self::App(_, args) => args,
+            _ => unreachable!(),
+        };
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            match arg.eval(val)? {
+                Some(value) => values.push(value),
+                None => return Ok(None),
+            }
+        }
+        let overflow = || format!("arithmetic overflow in `{}`", self);
+        let num = |value: Value| -> Res<(i64, i64)> {
+            match value.rational() {
+                Some(rational) => Ok(rational),
+                None => bail!("expected a number, found `{}` in `{}`", value, self),
+            }
+        };
+        let ints: Option<Vec<i64>> = values
+            .iter()
+            .map(|value| match value {
+                Value::Int(int) => Some(*int),
+                _ => None,
+            })
+            .collect();
+
+        let op = match self {
+            Self::Neg(_) => {
+                return match values[0] {
+                    Value::Int(int) => {
+                        Ok(Some(Value::Int(int.checked_neg().ok_or_else(overflow)?)))
+                    }
+                    value => {
+                        let (num, den) = num(value)?;
+                        Ok(Value::real(-num, den))
+                    }
+                }
+            }
+            Self::App(op, _) => *op,
+            _ => unreachable!(),
+        };
+        let res = match op {
+            Op::Eq | Op::Ne => {
+                let mut eq = true;
+                for pair in values.windows(2) {
+                    eq = eq
+                        && match (pair[0], pair[1]) {
+                            (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+                            (lhs, rhs) => compare(num(lhs)?, num(rhs)?).is_eq(),
+                        }
+                }
+                Value::Bool(eq == (op == Op::Eq))
+            }
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+                let mut res = true;
+                for pair in values.windows(2) {
+                    let cmp = compare(num(pair[0])?, num(pair[1])?);
+                    res = res
+                        && match op {
+                            Op::Lt => cmp.is_lt(),
+                            Op::Le => cmp.is_le(),
+                            Op::Gt => cmp.is_gt(),
+                            _ => cmp.is_ge(),
+                        }
+                }
+                Value::Bool(res)
+            }
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod => {
+                if let Some(ints) = ints {
+                    let mut res = ints[0];
+                    for int in &ints[1..] {
+                        if (op == Op::Div || op == Op::Mod) && *int == 0 {
+                            bail!("division by zero in `{}`", self)
+                        }
+                        res = match op {
+                            Op::Add => res.checked_add(*int),
+                            Op::Sub => res.checked_sub(*int),
+                            Op::Mul => res.checked_mul(*int),
+                            Op::Div => res.checked_div_euclid(*int),
+                            _ => res.checked_rem_euclid(*int),
+                        }
+                        .ok_or_else(overflow)?
+                    }
+                    Value::Int(res)
+                } else {
+                    if op == Op::Mod {
+                        bail!("modulo on rationals in `{}`", self)
+                    }
+                    let (mut res_num, mut res_den) = num(values[0])?;
+                    for value in &values[1..] {
+                        let (num, den) = num(*value)?;
+                        let (new_num, new_den) = match op {
+                            Op::Add | Op::Sub => {
+                                let num = if op == Op::Add { num } else { -num };
+                                (
+                                    res_num
+                                        .checked_mul(den)
+                                        .zip(num.checked_mul(res_den))
+                                        .and_then(|(lhs, rhs)| lhs.checked_add(rhs)),
+                                    res_den.checked_mul(den),
+                                )
+                            }
+                            Op::Mul => (res_num.checked_mul(num), res_den.checked_mul(den)),
+                            _ => {
+                                if num == 0 {
+                                    bail!("division by zero in `{}`", self)
+                                }
+                                (res_num.checked_mul(den), res_den.checked_mul(num))
+                            }
+                        };
+                        match Value::real(
+                            new_num.ok_or_else(overflow)?,
+                            new_den.ok_or_else(overflow)?,
+                        ) {
+                            Some(Value::Real(num, den)) => {
+                                res_num = num;
+                                res_den = den
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                    Value::Real(res_num, res_den)
+                }
+            }
+            Op::And | Op::Or | Op::Implies => unreachable!(),
+        };
+        Ok(Some(res))
+    }
+}
+
+/// Compares two rationals with positive denominators.
+fn compare((lhs_num, lhs_den): (i64, i64), (rhs_num, rhs_den): (i64, i64)) -> std::cmp::Ordering {
+    (lhs_num as i128 * rhs_den as i128).cmp(&(rhs_num as i128 * lhs_den as i128))
+}
+
+impl std::fmt::Display for Expr {
+    /// Mikino notation, compound arguments are parenthesized.
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let arg = |fmt: &mut std::fmt::Formatter, arg: &Expr| match arg {
+            Self::App(..) | Self::Ite(..) => write!(fmt, "({})", arg),
+            _ => arg.fmt(fmt),
+        };
+        match self {
+            Self::Bool(b) => b.fmt(fmt),
+            Self::Int(i) => i.fmt(fmt),
+            Self::Var { name, next } => write!(fmt, "{}{}", if *next { "'" } else { "" }, name),
+            Self::Not(a) => {
+                "¬".fmt(fmt)?;
+                arg(fmt, a)
+            }
+            Self::Neg(a) => {
+                "-".fmt(fmt)?;
+                arg(fmt, a)
+            }
+            Self::App(op, args) => {
+                for (idx, a) in args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(fmt, " {} ", op.symbol())?
+                    }
+                    arg(fmt, a)?
+                }
+                Ok(())
+            }
+            Self::Ite(cnd, thn, els) => {
+                write!(fmt, "if {} {{ {} }} else ", cnd, thn)?;
+                match &**els {
+                    Self::Ite(..) => els.fmt(fmt),
+                    els => write!(fmt, "{{ {} }}", els),
+                }
+            }
+        }
+    }
+}
+
+/// Conjunction of some predicates in SMT-LIB 2.
+fn conjunction(preds: &[Expr], vars: &[(String, Sort)]) -> String {
+    match preds {
+        [] => "true".into(),
+        [pred] => pred.to_smt2(vars),
+        preds => Expr::App(Op::And, preds.to_vec()).to_smt2(vars),
+    }
+}
+
+/// A `.mkn` system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mkn {
+    pub vars: Vec<(String, Sort)>,
+    pub init: Vec<Expr>,
+    pub trans: Vec<Expr>,
+    /// Candidates, with their name.
+    pub candidates: Vec<(String, Expr)>,
+}
+impl Mkn {
+    /// Loads and parses a `.mkn` file.
+    pub fn load(path: impl AsRef<Path>) -> Res<Self> {
+        let path = path.as_ref();
+        let text = load_file(path)?;
+        parse(&text).chain_err(|| format!("while parsing `{}`", path.display()))
+    }
+
+    /// SMT-LIB 2 version of the system.
+    pub fn to_system(&self) -> System {
+        let mut system = self
+            .vars
+            .iter()
+            .fold(System::new(), |system, (var, sort)| {
+                system.add_var(var.clone(), *sort)
+            })
+            .set_init(conjunction(&self.init, &self.vars))
+            .set_trans(conjunction(&self.trans, &self.vars));
+        for (name, candidate) in &self.candidates {
+            system = system.add_candidate(name.clone(), candidate.to_smt2(&self.vars))
+        }
+        system
+    }
+}
+
+/// Parses a `.mkn` text.
+pub fn parse(text: &str) -> Res<Mkn> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        idx: 0,
+        end: Pos::new(text.lines().count().max(1), 1),
+        mkn: Mkn::default(),
+    };
+    parser.items()?;
+    Ok(parser.mkn)
+}
+
+/// Parses an expression over the state variables of a system, next-state variables are illegal.
+pub fn parse_expr(mkn: &Mkn, text: &str) -> Res<Expr> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        idx: 0,
+        end: Pos::new(text.lines().count().max(1), 1),
+        mkn: Mkn {
+            vars: mkn.vars.clone(),
+            ..Mkn::default()
+        },
+    };
+    let expr = parser.expr(false)?;
+    if parser.peek().is_some() {
+        return parser.error("expected end of expression");
+    }
+    Ok(expr)
+}
+
+/// Tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tkn {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    /// Punctuation and operators, normalized to mikino's notation.
+    Punct(&'static str),
+}
+impl std::fmt::Display for Tkn {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Ident(ident) => ident.fmt(fmt),
+            Self::Int(int) => int.fmt(fmt),
+            Self::Str(s) => write!(fmt, "{:?}", s),
+            Self::Punct(p) => p.fmt(fmt),
+        }
+    }
+}
+
+/// Punctuation and operators, longest first, with their normalized form.
+const PUNCTS: &[(&str, &str)] = &[
+    ("=>", "⇒"),
+    ("<=", "≤"),
+    (">=", "≥"),
+    ("!=", "≠"),
+    ("&&", "∧"),
+    ("||", "∨"),
+    ("⇒", "⇒"),
+    ("≤", "≤"),
+    ("≥", "≥"),
+    ("≠", "≠"),
+    ("∧", "∧"),
+    ("⋀", "∧"),
+    ("∨", "∨"),
+    ("⋁", "∨"),
+    ("¬", "¬"),
+    ("!", "¬"),
+    ("{", "{"),
+    ("}", "}"),
+    ("(", "("),
+    (")", ")"),
+    (",", ","),
+    (":", ":"),
+    ("'", "'"),
+    ("=", "="),
+    ("<", "<"),
+    (">", ">"),
+    ("+", "+"),
+    ("-", "-"),
+    ("*", "*"),
+    ("/", "/"),
+    ("%", "%"),
+];
+
+fn tokenize(text: &str) -> Res<Vec<(Tkn, Pos)>> {
+    let mut res = vec![];
+    for (line_idx, line) in text.lines().enumerate() {
+        let mut rest = line;
+        let col = |rest: &str| line[..line.len() - rest.len()].chars().count() + 1;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with("//") {
+                break;
+            }
+            let pos = Pos::new(line_idx + 1, col(rest));
+            let first = rest.chars().next().expect("non-empty");
+            if first.is_alphabetic() || first == '_' {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                res.push((Tkn::Ident(rest[..len].into()), pos));
+                rest = &rest[len..];
+            } else if first.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let int = rest[..len]
+                    .parse()
+                    .chain_err(|| format!("{}: illegal integer `{}`", pos, &rest[..len]))?;
+                res.push((Tkn::Int(int), pos));
+                rest = &rest[len..];
+            } else if first == '"' {
+                let len = match rest[1..].find('"') {
+                    Some(len) => len,
+                    None => bail!("{}: unterminated string", pos),
+                };
+                res.push((Tkn::Str(rest[1..=len].into()), pos));
+                rest = &rest[len + 2..];
+            } else {
+                match PUNCTS.iter().find(|(p, _)| rest.starts_with(p)) {
+                    Some((p, normalized)) => {
+                        res.push((Tkn::Punct(normalized), pos));
+                        rest = &rest[p.len()..];
+                    }
+                    None => bail!("{}: unexpected character `{}`", pos, first),
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Parser state.
+struct Parser {
+    tokens: Vec<(Tkn, Pos)>,
+    idx: usize,
+    /// Position of the end of the text.
+    end: Pos,
+    mkn: Mkn,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Tkn> {
+        self.tokens.get(self.idx).map(|(tkn, _)| tkn)
+    }
+    fn pos(&self) -> Pos {
+        self.tokens
+            .get(self.idx)
+            .map(|(_, pos)| *pos)
+            .unwrap_or(self.end)
+    }
+    fn next(&mut self) -> Option<Tkn> {
+        let tkn = self.peek().cloned();
+        self.idx += 1;
+        tkn
+    }
+    /// Consumes a punctuation token if it is the next one.
+    fn punct(&mut self, p: &str) -> bool {
+        if self.peek() == Some(&Tkn::Punct(Self::normalize(p))) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn normalize(p: &str) -> &'static str {
+        PUNCTS
+            .iter()
+            .find(|(punct, _)| *punct == p)
+            .map(|(_, normalized)| *normalized)
+            .expect("known punctuation")
+    }
+    fn expect(&mut self, p: &str) -> Res<()> {
+        if self.punct(p) {
+            Ok(())
+        } else {
+            self.error(format!("expected `{}`", p))
+        }
+    }
+    fn error<T>(&self, msg: impl Into<String>) -> Res<T> {
+        let found = match self.peek() {
+            Some(tkn) => format!(", found `{}`", tkn),
+            None => ", found end of file".into(),
+        };
+        bail!("{}: {}{}", self.pos(), msg.into(), found)
+    }
+    fn ident(&mut self) -> Res<String> {
+        match self.peek() {
+            Some(Tkn::Ident(ident)) => {
+                let ident = ident.clone();
+                self.idx += 1;
+                Ok(ident)
+            }
+            _ => self.error("expected identifier"),
+        }
+    }
+
+    fn items(&mut self) -> Res<()> {
+        while self.peek().is_some() {
+            let pos = self.pos();
+            match self.ident()?.as_str() {
+                "svars" => self.svars()?,
+                "init" => {
+                    let preds = self.preds(false)?;
+                    self.mkn.init.extend(preds)
+                }
+                "trans" => {
+                    let preds = self.preds(true)?;
+                    self.mkn.trans.extend(preds)
+                }
+                "candidates" => self.candidates()?,
+                item => bail!(
+                    "{}: unexpected item `{}`, expected `svars`, `init`, `trans` or `candidates`",
+                    pos,
+                    item
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn svars(&mut self) -> Res<()> {
+        self.expect("{")?;
+        while !self.punct("}") {
+            let mut names = vec![];
+            while !self.punct(":") {
+                let pos = self.pos();
+                let name = self.ident()?;
+                if self.mkn.vars.iter().any(|(var, _)| *var == name) || names.contains(&name) {
+                    bail!("{}: state variable `{}` is declared twice", pos, name)
+                }
+                names.push(name)
+            }
+            let sort = match self.ident()?.as_str() {
+                "bool" => Sort::Bool,
+                "int" => Sort::Int,
+                "real" | "rat" => Sort::Real,
+                sort => bail!("{}: unknown sort `{}`", self.pos(), sort),
+            };
+            self.mkn
+                .vars
+                .extend(names.into_iter().map(|name| (name, sort)));
+            if !self.punct(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Comma-separated predicates between braces.
+    fn preds(&mut self, trans: bool) -> Res<Vec<Expr>> {
+        self.expect("{")?;
+        let mut res = vec![];
+        while !self.punct("}") {
+            res.push(self.expr(trans)?);
+            if !self.punct(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    fn candidates(&mut self) -> Res<()> {
+        self.expect("{")?;
+        while !self.punct("}") {
+            let pos = self.pos();
+            let name = match self.next() {
+                Some(Tkn::Str(name)) => name,
+                _ => {
+                    self.idx -= 1;
+                    return self.error("expected candidate name");
+                }
+            };
+            if self.mkn.candidates.iter().any(|(n, _)| *n == name) {
+                bail!("{}: candidate `{}` is declared twice", pos, name)
+            }
+            self.expect(":")?;
+            let expr = self.expr(false)?;
+            self.mkn.candidates.push((name, expr));
+            if !self.punct(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Expression, `trans` is true if next-state variables are legal.
+    fn expr(&mut self, trans: bool) -> Res<Expr> {
+        let lhs = self.binary(trans, 0)?;
+        // Implication is right-associative.
+        if self.punct("⇒") {
+            let rhs = self.expr(trans)?;
+            return Ok(Expr::App(Op::Implies, vec![lhs, rhs]));
+        }
+        Ok(lhs)
+    }
+
+    /// Binary operators by increasing precedence, n-ary ones are flattened.
+    const LEVELS: &'static [&'static [(&'static str, Op)]] = &[
+        &[("∨", Op::Or)],
+        &[("∧", Op::And)],
+        &[
+            ("=", Op::Eq),
+            ("≠", Op::Ne),
+            ("<", Op::Lt),
+            ("≤", Op::Le),
+            (">", Op::Gt),
+            ("≥", Op::Ge),
+        ],
+        &[("+", Op::Add), ("-", Op::Sub)],
+        &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Mod)],
+    ];
+
+    fn binary(&mut self, trans: bool, level: usize) -> Res<Expr> {
+        let ops = match Self::LEVELS.get(level) {
+            Some(ops) => *ops,
+            None => return self.unary(trans),
+        };
+        let mut lhs = self.binary(trans, level + 1)?;
+        'ops: loop {
+            for (p, op) in ops {
+                if self.punct(p) {
+                    let rhs = self.binary(trans, level + 1)?;
+                    lhs = match lhs {
+                        Expr::App(lhs_op, mut args)
+                            if lhs_op == *op
+                                && matches!(op, Op::Or | Op::And | Op::Add | Op::Mul) =>
+                        {
+                            args.push(rhs);
+                            Expr::App(*op, args)
+                        }
+                        lhs => Expr::App(*op, vec![lhs, rhs]),
+                    };
+                    continue 'ops;
+                }
+            }
+            break;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self, trans: bool) -> Res<Expr> {
+        if self.punct("¬")
+            || self.peek() == Some(&Tkn::Ident("not".into())) && self.next().is_some()
+        {
+            return Ok(Expr::Not(Box::new(self.unary(trans)?)));
+        }
+        if self.punct("-") {
+            return Ok(match self.unary(trans)? {
+                Expr::Int(int) => Expr::Int(-int),
+                arg => Expr::Neg(Box::new(arg)),
+            });
+        }
+        self.atom(trans)
+    }
+
+    fn atom(&mut self, trans: bool) -> Res<Expr> {
+        let pos = self.pos();
+        if self.punct("(") {
+            let expr = self.expr(trans)?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        let next = self.punct("'");
+        if next && !trans {
+            bail!("{}: next-state variables can only appear in `trans`", pos)
+        }
+        match self.next() {
+            Some(Tkn::Int(int)) if !next => Ok(Expr::Int(int)),
+            Some(Tkn::Ident(ident)) if !next && ident == "true" => Ok(Expr::Bool(true)),
+            Some(Tkn::Ident(ident)) if !next && ident == "false" => Ok(Expr::Bool(false)),
+            Some(Tkn::Ident(ident)) if !next && ident == "if" => self.ite(trans),
+            Some(Tkn::Ident(name)) => {
+                if !self.mkn.vars.iter().any(|(var, _)| *var == name) {
+                    bail!("{}: unknown state variable `{}`", pos, name)
+                }
+                Ok(Expr::Var { name, next })
+            }
+            _ => {
+                self.idx -= 1;
+                self.error("expected expression")
+            }
+        }
+    }
+
+    /// `if <expr> { <expr> } else { <expr> }`, `else if` is supported.
+    fn ite(&mut self, trans: bool) -> Res<Expr> {
+        let cnd = self.expr(trans)?;
+        self.expect("{")?;
+        let thn = self.expr(trans)?;
+        self.expect("}")?;
+        if self.peek() != Some(&Tkn::Ident("else".into())) {
+            return self.error("expected `else`");
+        }
+        self.idx += 1;
+        let els = if self.peek() == Some(&Tkn::Ident("if".into())) {
+            self.idx += 1;
+            self.ite(trans)?
+        } else {
+            self.expect("{")?;
+            let els = self.expr(trans)?;
+            self.expect("}")?;
+            els
+        };
+        Ok(Expr::Ite(Box::new(cnd), Box::new(thn), Box::new(els)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stopwatch() {
+        let mkn = parse(
+            "\
+// CMD: mikino check <file>
+svars {
+    /// Inputs.
+    start_stop reset: bool,
+    is_counting: bool,
+    cnt: int,
+}
+init {
+    is_counting = start_stop,
+    cnt ≥ 0,
+    (reset ⇒ cnt = 0),
+}
+trans {
+    'is_counting = if 'start_stop { ¬is_counting } else { is_counting },
+    'cnt = if 'reset { 0 } else if 'is_counting { cnt + 1 } else { cnt },
+}
+candidates {
+    \"cnt is positive\": cnt >= 0,
+    \"implies\": is_counting && !start_stop => cnt % 2 != -1,
+}
+",
+        )
+        .unwrap();
+        assert_eq!(mkn.vars.len(), 4);
+        let system = mkn.to_system();
+        assert_eq!(
+            system.init,
+            "(and (= |s.is_counting| |s.start_stop|) (>= |s.cnt| 0) (=> |s.reset| (= |s.cnt| 0)))"
+        );
+        assert_eq!(
+            system.trans,
+            "(and \
+            (= |s'.is_counting| (ite |s'.start_stop| (not |s.is_counting|) |s.is_counting|)) \
+            (= |s'.cnt| (ite |s'.reset| 0 (ite |s'.is_counting| (+ |s.cnt| 1) |s.cnt|))))"
+        );
+        assert_eq!(
+            system.candidates[1],
+            (
+                "implies".to_string(),
+                "(=> (and |s.is_counting| (not |s.start_stop|)) (not (= (mod |s.cnt| 2) (- 1))))"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn display() {
+        let text = "\
+svars { a b: bool, x: int }
+init { ¬(a ∧ b) ⇒ x = -1, x % 2 ≠ -(x + 1) }
+trans { 'x = if 'a { x - 1 } else if b { 0 } else { x * 2 } }
+";
+        let mkn = parse(text).unwrap();
+        assert_eq!(mkn.init[0].to_string(), "¬(a ∧ b) ⇒ (x = -1)");
+        assert_eq!(
+            mkn.trans[0].to_string(),
+            "'x = (if 'a { x - 1 } else if b { 0 } else { x * 2 })"
+        );
+        let reparsed = parse(&format!(
+            "svars {{ a b: bool, x: int }}\ninit {{ {} }}\ntrans {{ {} }}",
+            mkn.init
+                .iter()
+                .map(Expr::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            mkn.trans[0]
+        ))
+        .unwrap();
+        assert_eq!(reparsed.to_system(), mkn.to_system());
+    }
+
+    #[test]
+    fn eval() {
+        let mkn = parse(
+            "svars { a: bool, x: int }\ninit { x / 2, x % 3, -x, a ∧ x > 0, a ⇒ x = 0, x / (x - x) }",
+        )
+        .unwrap();
+        let eval = |expr: &Expr, a: Option<bool>, x: i64| {
+            expr.eval(&|var: &str, _| match var {
+                "a" => a.map(Value::Bool),
+                _ => Some(Value::Int(x)),
+            })
+        };
+        let values: Vec<_> = mkn.init[..5]
+            .iter()
+            .map(|expr| eval(expr, None, -7).unwrap())
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                Some(Value::Int(-4)),
+                Some(Value::Int(2)),
+                Some(Value::Int(7)),
+                Some(Value::Bool(false)),
+                None,
+            ]
+        );
+        assert_eq!(
+            eval(&mkn.init[4], Some(false), 1).unwrap(),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            eval(&mkn.init[5], None, 1).unwrap_err().to_string(),
+            "division by zero in `x / (x - x)`"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = |text: &str| parse(text).unwrap_err().to_string();
+        assert_eq!(
+            err("svars { x: int }\ninit { 'x = 0 }"),
+            "2:8: next-state variables can only appear in `trans`"
+        );
+        assert_eq!(
+            err("svars { x: int }\ninit { y = 0 }"),
+            "2:8: unknown state variable `y`"
+        );
+        assert_eq!(
+            err("svars { x: int }\ninit { x = }"),
+            "2:12: expected expression, found `}`"
+        );
+        let mkn = parse("svars { x: int }").unwrap();
+        assert_eq!(parse_expr(&mkn, "x ≥ 0").unwrap().to_string(), "x ≥ 0");
+        assert_eq!(
+            parse_expr(&mkn, "x = 0 0").unwrap_err().to_string(),
+            "1:7: expected end of expression, found `0`"
+        );
+        assert_eq!(
+            parse_expr(&mkn, "'x = 0").unwrap_err().to_string(),
+            "1:1: next-state variables can only appear in `trans`"
+        );
+    }
+
+    #[test]
+    fn book_systems() {
+        for snippet in test::snippets("../src", "mkn").unwrap() {
+            Mkn::load(&snippet).unwrap();
+        }
+    }
+}
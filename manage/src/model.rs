@@ -0,0 +1,452 @@
+//! Validation of the models in the output of `.smt2` snippets.
+//!
+//! A `sat` answer followed by a model in an `.out` file is what the text shows readers, so the
+//! model had better satisfy the snippet's constraints. [`validate`] walks the snippet's commands
+//! along with their answers in the output, and for each model printed after a `sat` answer it
+//! spawns a fresh Z3 in which the model's `define-fun`s replace the declarations of the snippet.
+//! Each assertion active at the time of the `check-sat`, and each assumption of a
+//! `check-sat-assuming`, is then checked to evaluate to `true` in the model.
+
+use std::fmt;
+
+use crate::{
+    engine::Solver,
+    smt2::{self, Pos, Sexp, TokenKind},
+};
+
+prelude!();
+
+/// Commands producing an answer when `:print-success` is `false`, z3's default.
+const ANSWERING: &[&str] = &[
+    "check-sat",
+    "check-sat-assuming",
+    "get-model",
+    "get-value",
+    "get-assignment",
+    "get-assertions",
+    "get-unsat-core",
+    "get-unsat-assumptions",
+    "get-proof",
+    "get-info",
+    "get-option",
+    "eval",
+    "simplify",
+];
+/// Commands sent before the model, which does not depend on them but may mention their sorts.
+const PRELUDE: &[&str] = &[
+    "set-logic",
+    "declare-sort",
+    "define-sort",
+    "declare-datatype",
+    "declare-datatypes",
+];
+
+/// An assertion or assumption that does not hold in a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Position of the assertion or assumption.
+    pub pos: Pos,
+    /// The term that does not evaluate to `true`.
+    pub term: String,
+    /// Position of the `check-sat` command the model comes from.
+    pub check: Pos,
+}
+impl fmt::Display for Violation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "`{}` at {} does not hold in the model of the `check-sat` at {}",
+            self.term, self.pos, self.check
+        )
+    }
+}
+
+/// Output of a snippet, consumed answer by answer.
+struct Answers<'a> {
+    text: &'a str,
+}
+impl<'a> Answers<'a> {
+    /// Text of the next answer, an atom or a list, without consuming it.
+    fn peek(&self) -> Option<(&'a str, usize)> {
+        let (tokens, _) = smt2::tokenize(self.text);
+        let mut depth = 0usize;
+        for token in tokens.iter().filter(|t| t.kind != TokenKind::Comment) {
+            match token.kind {
+                TokenKind::Open => depth += 1,
+                TokenKind::Close => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+            if depth == 0 {
+                let end = token.offset + token.text.len();
+                let start = tokens
+                    .iter()
+                    .find(|t| t.kind != TokenKind::Comment)
+                    .map_or(0, |t| t.offset);
+                return Some((&self.text[start..end], end));
+            }
+        }
+        None
+    }
+    /// Consumes the next answer.
+    fn next(&mut self) -> Option<&'a str> {
+        let (answer, end) = self.peek()?;
+        self.text = &self.text[end..];
+        Some(answer)
+    }
+    /// Consumes the output of an `echo` of string literal `lit`.
+    ///
+    /// Z3 prints the content of the literal as is, it is not an s-expression.
+    fn echo(&mut self, lit: &str) -> bool {
+        let content = lit
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(lit)
+            .replace("\"\"", "\"");
+        match self.text.trim_start().strip_prefix(content.as_str()) {
+            Some(rest) => {
+                self.text = rest;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Consumes the next answer if it is an error reported at `line`.
+    fn error_at(&mut self, line: usize) -> bool {
+        let prefix = format!("(error \"line {} column", line);
+        match self.peek() {
+            Some((answer, _)) if answer.starts_with(&prefix) => {
+                self.next();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A `check-sat` of a snippet, with its answer in the output.
+#[derive(Debug, Clone)]
+pub struct Query<'a> {
+    /// Position of the `check-sat`.
+    pub pos: Pos,
+    /// `sat`, `unsat` or `unknown`.
+    pub answer: String,
+    /// Assertion set at the time of the `check-sat`, flattened.
+    pub context: Vec<Sexp<'a>>,
+    /// Assumptions of the `check-sat-assuming`, if any.
+    pub assumptions: Vec<Sexp<'a>>,
+    /// Model printed by a `get-model` following a `sat` answer, if any.
+    pub model: Option<String>,
+}
+
+/// Retrieves the queries of a snippet and their answers in its output.
+///
+/// Fails if the output does not match the snippet's commands. Commands the output reports an
+/// error for are ignored.
+pub fn queries<'a>(snippet: &'a str, output: &str) -> Res<Vec<Query<'a>>> {
+    let (commands, errors) = smt2::parse(snippet);
+    if let Some(error) = errors.first() {
+        bail!("{}: {}", error.pos, error.msg)
+    }
+
+    let mut answers = Answers { text: output };
+    // Assertion levels, each with its declarations, definitions and assertions.
+    let mut levels: Vec<Vec<Sexp>> = vec![vec![]];
+    let mut queries: Vec<Query> = vec![];
+    // Index of the last query if it answered `sat` and the assertion set has not changed since.
+    let mut last_sat: Option<usize> = None;
+
+    for command in &commands {
+        let (head, args) = match (command.head(), command.list()) {
+            (Some(head), Some(elems)) => (head, &elems[1..]),
+            _ => bail!("{}: illegal command `{}`", command.pos(), command),
+        };
+        let pos = command.pos();
+        if answers.error_at(pos.line) {
+            continue;
+        }
+        let missing = || format!("{}: no answer to `{}` in the output", pos, head);
+
+        match head {
+            "exit" => break,
+            "echo" => {
+                let lit = args.first().map(|lit| lit.to_string()).unwrap_or_default();
+                if !answers.echo(&lit) {
+                    bail!("{}: the output does not echo {}", pos, lit)
+                }
+            }
+            "check-sat" | "check-sat-assuming" => {
+                let answer = answers.next().ok_or_else(missing)?;
+                if !matches!(answer, "sat" | "unsat" | "unknown") {
+                    bail!("{}: unexpected answer `{}` to `{}`", pos, answer, head)
+                }
+                let assumptions = match (head, args.first().and_then(Sexp::list)) {
+                    ("check-sat-assuming", Some(assumptions)) => assumptions.to_vec(),
+                    _ => vec![],
+                };
+                last_sat = if answer == "sat" {
+                    Some(queries.len())
+                } else {
+                    None
+                };
+                queries.push(Query {
+                    pos,
+                    answer: answer.into(),
+                    context: levels.iter().flatten().cloned().collect(),
+                    assumptions,
+                    model: None,
+                })
+            }
+            "get-model" => {
+                let model = answers.next().ok_or_else(missing)?;
+                if let Some(idx) = last_sat {
+                    queries[idx].model = Some(model.into())
+                }
+            }
+            _ if ANSWERING.contains(&head) => {
+                answers.next().ok_or_else(missing)?;
+            }
+            "push" | "pop" => {
+                let n = match args.first().map(|n| n.to_string()) {
+                    None => 1,
+                    Some(n) => n
+                        .parse::<usize>()
+                        .chain_err(|| format!("{}: illegal `{}` level `{}`", pos, head, n))?,
+                };
+                if head == "push" {
+                    levels.extend((0..n).map(|_| vec![]))
+                } else if n >= levels.len() {
+                    bail!("{}: cannot pop {} level(s)", pos, n)
+                } else {
+                    levels.truncate(levels.len() - n)
+                }
+                last_sat = None
+            }
+            "reset" | "reset-assertions" => {
+                levels = vec![vec![]];
+                last_sat = None
+            }
+            "set-option" | "set-info" => (),
+            _ => {
+                // Anything else changes the assertion set.
+                levels
+                    .last_mut()
+                    .expect("at least one level")
+                    .push(command.clone());
+                if head == "assert" {
+                    last_sat = None
+                }
+            }
+        }
+    }
+
+    Ok(queries)
+}
+
+/// Validates the models printed in the output of a snippet.
+///
+/// Returns the assertions and assumptions that do not hold in the model they come with, and fails
+/// if the output does not match the snippet's commands.
+pub fn validate(z3_cmd: &str, snippet: &str, output: &str) -> Res<Vec<Violation>> {
+    let mut res = vec![];
+    for query in queries(snippet, output)? {
+        if let Some(model) = &query.model {
+            res.extend(check(z3_cmd, &query, model)?)
+        }
+    }
+    Ok(res)
+}
+
+/// Checks that the assertions and assumptions of a query hold in a model.
+///
+/// The model is a list of `define-fun`s, as printed by `get-model`.
+pub fn check(z3_cmd: &str, query: &Query, model: &str) -> Res<Vec<Violation>> {
+    let (entries, _) = smt2::parse(model);
+    let entries = match entries.first().and_then(Sexp::list) {
+        // Older versions of z3 print `(model ...)`.
+        Some([first, rest @ ..]) if first.symbol() == Some("model") => rest,
+        Some(entries) => entries,
+        None => bail!("illegal model `{}`", model),
+    };
+    let entries: Vec<&Sexp> = entries
+        .iter()
+        .filter(|entry| matches!(entry.head(), Some("define-fun" | "declare-fun")))
+        .collect();
+    let name = |command: &Sexp| -> Option<String> {
+        command
+            .list()
+            .and_then(|elems| elems.get(1))
+            .and_then(Sexp::symbol)
+            .map(String::from)
+    };
+    let defined: Vec<String> = entries.iter().filter_map(|entry| name(entry)).collect();
+    let is_declaration =
+        |command: &Sexp| matches!(command.head(), Some("declare-const" | "declare-fun"));
+    let is_defined_by_user = |entry: &Sexp| {
+        query
+            .context
+            .iter()
+            .any(|command| !is_declaration(command) && name(command) == name(entry))
+    };
+
+    // Not chained below so that callers can tell z3 is not available.
+    let mut solver = Solver::new(z3_cmd)?;
+    let mut run = || -> Res<Vec<Violation>> {
+        let prelude = query
+            .context
+            .iter()
+            .filter(|command| command.head().is_some_and(|head| PRELUDE.contains(&head)));
+        solver.commands(prelude.map(Sexp::to_string))?;
+        solver.commands(
+            entries
+                .iter()
+                .filter(|entry| !is_defined_by_user(entry))
+                .map(|entry| entry.to_string()),
+        )?;
+        solver.commands(
+            query
+                .context
+                .iter()
+                .filter(|command| {
+                    let skipped = command
+                        .head()
+                        .is_some_and(|head| PRELUDE.contains(&head) || head == "assert");
+                    // Declarations the model defines.
+                    let replaced = is_declaration(command)
+                        && name(command).is_some_and(|n| defined.contains(&n));
+                    !skipped && !replaced
+                })
+                .map(Sexp::to_string),
+        )?;
+
+        let assertions = query
+            .context
+            .iter()
+            .filter(|command| command.head() == Some("assert"))
+            .filter_map(|command| command.list().and_then(|elems| elems.get(1)));
+        let mut res = vec![];
+        for term in assertions.chain(&query.assumptions) {
+            // Named terms are checked without their name.
+            let unnamed = match term.list() {
+                Some([bang, term, ..]) if bang.symbol() == Some("!") => term,
+                _ => term,
+            };
+            solver.command("(push 1)")?;
+            solver.command(&format!("(assert (not {}))", unnamed))?;
+            let falsifiable = solver.check_sat_assuming(&[])?;
+            solver.command("(pop 1)")?;
+            if falsifiable {
+                res.push(Violation {
+                    pos: term.pos(),
+                    term: term.to_string(),
+                    check: query.pos,
+                })
+            }
+        }
+        Ok(res)
+    };
+    run().chain_err(|| {
+        format!(
+            "while validating a model of the `check-sat` at {}",
+            query.pos
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validates a snippet, `None` if z3 is not available.
+    fn validate(snippet: &str, output: &str) -> Option<Vec<Violation>> {
+        match super::validate("z3", snippet, output) {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping");
+                None
+            }
+            res => Some(res.unwrap()),
+        }
+    }
+
+    #[test]
+    fn models() {
+        let snippet = load_file("../src/smt/code/ex_3.smt2").unwrap();
+        let output = load_file("../src/smt/code/ex_3.smt2.out").unwrap();
+        let violations = match validate(&snippet, &output) {
+            Some(violations) => violations,
+            None => return,
+        };
+        assert!(violations.is_empty());
+
+        let tampered = output.replace("16)", "17)");
+        let violations = validate(&snippet, &tampered).unwrap();
+        assert_eq!(
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec!["`(or (= y (* 2 x)) (= x 11))` at 5:9 does not hold in the model of the `check-sat` at 7:1"]
+        );
+
+        // Models after `unsat` are errors, not checked, and popped assertions do not count.
+        let snippet = "\
+(declare-const b Bool)
+(push 1)
+(assert (not b))
+(check-sat)
+(get-model)
+(pop 1)
+(assert (! b :named b_holds))
+(check-sat-assuming ((not b)))
+(get-model)
+(echo \"done \"\"here\"\"\")
+(check-sat)
+(get-model)
+";
+        let output = "\
+sat
+(
+  (define-fun b () Bool
+    false)
+)
+unsat
+(error \"line 9 column 10: model is not available\")
+done \"here\"
+sat
+(
+  (define-fun b () Bool
+    false)
+)
+";
+        let violations = validate(snippet, output).unwrap();
+        assert_eq!(
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec!["`(! b :named b_holds)` at 7:9 does not hold in the model of the `check-sat` at 11:1"]
+        );
+
+        assert_eq!(
+            super::validate("z3", "(check-sat)\n(get-model)", "sat\n")
+                .unwrap_err()
+                .to_string(),
+            "2:1: no answer to `get-model` in the output"
+        );
+    }
+
+    #[test]
+    fn book_snippets() {
+        let mut files = vec![];
+        smt2::collect_files("../src".as_ref(), &mut files).unwrap();
+        for file in files {
+            let out = PathBuf::from(format!("{}.out", file.display()));
+            if !out.exists() {
+                continue;
+            }
+            let (snippet, output) = (load_file(&file).unwrap(), load_file(&out).unwrap());
+            match validate(&snippet, &output) {
+                Some(violations) => assert!(
+                    violations.is_empty(),
+                    "{}: {:?}",
+                    file.display(),
+                    violations
+                ),
+                None => return,
+            }
+        }
+    }
+}
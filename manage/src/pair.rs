@@ -0,0 +1,332 @@
+//! Cross-checking of snippets encoding the same problem as a mikino script and in SMT-LIB 2.
+//!
+//! Some chapters show the same problem twice, as `name.hsmt` and `name.smt2`. Such pairs are
+//! detected by their stem and their outputs must agree: both snippets make the same number of
+//! queries, and the `n`-th queries have the same `sat`/`unsat`/`unknown` answer. Optionally, the
+//! models mikino prints are also checked against the assertions of the SMT-LIB 2 snippet, see
+//! [`crate::model::check`].
+
+use std::fmt;
+
+use crate::{
+    model,
+    smt2::{self, Pos},
+    trace::{State, Value},
+};
+
+prelude!();
+
+/// A query of a mikino script, with its answer in the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    /// Position of the `check_sat!`, if the output has no more answers than the script has
+    /// `check_sat!`s.
+    pub pos: Option<Pos>,
+    /// `sat`, `unsat` or `unknown`.
+    pub answer: String,
+    /// Model printed by a `get_model!` following a `sat` answer, if any.
+    pub model: Option<State>,
+}
+
+/// A disagreement between the two snippets of a pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Position in the mikino script, if relevant.
+    pub pos: Option<Pos>,
+    /// Explanation.
+    pub msg: String,
+}
+impl fmt::Display for Divergence {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.msg.fmt(fmt)
+    }
+}
+
+/// Retrieves the queries of a mikino script and their answers in its output.
+pub fn hsmt_queries(script: &str, output: &str) -> Res<Vec<Query>> {
+    let positions: Vec<Pos> = script
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let code = line.split("//").next().unwrap_or("");
+            code.find("check_sat")
+                .map(|col| Pos::new(idx + 1, code[..col].chars().count() + 1))
+        })
+        .collect();
+
+    let mut queries: Vec<Query> = vec![];
+    let mut lines = output.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        match line.trim() {
+            answer @ ("sat" | "unsat" | "unknown") => queries.push(Query {
+                pos: None,
+                answer: answer.into(),
+                model: None,
+            }),
+            "model {" => {
+                let mut model = State::new();
+                for (idx, line) in lines.by_ref() {
+                    let line = line.trim();
+                    if line == "}" {
+                        break;
+                    }
+                    let (var, value) = line
+                        .trim_end_matches(',')
+                        .split_once(':')
+                        .ok_or_else(|| format!("{}: illegal model entry `{}`", idx + 1, line))?;
+                    let value = Value::parse(value)
+                        .or_else(|| smt2::parse(value).0.first().and_then(Value::of_sexp))
+                        .ok_or_else(|| format!("{}: illegal value `{}`", idx + 1, value.trim()))?;
+                    model.insert(var.trim().into(), value);
+                }
+                match queries.last_mut() {
+                    Some(query) if query.answer == "sat" => query.model = Some(model),
+                    _ => bail!("{}: model without a preceding `sat` answer", idx + 1),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if queries.len() <= positions.len() {
+        for (query, pos) in queries.iter_mut().zip(positions) {
+            query.pos = Some(pos)
+        }
+    }
+    Ok(queries)
+}
+
+/// The mikino scripts in `dir` that have an SMT-LIB 2 counterpart, both with an output file.
+pub fn pairs(dir: &Path) -> Res<Vec<PathBuf>> {
+    let mut res = vec![];
+    for entry in dir
+        .read_dir()
+        .chain_err(|| format!("while reading directory `{}`", dir.display()))?
+    {
+        let path = entry
+            .chain_err(|| format!("while reading directory `{}`", dir.display()))?
+            .path();
+        let is_pair = path.extension().is_some_and(|ext| ext == "hsmt")
+            && [
+                path.with_extension("hsmt.out"),
+                path.with_extension("smt2"),
+                path.with_extension("smt2.out"),
+            ]
+            .iter()
+            .all(|path| path.is_file());
+        if is_pair {
+            res.push(path)
+        }
+    }
+    res.sort();
+    Ok(res)
+}
+
+/// Cross-checks a mikino script with its SMT-LIB 2 counterpart, using their output files.
+///
+/// If `z3_cmd` is provided, mikino's models are checked against the SMT-LIB 2 snippet's
+/// assertions.
+pub fn cross_check(z3_cmd: Option<&str>, hsmt: &Path) -> Res<Vec<Divergence>> {
+    let smt2 = hsmt.with_extension("smt2");
+    let name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let (hsmt_name, smt2_name) = (name(hsmt), name(&smt2));
+
+    let hsmt_queries = hsmt_queries(
+        &load_file(hsmt)?,
+        &load_file(hsmt.with_extension("hsmt.out"))?,
+    )
+    .chain_err(|| format!("in the output of `{}`", hsmt.display()))?;
+    let smt2_text = load_file(&smt2)?;
+    let smt2_queries = model::queries(&smt2_text, &load_file(smt2.with_extension("smt2.out"))?)
+        .chain_err(|| format!("in the output of `{}`", smt2.display()))?;
+
+    let mut res = vec![];
+    let at = |pos: Option<Pos>| pos.map(|pos| format!(" at {}", pos)).unwrap_or_default();
+    if hsmt_queries.len() != smt2_queries.len() {
+        res.push(Divergence {
+            pos: None,
+            msg: format!(
+                "`{}` makes {} quer{} but `{}` makes {}",
+                hsmt_name,
+                hsmt_queries.len(),
+                if hsmt_queries.len() == 1 { "y" } else { "ies" },
+                smt2_name,
+                smt2_queries.len()
+            ),
+        })
+    }
+    for (idx, (hsmt_query, smt2_query)) in hsmt_queries.iter().zip(&smt2_queries).enumerate() {
+        if hsmt_query.answer != smt2_query.answer {
+            res.push(Divergence {
+                pos: hsmt_query.pos,
+                msg: format!(
+                    "query {} diverged: `{}` answers `{}`{}, `{}` answers `{}` at {}",
+                    idx + 1,
+                    hsmt_name,
+                    hsmt_query.answer,
+                    at(hsmt_query.pos),
+                    smt2_name,
+                    smt2_query.answer,
+                    smt2_query.pos
+                ),
+            });
+            continue;
+        }
+        let (z3_cmd, values) = match (z3_cmd, &hsmt_query.model) {
+            (Some(z3_cmd), Some(values)) => (z3_cmd, values),
+            _ => continue,
+        };
+        let model = to_smt2_model(values, &smt2_query.context);
+        let violations = model::check(z3_cmd, smt2_query, &model)?;
+        for violation in violations {
+            res.push(Divergence {
+                pos: hsmt_query.pos,
+                msg: format!(
+                    "query {} diverged: the model of `{}`{} falsifies `{}` at {} in `{}`",
+                    idx + 1,
+                    hsmt_name,
+                    at(hsmt_query.pos),
+                    violation.term,
+                    violation.pos,
+                    smt2_name
+                ),
+            })
+        }
+    }
+    Ok(res)
+}
+
+/// Turns a mikino model into `define-fun`s for the constants an SMT-LIB 2 context declares.
+///
+/// Variables the context does not declare as constants are ignored.
+fn to_smt2_model(values: &State, context: &[smt2::Sexp]) -> String {
+    let mut model = String::from("(");
+    for command in context {
+        let (name, sort) = match (command.head(), command.list()) {
+            (Some("declare-const"), Some([_, name, sort])) => (name, sort),
+            (Some("declare-fun"), Some([_, name, args, sort]))
+                if args.list().is_some_and(<[_]>::is_empty) =>
+            {
+                (name, sort)
+            }
+            _ => continue,
+        };
+        let value = match name.symbol().and_then(|name| values.get(name)) {
+            Some(value) => value,
+            None => continue,
+        };
+        model.push_str(&format!(
+            "(define-fun {} () {} {})",
+            name,
+            sort,
+            value.to_smt2()
+        ));
+    }
+    model.push(')');
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        let queries = hsmt_queries(
+            "vars { x : int }\n// check_sat!()\nassert(x > 7)\ncheck_sat!()\nget_model!()\n",
+            "sat\nmodel {\n    x: -8,\n}\nsuccess\n",
+        )
+        .unwrap();
+        let mut model = State::new();
+        model.insert("x".into(), Value::Int(-8));
+        assert_eq!(
+            queries,
+            vec![Query {
+                pos: Some(Pos::new(4, 1)),
+                answer: "sat".into(),
+                model: Some(model),
+            }]
+        );
+
+        let ex_5 = Path::new("../src/smt/code/ex_5.hsmt");
+        let queries = hsmt_queries(
+            &load_file(ex_5).unwrap(),
+            &load_file(ex_5.with_extension("hsmt.out")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].answer, "unsat");
+        assert_eq!(queries[0].pos, Some(Pos::new(17, 1)));
+    }
+
+    #[test]
+    fn book_pairs() {
+        let dir = Path::new("../src/smt/code");
+        let pairs = pairs(dir).unwrap();
+        assert_eq!(pairs.len(), 5);
+        for hsmt in pairs {
+            let divergences = match cross_check(Some("z3"), &hsmt) {
+                Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                    eprintln!("z3 is not available, skipping models");
+                    cross_check(None, &hsmt).unwrap()
+                }
+                res => res.unwrap(),
+            };
+            assert!(divergences.is_empty(), "{:?}", divergences)
+        }
+    }
+
+    #[test]
+    fn divergences() {
+        let dir = std::env::temp_dir().join(format!("manage_pair_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let hsmt = dir.join("ex.hsmt");
+        let write = |path: PathBuf, text: &str| fs::write(path, text).unwrap();
+        write(
+            hsmt.clone(),
+            "vars { x : int }\nassert(x > 7)\ncheck_sat!()\nget_model!()\ncheck_sat!()\n",
+        );
+        write(
+            hsmt.with_extension("hsmt.out"),
+            "sat\nmodel {\n    x: 3,\n}\nsat\nsuccess\n",
+        );
+        write(
+            hsmt.with_extension("smt2"),
+            "(declare-const x Int)\n(assert (> x 7))\n(check-sat)\n",
+        );
+        write(hsmt.with_extension("smt2.out"), "unsat\n");
+
+        let msgs = |z3_cmd| -> Option<Vec<String>> {
+            match cross_check(z3_cmd, &hsmt) {
+                Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => None,
+                res => Some(res.unwrap().iter().map(|d| d.to_string()).collect()),
+            }
+        };
+        assert_eq!(
+            msgs(None).unwrap(),
+            vec![
+                "`ex.hsmt` makes 2 queries but `ex.smt2` makes 1",
+                "query 1 diverged: `ex.hsmt` answers `sat` at 3:1, `ex.smt2` answers `unsat` at 3:1",
+            ]
+        );
+
+        write(
+            hsmt.with_extension("smt2"),
+            "(declare-const x Int)\n(assert (> x 7))\n(check-sat)\n(check-sat)\n",
+        );
+        write(hsmt.with_extension("smt2.out"), "sat\nsat\n");
+        if let Some(msgs) = msgs(Some("z3")) {
+            assert_eq!(
+                msgs,
+                vec![
+                    "query 1 diverged: the model of `ex.hsmt` at 3:1 falsifies `(> x 7)` at 2:9 in `ex.smt2`"
+                ]
+            )
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1045 @@
+//! SMT-LIB 2.6 portability checker for the book's snippets.
+//!
+//! The snippets are written for and tested with Z3, which accepts many constructs that are not
+//! part of the SMT-LIB 2.6 standard. Readers trying them with another solver should not run into
+//! these, so each command, option and theory symbol used by a snippet is classified as standard or
+//! not. The logic a snippet needs is also inferred from the theories it uses.
+//!
+//! Z3 is also lenient on the order of commands: it produces models without `:produce-models` and
+//! accepts declarations before `set-logic`. These are reported as [`Misuse`]s.
+
+use std::collections::BTreeSet;
+
+use crate::smt2::{self, Pos, Sexp, TokenKind};
+
+prelude!();
+
+/// Name of the diagnostics' source.
+const SOURCE: &str = "portability";
+
+/// SMT-LIB 2.6 commands.
+const COMMANDS: &[&str] = &[
+    "assert",
+    "check-sat",
+    "check-sat-assuming",
+    "declare-const",
+    "declare-datatype",
+    "declare-datatypes",
+    "declare-fun",
+    "declare-sort",
+    "define-fun",
+    "define-fun-rec",
+    "define-funs-rec",
+    "define-sort",
+    "echo",
+    "exit",
+    "get-assertions",
+    "get-assignment",
+    "get-info",
+    "get-model",
+    "get-option",
+    "get-proof",
+    "get-unsat-assumptions",
+    "get-unsat-core",
+    "get-value",
+    "pop",
+    "push",
+    "reset",
+    "reset-assertions",
+    "set-info",
+    "set-logic",
+    "set-option",
+];
+/// Z3 commands with a standard alternative.
+const COMMAND_HINTS: &[(&str, &str)] = &[
+    ("define-const", "use `define-fun` with no arguments"),
+    ("eval", "use `get-value`"),
+    ("check-sat-using", "use `check-sat`"),
+];
+
+/// SMT-LIB 2.6 options.
+const OPTIONS: &[&str] = &[
+    ":diagnostic-output-channel",
+    ":global-declarations",
+    ":interactive-mode",
+    ":print-success",
+    ":produce-assertions",
+    ":produce-assignments",
+    ":produce-models",
+    ":produce-proofs",
+    ":produce-unsat-assumptions",
+    ":produce-unsat-cores",
+    ":random-seed",
+    ":regular-output-channel",
+    ":reproducible-resource-limit",
+    ":verbosity",
+];
+/// SMT-LIB 2.6 `get-info` flags.
+const INFO_FLAGS: &[&str] = &[
+    ":all-statistics",
+    ":assertion-stack-levels",
+    ":authors",
+    ":error-behavior",
+    ":name",
+    ":reason-unknown",
+    ":version",
+];
+
+const CORE: &str = "Core";
+const INTS: &str = "Ints";
+const REALS: &str = "Reals";
+const ARITH: &str = "Ints/Reals";
+const REALS_INTS: &str = "Reals_Ints";
+const ARRAYS: &str = "ArraysEx";
+const BV: &str = "FixedSizeBitVectors";
+const STRINGS: &str = "Strings";
+const DATATYPES: &str = "Datatypes";
+
+/// Function symbols of the standard theories, with their theory.
+const FUNS: &[(&str, &str)] = &[
+    ("true", CORE),
+    ("false", CORE),
+    ("not", CORE),
+    ("=>", CORE),
+    ("and", CORE),
+    ("or", CORE),
+    ("xor", CORE),
+    ("=", CORE),
+    ("distinct", CORE),
+    ("ite", CORE),
+    ("-", ARITH),
+    ("+", ARITH),
+    ("*", ARITH),
+    ("<=", ARITH),
+    ("<", ARITH),
+    (">=", ARITH),
+    (">", ARITH),
+    ("div", INTS),
+    ("mod", INTS),
+    ("abs", INTS),
+    ("/", REALS),
+    ("to_real", REALS_INTS),
+    ("to_int", REALS_INTS),
+    ("is_int", REALS_INTS),
+    ("select", ARRAYS),
+    ("store", ARRAYS),
+    ("concat", BV),
+    ("bvnot", BV),
+    ("bvand", BV),
+    ("bvor", BV),
+    ("bvneg", BV),
+    ("bvadd", BV),
+    ("bvmul", BV),
+    ("bvudiv", BV),
+    ("bvurem", BV),
+    ("bvshl", BV),
+    ("bvlshr", BV),
+    ("bvult", BV),
+    ("bvnand", BV),
+    ("bvnor", BV),
+    ("bvxor", BV),
+    ("bvxnor", BV),
+    ("bvcomp", BV),
+    ("bvsub", BV),
+    ("bvsdiv", BV),
+    ("bvsrem", BV),
+    ("bvsmod", BV),
+    ("bvashr", BV),
+    ("bvule", BV),
+    ("bvugt", BV),
+    ("bvuge", BV),
+    ("bvslt", BV),
+    ("bvsle", BV),
+    ("bvsgt", BV),
+    ("bvsge", BV),
+    ("str.++", STRINGS),
+    ("str.len", STRINGS),
+    ("str.<", STRINGS),
+    ("str.<=", STRINGS),
+    ("str.at", STRINGS),
+    ("str.substr", STRINGS),
+    ("str.prefixof", STRINGS),
+    ("str.suffixof", STRINGS),
+    ("str.contains", STRINGS),
+    ("str.indexof", STRINGS),
+    ("str.replace", STRINGS),
+    ("str.replace_all", STRINGS),
+    ("str.is_digit", STRINGS),
+    ("str.to_code", STRINGS),
+    ("str.from_code", STRINGS),
+    ("str.to_int", STRINGS),
+    ("str.from_int", STRINGS),
+    ("str.to_re", STRINGS),
+    ("str.in_re", STRINGS),
+    ("re.none", STRINGS),
+    ("re.all", STRINGS),
+    ("re.allchar", STRINGS),
+    ("re.++", STRINGS),
+    ("re.union", STRINGS),
+    ("re.inter", STRINGS),
+    ("re.*", STRINGS),
+    ("re.+", STRINGS),
+    ("re.opt", STRINGS),
+    ("re.range", STRINGS),
+    ("re.comp", STRINGS),
+    ("re.diff", STRINGS),
+];
+/// Indexed function symbols of the standard theories, `(_ <symbol> <index>+)`.
+const INDEXED_FUNS: &[(&str, &str)] = &[
+    ("extract", BV),
+    ("repeat", BV),
+    ("zero_extend", BV),
+    ("sign_extend", BV),
+    ("rotate_left", BV),
+    ("rotate_right", BV),
+    ("re.loop", STRINGS),
+    ("re.^", STRINGS),
+    ("is", DATATYPES),
+];
+/// Z3 function symbols with a standard alternative.
+const FUN_HINTS: &[(&str, &str)] = &[
+    ("implies", "use `=>`"),
+    ("iff", "use `=`"),
+    ("if", "use `ite`"),
+    ("rem", "not in the `Ints` theory, use `mod`"),
+    (
+        "^",
+        "exponentiation is not in the `Ints` and `Reals` theories",
+    ),
+    ("str.to.int", "use `str.to_int`"),
+    ("int.to.str", "use `str.from_int`"),
+    ("bv2int", "not in SMT-LIB 2.6"),
+    ("bv2nat", "not in SMT-LIB 2.6"),
+    ("int2bv", "not in SMT-LIB 2.6"),
+];
+
+/// Kinds of constructs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Command,
+    Option,
+    Info,
+    Sort,
+    Symbol,
+}
+impl std::fmt::Display for Kind {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Command => "command".fmt(fmt),
+            Self::Option => "option".fmt(fmt),
+            Self::Info => "info flag".fmt(fmt),
+            Self::Sort => "sort".fmt(fmt),
+            Self::Symbol => "symbol".fmt(fmt),
+        }
+    }
+}
+
+/// Classification of a construct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Class {
+    /// Standard, with the part of the standard it belongs to: a theory, `commands`...
+    Standard(&'static str),
+    /// Solver-specific, with a hint to make it standard if any.
+    Specific(Option<String>),
+}
+
+/// A construct used by a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Use<'a> {
+    pub kind: Kind,
+    pub name: &'a str,
+    pub pos: Pos,
+    pub class: Class,
+}
+
+/// A command that is standard, but not where the script uses it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misuse<'a> {
+    /// `get-model` or `get-value` without `(set-option :produce-models true)` before.
+    NoModels { command: &'a str, pos: Pos },
+    /// First declaration or definition of a script, if it comes before `set-logic`.
+    BeforeLogic { command: &'a str, pos: Pos },
+}
+impl<'a> Misuse<'a> {
+    /// Position of the command.
+    pub fn pos(&self) -> Pos {
+        match self {
+            Self::NoModels { pos, .. } | Self::BeforeLogic { pos, .. } => *pos,
+        }
+    }
+}
+impl std::fmt::Display for Misuse<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoModels { command, .. } => write!(
+                fmt,
+                "`{}` requires `(set-option :produce-models true)` first",
+                command
+            ),
+            Self::BeforeLogic { command, .. } => {
+                write!(fmt, "`{}` before `set-logic`", command)
+            }
+        }
+    }
+}
+
+/// Theories and features a script relies on, used to infer its logic.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Features {
+    pub quantifiers: bool,
+    pub arrays: bool,
+    pub uf: bool,
+    pub datatypes: bool,
+    pub bv: bool,
+    pub strings: bool,
+    pub ints: bool,
+    pub reals: bool,
+    pub nonlinear: bool,
+}
+impl Features {
+    /// Name of the logic, following the SMT-LIB naming scheme.
+    ///
+    /// Scripts that only use the core theory get `QF_UF`, since there is no purely propositional
+    /// logic.
+    pub fn logic(&self) -> String {
+        let mut logic = if self.quantifiers { "" } else { "QF_" }.to_string();
+        let len = logic.len();
+        for (has, part) in [
+            (self.arrays, "A"),
+            (self.uf, "UF"),
+            (self.datatypes, "DT"),
+            (self.bv, "BV"),
+            (self.strings, "S"),
+        ] {
+            if has {
+                logic.push_str(part)
+            }
+        }
+        let arith = match (self.ints, self.reals) {
+            (true, false) => "IA",
+            (false, true) => "RA",
+            (true, true) => "IRA",
+            (false, false) => "",
+        };
+        if !arith.is_empty() {
+            logic.push(if self.nonlinear { 'N' } else { 'L' });
+            logic.push_str(arith)
+        }
+        if logic.len() == len {
+            logic.push_str("UF")
+        }
+        logic
+    }
+}
+
+/// Portability analysis of a script.
+#[derive(Debug, Clone)]
+pub struct Analysis<'a> {
+    /// Constructs used, in order of appearance.
+    pub uses: Vec<Use<'a>>,
+    /// Theories and features used.
+    pub features: Features,
+    /// Logic set by the script, if any.
+    pub declared_logic: Option<(&'a str, Pos)>,
+    /// Misplaced commands, in order of appearance.
+    pub misuses: Vec<Misuse<'a>>,
+}
+impl<'a> Analysis<'a> {
+    /// Solver-specific constructs used.
+    pub fn specific(&self) -> impl Iterator<Item = &Use<'a>> {
+        self.uses
+            .iter()
+            .filter(|u| matches!(u.class, Class::Specific(_)))
+    }
+    /// Logic the script needs.
+    pub fn logic(&self) -> String {
+        self.features.logic()
+    }
+
+    /// Warnings for solver-specific constructs, misplaced commands and for a declared logic that
+    /// does not match.
+    pub fn diagnostics(&self, file: &Path) -> Vec<Diagnostic> {
+        let located = |pos: Pos, msg: String| {
+            Diagnostic::new(Severity::Warning, SOURCE, msg)
+                .set_file(file)
+                .set_line(pos.line)
+                .set_col(pos.col)
+        };
+        let mut res: Vec<Diagnostic> = self
+            .specific()
+            .map(|u| {
+                let mut msg = format!("non-standard {} `{}`", u.kind, u.name);
+                if let Class::Specific(Some(hint)) = &u.class {
+                    msg.push_str(", ");
+                    msg.push_str(hint)
+                }
+                located(u.pos, msg)
+            })
+            .collect();
+        res.extend(
+            self.misuses
+                .iter()
+                .map(|misuse| located(misuse.pos(), misuse.to_string())),
+        );
+        if let Some((declared, pos)) = self.declared_logic {
+            let logic = self.logic();
+            if declared != logic && declared != "ALL" {
+                res.push(located(
+                    pos,
+                    format!("script sets logic `{}` but uses `{}`", declared, logic),
+                ))
+            }
+        }
+        res.sort_by_key(|d| (d.line, d.col));
+        res
+    }
+}
+
+/// Analyzes a script, fails on syntax errors.
+pub fn analyze(text: &str) -> Res<Analysis<'_>> {
+    let (commands, errors) = smt2::parse(text);
+    if let Some(error) = errors.first() {
+        bail!("syntax error at {}: {}", error.pos, error.msg)
+    }
+    let mut analyzer = Analyzer::default();
+    for command in &commands {
+        analyzer.command(command)
+    }
+    Ok(analyzer.finish())
+}
+
+/// Analysis state.
+#[derive(Default)]
+struct Analyzer<'a> {
+    uses: Vec<Use<'a>>,
+    features: Features,
+    declared_logic: Option<(&'a str, Pos)>,
+    misuses: Vec<Misuse<'a>>,
+    /// True once `(set-option :produce-models true)` is seen.
+    produce_models: bool,
+    /// True once a declaration or definition is seen.
+    declared: bool,
+    /// User-defined function symbols, constants, constructors and selectors included.
+    funs: BTreeSet<&'a str>,
+    /// Datatype constructors, also in `funs`.
+    ctors: BTreeSet<&'a str>,
+    /// User-defined sorts.
+    sorts: BTreeSet<&'a str>,
+    /// Local term variables and sort parameters, innermost last.
+    locals: Vec<&'a str>,
+    /// True if the script uses arithmetic.
+    arith: bool,
+}
+impl<'a> Analyzer<'a> {
+    /// Arithmetic without any sort information is integer arithmetic.
+    fn finish(mut self) -> Analysis<'a> {
+        if self.arith && !self.features.ints && !self.features.reals {
+            self.features.ints = true
+        }
+        Analysis {
+            uses: self.uses,
+            features: self.features,
+            declared_logic: self.declared_logic,
+            misuses: self.misuses,
+        }
+    }
+
+    fn push(&mut self, kind: Kind, name: &'a str, pos: Pos, class: Class) {
+        self.uses.push(Use {
+            kind,
+            name,
+            pos,
+            class,
+        })
+    }
+
+    /// Records that some theory is used.
+    fn theory(&mut self, theory: &str) {
+        match theory {
+            INTS => self.features.ints = true,
+            REALS => self.features.reals = true,
+            REALS_INTS => {
+                self.features.ints = true;
+                self.features.reals = true
+            }
+            ARRAYS => self.features.arrays = true,
+            BV => self.features.bv = true,
+            STRINGS => {
+                self.features.strings = true;
+                // `str.len` and friends are integer-valued.
+                self.features.ints = true
+            }
+            DATATYPES => self.features.datatypes = true,
+            // Arithmetic without sort information, see `finish`.
+            ARITH => self.arith = true,
+            _ => (),
+        }
+    }
+
+    fn command(&mut self, command: &Sexp<'a>) {
+        let elems = match command.list() {
+            Some(elems) => elems,
+            None => return,
+        };
+        let (head, args) = match elems.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        let name = match head.symbol() {
+            Some(name) => name,
+            None => return,
+        };
+        let class = if COMMANDS.contains(&name) {
+            Class::Standard("commands")
+        } else {
+            let hint = COMMAND_HINTS
+                .iter()
+                .find(|(cmd, _)| *cmd == name)
+                .map(|(_, hint)| hint.to_string());
+            Class::Specific(hint)
+        };
+        self.push(Kind::Command, name, head.pos(), class);
+
+        let is_declaration = name.starts_with("declare-") || name.starts_with("define-");
+        if is_declaration && !self.declared && self.declared_logic.is_none() {
+            self.misuses.push(Misuse::BeforeLogic {
+                command: name,
+                pos: head.pos(),
+            })
+        }
+        self.declared = self.declared || is_declaration;
+        if (name == "get-model" || name == "get-value") && !self.produce_models {
+            self.misuses.push(Misuse::NoModels {
+                command: name,
+                pos: head.pos(),
+            })
+        }
+
+        match (name, args) {
+            ("set-logic", [logic]) => {
+                self.declared_logic = logic.symbol().map(|l| (l, logic.pos()))
+            }
+            ("set-option", [option, value @ ..]) => {
+                if let (Sexp::Atom(token), [value]) = (option, value) {
+                    if token.text == ":produce-models" {
+                        self.produce_models = value.symbol() == Some("true")
+                    }
+                }
+                self.keyword(Kind::Option, option, OPTIONS)
+            }
+            ("get-info", [flag]) => self.keyword(Kind::Info, flag, INFO_FLAGS),
+            ("declare-const", [name, sort]) => {
+                self.funs.extend(name.symbol());
+                self.sort(sort)
+            }
+            // Non-standard, still defines a symbol.
+            ("define-const", [name, sort, value]) => {
+                self.funs.extend(name.symbol());
+                self.sort(sort);
+                self.term(value)
+            }
+            ("declare-fun", [name, params, sort]) => {
+                self.funs.extend(name.symbol());
+                let params = params.list().unwrap_or(&[]);
+                if !params.is_empty() {
+                    self.features.uf = true
+                }
+                for param in params {
+                    self.sort(param)
+                }
+                self.sort(sort)
+            }
+            ("define-fun" | "define-fun-rec", [name, params, sort, body]) => {
+                self.funs.extend(name.symbol());
+                let locals = self.locals.len();
+                self.sorted_vars(params);
+                self.sort(sort);
+                self.term(body);
+                self.locals.truncate(locals)
+            }
+            ("declare-sort", [name, ..]) => {
+                self.sorts.extend(name.symbol());
+                self.features.uf = true
+            }
+            ("define-sort", [name, params, sort]) => {
+                self.sorts.extend(name.symbol());
+                let locals = self.locals.len();
+                self.locals
+                    .extend(params.list().unwrap_or(&[]).iter().filter_map(Sexp::symbol));
+                self.sort(sort);
+                self.locals.truncate(locals)
+            }
+            ("declare-datatype", [name, decl]) => {
+                self.features.datatypes = true;
+                self.sorts.extend(name.symbol());
+                self.datatype(decl)
+            }
+            ("declare-datatypes", [sorts, decls]) => {
+                self.features.datatypes = true;
+                for sort in sorts.list().unwrap_or(&[]) {
+                    self.sorts.extend(sort.head())
+                }
+                for decl in decls.list().unwrap_or(&[]) {
+                    self.datatype(decl)
+                }
+            }
+            ("assert", [term]) => self.term(term),
+            ("check-sat-assuming" | "get-value", [terms]) => {
+                for term in terms.list().unwrap_or(&[]) {
+                    self.term(term)
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Classifies a keyword argument against a list of standard keywords.
+    fn keyword(&mut self, kind: Kind, keyword: &Sexp<'a>, standard: &[&str]) {
+        if let Sexp::Atom(token) = keyword {
+            if token.kind == TokenKind::Keyword {
+                let class = if standard.contains(&token.text) {
+                    Class::Standard("commands")
+                } else {
+                    Class::Specific(None)
+                };
+                self.push(kind, token.text, token.pos, class)
+            }
+        }
+    }
+
+    /// Datatype declaration `(par (<symbol>*) (<constructor>*))` or `(<constructor>*)`.
+    fn datatype(&mut self, decl: &Sexp<'a>) {
+        let locals = self.locals.len();
+        let ctors = match decl.list() {
+            Some([par, params, ctors]) if par.symbol() == Some("par") => {
+                self.locals
+                    .extend(params.list().unwrap_or(&[]).iter().filter_map(Sexp::symbol));
+                ctors.list().unwrap_or(&[])
+            }
+            Some(ctors) => ctors,
+            None => &[],
+        };
+        for ctor in ctors {
+            let (name, selectors) = match ctor {
+                Sexp::Atom(_) => (ctor, &[][..]),
+                Sexp::List { elems, .. } => match elems.split_first() {
+                    Some(split) => split,
+                    None => continue,
+                },
+            };
+            self.funs.extend(name.symbol());
+            self.ctors.extend(name.symbol());
+            for selector in selectors {
+                if let Some([name, sort]) = selector.list() {
+                    self.funs.extend(name.symbol());
+                    self.sort(sort)
+                }
+            }
+        }
+        self.locals.truncate(locals)
+    }
+
+    /// Sorted variables `((<symbol> <sort>)*)`, adds them to the locals.
+    fn sorted_vars(&mut self, vars: &Sexp<'a>) {
+        for var in vars.list().unwrap_or(&[]) {
+            if let Some([name, sort]) = var.list() {
+                self.sort(sort);
+                self.locals.extend(name.symbol())
+            }
+        }
+    }
+
+    fn sort(&mut self, sort: &Sexp<'a>) {
+        match sort {
+            Sexp::Atom(token) => {
+                let name = match token.symbol() {
+                    Some(name) => name,
+                    None => return,
+                };
+                if self.sorts.contains(name) || self.locals.contains(&name) {
+                    return;
+                }
+                let theory = match name {
+                    "Bool" => CORE,
+                    "Int" => INTS,
+                    "Real" => REALS,
+                    "String" | "RegLan" => STRINGS,
+                    _ => return self.push(Kind::Sort, name, token.pos, Class::Specific(None)),
+                };
+                self.theory(theory);
+                self.push(Kind::Sort, name, token.pos, Class::Standard(theory))
+            }
+            Sexp::List { elems, .. } => match elems.split_first() {
+                // `(_ BitVec 32)`.
+                Some((head, [index, ..])) if head.symbol() == Some("_") => {
+                    if let Some(name) = index.symbol() {
+                        if name == "BitVec" {
+                            self.theory(BV);
+                            self.push(Kind::Sort, name, index.pos(), Class::Standard(BV))
+                        } else {
+                            self.push(Kind::Sort, name, index.pos(), Class::Specific(None))
+                        }
+                    }
+                }
+                Some((head, args)) => {
+                    match head.symbol() {
+                        Some("Array") => {
+                            self.theory(ARRAYS);
+                            self.push(Kind::Sort, "Array", head.pos(), Class::Standard(ARRAYS))
+                        }
+                        _ => self.sort(head),
+                    }
+                    for arg in args {
+                        self.sort(arg)
+                    }
+                }
+                None => (),
+            },
+        }
+    }
+
+    /// True if a term is a numeric literal, possibly negated.
+    fn is_literal(term: &Sexp) -> bool {
+        match term {
+            Sexp::Atom(token) => matches!(token.kind, TokenKind::Numeral | TokenKind::Decimal),
+            Sexp::List { elems, .. } => {
+                matches!(&elems[..], [minus, arg] if minus.symbol() == Some("-") && Self::is_literal(arg))
+            }
+        }
+    }
+
+    /// Classifies a function symbol that is not user-defined.
+    fn fun(&mut self, name: &'a str, pos: Pos) {
+        if let Some((_, theory)) = FUNS.iter().find(|(fun, _)| *fun == name) {
+            self.theory(theory);
+            return self.push(Kind::Symbol, name, pos, Class::Standard(theory));
+        }
+        let hint = match name.strip_prefix("is-") {
+            Some(ctor) if self.ctors.contains(ctor) => {
+                Some(format!("the standard tester is `(_ is {})`", ctor))
+            }
+            _ => FUN_HINTS
+                .iter()
+                .find(|(fun, _)| *fun == name)
+                .map(|(_, hint)| hint.to_string()),
+        };
+        self.push(Kind::Symbol, name, pos, Class::Specific(hint))
+    }
+
+    fn term(&mut self, term: &Sexp<'a>) {
+        let elems = match term {
+            Sexp::Atom(token) => {
+                match token.kind {
+                    TokenKind::Symbol => {
+                        let name = token.symbol().expect("symbol token");
+                        if !(self.locals.contains(&name) || self.funs.contains(name)) {
+                            self.fun(name, token.pos)
+                        }
+                    }
+                    TokenKind::Numeral => self.arith = true,
+                    TokenKind::Decimal => self.features.reals = true,
+                    TokenKind::Hexadecimal | TokenKind::Binary => self.features.bv = true,
+                    TokenKind::String => self.theory(STRINGS),
+                    _ => (),
+                }
+                return;
+            }
+            Sexp::List { elems, .. } => elems,
+        };
+        let (head, args) = match elems.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        let locals = self.locals.len();
+
+        match head.symbol() {
+            Some("let") => {
+                let bindings = args.first().and_then(Sexp::list).unwrap_or(&[]);
+                for binding in bindings {
+                    if let Some([_, def]) = binding.list() {
+                        self.term(def)
+                    }
+                }
+                for binding in bindings {
+                    self.locals.extend(binding.head())
+                }
+                for arg in args.iter().skip(1) {
+                    self.term(arg)
+                }
+            }
+            Some(binder @ ("forall" | "exists")) => {
+                self.features.quantifiers = true;
+                self.push(
+                    Kind::Symbol,
+                    binder,
+                    head.pos(),
+                    Class::Standard("quantifiers"),
+                );
+                if let Some(vars) = args.first() {
+                    self.sorted_vars(vars)
+                }
+                for arg in args.iter().skip(1) {
+                    self.term(arg)
+                }
+            }
+            Some("match") => {
+                self.features.datatypes = true;
+                if let Some(matched) = args.first() {
+                    self.term(matched)
+                }
+                for case in args.get(1).and_then(Sexp::list).unwrap_or(&[]) {
+                    if let Some([pattern, term]) = case.list() {
+                        let vars: Vec<&Sexp<'a>> = match pattern.list() {
+                            Some(elems) => elems.iter().skip(1).collect(),
+                            None => vec![pattern],
+                        };
+                        for var in vars {
+                            if let Some(name) = var.symbol() {
+                                if !self.funs.contains(name) {
+                                    self.locals.push(name)
+                                }
+                            }
+                        }
+                        self.term(term);
+                        self.locals.truncate(locals)
+                    }
+                }
+            }
+            // Attributes are standard, only the term matters.
+            Some("!") => {
+                if let Some(term) = args.first() {
+                    self.term(term)
+                }
+            }
+            Some("_") => self.indexed(args),
+            Some("as") => {
+                match args.first() {
+                    Some(Sexp::Atom(token)) if token.symbol() == Some("const") => {
+                        self.features.arrays = true;
+                        self.push(
+                            Kind::Symbol,
+                            "const",
+                            token.pos,
+                            Class::Specific(Some(
+                                "constant arrays are not in the `ArraysEx` theory".into(),
+                            )),
+                        )
+                    }
+                    Some(term) => self.term(term),
+                    None => (),
+                }
+                if let Some(sort) = args.get(1) {
+                    self.sort(sort)
+                }
+            }
+            _ => {
+                if let Some(op @ ("*" | "/" | "div" | "mod")) = head.symbol() {
+                    // Linear if at most one factor, or the divisor, is not a literal.
+                    let nonlinear = if op == "*" {
+                        args.iter().filter(|arg| !Self::is_literal(arg)).count() > 1
+                    } else {
+                        args.iter().skip(1).any(|arg| !Self::is_literal(arg))
+                    };
+                    self.features.nonlinear = self.features.nonlinear || nonlinear
+                }
+                self.term(head);
+                for arg in args {
+                    self.term(arg)
+                }
+            }
+        }
+
+        self.locals.truncate(locals)
+    }
+
+    /// Indexed identifier `(_ <symbol> <index>+)`.
+    fn indexed(&mut self, args: &[Sexp<'a>]) {
+        let (name, pos) = match args.first() {
+            Some(Sexp::Atom(token)) => match token.symbol() {
+                Some(name) => (name, token.pos),
+                None => return,
+            },
+            _ => return,
+        };
+        // Bit-vector literal `(_ bv5 32)`.
+        if name.starts_with("bv") && name[2..].chars().all(|c| c.is_ascii_digit()) {
+            self.theory(BV);
+            return;
+        }
+        let class = match INDEXED_FUNS.iter().find(|(fun, _)| *fun == name) {
+            Some((_, theory)) => {
+                self.theory(theory);
+                Class::Standard(theory)
+            }
+            None => Class::Specific(None),
+        };
+        self.push(Kind::Symbol, name, pos, class)
+    }
+}
+
+/// Checks the portability of the `.smt2` snippets of a book.
+pub struct Portability<'s> {
+    src: &'s str,
+}
+impl<'s> Portability<'s> {
+    /// Constructor.
+    pub fn new(src: &'s str) -> Self {
+        Self { src }
+    }
+    /// Source directory accessor.
+    pub fn src(&self) -> &'s str {
+        self.src
+    }
+
+    /// Prints the logic of each snippet and reports non-standard constructs.
+    pub fn run(&self) -> Res<()> {
+        let snippets = test::snippets(self.src, "smt2")?;
+        log::info!("checking {} SMT-LIB 2 snippet(s)", snippets.len());
+
+        let mut report = Report::new();
+        for snippet in &snippets {
+            let text = load_file(snippet)?;
+            let analysis =
+                analyze(&text).chain_err(|| format!("while analyzing `{}`", snippet.display()))?;
+            let specific = analysis.specific().count();
+            println!(
+                "{}: {}, {} non-standard construct(s), {} misplaced command(s)",
+                snippet.display(),
+                analysis.logic(),
+                specific,
+                analysis.misuses.len()
+            );
+            for diagnostic in analysis.diagnostics(snippet) {
+                report.push(diagnostic)
+            }
+        }
+
+        if !report.diagnostics.is_empty() {
+            eprintln!("|===| Report:");
+            report.pretty_eprint("| ");
+            eprintln!("|===|");
+        }
+        log::info!(
+            "done checking portability, {} warning(s)",
+            report.count(Severity::Warning)
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let text = "\
+(set-logic QF_LIA)
+(set-option :produce-models true)
+(set-option :model.completion true)
+(declare-datatype Color ((red) (green)))
+(declare-const c Color)
+(declare-const x Int)
+(define-const y Int 3)
+(assert (implies (is-red c) (> (* 2 x) y)))
+(check-sat)
+";
+        let analysis = analyze(text).unwrap();
+        let specific: Vec<(Kind, &str)> = analysis.specific().map(|u| (u.kind, u.name)).collect();
+        assert_eq!(
+            specific,
+            vec![
+                (Kind::Option, ":model.completion"),
+                (Kind::Command, "define-const"),
+                (Kind::Symbol, "implies"),
+                (Kind::Symbol, "is-red"),
+            ]
+        );
+        assert_eq!(analysis.logic(), "QF_DTLIA");
+
+        let diagnostics = analysis.diagnostics(Path::new("test.smt2"));
+        assert_eq!(diagnostics.len(), 5);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "warning[portability] test.smt2:1:12: script sets logic `QF_LIA` but uses `QF_DTLIA`"
+        );
+        assert_eq!(
+            diagnostics[4].to_string(),
+            "warning[portability] test.smt2:8:19: non-standard symbol `is-red`, \
+            the standard tester is `(_ is red)`"
+        );
+    }
+
+    #[test]
+    fn logics() {
+        let logic = |text: &str| analyze(text).unwrap().logic();
+        assert_eq!(logic("(declare-const b Bool) (assert b)"), "QF_UF");
+        assert_eq!(
+            logic("(declare-fun f (Int) Int) (assert (= (f 0) 1))"),
+            "QF_UFLIA"
+        );
+        assert_eq!(
+            logic("(declare-const x Real) (assert (> (* x x) 0.5))"),
+            "QF_NRA"
+        );
+        assert_eq!(
+            logic("(assert (forall ((x Int)) (>= (* 2 x) (- 3))))"),
+            "LIA"
+        );
+        assert_eq!(
+            logic("(declare-const a (Array Int (_ BitVec 8))) (assert (= (select a 0) #x00))"),
+            "QF_ABVLIA"
+        );
+    }
+
+    #[test]
+    fn misuses() {
+        let text = "\
+(declare-const x Int)
+(set-logic QF_LIA)
+(declare-const y Int)
+(check-sat)
+(get-value (x))
+(set-option :produce-models true)
+(get-model)
+";
+        let analysis = analyze(text).unwrap();
+        let misuses: Vec<String> = analysis
+            .misuses
+            .iter()
+            .map(|misuse| format!("{}: {}", misuse.pos().line, misuse))
+            .collect();
+        assert_eq!(
+            misuses,
+            vec![
+                "1: `declare-const` before `set-logic`",
+                "5: `get-value` requires `(set-option :produce-models true)` first",
+            ]
+        );
+        assert_eq!(analysis.diagnostics(Path::new("test.smt2")).len(), 2);
+    }
+
+    #[test]
+    fn book_snippets() {
+        for snippet in test::snippets("../src", "smt2").unwrap() {
+            let text = load_file(&snippet).unwrap();
+            let analysis = analyze(&text).unwrap();
+            assert_eq!(
+                analysis.specific().count(),
+                0,
+                "`{}` is not portable",
+                snippet.display()
+            );
+            assert_eq!(analysis.logic(), "QF_LIA", "`{}`", snippet.display());
+
+            // Snippets never set the logic, nor `:produce-models` before getting models.
+            let (before_logic, no_models): (Vec<&Misuse>, Vec<&Misuse>) = analysis
+                .misuses
+                .iter()
+                .partition(|misuse| matches!(misuse, Misuse::BeforeLogic { .. }));
+            assert_eq!(before_logic.len(), 1, "`{}`", snippet.display());
+            let gets = text.matches("(get-model)").count() + text.matches("(get-value").count();
+            assert_eq!(no_models.len(), gets, "`{}`", snippet.display());
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! `mdbook` preprocessor.
+//!
+//! Registered in `book.toml` under `[preprocessor.manage]`, runs before mdbook's `links`
+//! preprocessor so that it sees the `{{ #include ... }}` directives. It validates includes and
+//! their anchors, and expands the book's custom directives:
+//!
+//! - `{{ #run code/x.smt2 }}` runs a snippet with the tool corresponding to its extension (see
+//!   [`test::snippet_output`]) and expands to a `text` block containing its output. If the tool
+//!   is unavailable or deactivated, the cached output `code/x.smt2.out` is used instead.
+//!
+//!   mdbook runs preprocessors once per renderer. Outputs are saved in the build directory,
+//!   in [`RUN_CACHE_DIR`], so that a build runs each snippet once: the first renderer of the build
+//!   clears the directory, the following ones reuse its outputs.
+//! - `{{ #timing code/x.mkn.out <candidate> }}` expands to the SVG timing diagram (see
+//!   [`crate::timing`]) of the counterexample for `<candidate>`, or of the first counterexample if
+//!   no candidate is given. `{{ #timing code/x.mkn <script> }}` simulates the system instead,
+//!   following the optional script.
+
+use std::{cell::RefCell, collections::BTreeMap, ops::Range};
+
+use mdbook::{
+    book::{Book, BookItem},
+    preprocess::{CmdPreprocessor, Preprocessor, PreprocessorContext},
+};
+
+prelude!();
+
+/// Name of the preprocessor.
+pub const NAME: &str = "manage";
+
+/// Directory of the outputs of `{{ #run }}` snippets during a build, in the build directory.
+pub const RUN_CACHE_DIR: &str = ".manage_run";
+
+/// A `{{ #<name> <args> }}` directive in a markdown line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive<'a> {
+    /// Byte range of the whole directive in the line.
+    pub range: Range<usize>,
+    /// Name of the directive, without the `#`.
+    pub name: &'a str,
+    /// Arguments of the directive, trimmed.
+    pub args: &'a str,
+}
+impl<'a> Directive<'a> {
+    /// Directives appearing in a line, ignoring escaped ones (`\{{ ... }}`).
+    pub fn all_in(line: &'a str) -> Vec<Self> {
+        let mut res = vec![];
+        let mut offset = 0;
+        while let Some(start) = line[offset..].find("{{").map(|idx| idx + offset) {
+            let end = match line[start..].find("}}") {
+                Some(end) => start + end + "}}".len(),
+                None => break,
+            };
+            offset = end;
+            if line[..start].ends_with('\\') {
+                continue;
+            }
+            let inner = line[start + "{{".len()..end - "}}".len()].trim();
+            let inner = match inner.strip_prefix('#') {
+                Some(inner) => inner,
+                None => continue,
+            };
+            let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+            res.push(Self {
+                range: start..end,
+                name: &inner[..name_end],
+                args: inner[name_end..].trim(),
+            })
+        }
+        res
+    }
+}
+
+/// The book's preprocessor.
+pub struct Manage<'s> {
+    /// Configuration for running snippets.
+    conf: Conf<'s>,
+    /// Outputs of the snippets run so far, by snippet path.
+    outputs: RefCell<BTreeMap<PathBuf, String>>,
+    /// Directory where outputs are shared with the other renderers of the build, if any.
+    cache_dir: Option<PathBuf>,
+    /// If false, `{{ #timing }}` produces the trace as text instead of an SVG diagram.
+    timing_svg: bool,
+}
+impl<'s> Manage<'s> {
+    /// Constructor.
+    pub fn new(conf: Conf<'s>) -> Self {
+        Self {
+            conf,
+            outputs: RefCell::new(BTreeMap::new()),
+            cache_dir: None,
+            timing_svg: true,
+        }
+    }
+
+    /// Sets whether `{{ #timing }}` produces SVG diagrams, for exporters that cannot show them.
+    pub fn set_timing_svg(mut self, timing_svg: bool) -> Self {
+        self.timing_svg = timing_svg;
+        self
+    }
+
+    /// Sets the directory where snippet outputs are shared between renderers.
+    pub fn set_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Handles a single directive, returns its expansion, if any.
+    ///
+    /// - `md_path` is the path of the chapter the directive is from.
+    fn directive(&self, md_path: &Path, line: &str, directive: &Directive) -> Res<Option<String>> {
+        match directive.name {
+            // Left for mdbook to expand, we only check the file and the anchor exist.
+            "include" => {
+                let include = Include::parse(md_path, &line[directive.range.clone()])?;
+                let _ = include.lines()?;
+                Ok(None)
+            }
+            "run" => self.run_snippet(md_path, directive.args).map(Some),
+            "timing" => self.timing(md_path, directive.args).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Expands a `{{ #run <snippet> }}` directive.
+    fn run_snippet(&self, md_path: &Path, snippet: &str) -> Res<String> {
+        if snippet.is_empty() {
+            bail!("expected a snippet path")
+        }
+        let snippet_path = md_path
+            .parent()
+            .ok_or_else(|| format!("illegal markdown path `{}`", md_path.display()))?
+            .join(snippet);
+        if !snippet_path.is_file() {
+            bail!("snippet `{}` does not exist", snippet_path.display())
+        }
+
+        let output = self.snippet_output(&snippet_path)?;
+
+        let mut res = String::with_capacity(output.len() + 20);
+        res.push_str("```text\n");
+        res.push_str(&output);
+        if !output.ends_with('\n') {
+            res.push('\n')
+        }
+        res.push_str("```");
+        Ok(res)
+    }
+
+    /// Output of a snippet, runs it only if no renderer of the build has already.
+    fn snippet_output(&self, snippet_path: &Path) -> Res<String> {
+        if let Some(output) = self.outputs.borrow().get(snippet_path) {
+            return Ok(output.clone());
+        }
+        let cache_path = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| {
+                let mut key = snippet_path.to_string_lossy().into_owned().into_bytes();
+                key.extend(load_file(snippet_path)?.into_bytes());
+                Ok::<_, err::Error>(dir.join(format!("{:016x}", crate::assets::hash(&key))))
+            })
+            .transpose()?;
+        let cached = cache_path
+            .as_ref()
+            .filter(|path| path.is_file())
+            .map(load_file)
+            .transpose()?;
+
+        let output = match cached {
+            Some(output) => {
+                log::debug!("reusing the output of `{}`", snippet_path.display());
+                output
+            }
+            None => {
+                let output = self.run_output(snippet_path)?;
+                if let Some(cache_path) = cache_path {
+                    fs::write(&cache_path, &output)
+                        .chain_err(|| format!("while writing `{}`", cache_path.display()))?
+                }
+                output
+            }
+        };
+        self.outputs
+            .borrow_mut()
+            .insert(snippet_path.into(), output.clone());
+        Ok(output)
+    }
+
+    /// Runs a snippet, or retrieves its cached output if its tool is unavailable.
+    fn run_output(&self, snippet_path: &Path) -> Res<String> {
+        let output = match test::snippet_output(&self.conf, snippet_path) {
+            Ok(Some(output)) => output,
+            Ok(None) => self.cached_output(snippet_path)?,
+            Err(err::Error(err::ErrorKind::ToolUnavailable(tool), _)) => {
+                log::warn!(
+                    "tool `{}` is not available, using cached output for `{}`",
+                    tool,
+                    snippet_path.display(),
+                );
+                self.cached_output(snippet_path)?
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(output)
+    }
+
+    /// Expands a `{{ #timing <trace> <arg> }}` directive.
+    fn timing(&self, md_path: &Path, args: &str) -> Res<String> {
+        let (trace, arg) = match args.split_once(char::is_whitespace) {
+            Some((trace, arg)) => (trace, Some(arg.trim())),
+            None => (args, None),
+        };
+        if trace.is_empty() {
+            bail!("expected a trace path")
+        }
+        let dir = md_path
+            .parent()
+            .ok_or_else(|| format!("illegal markdown path `{}`", md_path.display()))?;
+        let trace_path = dir.join(trace);
+        let (candidate, script) = match trace_path.extension() {
+            Some(ext) if ext == "mkn" => (None, arg.map(|script| dir.join(script))),
+            _ => (arg, None),
+        };
+        let (trace, relative) = crate::timing::load(&trace_path, candidate, script.as_deref())?;
+        if self.timing_svg {
+            Ok(crate::timing::render(&trace, relative))
+        } else {
+            Ok(format!("```text\n{}\n```", trace))
+        }
+    }
+
+    /// Cached output of a snippet, *i.e.* the content of its `.out` file.
+    fn cached_output(&self, snippet_path: &Path) -> Res<String> {
+        let mut out_path = snippet_path.as_os_str().to_owned();
+        out_path.push(".out");
+        let out_path = PathBuf::from(out_path);
+        if !out_path.is_file() {
+            bail!(
+                "cannot run snippet `{}` and there is no cached output `{}`",
+                snippet_path.display(),
+                out_path.display(),
+            )
+        }
+        load_file(&out_path)
+    }
+
+    /// Preprocesses the content of a chapter.
+    ///
+    /// Returns the new content and the errors encountered.
+    pub fn chapter(&self, md_path: impl AsRef<Path>, content: &str) -> (String, Vec<err::Error>) {
+        let md_path = md_path.as_ref();
+        let mut res = String::with_capacity(content.len());
+        let mut errors = vec![];
+
+        for (idx, line) in content.lines().enumerate() {
+            let mut line = line.to_string();
+            // Back to front so that ranges stay valid.
+            for directive in Directive::all_in(&line.clone()).into_iter().rev() {
+                match self.directive(md_path, &line, &directive) {
+                    Ok(None) => (),
+                    Ok(Some(expansion)) => line.replace_range(directive.range, &expansion),
+                    Err(e) => errors.push(e.chain_err(|| {
+                        format!(
+                            "on `{{{{ #{} }}}}` directive, {}:{}",
+                            directive.name,
+                            md_path.display(),
+                            idx + 1,
+                        )
+                    })),
+                }
+            }
+            res.push_str(&line);
+            res.push('\n');
+        }
+
+        (res, errors)
+    }
+
+    /// Expands the directives of a chapter, fails on the first error.
+    ///
+    /// Used by the exporters, which work on the book's sources rather than on a preprocessed book.
+    pub fn expand(&self, md_path: impl AsRef<Path>, content: &str) -> Res<String> {
+        let (content, errors) = self.chapter(md_path, content);
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(content),
+        }
+    }
+
+    /// Preprocesses the chapters of a book, `src_dir` is the book's source directory.
+    ///
+    /// Returns the errors encountered, with the path of the chapter they come from.
+    pub fn book(&self, src_dir: &Path, book: &mut Book) -> Vec<(PathBuf, err::Error)> {
+        let mut errors = vec![];
+        book.for_each_mut(|item| {
+            if let BookItem::Chapter(chapter) = item {
+                if let Some(path) = chapter.path.as_ref() {
+                    log::debug!("preprocessing `{}`", path.display());
+                    let md_path = src_dir.join(path);
+                    let (content, errs) = self.chapter(&md_path, &chapter.content);
+                    chapter.content = content;
+                    errors.extend(errs.into_iter().map(|e| (md_path.clone(), e)));
+                }
+            }
+        });
+        errors
+    }
+
+    /// Reads a book from `stdin`, preprocesses it, and writes it on `stdout`.
+    pub fn run_cmd(&self) -> Res<()> {
+        let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())
+            .map_err(|e| format!("failed to parse mdbook input: {}", e))?;
+        if ctx.mdbook_version != mdbook::MDBOOK_VERSION {
+            log::warn!(
+                "preprocessor built against mdbook v{}, called from mdbook v{}",
+                mdbook::MDBOOK_VERSION,
+                ctx.mdbook_version,
+            )
+        }
+        let book = self.run(&ctx, book).map_err(|e| format!("{:?}", e))?;
+        serde_json::to_writer(io::stdout(), &book)
+            .chain_err(|| "while writing preprocessed book on stdout")?;
+        Ok(())
+    }
+}
+
+impl<'s> Preprocessor for Manage<'s> {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> mdbook::errors::Result<Book> {
+        let src_dir = ctx.root.join(&ctx.config.book.src);
+        let cache_dir = ctx
+            .root
+            .join(&ctx.config.build.build_dir)
+            .join(RUN_CACHE_DIR);
+        // Same order as mdbook, which runs the renderers of the `output` table, or just `html`.
+        let first_renderer = ctx
+            .config
+            .get("output")
+            .and_then(|output| output.as_table())
+            .and_then(|output| output.keys().next().cloned())
+            .unwrap_or_else(|| "html".into());
+        if ctx.renderer == first_renderer {
+            let _ = fs::remove_dir_all(&cache_dir);
+        }
+        fs::create_dir_all(&cache_dir)
+            .map_err(|e| mdbook::errors::Error::msg(format!("{}: {}", cache_dir.display(), e)))?;
+        let manage = Manage::new(self.conf.clone()).set_cache_dir(cache_dir);
+        let errors = manage.book(&src_dir, &mut book);
+
+        if errors.is_empty() {
+            Ok(book)
+        } else {
+            let mut msg = format!("{} error(s) in preprocessor `{}`", errors.len(), NAME);
+            for (_, e) in errors {
+                for (idx, e) in e.iter().enumerate() {
+                    msg.push_str(if idx == 0 { "\n- " } else { "\n  " });
+                    msg.push_str(&e.to_string());
+                }
+            }
+            Err(mdbook::errors::Error::msg(msg))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_directives() {
+        let line = "a {{ #include code/a.smt2:all }} b \\{{#include nope}} {{#run code/b.smt2}}";
+        let directives = Directive::all_in(line);
+        assert_eq!(directives.len(), 2);
+        assert_eq!(directives[0].name, "include");
+        assert_eq!(directives[0].args, "code/a.smt2:all");
+        assert_eq!(
+            &line[directives[0].range.clone()],
+            "{{ #include code/a.smt2:all }}"
+        );
+        assert_eq!(directives[1].name, "run");
+        assert_eq!(directives[1].args, "code/b.smt2");
+    }
+
+    #[test]
+    fn run_cached() {
+        let md_path = "../src/bmc/readme.md";
+        let expected = format!(
+            "```text\n{}```\n",
+            load_file("../src/bmc/code/sw_init_1.smt2.out").unwrap()
+        );
+        // Tools deactivated, uses the cached output.
+        let conf = Conf::new()
+            .set_smt2(false, "z3")
+            .set_mikino(false, "mikino");
+        let (res, errors) = Manage::new(conf).chapter(md_path, "{{ #run code/sw_init_1.smt2 }}");
+        assert!(errors.is_empty());
+        assert_eq!(res, expected);
+        // Tool unavailable, same.
+        let conf = Conf::new()
+            .set_smt2(true, "manage_no_such_z3")
+            .set_mikino(true, "mikino");
+        let manage = Manage::new(conf);
+        let (res, errors) = manage.chapter(md_path, "{{ #run code/sw_init_1.smt2 }}");
+        assert!(errors.is_empty());
+        assert_eq!(res, expected);
+        // No cached output.
+        let (_, errors) = manage.chapter(md_path, "{{ #run code/no_such.smt2 }}");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn run_once_per_build() {
+        let md_path = "../src/bmc/readme.md";
+        let cache_dir = std::env::temp_dir().join(format!("manage_api_run_{}", std::process::id()));
+        fs::create_dir_all(&cache_dir).unwrap();
+        let conf = Conf::new()
+            .set_smt2(false, "z3")
+            .set_mikino(false, "mikino");
+        let (_, errors) = Manage::new(conf.clone())
+            .set_cache_dir(&cache_dir)
+            .chapter(md_path, "{{ #run code/sw_init_1.smt2 }}");
+        assert!(errors.is_empty());
+        let entries: Vec<PathBuf> = fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(entries.len(), 1);
+
+        // Later renderers of the build reuse the output.
+        fs::write(&entries[0], "from a previous renderer\n").unwrap();
+        let (res, errors) = Manage::new(conf)
+            .set_cache_dir(&cache_dir)
+            .chapter(md_path, "{{ #run code/sw_init_1.smt2 }}");
+        assert!(errors.is_empty());
+        assert_eq!(res, "```text\nfrom a previous renderer\n```\n");
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn timing() {
+        let md_path = "../src/mikino_bmc/readme.md";
+        let (res, errors) = Manage::new(Conf::default())
+            .chapter(md_path, "{{ #timing code/sw_1.mkn.out cnt ≤ 4 }}");
+        assert!(errors.is_empty());
+        assert!(res.starts_with("<svg "));
+        assert!(res.contains(">5</text>"));
+        let (_, errors) =
+            Manage::new(Conf::default()).chapter(md_path, "{{ #timing code/sw_1.mkn.out nope }}");
+        assert_eq!(errors.len(), 1);
+        let res = Manage::new(Conf::default())
+            .set_timing_svg(false)
+            .expand(md_path, "{{ #timing code/sw_1.mkn.out cnt ≤ 4 }}")
+            .unwrap();
+        assert!(res.starts_with("```text\n|=| Step 0\n"));
+        assert!(res.ends_with("|=|\n```\n"));
+    }
+
+    #[test]
+    fn exporters() {
+        let conf = Conf::new()
+            .set_smt2(false, "z3")
+            .set_mikino(false, "mikino");
+        let md = Vanilla::inline_md(&conf, "../src/mikino_bmc/readme.md").unwrap();
+        assert!(!md.contains("{{ #timing"));
+        assert!(md.contains("<svg "));
+    }
+
+    #[test]
+    fn check_includes() {
+        let md_path = "../src/bmc/readme.md";
+        let content = "```text\n{{ #include code/sw_init_1.smt2:init_def }}\n```\n";
+        let (res, errors) = Manage::new(Conf::default()).chapter(md_path, content);
+        assert!(errors.is_empty());
+        assert_eq!(res, content);
+
+        let content = "{{ #include code/sw_init_1.smt2:no_such_anchor }}\n\
+                       {{ #include code/no_such_file.smt2 }}\n";
+        let (_, errors) = Manage::new(Conf::default()).chapter(md_path, content);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn book_includes() {
+        let book = mdbook::MDBook::load("..").expect("failed to load book");
+        let src_dir = book.root.join(&book.config.book.src);
+        for item in book.iter() {
+            if let BookItem::Chapter(chapter) = item {
+                if let Some(path) = chapter.path.as_ref() {
+                    let (_, errors) =
+                        Manage::new(Conf::default()).chapter(src_dir.join(path), &chapter.content);
+                    for e in &errors {
+                        e.pretty_eprint("| ")
+                    }
+                    assert!(errors.is_empty(), "errors in `{}`", path.display());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! k-induction on `.mkn` systems, with the reference engine.
+//!
+//! Reports the minimal `k` at which each candidate is k-inductive, see
+//! [`crate::engine::Engine::k_induction`]. The output only has verdicts, `k`-s and depths, not
+//! counterexamples, so that it does not depend on the models Z3 picks and can be checked: a
+//! `.mkn` snippet with a `// CMD: manage prove <file>` first line (`--max <k>` is also accepted)
+//! is tested against its output file like mikino snippets are.
+
+use crate::{engine::Engine, mkn::Mkn};
+
+prelude!();
+
+/// Runs k-induction on a system.
+pub struct Prove<'s> {
+    source: &'s str,
+    max: usize,
+    z3_cmd: &'s str,
+}
+impl<'s> Prove<'s> {
+    /// Constructor, the maximum `k` is `5`.
+    pub fn new(source: &'s str) -> Self {
+        Self {
+            source,
+            max: 5,
+            z3_cmd: "z3",
+        }
+    }
+
+    /// Sets the maximum `k`.
+    pub fn set_max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+    /// Sets the Z3 command.
+    pub fn set_z3_cmd(mut self, z3_cmd: &'s str) -> Self {
+        self.z3_cmd = z3_cmd;
+        self
+    }
+
+    /// System source.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// Runs k-induction and produces the verdicts, one line per candidate in declaration order.
+    ///
+    /// Fails with [`err::ErrorKind::ToolUnavailable`] if Z3 cannot be found.
+    pub fn output(&self) -> Res<String> {
+        let mkn = Mkn::load(self.source)?;
+        let system = mkn.to_system();
+        let res = match Engine::new(self.z3_cmd, &system).k_induction(self.max) {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(tool), _)) => {
+                bail!(err::ErrorKind::ToolUnavailable(tool))
+            }
+            res => res.chain_err(|| format!("while running k-induction on `{}`", self.source))?,
+        };
+
+        let mut output = format!("|===| k-induction, up to k = {}\n", self.max);
+        for (name, _) in &mkn.candidates {
+            let verdict = if let Some((_, k)) = res.proved.iter().find(|(cand, _)| cand == name) {
+                format!("is {}-inductive", k)
+            } else if let Some(cex) = res.falsified.iter().find(|cex| &cex.candidate == name) {
+                format!(
+                    "is falsified at depth {}",
+                    cex.trace.depth().unwrap_or_default()
+                )
+            } else {
+                format!("is not k-inductive for any k ≤ {}", self.max)
+            };
+            output.push_str(&format!("| `{}` {}\n", name, verdict))
+        }
+        output.push_str(&format!(
+            "|===| {} proved, {} falsified, {} unknown\n",
+            res.proved.len(),
+            res.falsified.len(),
+            res.unknown.len()
+        ));
+        Ok(output)
+    }
+
+    /// Prints the verdicts.
+    pub fn run(&self) -> Res<()> {
+        print!("{}", self.output()?);
+        Ok(())
+    }
+}
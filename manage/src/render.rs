@@ -0,0 +1,131 @@
+//! `mdbook` renderer for vanilla markdown.
+//!
+//! Registered in `book.toml` under `[output.vanilla]`. Unlike the `vanilla` subcommand, it works on
+//! the book as preprocessed by `mdbook`, so includes and the [`crate::preprocess`] directives are
+//! already expanded. Setting `prefix = true` in `[output.vanilla]` prefixes file names with the
+//! chapter's position in the summary.
+
+use std::collections::BTreeMap;
+
+use mdbook::{
+    book::BookItem,
+    renderer::{RenderContext, Renderer},
+};
+
+prelude!();
+
+/// Name of the renderer.
+pub const NAME: &str = "vanilla";
+
+/// The vanilla markdown renderer.
+pub struct Render;
+impl Render {
+    /// Generates vanilla markdown for a book rendered by `mdbook`.
+    pub fn vanilla(&self, ctx: &RenderContext) -> Res<()> {
+        let target = ctx.destination.to_str().ok_or_else(|| {
+            format!(
+                "illegal non-UTF-8 destination `{}`",
+                ctx.destination.display()
+            )
+        })?;
+        let prefix = match ctx.config.get(&format!("output.{}.prefix", NAME)) {
+            None => false,
+            Some(prefix) => prefix
+                .as_bool()
+                .ok_or_else(|| format!("expected boolean for `output.{}.prefix`", NAME))?,
+        };
+        let vanilla = Vanilla::new(Conf::default(), target)
+            .set_prefix(prefix)
+            .set_preprocessed(true)
+            .set_src(ctx.root.join(&ctx.config.book.src));
+
+        let mut top_level = vec![];
+        let mut contents = BTreeMap::new();
+        for item in &ctx.book.sections {
+            if let BookItem::Chapter(chapter) = item {
+                let path = match chapter.path.as_ref() {
+                    Some(path) => path.to_string_lossy(),
+                    None => continue,
+                };
+                if !TopLevelMd::is_top_level(&chapter.name, &path) {
+                    continue;
+                }
+                contents.insert(path.to_string(), chapter.content.clone());
+                top_level.push(TopLevelMd::new(&chapter.name, path))
+            }
+        }
+
+        vanilla.write_chapters(top_level, |file| {
+            contents
+                .get(file.path())
+                .cloned()
+                .ok_or_else(|| format!("[internal] no content for `{}`", file.path()).into())
+        })
+    }
+
+    /// Reads a render context from `stdin` and renders it.
+    pub fn run_cmd(&self) -> Res<()> {
+        let ctx = RenderContext::from_json(io::stdin())
+            .map_err(|e| format!("failed to parse mdbook input: {}", e))?;
+        if ctx.version != mdbook::MDBOOK_VERSION {
+            log::warn!(
+                "renderer built against mdbook v{}, called from mdbook v{}",
+                mdbook::MDBOOK_VERSION,
+                ctx.version,
+            )
+        }
+        self.vanilla(&ctx)
+    }
+}
+
+impl Renderer for Render {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn render(&self, ctx: &RenderContext) -> mdbook::errors::Result<()> {
+        self.vanilla(ctx).map_err(|e| {
+            let msg: Vec<String> = e.iter().map(|e| e.to_string()).collect();
+            mdbook::errors::Error::msg(msg.join("\n  "))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_book() {
+        let book = mdbook::MDBook::load("..").expect("failed to load book");
+        let target = std::env::temp_dir().join(format!("manage_api_render_{}", std::process::id()));
+        let ctx = RenderContext::new(&book.root, book.book.clone(), book.config.clone(), &target);
+        Render.vanilla(&ctx).unwrap();
+
+        let manifest = crate::Manifest::load(&target).unwrap();
+        let files: Vec<String> = manifest.entries.into_iter().map(|e| e.file).collect();
+        assert_eq!(files.len(), 11);
+        assert_eq!(files[0], "preface.md");
+        assert!(!files.iter().any(|file| file.contains("introduction")));
+        assert!(target.join("unrolling_and_bmc.md").is_file());
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn preprocessed_as_is() {
+        let target = std::env::temp_dir().join(format!("manage_api_as_is_{}", std::process::id()));
+        let target = target.to_str().unwrap();
+        let file = TopLevelMd::new("Chapter", "chapter/readme.md");
+        // Directives that `mdbook` left as is, *e.g.* escaped ones, must not be expanded again.
+        let content = "```text\n{{ #include missing.rs }}\n{{ #run missing.mkn }}\n```\n";
+
+        let mut assets = Assets::new(target);
+        let vanilla = Vanilla::new(Conf::default(), target).set_src("..");
+        assert!(vanilla.work_one(&file, content, &mut assets).is_err());
+        let vanilla = vanilla.set_preprocessed(true);
+        assert_eq!(
+            vanilla.work_one(&file, content, &mut assets).unwrap(),
+            content
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Replays mikino counterexamples against a Rust implementation of the system.
+//!
+//! The Rust file must define a `State` struct whose fields are named after the state variables,
+//! with `State::init(..) -> State` and `State::step(&mut self, ..)`, like `src/trans/code/sw_1.rs`.
+//! Parameters of `init` and `step` are the inputs of the system: a parameter named `x` or
+//! `random_x` takes the value of `x` in the trace.
+//!
+//! The file is compiled with a generated `main` that builds the initial state, with `State::init`
+//! for BMC counterexamples or directly from the first state of the trace for step
+//! counterexamples, then steps with the trace's inputs and prints the state after each step.
+//! Replaying fails at the first step where a field does not have the value the trace says.
+
+use crate::{
+    cex::Cex,
+    trace::{State, Value},
+};
+
+prelude!();
+
+/// First difference between a trace and the Rust implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Step of the trace.
+    pub step: usize,
+    pub var: String,
+    /// Value in the trace.
+    pub expected: Value,
+    /// Value in the Rust state, as printed by `Debug`.
+    pub got: String,
+}
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "at step {}, `{}` is {} in the trace but {} in the Rust implementation",
+            self.step, self.var, self.expected, self.got
+        )
+    }
+}
+
+/// Replays the counterexamples of a mikino output.
+pub struct Replay<'s> {
+    output: &'s str,
+    rust: &'s str,
+    candidate: Option<&'s str>,
+}
+impl<'s> Replay<'s> {
+    /// Constructor, `output` is a mikino output and `rust` the Rust implementation.
+    pub fn new(output: &'s str, rust: &'s str) -> Self {
+        Self {
+            output,
+            rust,
+            candidate: None,
+        }
+    }
+
+    /// Only replays the counterexamples for a candidate.
+    pub fn set_candidate(mut self, candidate: Option<&'s str>) -> Self {
+        self.candidate = candidate;
+        self
+    }
+
+    /// Mikino output.
+    pub fn output(&self) -> &str {
+        self.output
+    }
+    /// Rust implementation.
+    pub fn rust(&self) -> &str {
+        self.rust
+    }
+
+    /// Runs replay, fails if the implementation diverges from some counterexample.
+    pub fn run(&self) -> Res<()> {
+        let output = crate::cex::parse(&load_file(self.output)?)
+            .chain_err(|| format!("while parsing mikino output `{}`", self.output))?;
+        let rust = load_file(self.rust)?;
+        let cexs: Vec<&Cex> = output
+            .analyses
+            .iter()
+            .flat_map(|analysis| &analysis.cexs)
+            .filter(|cex| self.candidate.is_none_or(|c| c == cex.candidate))
+            .collect();
+        if cexs.is_empty() {
+            bail!("no counterexample to replay in `{}`", self.output)
+        }
+
+        let mut report = Report::new();
+        for cex in &cexs {
+            let divergence = replay(&rust, cex)
+                .chain_err(|| format!("while replaying the cex for `{}`", cex.candidate))?;
+            match divergence {
+                None => println!(
+                    "`{}`: {} state(s) replayed, no divergence",
+                    cex.candidate,
+                    cex.trace.states.len()
+                ),
+                Some(divergence) => {
+                    println!("`{}`: diverges {}", cex.candidate, divergence);
+                    report.push(
+                        Diagnostic::new(
+                            Severity::Error,
+                            "replay",
+                            format!("cex for `{}` diverges {}", cex.candidate, divergence),
+                        )
+                        .set_file(self.rust),
+                    )
+                }
+            }
+        }
+
+        if !report.diagnostics.is_empty() {
+            eprintln!("|===| Report:");
+            report.pretty_eprint("| ");
+            eprintln!("|===|");
+            bail!(
+                "{} of {} cex(s) diverge from `{}`",
+                report.diagnostics.len(),
+                cexs.len(),
+                self.rust
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Replays a counterexample against a Rust implementation, returns the first divergence if any.
+///
+/// Fails with [`err::ErrorKind::ToolUnavailable`] if `rustc` cannot be found.
+pub fn replay(rust: &str, cex: &Cex) -> Res<Option<Divergence>> {
+    let fields = fields(rust)?;
+    let harness = harness(rust, &fields, cex)?;
+    let output = run(&harness)?;
+
+    let mut states = vec![];
+    for line in output.lines() {
+        if line == "|=| Step" {
+            states.push(vec![]);
+        } else if let Some((var, value)) = line.split_once(" = ") {
+            match states.last_mut() {
+                Some(state) => state.push((var.to_string(), value.to_string())),
+                None => bail!("[internal] unexpected harness output `{}`", line),
+            }
+        }
+    }
+    if states.len() != cex.trace.states.len() {
+        bail!(
+            "[internal] harness printed {} state(s), expected {}",
+            states.len(),
+            cex.trace.states.len()
+        )
+    }
+
+    for (step, (expected, got)) in cex.trace.states.iter().zip(&states).enumerate() {
+        for (var, got) in got {
+            let expected = match expected.get(var) {
+                Some(expected) => *expected,
+                None => continue,
+            };
+            if Value::parse(got) != Some(expected) {
+                return Ok(Some(Divergence {
+                    step,
+                    var: var.clone(),
+                    expected,
+                    got: got.clone(),
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Text between `pref` and the matching closing delimiter `close`, `pref` included the opening one.
+fn delimited<'a>(text: &'a str, pref: &str, close: char) -> Res<&'a str> {
+    let start = match text.find(pref) {
+        Some(start) => start + pref.len(),
+        None => bail!(
+            "could not find `{}` in the Rust implementation",
+            pref.trim()
+        ),
+    };
+    match text[start..].find(close) {
+        Some(len) => Ok(&text[start..start + len]),
+        None => bail!("could not find the end of `{}`", pref.trim()),
+    }
+}
+
+/// Comma-separated `name: type` list, comments are ignored.
+fn typed_names(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split(',')
+        .filter_map(|binding| binding.split_once(':'))
+        .map(|(name, _)| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Fields of the `State` struct.
+fn fields(rust: &str) -> Res<Vec<String>> {
+    let fields = typed_names(delimited(rust, "struct State {", '}')?);
+    if fields.is_empty() {
+        bail!("`State` has no fields")
+    }
+    Ok(fields)
+}
+
+/// Rust literal for a value.
+fn literal(value: Value) -> Res<String> {
+    match value {
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Int(int) => Ok(format!("({})", int)),
+        Value::Real(..) => bail!("cannot replay traces with rational values"),
+    }
+}
+
+/// Arguments for some parameters, from a state of the trace.
+fn arguments(params: &[String], state: &State, fun: &str) -> Res<String> {
+    let mut args = vec![];
+    for param in params {
+        let value = state
+            .get(param)
+            .or_else(|| param.strip_prefix("random_").and_then(|var| state.get(var)));
+        match value {
+            Some(value) => args.push(literal(*value)?),
+            None => bail!(
+                "no value in the trace for parameter `{}` of `State::{}`",
+                param,
+                fun
+            ),
+        }
+    }
+    Ok(args.join(", "))
+}
+
+/// Rust implementation with a `main` replaying a counterexample.
+fn harness(rust: &str, fields: &[String], cex: &Cex) -> Res<String> {
+    let states = &cex.trace.states;
+    let first = match states.first() {
+        Some(first) => first,
+        None => bail!("empty trace"),
+    };
+    let init_params = typed_names(delimited(rust, "fn init(", ')')?);
+    let step_params: Vec<String> = typed_names(delimited(rust, "fn step(", ')')?);
+
+    let mut res = rust.replacen("fn main()", "#[allow(dead_code)]\nfn replaced_main()", 1);
+    res.push_str("\nfn main() {\n    let mut state = ");
+    if cex.depth.is_some() {
+        res.push_str(&format!(
+            "State::init({});\n",
+            arguments(&init_params, first, "init")?
+        ));
+    } else {
+        // Step cex, the first state is not necessarily initial.
+        res.push_str("State {\n");
+        for field in fields {
+            match first.get(field) {
+                Some(value) => res.push_str(&format!("        {}: {},\n", field, literal(*value)?)),
+                None => bail!("no value in the trace for field `{}`", field),
+            }
+        }
+        res.push_str("    };\n");
+    }
+    res.push_str("    dump(&state);\n");
+    for state in &states[1..] {
+        res.push_str(&format!(
+            "    state.step({});\n    dump(&state);\n",
+            arguments(&step_params, state, "step")?
+        ));
+    }
+    res.push_str("}\n\nfn dump(state: &State) {\n    println!(\"|=| Step\");\n");
+    for field in fields {
+        res.push_str(&format!(
+            "    println!(\"{} = {{:?}}\", state.{});\n",
+            field, field
+        ));
+    }
+    res.push_str("}\n");
+    Ok(res)
+}
+
+/// Compiles and runs a Rust program, returns its output.
+fn run(program: &str) -> Res<String> {
+    use std::process::Command;
+    let dir = std::env::temp_dir().join(format!("manage_replay_{}", std::process::id()));
+    fs::create_dir_all(&dir)
+        .chain_err(|| format!("while creating directory `{}`", dir.display()))?;
+    let (source, binary) = (dir.join("replay.rs"), dir.join("replay"));
+    {
+        let mut file = open_write(&source)?;
+        io::Write::write_all(&mut file, program.as_bytes())
+            .chain_err(|| format!("while writing `{}`", source.display()))?;
+    }
+
+    let res = (|| {
+        let output = match Command::new("rustc")
+            .arg("-A")
+            .arg("warnings")
+            .arg("-o")
+            .arg(&binary)
+            .arg(&source)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                bail!(err::ErrorKind::ToolUnavailable("rustc".into()))
+            }
+            Err(e) => return Err(e).chain_err(|| "while running rustc"),
+        };
+        if !output.status.success() {
+            bail!(
+                "failed to compile the replay harness:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+        }
+        let output = Command::new(&binary)
+            .output()
+            .chain_err(|| "while running the replay harness")?;
+        if !output.status.success() {
+            bail!(
+                "the replay harness failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    })();
+
+    fs::remove_dir_all(&dir)
+        .chain_err(|| format!("while deleting temporary directory `{}`", dir.display()))?;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = "../src/trans/code/sw_1.rs";
+
+    fn with_rustc(f: impl FnOnce() -> Res<()>) {
+        match f() {
+            Ok(()) => (),
+            Err(err::Error(err::ErrorKind::ToolUnavailable(tool), _)) => {
+                eprintln!("`{}` is not available, skipping", tool)
+            }
+            res => res.unwrap(),
+        }
+    }
+
+    #[test]
+    fn harness_generation() {
+        let rust = load_file(RUST).unwrap();
+        assert_eq!(
+            fields(&rust).unwrap(),
+            vec!["start_stop", "reset", "is_counting", "cnt"]
+        );
+        let output =
+            crate::cex::parse(&load_file("../src/mikino_induction/code/sw_2.mkn.out").unwrap())
+                .unwrap();
+        let bmc = output.analyses[1].cex_of("cnt ≤ 2").unwrap();
+        let harness = harness(&rust, &fields(&rust).unwrap(), bmc).unwrap();
+        assert!(harness.contains("let mut state = State::init(true, false, (0));"));
+        assert!(harness.contains("state.step(false, false);"));
+
+        let step = output.analyses[0].cex_of("cnt ≤ 2").unwrap();
+        let harness = super::harness(&rust, &fields(&rust).unwrap(), step).unwrap();
+        assert!(harness.contains("        cnt: (2),\n"));
+    }
+
+    #[test]
+    fn book_cexs() {
+        with_rustc(|| {
+            let rust = load_file(RUST)?;
+            for out in ["mikino_bmc/code/sw_1", "mikino_induction/code/sw_2"] {
+                let output = crate::cex::parse(&load_file(format!("../src/{}.mkn.out", out))?)?;
+                for analysis in &output.analyses {
+                    for cex in &analysis.cexs {
+                        assert_eq!(replay(&rust, cex)?, None, "{}: {}", out, cex.candidate);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn divergence() {
+        with_rustc(|| {
+            let rust = load_file(RUST)?;
+            let mut output =
+                crate::cex::parse(&load_file("../src/mikino_induction/code/sw_2.mkn.out")?)?;
+            let cex = &mut output.analyses[1].cexs[0];
+            cex.trace.states[2].insert("cnt".into(), Value::Int(7));
+            assert_eq!(
+                replay(&rust, cex)?,
+                Some(Divergence {
+                    step: 2,
+                    var: "cnt".into(),
+                    expected: Value::Int(7),
+                    got: "2".into(),
+                })
+            );
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,221 @@
+//! Diagnostics produced when testing the book.
+//!
+//! Building the book, running its doctests and checking its snippets all report to a [`Report`],
+//! which is displayed once everything has run. Warnings and errors `mdbook` logs while building are
+//! collected by the [`Capture`] logger.
+
+use std::sync::Mutex;
+
+prelude!();
+
+/// Severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+impl std::fmt::Display for Severity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Warning => "warning".fmt(fmt),
+            Self::Error => "error".fmt(fmt),
+        }
+    }
+}
+
+/// A diagnostic, optionally located in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Severity.
+    pub severity: Severity,
+    /// What produced the diagnostic: `mdbook`, `doctest`, `snippet`...
+    pub source: &'static str,
+    /// File the diagnostic is about.
+    pub file: Option<PathBuf>,
+    /// Line in `file`, starting at `1`.
+    pub line: Option<usize>,
+    /// Column in `line`, starting at `1`.
+    pub col: Option<usize>,
+    /// Message, can span several lines.
+    pub message: String,
+}
+impl Diagnostic {
+    /// Constructor.
+    pub fn new(severity: Severity, source: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            source,
+            file: None,
+            line: None,
+            col: None,
+            message: message.into(),
+        }
+    }
+    /// Error constructor, the message is the error's chain.
+    pub fn of_error(source: &'static str, e: &err::Error) -> Self {
+        let message: Vec<String> = e.iter().map(|e| e.to_string()).collect();
+        Self::new(Severity::Error, source, message.join("\n"))
+    }
+
+    /// Sets the file the diagnostic is about.
+    pub fn set_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+    /// Sets the line the diagnostic is about.
+    pub fn set_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+    /// Sets the column the diagnostic is about.
+    pub fn set_col(mut self, col: usize) -> Self {
+        self.col = Some(col);
+        self
+    }
+}
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}[{}]", self.severity, self.source)?;
+        if let Some(file) = self.file.as_ref() {
+            write!(fmt, " {}", file.display())?;
+            if let Some(line) = self.line {
+                write!(fmt, ":{}", line)?;
+                if let Some(col) = self.col {
+                    write!(fmt, ":{}", col)?;
+                }
+            }
+        }
+        let mut lines = self.message.lines();
+        if let Some(line) = lines.next() {
+            write!(fmt, ": {}", line)?;
+        }
+        for line in lines {
+            write!(fmt, "\n    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A list of diagnostics.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+impl Report {
+    /// Constructor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a diagnostic.
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic)
+    }
+
+    /// Number of diagnostics with some severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+    /// True if the report contains errors.
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    /// Pretty-prints the report on `stderr`.
+    pub fn pretty_eprint(&self, pref: &str) {
+        for diagnostic in &self.diagnostics {
+            for line in diagnostic.to_string().lines() {
+                eprintln!("{}{}", pref, line);
+            }
+        }
+    }
+}
+
+/// Diagnostics collected by [`Capture`], `None` when not capturing.
+static CAPTURED: Mutex<Option<Vec<Diagnostic>>> = Mutex::new(None);
+
+/// Runs `f`, returns its result and the warnings and errors `mdbook` logged meanwhile.
+///
+/// Only works if [`Capture`] is the logger.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    *CAPTURED.lock().unwrap_or_else(|e| e.into_inner()) = Some(vec![]);
+    let res = f();
+    let captured = CAPTURED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .unwrap_or_default();
+    (res, captured)
+}
+
+/// Logger wrapper that also records `mdbook`'s warnings and errors during a [`capture`].
+pub struct Capture<L> {
+    logger: L,
+}
+impl<L: log::Log + 'static> Capture<L> {
+    /// Constructor.
+    pub fn new(logger: L) -> Self {
+        Self { logger }
+    }
+
+    /// Sets `self` as the logger.
+    pub fn init(self, level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+impl<L: log::Log> log::Log for Capture<L> {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.logger.enabled(metadata)
+            || (metadata.level() <= log::Level::Warn && metadata.target().starts_with("mdbook"))
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() <= log::Level::Warn && record.target().starts_with("mdbook") {
+            if let Some(captured) = CAPTURED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                let severity = if record.level() == log::Level::Error {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                };
+                captured.push(Diagnostic::new(
+                    severity,
+                    "mdbook",
+                    record.args().to_string(),
+                ))
+            }
+        }
+        self.logger.log(record)
+    }
+
+    fn flush(&self) {
+        self.logger.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let mut report = Report::new();
+        report.push(
+            Diagnostic::new(Severity::Error, "doctest", "failed\ndetails")
+                .set_file("src/strength/readme.md")
+                .set_line(47),
+        );
+        report.push(Diagnostic::new(Severity::Warning, "mdbook", "warned"));
+        assert!(report.has_errors());
+        assert_eq!(report.count(Severity::Warning), 1);
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            "error[doctest] src/strength/readme.md:47: failed\n    details"
+        );
+        assert_eq!(report.diagnostics[1].to_string(), "warning[mdbook]: warned");
+    }
+}
@@ -0,0 +1,458 @@
+//! Random simulation of `.mkn` systems, without a solver.
+//!
+//! Each state is built by evaluating the system's predicates concretely. Predicates of the form
+//! `x = <expr>` (`'x = <expr>` in `trans`), possibly under an implication or a conditional, define
+//! `x` as soon as `<expr>` can be evaluated. Variables that no such predicate defines are chosen
+//! from a script if any, and randomly otherwise. When the relation is functional, *i.e.* all
+//! non-input variables are defined this way, simulation never guesses. Otherwise random choices
+//! that falsify some predicate are drawn again, a bounded number of times.
+//!
+//! Scripts have one line per state, each line being a comma-separated list of `<var> = <value>`
+//! choices. Empty lines and `//` comments are ignored. Variables a line does not mention are chosen
+//! randomly.
+
+use crate::{
+    engine::Sort,
+    mkn::{Expr, Mkn, Op},
+    trace::{State, Trace, Value},
+};
+
+prelude!();
+
+/// Default number of transitions.
+pub const DEFAULT_DEPTH: usize = 5;
+/// Default bound on random integers, which are in `[-bound, bound]`.
+pub const DEFAULT_BOUND: i64 = 10;
+/// Number of random choices attempted for a state before giving up.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// Simulates a `.mkn` system and prints the trace.
+pub struct Simulate<'s> {
+    source: &'s str,
+    depth: usize,
+    seed: u64,
+    bound: i64,
+    script: Option<&'s str>,
+}
+impl<'s> Simulate<'s> {
+    /// Constructor.
+    pub fn new(source: &'s str) -> Self {
+        Self {
+            source,
+            depth: DEFAULT_DEPTH,
+            seed: 0,
+            bound: DEFAULT_BOUND,
+            script: None,
+        }
+    }
+
+    /// Sets the number of transitions, extended to the length of the script if shorter.
+    pub fn set_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+    /// Sets the seed of random choices.
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    /// Sets the bound on random integers.
+    pub fn set_bound(mut self, bound: i64) -> Self {
+        self.bound = bound;
+        self
+    }
+    /// Sets the script file.
+    pub fn set_script(mut self, script: Option<&'s str>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// System simulated.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// Runs the simulation.
+    pub fn run(&self) -> Res<()> {
+        let mkn = Mkn::load(self.source)?;
+        let script = match self.script {
+            Some(path) => {
+                parse_script(&mkn, &load_file(path)?).chain_err(|| format!("in `{}`", path))?
+            }
+            None => vec![],
+        };
+        let trace = Simulator::new(&mkn, self.seed)
+            .set_bound(self.bound)
+            .run(self.depth, &script)
+            .chain_err(|| format!("while simulating `{}`", self.source))?;
+        println!("{}", trace);
+        Ok(())
+    }
+}
+
+/// Parses a script, the choices for each state.
+pub fn parse_script(mkn: &Mkn, text: &str) -> Res<Vec<State>> {
+    let mut states = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut state = State::new();
+        for choice in line.split(',') {
+            let (var, value) = match choice.split_once('=') {
+                Some((var, value)) => (var.trim(), value.trim()),
+                None => bail!(
+                    "{}: expected `<var> = <value>`, found `{}`",
+                    idx + 1,
+                    choice.trim()
+                ),
+            };
+            let sort = match mkn.vars.iter().find(|(name, _)| name == var) {
+                Some((_, sort)) => *sort,
+                None => bail!("{}: unknown state variable `{}`", idx + 1, var),
+            };
+            let value = match (sort, Value::parse(value)) {
+                (Sort::Bool, Some(value @ Value::Bool(_)))
+                | (Sort::Int, Some(value @ Value::Int(_)))
+                | (Sort::Real, Some(value @ Value::Real(..))) => value,
+                (Sort::Real, Some(Value::Int(int))) => Value::Real(int, 1),
+                _ => bail!(
+                    "{}: `{}` is not a legal value for `{}` of sort {}",
+                    idx + 1,
+                    value,
+                    var,
+                    sort
+                ),
+            };
+            if state.insert(var.to_string(), value).is_some() {
+                bail!("{}: `{}` is chosen twice", idx + 1, var)
+            }
+        }
+        states.push(state)
+    }
+    Ok(states)
+}
+
+/// Simulator for a system.
+pub struct Simulator<'a> {
+    mkn: &'a Mkn,
+    rng: Rng,
+    bound: i64,
+}
+impl<'a> Simulator<'a> {
+    /// Constructor.
+    pub fn new(mkn: &'a Mkn, seed: u64) -> Self {
+        Self {
+            mkn,
+            rng: Rng::new(seed),
+            bound: DEFAULT_BOUND,
+        }
+    }
+
+    /// Sets the bound on random integers.
+    pub fn set_bound(mut self, bound: i64) -> Self {
+        self.bound = bound.abs();
+        self
+    }
+
+    /// Simulates `depth` transitions, or as many as there are states in the script minus one.
+    pub fn run(&mut self, depth: usize, script: &[State]) -> Res<Trace> {
+        let no_choice = State::new();
+        let choices = |idx: usize| script.get(idx).unwrap_or(&no_choice);
+        let mut states = vec![self.init(choices(0)).chain_err(|| "in the initial state")?];
+        for idx in 1..=depth.max(script.len().saturating_sub(1)) {
+            let state = self
+                .step(&states[idx - 1], choices(idx))
+                .chain_err(|| format!("at step {}", idx))?;
+            states.push(state)
+        }
+        Ok(Trace::new(states))
+    }
+
+    /// Initial state.
+    pub fn init(&mut self, choices: &State) -> Res<State> {
+        self.state(None, choices)
+    }
+
+    /// Successor of a state.
+    pub fn step(&mut self, prev: &State, choices: &State) -> Res<State> {
+        self.state(Some(prev), choices)
+    }
+
+    /// Builds a state, initial if `prev` is `None`.
+    fn state(&mut self, prev: Option<&State>, choices: &State) -> Res<State> {
+        let preds = if prev.is_some() {
+            &self.mkn.trans
+        } else {
+            &self.mkn.init
+        };
+        // Variables the predicates can define come last, so that they are chosen randomly only
+        // when no predicate defines them.
+        let mut vars: Vec<&(String, Sort)> = self.mkn.vars.iter().collect();
+        vars.sort_by_key(|(var, _)| {
+            preds
+                .iter()
+                .any(|pred| may_define(pred, var, prev.is_some()))
+        });
+
+        let mut last_falsified = None;
+        for _ in 0..MAX_ATTEMPTS {
+            let mut state = choices.clone();
+            let mut guessed = false;
+            loop {
+                self.propagate(preds, prev, &mut state)?;
+                match vars.iter().find(|(var, _)| !state.contains_key(var)) {
+                    Some((var, sort)) => {
+                        let value = self.random(*sort);
+                        state.insert(var.clone(), value);
+                        guessed = true
+                    }
+                    None => break,
+                }
+            }
+
+            let mut falsified = None;
+            for pred in preds {
+                if eval(pred, prev, &state)? != Some(Value::Bool(true)) {
+                    falsified = Some(pred);
+                    break;
+                }
+            }
+            match falsified {
+                None => return Ok(state),
+                Some(pred) if !guessed => bail!("`{}` does not hold", pred),
+                Some(pred) => last_falsified = Some(pred),
+            }
+        }
+        bail!(
+            "no {} found after {} random attempts, the last one falsifies `{}`",
+            if prev.is_some() {
+                "successor state"
+            } else {
+                "initial state"
+            },
+            MAX_ATTEMPTS,
+            last_falsified.expect("at least one attempt failed")
+        )
+    }
+
+    /// Assigns the variables the predicates define until fixpoint.
+    fn propagate(&self, preds: &[Expr], prev: Option<&State>, state: &mut State) -> Res<()> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for pred in preds {
+                if let Some((var, value)) = definition(pred, prev, state)? {
+                    state.insert(var, value);
+                    changed = true
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Random value of some sort.
+    fn random(&mut self, sort: Sort) -> Value {
+        match sort {
+            Sort::Bool => Value::Bool(self.rng.next().is_multiple_of(2)),
+            Sort::Int => Value::Int(self.rng.range(-self.bound, self.bound)),
+            Sort::Real => Value::Real(self.rng.range(-self.bound, self.bound), 1),
+        }
+    }
+}
+
+/// Evaluates an expression, next-state variables are the ones in `state` when `prev` is some.
+fn eval(expr: &Expr, prev: Option<&State>, state: &State) -> Res<Option<Value>> {
+    expr.eval(&|var: &str, next| {
+        match (prev, next) {
+            (Some(prev), false) => prev.get(var),
+            _ => state.get(var),
+        }
+        .copied()
+    })
+}
+
+/// True if `var` is a variable of the state being built.
+fn is_target(expr: &Expr, var: &str, trans: bool) -> bool {
+    matches!(expr, Expr::Var { name, next } if name == var && *next == trans)
+}
+
+/// True if a predicate is a definition of `var`, possibly under a condition.
+fn may_define(pred: &Expr, var: &str, trans: bool) -> bool {
+    match pred {
+        Expr::App(Op::Eq, args) if args.len() == 2 => {
+            is_target(&args[0], var, trans) || is_target(&args[1], var, trans)
+        }
+        Expr::App(Op::Implies, args) if args.len() == 2 => may_define(&args[1], var, trans),
+        Expr::App(Op::And, args) => args.iter().any(|arg| may_define(arg, var, trans)),
+        Expr::Ite(_, thn, els) => may_define(thn, var, trans) || may_define(els, var, trans),
+        _ => false,
+    }
+}
+
+/// Value of a variable of the state being built that a predicate defines, if any and not
+/// already assigned.
+fn definition(pred: &Expr, prev: Option<&State>, state: &State) -> Res<Option<(String, Value)>> {
+    let active = |cnd: &Expr| -> Res<Option<bool>> {
+        match eval(cnd, prev, state)? {
+            Some(Value::Bool(b)) => Ok(Some(b)),
+            _ => Ok(None),
+        }
+    };
+    match pred {
+        Expr::App(Op::Eq, args) if args.len() == 2 => {
+            for (lhs, rhs) in [(&args[0], &args[1]), (&args[1], &args[0])] {
+                if let Expr::Var { name, next } = lhs {
+                    if *next == prev.is_some() && !state.contains_key(name) {
+                        if let Some(value) = eval(rhs, prev, state)? {
+                            return Ok(Some((name.clone(), value)));
+                        }
+                    }
+                }
+            }
+            Ok(None)
+        }
+        Expr::App(Op::Implies, args) if args.len() == 2 => match active(&args[0])? {
+            Some(true) => definition(&args[1], prev, state),
+            _ => Ok(None),
+        },
+        Expr::App(Op::And, args) => {
+            for arg in args {
+                if let Some(def) = definition(arg, prev, state)? {
+                    return Ok(Some(def));
+                }
+            }
+            Ok(None)
+        }
+        Expr::Ite(cnd, thn, els) => match active(cnd)? {
+            Some(true) => definition(thn, prev, state),
+            Some(false) => definition(els, prev, state),
+            None => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Seeded pseudo-random generator, splitmix64.
+struct Rng {
+    state: u64,
+}
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    /// Integer in `[lo, hi]`.
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        let width = (hi as i128 - lo as i128 + 1) as u128;
+        (lo as i128 + (self.next() as u128 % width) as i128) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stopwatch() -> Mkn {
+        Mkn::load("../systems/stopwatch.mkn").unwrap()
+    }
+
+    /// True if the trace satisfies the system.
+    fn valid(mkn: &Mkn, trace: &Trace) -> bool {
+        let holds = |preds: &[Expr], prev: Option<&State>, state: &State| {
+            preds
+                .iter()
+                .all(|pred| eval(pred, prev, state).unwrap() == Some(Value::Bool(true)))
+        };
+        holds(&mkn.init, None, &trace.states[0])
+            && trace
+                .states
+                .windows(2)
+                .all(|pair| holds(&mkn.trans, Some(&pair[0]), &pair[1]))
+    }
+
+    #[test]
+    fn scripted() {
+        let mkn = stopwatch();
+        let script = parse_script(
+            &mkn,
+            "\
+start_stop = true, reset = true, cnt = 0
+start_stop = false, reset = false // counting
+start_stop = false, reset = false
+
+start_stop = true, reset = false
+",
+        )
+        .unwrap();
+        let trace = Simulator::new(&mkn, 0).run(0, &script).unwrap();
+        let cnts: Vec<_> = trace.states.iter().map(|state| state["cnt"]).collect();
+        assert_eq!(
+            cnts,
+            vec![Value::Int(0), Value::Int(1), Value::Int(2), Value::Int(2)]
+        );
+        assert_eq!(trace.states[3]["is_counting"], Value::Bool(false));
+        assert!(trace.to_string().starts_with(
+            "\
+|=| Step 0
+|         cnt = 0
+| is_counting = true
+|       reset = true
+|  start_stop = true
+|=| Step 1
+"
+        ));
+
+        let err = Simulator::new(&mkn, 0)
+            .run(0, &parse_script(&mkn, "reset = true, cnt = 3").unwrap())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "in the initial state");
+        assert_eq!(
+            err.iter().nth(1).unwrap().to_string(),
+            "no initial state found after 1000 random attempts, \
+            the last one falsifies `reset ⇒ (cnt = 0)`"
+        );
+        let script =
+            "start_stop = false, reset = true, cnt = 0\nstart_stop = false, reset = false, cnt = 3";
+        let err = Simulator::new(&mkn, 0)
+            .run(0, &parse_script(&mkn, script).unwrap())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "at step 1");
+        assert_eq!(
+            err.iter().nth(1).unwrap().to_string(),
+            "`'cnt = (if 'reset { 0 } else if 'is_counting { cnt + 1 } else { cnt })` does not hold"
+        );
+        assert_eq!(
+            parse_script(&mkn, "cnt = true").unwrap_err().to_string(),
+            "1: `true` is not a legal value for `cnt` of sort Int"
+        );
+    }
+
+    #[test]
+    fn random() {
+        let mkn = stopwatch();
+        let trace = Simulator::new(&mkn, 7).run(20, &[]).unwrap();
+        assert_eq!(trace.states.len(), 21);
+        assert!(valid(&mkn, &trace));
+        assert_eq!(trace, Simulator::new(&mkn, 7).run(20, &[]).unwrap());
+        assert_ne!(trace, Simulator::new(&mkn, 8).run(20, &[]).unwrap());
+    }
+
+    #[test]
+    fn book_systems() {
+        for snippet in test::snippets("../src", "mkn").unwrap() {
+            let mkn = Mkn::load(&snippet).unwrap();
+            let trace = Simulator::new(&mkn, 0)
+                .run(DEFAULT_DEPTH, &[])
+                .unwrap_or_else(|e| panic!("{}: {}", snippet.display(), e));
+            assert!(valid(&mkn, &trace), "{}", snippet.display());
+        }
+    }
+}
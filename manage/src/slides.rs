@@ -0,0 +1,252 @@
+//! Slide deck export of the chapters, as [Marp] markdown.
+//!
+//! A chapter is split into slides at `<!-- slide -->` markers if it has any, and at `##` headings
+//! otherwise. A paragraph preceded by a `<!-- notes -->` marker becomes the speaker notes of its
+//! slide.
+//!
+//! [Marp]: https://marp.app
+
+use std::io::Write;
+
+prelude!();
+
+/// Marker splitting slides explicitly.
+pub const SLIDE_MARKER: &str = "<!-- slide -->";
+/// Marker for speaker notes, applies to the paragraph that follows.
+pub const NOTES_MARKER: &str = "<!-- notes -->";
+
+/// A slide.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Slide {
+    /// Markdown content.
+    pub content: Vec<String>,
+    /// Speaker notes.
+    pub notes: Vec<String>,
+}
+
+/// Slide deck generator.
+pub struct Slides<'s> {
+    /// Configuration for running the snippets of `{{ #run }}` directives.
+    conf: Conf<'s>,
+    target: &'s str,
+}
+impl<'s> Slides<'s> {
+    /// Constructor.
+    pub fn new(conf: Conf<'s>, target: &'s str) -> Self {
+        Self { conf, target }
+    }
+    /// Target accessor.
+    pub fn target(&self) -> &'s str {
+        self.target
+    }
+
+    /// Runs slide deck generation, one deck per top-level chapter.
+    pub fn run(&self) -> Res<()> {
+        fs::create_dir_all(self.target)
+            .chain_err(|| format!("during (recursive) folder creation for `{}`", self.target))?;
+        let top_level = Vanilla::top_level_md(Vanilla::SRC)?;
+        log::info!("generating slide decks for {} chapter(s)", top_level.len());
+
+        for file in top_level {
+            let src_path = PathBuf::from(Vanilla::SRC).join(file.path());
+            let tgt_path = PathBuf::from(self.target).join(format!("{}.md", file.slug()));
+            log::debug!(
+                "generating slides for `{}` from `{}`",
+                file.title(),
+                src_path.display(),
+            );
+
+            let md = Vanilla::inline_md(&self.conf, &src_path)?;
+            let slides = split(&md);
+            log::trace!("{} slide(s) for `{}`", slides.len(), src_path.display());
+
+            let mut tgt_file = open_write(&tgt_path)?;
+            write_deck(file.title(), &slides, &mut tgt_file)
+                .chain_err(|| format!("while writing slides to `{}`", tgt_path.display()))?;
+        }
+
+        log::info!("done with slide deck generation");
+        Ok(())
+    }
+}
+
+/// Splits the inlined markdown of a chapter into slides.
+pub fn split(md: &str) -> Vec<Slide> {
+    let has_markers = md.lines().any(|line| line.trim() == SLIDE_MARKER);
+
+    let mut slides = vec![];
+    let mut current = Slide::default();
+    let mut in_code = false;
+    let mut in_rust_code = false;
+    let mut in_notes = false;
+    let mut notes_pending = false;
+
+    for line in md.lines() {
+        let trimmed = line.trim();
+        if let Some(info) = trimmed.strip_prefix("```") {
+            in_code = !in_code;
+            in_rust_code = in_code && info.split([',', ' ']).next() == Some("rust");
+        }
+        if in_code || trimmed.starts_with("```") {
+            // Hidden lines are setup code, not for the audience.
+            if in_rust_code && crate::is_hidden_rust_line(line) {
+                continue;
+            }
+            current.content.push(line.into());
+            continue;
+        }
+
+        let is_break = if has_markers {
+            trimmed == SLIDE_MARKER
+        } else {
+            line.starts_with("## ")
+        };
+        if is_break {
+            if !current.content.iter().all(|line| line.trim().is_empty()) {
+                slides.push(std::mem::take(&mut current));
+            }
+            in_notes = false;
+            notes_pending = false;
+            if has_markers {
+                continue;
+            }
+        }
+
+        if trimmed == NOTES_MARKER {
+            notes_pending = true;
+            continue;
+        }
+        if notes_pending && !trimmed.is_empty() {
+            notes_pending = false;
+            in_notes = true;
+        }
+        if in_notes {
+            if trimmed.is_empty() {
+                in_notes = false;
+            } else {
+                current.notes.push(line.into());
+                continue;
+            }
+        }
+
+        // Strip the book's HTML, keep the summary text.
+        if trimmed == "<details>" || trimmed == "</details>" || trimmed == "\\" {
+            continue;
+        }
+        if let Some(summary) = trimmed
+            .strip_prefix("<summary>")
+            .and_then(|s| s.strip_suffix("</summary>"))
+        {
+            current.content.push(format!("*{}*", summary));
+            continue;
+        }
+
+        current.content.push(line.into());
+    }
+    if !current.content.iter().all(|line| line.trim().is_empty()) {
+        slides.push(current);
+    }
+
+    slides
+}
+
+/// Writes a Marp deck.
+pub fn write_deck(title: &str, slides: &[Slide], target: &mut impl Write) -> Res<()> {
+    writeln!(target, "---")?;
+    writeln!(target, "marp: true")?;
+    writeln!(target, "title: \"{}\"", title.replace('"', "\\\""))?;
+    writeln!(target, "paginate: true")?;
+    writeln!(target, "---")?;
+
+    for (idx, slide) in slides.iter().enumerate() {
+        if idx > 0 {
+            writeln!(target, "\n---")?;
+        }
+        let start = slide
+            .content
+            .iter()
+            .position(|line| !line.trim().is_empty())
+            .unwrap_or(0);
+        let end = slide
+            .content
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        writeln!(target)?;
+        for line in &slide.content[start..end] {
+            writeln!(target, "{}", line)?;
+        }
+        if !slide.notes.is_empty() {
+            writeln!(target, "\n<!--")?;
+            for line in &slide.notes {
+                writeln!(target, "{}", line)?;
+            }
+            writeln!(target, "-->")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_on_headings() {
+        let md = "# Title\n\nIntro.\n\n## First\n\n```text\n## not a heading\n```\n\n\
+                  <!-- notes -->\nSay this.\nAnd this.\n\nShown.\n\n## Second\n\n\
+                  ```rust\n# fn main() {\nlet x = 1;\n# }\n```\n\n<details>\n\t<summary>Expand.</summary>\n\nHidden.\n</details>\n";
+        let slides = split(md);
+        assert_eq!(slides.len(), 3);
+        assert_eq!(slides[0].content, vec!["# Title", "", "Intro.", ""]);
+        assert_eq!(
+            slides[1].content,
+            vec![
+                "## First",
+                "",
+                "```text",
+                "## not a heading",
+                "```",
+                "",
+                "",
+                "Shown.",
+                "",
+            ]
+        );
+        assert_eq!(slides[1].notes, vec!["Say this.", "And this."]);
+        assert_eq!(
+            slides[2].content,
+            vec![
+                "## Second",
+                "",
+                "```rust",
+                "let x = 1;",
+                "```",
+                "",
+                "*Expand.*",
+                "",
+                "Hidden."
+            ]
+        );
+    }
+
+    #[test]
+    fn split_on_markers() {
+        let md = "# Title\n\n## Not split\n\n<!-- slide -->\n\nSecond.\n<!-- slide -->\nThird.\n";
+        let slides = split(md);
+        assert_eq!(slides.len(), 3);
+        assert_eq!(slides[0].content, vec!["# Title", "", "## Not split", ""]);
+        assert_eq!(slides[1].content, vec!["", "Second."]);
+        assert_eq!(slides[2].content, vec!["Third."]);
+
+        let mut deck = vec![];
+        write_deck("Title", &slides, &mut deck).unwrap();
+        assert_eq!(
+            String::from_utf8(deck).unwrap(),
+            "---\nmarp: true\ntitle: \"Title\"\npaginate: true\n---\n\n\
+             # Title\n\n## Not split\n\n---\n\nSecond.\n\n---\n\nThird.\n"
+        );
+    }
+}
@@ -0,0 +1,448 @@
+//! SMT-LIB 2 lexer and parser.
+//!
+//! Parsing is error-tolerant: syntax errors are collected rather than returned at the first one,
+//! so that tools built on top of the parser can report them all.
+
+prelude!();
+
+/// Collects the `.smt2` files under `path`, recursively.
+///
+/// If `path` is a file, it is collected regardless of its extension.
+pub fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Res<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    if !path.is_dir() {
+        bail!("expected file or directory path, got `{}`", path.display())
+    }
+    for entry in path
+        .read_dir()
+        .chain_err(|| format!("while reading directory `{}`", path.display()))?
+    {
+        let entry_path = entry
+            .chain_err(|| format!("while reading directory `{}`", path.display()))?
+            .path();
+        if entry_path.is_dir() {
+            collect_files(&entry_path, files)?
+        } else if entry_path
+            .extension()
+            .map(|ext| ext == "smt2")
+            .unwrap_or(false)
+        {
+            files.push(entry_path)
+        }
+    }
+    Ok(())
+}
+
+/// A position in a text, lines and columns start at `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+impl Pos {
+    /// Constructor.
+    pub fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+}
+impl std::fmt::Display for Pos {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}:{}", self.line, self.col)
+    }
+}
+
+/// Kinds of tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `(`.
+    Open,
+    /// `)`.
+    Close,
+    /// Simple or quoted (`|...|`) symbol.
+    Symbol,
+    /// `:<symbol>`.
+    Keyword,
+    /// Natural number.
+    Numeral,
+    /// `<numeral>.<digits>`.
+    Decimal,
+    /// `#x<hex digits>`.
+    Hexadecimal,
+    /// `#b<binary digits>`.
+    Binary,
+    /// Double-quoted string literal.
+    String,
+    /// From `;` to the end of the line.
+    Comment,
+}
+
+/// A token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    /// Kind of the token.
+    pub kind: TokenKind,
+    /// Text of the token, quotes and bars included.
+    pub text: &'a str,
+    /// Position of the token's first character.
+    pub pos: Pos,
+    /// Byte offset of the token in the text.
+    pub offset: usize,
+}
+impl<'a> Token<'a> {
+    /// Name of a symbol token, without the bars of quoted symbols.
+    ///
+    /// `|x|` and `x` are the same symbol in SMT-LIB 2.
+    pub fn symbol(&self) -> Option<&'a str> {
+        if self.kind != TokenKind::Symbol {
+            return None;
+        }
+        Some(
+            self.text
+                .strip_prefix('|')
+                .and_then(|s| s.strip_suffix('|'))
+                .unwrap_or(self.text),
+        )
+    }
+}
+
+/// A syntax error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub pos: Pos,
+    pub msg: String,
+}
+impl SyntaxError {
+    /// Constructor.
+    pub fn new(pos: Pos, msg: impl Into<String>) -> Self {
+        Self {
+            pos,
+            msg: msg.into(),
+        }
+    }
+}
+
+/// True for the characters that can appear in a simple symbol.
+pub fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c)
+}
+
+/// Splits a text into tokens, comments included.
+pub fn tokenize(text: &str) -> (Vec<Token<'_>>, Vec<SyntaxError>) {
+    let mut tokens = vec![];
+    let mut errors = vec![];
+
+    let mut chars = text.char_indices().peekable();
+    let (mut line, mut col) = (1, 1);
+
+    // Advances the position over `c`.
+    fn advance(c: char, line: &mut usize, col: &mut usize) {
+        if c == '\n' {
+            *line += 1;
+            *col = 1;
+        } else {
+            *col += 1;
+        }
+    }
+
+    while let Some((start, c)) = chars.next() {
+        let pos = Pos::new(line, col);
+        advance(c, &mut line, &mut col);
+        if c.is_whitespace() {
+            continue;
+        }
+
+        // Consumes characters while `keep` holds, returns the end offset.
+        macro_rules! eat_while {
+            ($keep:expr) => {{
+                let mut end = start + c.len_utf8();
+                while let Some((idx, next)) = chars.peek().cloned() {
+                    if !$keep(next) {
+                        break;
+                    }
+                    advance(next, &mut line, &mut col);
+                    chars.next();
+                    end = idx + next.len_utf8();
+                }
+                end
+            }};
+        }
+
+        let (kind, end) = match c {
+            '(' => (TokenKind::Open, start + 1),
+            ')' => (TokenKind::Close, start + 1),
+            ';' => (TokenKind::Comment, eat_while!(|c| c != '\n')),
+            '"' => {
+                let mut end = None;
+                while let Some((idx, next)) = chars.next() {
+                    advance(next, &mut line, &mut col);
+                    if next == '"' {
+                        // `""` is an escaped quote.
+                        if chars.peek().map(|(_, c)| *c == '"').unwrap_or(false) {
+                            chars.next();
+                            advance('"', &mut line, &mut col);
+                        } else {
+                            end = Some(idx + 1);
+                            break;
+                        }
+                    }
+                }
+                match end {
+                    Some(end) => (TokenKind::String, end),
+                    None => {
+                        errors.push(SyntaxError::new(pos, "unterminated string literal"));
+                        break;
+                    }
+                }
+            }
+            '|' => {
+                let mut end = None;
+                for (idx, next) in chars.by_ref() {
+                    advance(next, &mut line, &mut col);
+                    if next == '|' {
+                        end = Some(idx + 1);
+                        break;
+                    }
+                }
+                match end {
+                    Some(end) => (TokenKind::Symbol, end),
+                    None => {
+                        errors.push(SyntaxError::new(pos, "unterminated quoted symbol"));
+                        break;
+                    }
+                }
+            }
+            ':' => (TokenKind::Keyword, eat_while!(is_symbol_char)),
+            '#' => {
+                let end = eat_while!(|c: char| c.is_ascii_alphanumeric());
+                let token = &text[start..end];
+                if token.len() > 2
+                    && token.starts_with("#x")
+                    && token[2..].chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    (TokenKind::Hexadecimal, end)
+                } else if token.len() > 2
+                    && token.starts_with("#b")
+                    && token[2..].chars().all(|c| c == '0' || c == '1')
+                {
+                    (TokenKind::Binary, end)
+                } else {
+                    errors.push(SyntaxError::new(
+                        pos,
+                        format!("illegal literal `{}`", token),
+                    ));
+                    continue;
+                }
+            }
+            _ if c.is_ascii_digit() => {
+                let end = eat_while!(|c: char| c.is_ascii_digit() || c == '.');
+                let token = &text[start..end];
+                let mut parts = token.split('.');
+                let (int, frac) = (parts.next(), parts.next());
+                let legal_int = |int: &str| int == "0" || !int.starts_with('0');
+                match (int, frac, parts.next()) {
+                    (Some(int), None, None) if legal_int(int) => (TokenKind::Numeral, end),
+                    (Some(int), Some(frac), None) if legal_int(int) && !frac.is_empty() => {
+                        (TokenKind::Decimal, end)
+                    }
+                    _ => {
+                        errors.push(SyntaxError::new(
+                            pos,
+                            format!("illegal numeral `{}`", token),
+                        ));
+                        continue;
+                    }
+                }
+            }
+            _ if is_symbol_char(c) => (TokenKind::Symbol, eat_while!(is_symbol_char)),
+            _ => {
+                errors.push(SyntaxError::new(
+                    pos,
+                    format!("unexpected character `{}`", c),
+                ));
+                continue;
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &text[start..end],
+            pos,
+            offset: start,
+        })
+    }
+
+    (tokens, errors)
+}
+
+/// An s-expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sexp<'a> {
+    /// Any token but parentheses and comments.
+    Atom(Token<'a>),
+    /// A parenthesized list.
+    List {
+        /// Position of the opening parenthesis.
+        open: Pos,
+        /// Elements of the list.
+        elems: Vec<Sexp<'a>>,
+    },
+}
+impl<'a> Sexp<'a> {
+    /// Position of the s-expression.
+    pub fn pos(&self) -> Pos {
+        match self {
+            Self::Atom(token) => token.pos,
+            Self::List { open, .. } => *open,
+        }
+    }
+    /// Symbol name, if the s-expression is a symbol.
+    pub fn symbol(&self) -> Option<&'a str> {
+        match self {
+            Self::Atom(token) => token.symbol(),
+            Self::List { .. } => None,
+        }
+    }
+    /// Elements, if the s-expression is a list.
+    pub fn list(&self) -> Option<&[Sexp<'a>]> {
+        match self {
+            Self::Atom(_) => None,
+            Self::List { elems, .. } => Some(elems),
+        }
+    }
+    /// Symbol at the head of a list, if any.
+    pub fn head(&self) -> Option<&'a str> {
+        self.list()
+            .and_then(|elems| elems.first())
+            .and_then(Self::symbol)
+    }
+}
+
+impl std::fmt::Display for Sexp<'_> {
+    /// Prints the s-expression on one line, without comments.
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Atom(token) => token.text.fmt(fmt),
+            Self::List { elems, .. } => {
+                "(".fmt(fmt)?;
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        " ".fmt(fmt)?
+                    }
+                    elem.fmt(fmt)?
+                }
+                ")".fmt(fmt)
+            }
+        }
+    }
+}
+
+/// Parses a text as a sequence of s-expressions, ignoring comments.
+///
+/// Unclosed lists are closed at the end of the text.
+pub fn parse(text: &str) -> (Vec<Sexp<'_>>, Vec<SyntaxError>) {
+    let (tokens, mut errors) = tokenize(text);
+    let mut res = vec![];
+    // Open lists, with the position of their opening parenthesis.
+    let mut stack: Vec<(Pos, Vec<Sexp>)> = vec![];
+
+    for token in tokens {
+        let sexp = match token.kind {
+            TokenKind::Comment => continue,
+            TokenKind::Open => {
+                stack.push((token.pos, vec![]));
+                continue;
+            }
+            TokenKind::Close => match stack.pop() {
+                Some((open, elems)) => Sexp::List { open, elems },
+                None => {
+                    errors.push(SyntaxError::new(
+                        token.pos,
+                        "unexpected closing parenthesis",
+                    ));
+                    continue;
+                }
+            },
+            _ => Sexp::Atom(token),
+        };
+        match stack.last_mut() {
+            Some((_, elems)) => elems.push(sexp),
+            None => res.push(sexp),
+        }
+    }
+
+    while let Some((open, elems)) = stack.pop() {
+        errors.push(SyntaxError::new(open, "unclosed parenthesis"));
+        let sexp = Sexp::List { open, elems };
+        match stack.last_mut() {
+            Some((_, parent)) => parent.push(sexp),
+            None => res.push(sexp),
+        }
+    }
+    errors.sort_by_key(|e| e.pos);
+
+    (res, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let text =
+            "; comment\n(declare-const |s.cnt| Int)\n(echo \"a \"\"b\"\"\") :named 7 0.5 #x1f";
+        let (tokens, errors) = tokenize(text);
+        assert!(errors.is_empty());
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        use TokenKind::*;
+        assert_eq!(
+            kinds,
+            vec![
+                Comment,
+                Open,
+                Symbol,
+                Symbol,
+                Symbol,
+                Close,
+                Open,
+                Symbol,
+                String,
+                Close,
+                Keyword,
+                Numeral,
+                Decimal,
+                Hexadecimal,
+            ]
+        );
+        assert_eq!(tokens[3].symbol(), Some("s.cnt"));
+        assert_eq!(tokens[3].pos, Pos::new(2, 16));
+        assert_eq!(tokens[8].text, "\"a \"\"b\"\"\"");
+
+        let (_, errors) = tokenize("(echo \"oops)\n");
+        assert_eq!(
+            errors,
+            vec![SyntaxError::new(
+                Pos::new(1, 7),
+                "unterminated string literal"
+            )]
+        );
+    }
+
+    #[test]
+    fn unbalanced() {
+        let (sexps, errors) = parse("(assert (and a b)))\n(check-sat");
+        assert_eq!(sexps.len(), 2);
+        assert_eq!(sexps[0].head(), Some("assert"));
+        assert_eq!(sexps[0].to_string(), "(assert (and a b))");
+        assert_eq!(
+            errors,
+            vec![
+                SyntaxError::new(Pos::new(1, 19), "unexpected closing parenthesis"),
+                SyntaxError::new(Pos::new(2, 1), "unclosed parenthesis"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Candidate strengthening with the Houdini algorithm.
+//!
+//! Generates lemmas over the state variables of a system from simple templates:
+//!
+//! - bounds `x ≥ c` and `x ≤ c` for numeric variables, where `c` is `0` or a constant of the
+//!   system, and `x ≤ y`, `x ≥ y` between numeric variables of the same sort;
+//! - equalities `x = c` and `x = y`;
+//! - boolean literals `a`, `¬a`, and implications `a ⇒ b`, `b ⇒ a`, `a ⇒ ¬b`, `¬a ⇒ b` between
+//!   boolean variables.
+//!
+//! Houdini then prunes the lemmas and the system's own candidates. It first drops those that do
+//! not hold in all initial states, then repeats the step check of induction, assuming all the
+//! remaining ones in state `k`, and drops those that do not hold in state `k + 1` in the model,
+//! until no model remains. The survivors are inductive together: each of them is an invariant.
+
+use crate::{
+    engine::{Solver, Sort},
+    mkn::{Expr, Mkn, Op},
+    trace::Value,
+};
+
+prelude!();
+
+/// Strengthens the candidates of a system.
+pub struct Strengthen<'s> {
+    source: &'s str,
+    consts: Vec<i64>,
+    z3_cmd: &'s str,
+}
+impl<'s> Strengthen<'s> {
+    /// Constructor.
+    pub fn new(source: &'s str) -> Self {
+        Self {
+            source,
+            consts: vec![],
+            z3_cmd: "z3",
+        }
+    }
+
+    /// Sets additional constants for the bound and equality templates.
+    pub fn set_consts(mut self, consts: Vec<i64>) -> Self {
+        self.consts = consts;
+        self
+    }
+    /// Sets the Z3 command.
+    pub fn set_z3_cmd(mut self, z3_cmd: &'s str) -> Self {
+        self.z3_cmd = z3_cmd;
+        self
+    }
+
+    /// System source.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// Prints the invariants Houdini finds in mikino's `candidates { ... }` syntax.
+    pub fn run(&self) -> Res<()> {
+        let mkn = Mkn::load(self.source)?;
+        let lemmas = lemmas(&mkn, &self.consts);
+        let mut pool = mkn.candidates.clone();
+        pool.extend(lemmas);
+        log::info!(
+            "pruning {} candidate(s), {} of them generated",
+            pool.len(),
+            pool.len() - mkn.candidates.len()
+        );
+
+        let survivors = houdini(self.z3_cmd, &mkn, &pool)
+            .chain_err(|| format!("while strengthening `{}`", self.source))?;
+        for (idx, (name, _)) in mkn.candidates.iter().enumerate() {
+            if !survivors.contains(&idx) {
+                log::warn!("candidate `{}` is not inductive, even strengthened", name)
+            }
+        }
+        log::info!("{} invariant(s) found", survivors.len());
+
+        println!("candidates {{");
+        for idx in survivors {
+            let (name, expr) = &pool[idx];
+            println!("\t{:?}: {},", name, expr)
+        }
+        println!("}}");
+        Ok(())
+    }
+}
+
+/// Constants of a system, `0` included, sorted.
+fn consts(mkn: &Mkn) -> Vec<i64> {
+    fn collect(expr: &Expr, res: &mut Vec<i64>) {
+        match expr {
+            Expr::Int(int) => res.push(*int),
+            Expr::Neg(arg) => match &**arg {
+                Expr::Int(int) => res.push(-int),
+                arg => collect(arg, res),
+            },
+            Expr::Not(arg) => collect(arg, res),
+            Expr::App(_, args) => args.iter().for_each(|arg| collect(arg, res)),
+            Expr::Ite(cnd, thn, els) => {
+                collect(cnd, res);
+                collect(thn, res);
+                collect(els, res)
+            }
+            Expr::Bool(_) | Expr::Var { .. } => (),
+        }
+    }
+    let mut res = vec![0];
+    let candidates = mkn.candidates.iter().map(|(_, expr)| expr);
+    for expr in mkn.init.iter().chain(&mkn.trans).chain(candidates) {
+        collect(expr, &mut res)
+    }
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/// Generates lemmas over the state variables of a system, named after their mikino notation.
+///
+/// `consts` are used in bounds and equalities in addition to the constants of the system. Lemmas
+/// identical to a candidate of the system are not generated.
+pub fn lemmas(mkn: &Mkn, consts: &[i64]) -> Vec<(String, Expr)> {
+    let var = |name: &str| Expr::Var {
+        name: name.into(),
+        next: false,
+    };
+    let not = |expr: Expr| Expr::Not(Box::new(expr));
+    let int = |int: i64| {
+        if int < 0 {
+            Expr::Neg(Box::new(Expr::Int(-int)))
+        } else {
+            Expr::Int(int)
+        }
+    };
+    let mut all_consts = self::consts(mkn);
+    all_consts.extend(consts);
+    all_consts.sort_unstable();
+    all_consts.dedup();
+
+    let bools: Vec<&str> = mkn
+        .vars
+        .iter()
+        .filter(|(_, sort)| *sort == Sort::Bool)
+        .map(|(var, _)| var.as_str())
+        .collect();
+    let nums: Vec<(&str, Sort)> = mkn
+        .vars
+        .iter()
+        .filter(|(_, sort)| *sort != Sort::Bool)
+        .map(|(var, sort)| (var.as_str(), *sort))
+        .collect();
+
+    let mut res = vec![];
+    for (x, _) in &nums {
+        for c in &all_consts {
+            for op in [Op::Ge, Op::Le, Op::Eq].iter() {
+                res.push(Expr::App(*op, vec![var(x), int(*c)]))
+            }
+        }
+    }
+    for (idx, (x, x_sort)) in nums.iter().enumerate() {
+        for (y, y_sort) in &nums[idx + 1..] {
+            if x_sort == y_sort {
+                for op in [Op::Eq, Op::Le, Op::Ge].iter() {
+                    res.push(Expr::App(*op, vec![var(x), var(y)]))
+                }
+            }
+        }
+    }
+    for a in &bools {
+        res.push(var(a));
+        res.push(not(var(a)))
+    }
+    for (idx, a) in bools.iter().enumerate() {
+        for b in &bools[idx + 1..] {
+            for (lft, rgt) in [
+                (var(a), var(b)),
+                (var(a), not(var(b))),
+                (not(var(a)), var(b)),
+                (var(b), var(a)),
+            ]
+            .iter()
+            {
+                res.push(Expr::App(Op::Implies, vec![lft.clone(), rgt.clone()]))
+            }
+        }
+    }
+
+    res.into_iter()
+        .filter(|lemma| mkn.candidates.iter().all(|(_, expr)| expr != lemma))
+        .map(|lemma| (lemma.to_string(), lemma))
+        .collect()
+}
+
+/// Prunes candidates until the remaining ones are inductive together, returns their indices.
+pub fn houdini(z3_cmd: &str, mkn: &Mkn, candidates: &[(String, Expr)]) -> Res<Vec<usize>> {
+    let without_candidates = Mkn {
+        candidates: vec![],
+        ..mkn.clone()
+    };
+    let system = candidates
+        .iter()
+        .fold(without_candidates.to_system(), |system, (name, expr)| {
+            system.add_candidate(name.clone(), expr.to_smt2(&mkn.vars))
+        });
+    let cand = |idx: usize, k: usize| system.apply(&format!("cand_{}", idx), &[k]);
+    let mut alive: Vec<usize> = (0..candidates.len()).collect();
+
+    // Drops the candidates that are false in state `k` until no model falsifies any of them.
+    let prune = |solver: &mut Solver, alive: &mut Vec<usize>, k: usize, acts: bool| -> Res<()> {
+        let mut goals = 0;
+        while !alive.is_empty() {
+            let goal = format!("goal_{}", goals);
+            goals += 1;
+            let conj: Vec<String> = alive.iter().map(|idx| cand(*idx, k)).collect();
+            solver.command(&format!("(declare-const {} Bool)", goal))?;
+            solver.command(&format!(
+                "(assert (=> {} (not (and {}))))",
+                goal,
+                conj.join(" ")
+            ))?;
+            let mut assumptions: Vec<String> = if acts {
+                alive.iter().map(|idx| format!("act_{}", idx)).collect()
+            } else {
+                vec![]
+            };
+            assumptions.push(goal.clone());
+            let assumptions: Vec<&str> = assumptions.iter().map(String::as_str).collect();
+            let sat = solver.check_sat_assuming(&assumptions)?;
+            if sat {
+                let values = solver.get_values(&conj)?;
+                let before = alive.len();
+                let mut values = values.into_iter();
+                alive.retain(|_| values.next() != Some(Value::Bool(false)));
+                if alive.len() == before {
+                    bail!("the model falsifies none of the candidates")
+                }
+            }
+            solver.command(&format!("(assert (not {}))", goal))?;
+            if !sat {
+                break;
+            }
+        }
+        Ok(())
+    };
+
+    log::debug!("houdini: base case");
+    let mut base = Solver::new(z3_cmd)?;
+    base.commands(system.definitions())?;
+    base.commands(system.declare_state(0))?;
+    base.command(&format!("(assert {})", system.apply("init", &[0])))?;
+    prune(&mut base, &mut alive, 0, false)?;
+    drop(base);
+
+    log::debug!("houdini: step case, {} candidate(s) left", alive.len());
+    let mut step = Solver::new(z3_cmd)?;
+    step.commands(system.definitions())?;
+    step.commands(system.declare_state(0))?;
+    step.commands(system.declare_state(1))?;
+    step.command(&format!("(assert {})", system.apply("trans", &[0, 1])))?;
+    for idx in &alive {
+        step.command(&format!("(declare-const act_{} Bool)", idx))?;
+        step.command(&format!("(assert (=> act_{} {}))", idx, cand(*idx, 0)))?;
+    }
+    prune(&mut step, &mut alive, 1, true)?;
+
+    Ok(alive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM: &str = "\
+svars {
+    a b: bool,
+    x y: int,
+}
+init {
+    a, ¬b, x = 0, y = 0,
+}
+trans {
+    'a = ¬a, 'b = a, 'x = x + 1, 'y = y + 1,
+}
+candidates {
+    \"x = y\": x = y,
+}
+";
+
+    #[test]
+    fn templates() {
+        let mkn = crate::mkn::parse(SYSTEM).unwrap();
+        assert_eq!(consts(&mkn), vec![0, 1]);
+        let lemmas: Vec<String> = lemmas(&mkn, &[-2])
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(lemmas.len(), 2 * 3 * 3 + 2 + 4 + 4);
+        assert!(lemmas.contains(&"x ≥ -2".to_string()));
+        assert!(lemmas.contains(&"x ≤ y".to_string()));
+        assert!(!lemmas.contains(&"x = y".to_string()));
+        assert!(lemmas.contains(&"¬a ⇒ b".to_string()));
+    }
+
+    #[test]
+    fn pruning() {
+        let mkn = crate::mkn::parse(SYSTEM).unwrap();
+        let mut pool = mkn.candidates.clone();
+        pool.extend(lemmas(&mkn, &[]));
+        let survivors = match houdini("z3", &mkn, &pool) {
+            Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                eprintln!("z3 is not available, skipping");
+                return;
+            }
+            res => res.unwrap(),
+        };
+        let survivors: Vec<&str> = survivors.iter().map(|idx| pool[*idx].0.as_str()).collect();
+        assert_eq!(
+            survivors,
+            vec![
+                "x = y",
+                "x ≥ 0",
+                "y ≥ 0",
+                "x ≤ y",
+                "x ≥ y",
+                "a ⇒ ¬b",
+                "¬a ⇒ b"
+            ]
+        );
+
+        // `cnt ≥ 0` is inductive on its own, nothing else about the inputs is.
+        let mkn = Mkn::load("../systems/stopwatch.mkn").unwrap();
+        let mut pool = mkn.candidates.clone();
+        pool.extend(lemmas(&mkn, &[]));
+        let survivors = houdini("z3", &mkn, &pool).unwrap();
+        assert_eq!(survivors, vec![0]);
+    }
+}
@@ -0,0 +1,415 @@
+//! Timing diagrams of traces, as SVG.
+//!
+//! Traces come from a mikino output (`.out`), in which case the diagram shows one of its
+//! counterexamples, or from a system (`.mkn`) simulated with [`crate::simulate`], or checked by the
+//! reference engine of [`crate::engine`] for the counterexample of a candidate. Each variable is
+//! a row: booleans are high/low lines, numbers are bands labeled with their value that change
+//! shape when the value changes.
+//!
+//! Diagrams use `currentColor` so that they follow the color scheme of the page embedding them.
+//! Chapters embed them with `{{ #timing <trace> }}` (see [`crate::preprocess`]), which renders the
+//! diagram each time the book is built so that it is never out of date.
+
+use std::fmt::Write;
+
+use crate::{
+    mkn::Mkn,
+    simulate::{parse_script, Simulator, DEFAULT_DEPTH},
+    trace::{Trace, Value},
+};
+
+prelude!();
+
+/// Width of a step.
+const STEP_W: usize = 64;
+/// Height of a row.
+const ROW_H: usize = 32;
+/// Height of a signal, inside its row.
+const SIG_H: usize = 20;
+/// Padding.
+const PAD: usize = 8;
+/// Approximate width of a character.
+const CHAR_W: usize = 8;
+/// Horizontal length of a band's slanted edges.
+const SLANT: usize = 4;
+/// Maximum depth of the reference engine's BMC, see [`engine_cex`].
+pub const BMC_DEPTH: usize = 10;
+
+/// Renders the timing diagram of a trace to a file or stdout.
+pub struct Timing<'s> {
+    source: &'s str,
+    candidate: Option<&'s str>,
+    script: Option<&'s str>,
+    output: Option<&'s str>,
+    z3_cmd: &'s str,
+}
+impl<'s> Timing<'s> {
+    /// Constructor.
+    pub fn new(source: &'s str) -> Self {
+        Self {
+            source,
+            candidate: None,
+            script: None,
+            output: None,
+            z3_cmd: "z3",
+        }
+    }
+
+    /// Sets the candidate whose counterexample to render.
+    ///
+    /// For systems, the counterexample comes from the reference engine, see [`engine_cex`].
+    pub fn set_candidate(mut self, candidate: Option<&'s str>) -> Self {
+        self.candidate = candidate;
+        self
+    }
+    /// Sets the simulation script, for systems.
+    pub fn set_script(mut self, script: Option<&'s str>) -> Self {
+        self.script = script;
+        self
+    }
+    /// Sets the output file, stdout if none.
+    pub fn set_output(mut self, output: Option<&'s str>) -> Self {
+        self.output = output;
+        self
+    }
+    /// Sets the Z3 command, for the counterexamples of systems.
+    pub fn set_z3_cmd(mut self, z3_cmd: &'s str) -> Self {
+        self.z3_cmd = z3_cmd;
+        self
+    }
+
+    /// Trace source.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// Renders the diagram.
+    pub fn run(&self) -> Res<()> {
+        let path = Path::new(self.source);
+        let (trace, relative) = match self.candidate {
+            Some(candidate) if path.extension().is_some_and(|ext| ext == "mkn") => {
+                if self.script.is_some() {
+                    bail!("cannot use a script with a candidate's counterexample")
+                }
+                engine_cex(path, candidate, self.z3_cmd)?
+            }
+            candidate => load(path, candidate, self.script.map(Path::new))?,
+        };
+        let svg = render(&trace, relative);
+        match self.output {
+            None => print!("{}", svg),
+            Some(path) => {
+                let mut file = open_write(path)?;
+                io::Write::write_all(&mut file, svg.as_bytes())
+                    .chain_err(|| format!("while writing `{}`", path))?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads a trace from a mikino output or a system.
+///
+/// For mikino outputs, the trace is the counterexample for `candidate`, or the first one. Systems
+/// are simulated with seed `0`, following `script` if any. The boolean is true for induction step
+/// counterexamples, whose first state is not initial.
+pub fn load(path: &Path, candidate: Option<&str>, script: Option<&Path>) -> Res<(Trace, bool)> {
+    let is_mkn = path.extension().is_some_and(|ext| ext == "mkn");
+    if is_mkn {
+        if candidate.is_some() {
+            bail!("cannot select a candidate in system `{}`", path.display())
+        }
+        let mkn = Mkn::load(path)?;
+        let script = match script {
+            Some(script) => parse_script(&mkn, &load_file(script)?)
+                .chain_err(|| format!("in `{}`", script.display()))?,
+            None => vec![],
+        };
+        let trace = Simulator::new(&mkn, 0)
+            .run(DEFAULT_DEPTH, &script)
+            .chain_err(|| format!("while simulating `{}`", path.display()))?;
+        Ok((trace, false))
+    } else {
+        if script.is_some() {
+            bail!("scripts only apply to systems, not `{}`", path.display())
+        }
+        let output = crate::cex::parse(&load_file(path)?)
+            .chain_err(|| format!("while parsing mikino output `{}`", path.display()))?;
+        let mut cexs = output.analyses.iter().flat_map(|analysis| &analysis.cexs);
+        let cex = match candidate {
+            None => cexs.next(),
+            Some(candidate) => cexs.find(|cex| cex.candidate == candidate),
+        };
+        match cex {
+            Some(cex) => Ok((cex.trace.clone(), cex.depth.is_none())),
+            None => bail!(
+                "no counterexample{} in `{}`",
+                candidate
+                    .map(|c| format!(" for `{}`", c))
+                    .unwrap_or_default(),
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Counterexample of the reference engine for a candidate of a system.
+///
+/// BMC looks for a falsification up to depth [`BMC_DEPTH`], the counterexample is the induction
+/// step one otherwise. As in [`load`], the boolean is true for step counterexamples.
+///
+/// Fails if the candidate is proved by induction, and with [`err::ErrorKind::ToolUnavailable`] if
+/// Z3 cannot be found.
+pub fn engine_cex(path: &Path, candidate: &str, z3_cmd: &str) -> Res<(Trace, bool)> {
+    let mkn = Mkn::load(path)?;
+    if !mkn.candidates.iter().any(|(name, _)| name == candidate) {
+        bail!("unknown candidate `{}` in `{}`", candidate, path.display())
+    }
+    let system = mkn.to_system();
+    let engine = crate::engine::Engine::new(z3_cmd, &system);
+    let find = |cexs: Vec<crate::engine::Cex>| {
+        cexs.into_iter()
+            .find(|cex| cex.candidate == candidate)
+            .map(|cex| cex.trace)
+    };
+    if let Some(trace) = find(engine.bmc(BMC_DEPTH)?.falsified) {
+        return Ok((trace, false));
+    }
+    match find(engine.induction()?.step) {
+        Some(trace) => Ok((trace, true)),
+        None => bail!(
+            "candidate `{}` has no counterexample in `{}`, it is inductive",
+            candidate,
+            path.display()
+        ),
+    }
+}
+
+/// Escapes XML special characters.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Renders the timing diagram of a trace.
+///
+/// If `relative`, steps are named `k`, `k + 1`... instead of `0`, `1`...
+pub fn render(trace: &Trace, relative: bool) -> String {
+    let vars: Vec<&String> = match trace.states.first() {
+        Some(state) => state.keys().collect(),
+        None => vec![],
+    };
+    let len = trace.states.len();
+    let label_w = vars.iter().map(|var| var.len()).max().unwrap_or(0) * CHAR_W + 2 * PAD;
+    let (width, height) = (label_w + len * STEP_W + PAD, (vars.len() + 1) * ROW_H + PAD);
+    let x = |step: usize| label_w + step * STEP_W;
+
+    let mut svg = String::new();
+    macro_rules! svg {
+        ($($fmt:tt)*) => {
+            writeln!(svg, $($fmt)*).expect("writing to string cannot fail")
+        };
+    }
+    svg!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="13">"#,
+        w = width,
+        h = height,
+    );
+
+    // Step boundaries and names.
+    svg!(r#"<g stroke="currentColor" stroke-opacity="0.3" stroke-dasharray="2,2">"#);
+    for step in 0..=len {
+        svg!(
+            r#"<line x1="{x}" y1="{}" x2="{x}" y2="{}"/>"#,
+            PAD,
+            height - PAD,
+            x = x(step)
+        );
+    }
+    svg!("</g>");
+    svg!(r#"<g fill="currentColor" text-anchor="middle">"#);
+    for step in 0..len {
+        let name = match (relative, step) {
+            (false, _) => step.to_string(),
+            (true, 0) => "k".into(),
+            (true, _) => format!("k + {}", step),
+        };
+        svg!(
+            r#"<text x="{}" y="{}">{}</text>"#,
+            x(step) + STEP_W / 2,
+            ROW_H - PAD,
+            name
+        );
+    }
+    svg!("</g>");
+
+    for (row, var) in vars.iter().enumerate() {
+        let top = (row + 1) * ROW_H + (ROW_H - SIG_H) / 2;
+        let (mid, bot) = (top + SIG_H / 2, top + SIG_H);
+        let values: Vec<Option<Value>> = trace
+            .states
+            .iter()
+            .map(|state| state.get(*var).copied())
+            .collect();
+        svg!(
+            r#"<text x="{}" y="{}" fill="currentColor" text-anchor="end">{}</text>"#,
+            label_w - PAD,
+            mid + 4,
+            escape(var)
+        );
+
+        if values.iter().flatten().all(|v| matches!(v, Value::Bool(_))) {
+            // High/low line.
+            let mut path = String::new();
+            let mut prev = None;
+            for (step, value) in values.iter().enumerate() {
+                let b = match value {
+                    Some(Value::Bool(b)) => *b,
+                    _ => {
+                        prev = None;
+                        continue;
+                    }
+                };
+                let y = if b { top } else { bot };
+                // Move to the start of the step, with a vertical edge if the value changed.
+                match prev {
+                    None => write!(path, "M{} {} ", x(step), y),
+                    Some(prev) if prev != b => write!(path, "L{} {} ", x(step), y),
+                    Some(_) => Ok(()),
+                }
+                .expect("writing to string cannot fail");
+                write!(path, "L{} {} ", x(step + 1), y).expect("writing to string cannot fail");
+                prev = Some(b)
+            }
+            svg!(
+                r#"<path d="{}" stroke="currentColor" stroke-width="2" fill="none"/>"#,
+                path.trim_end()
+            );
+        } else {
+            // Labeled bands, one per run of equal values.
+            let mut start = 0;
+            while start < len {
+                let value = values[start];
+                let mut end = start + 1;
+                while end < len && values[end] == value {
+                    end += 1
+                }
+                if let Some(value) = value {
+                    let (x0, x1) = (x(start), x(end));
+                    svg!(
+                        r#"<polygon points="{},{mid} {},{top} {},{top} {},{mid} {},{bot} {},{bot}" stroke="currentColor" fill="none"/>"#,
+                        x0,
+                        x0 + SLANT,
+                        x1 - SLANT,
+                        x1,
+                        x1 - SLANT,
+                        x0 + SLANT,
+                        mid = mid,
+                        top = top,
+                        bot = bot,
+                    );
+                    svg!(
+                        r#"<text x="{}" y="{}" fill="currentColor" text-anchor="middle">{}</text>"#,
+                        (x0 + x1) / 2,
+                        mid + 4,
+                        escape(&value.to_string())
+                    );
+                }
+                start = end
+            }
+        }
+    }
+    svg!("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waveforms() {
+        let state = |b: bool, i: i64| {
+            let mut state = crate::trace::State::new();
+            state.insert("b".into(), Value::Bool(b));
+            state.insert("cnt".into(), Value::Int(i));
+            state
+        };
+        let trace = Trace::new(vec![state(true, 0), state(true, 0), state(false, -1)]);
+        let svg = render(&trace, false);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="240" "#));
+        assert!(svg.contains(
+            r#"<path d="M40 38 L104 38 L168 38 L168 58 L232 58" stroke="currentColor" stroke-width="2" fill="none"/>"#
+        ));
+        assert_eq!(svg.matches("<polygon").count(), 2);
+        assert!(svg
+            .contains(r#"<text x="104" y="84" fill="currentColor" text-anchor="middle">0</text>"#));
+        assert!(svg.contains(">-1</text>"));
+        assert!(!svg.contains("\n\n"));
+
+        let svg = render(&trace, true);
+        assert!(svg.contains(">k</text>") && svg.contains(">k + 2</text>"));
+    }
+
+    #[test]
+    fn sources() {
+        let path = Path::new("../src/mikino_induction/code/sw_2.mkn.out");
+        let (trace, relative) = load(path, None, None).unwrap();
+        assert!(relative);
+        assert_eq!(trace.states.len(), 2);
+        let (trace, relative) = load(path, Some("cnt ≤ 4"), None).unwrap();
+        assert!(relative);
+        assert_eq!(trace.states[1]["cnt"], Value::Int(5));
+        assert_eq!(
+            load(path, Some("nope"), None).unwrap_err().to_string(),
+            "no counterexample for `nope` in `../src/mikino_induction/code/sw_2.mkn.out`"
+        );
+
+        let (trace, relative) = load("../systems/stopwatch.mkn".as_ref(), None, None).unwrap();
+        assert!(!relative);
+        assert_eq!(trace.states.len(), DEFAULT_DEPTH + 1);
+        let svg = render(&trace, relative);
+        assert_eq!(svg.matches("<path").count(), 3);
+    }
+
+    /// The timing diagrams of the paper are up to date, same as `make paper_timing`.
+    #[test]
+    fn paper() {
+        let runs = [
+            ("stopwatch_1", "candidate 1", "stopwatch_run_1"),
+            ("stopwatch_3", "falsifiable", "stopwatch_run_3"),
+        ];
+        for (system, candidate, run) in runs {
+            let path = format!("../paper/rsc/{}.mkn", system);
+            let (trace, relative) = match engine_cex(path.as_ref(), candidate, "z3") {
+                Err(err::Error(err::ErrorKind::ToolUnavailable(_), _)) => {
+                    eprintln!("z3 is not available, skipping");
+                    return;
+                }
+                res => res.unwrap(),
+            };
+            assert_eq!(
+                render(&trace, relative),
+                load_file(format!("../paper/rsc/{}.svg", run)).unwrap(),
+                "`{}` is out of date, run `make paper_timing`",
+                run
+            );
+        }
+        let err = |system: &str, candidate: &str| {
+            let path = format!("../paper/rsc/{}.mkn", system);
+            engine_cex(path.as_ref(), candidate, "z3")
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err("stopwatch_1", "nope"),
+            "unknown candidate `nope` in `../paper/rsc/stopwatch_1.mkn`"
+        );
+        assert_eq!(
+            err("stopwatch_1", "candidate 2"),
+            "candidate `candidate 2` has no counterexample in `../paper/rsc/stopwatch_1.mkn`, it \
+            is inductive"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Traces of transition systems, as produced by counterexamples.
+
+use std::collections::BTreeMap;
+
+use crate::smt2::{Sexp, TokenKind};
+
+/// A value of a state variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    /// Normalized rational, the denominator is positive and coprime with the numerator.
+    Real(i64, i64),
+}
+impl Value {
+    /// Normalized rational constructor, `None` if `den` is zero.
+    pub fn real(num: i64, den: i64) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        fn gcd(a: i64, b: i64) -> i64 {
+            if b == 0 {
+                a.abs()
+            } else {
+                gcd(b, a % b)
+            }
+        }
+        let gcd = gcd(num, den).max(1) * den.signum();
+        Some(Self::Real(num / gcd, den / gcd))
+    }
+
+    /// Parses a value as printed by `Display`: `true`, `-7`, `1/2`, `0.5`...
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        match text {
+            "true" => return Some(Self::Bool(true)),
+            "false" => return Some(Self::Bool(false)),
+            _ => (),
+        }
+        if let Ok(int) = text.parse() {
+            return Some(Self::Int(int));
+        }
+        if let Some((num, den)) = text.split_once('/') {
+            return Self::real(num.trim().parse().ok()?, den.trim().parse().ok()?);
+        }
+        Self::decimal(text)
+    }
+    /// Parses a decimal `<int>.<digits>`.
+    fn decimal(text: &str) -> Option<Self> {
+        let (int, frac) = text.split_once('.')?;
+        let den = 10i64.checked_pow(frac.len() as u32)?;
+        let num = format!("{}{}", int, frac).parse().ok()?;
+        Self::real(num, den)
+    }
+
+    /// Turns an SMT-LIB 2 value, as produced by `get-value`, into a value.
+    ///
+    /// Handles `true`, `false`, numerals, decimals, `(- <value>)` and `(/ <value> <value>)`.
+    pub fn of_sexp(sexp: &Sexp) -> Option<Self> {
+        match sexp {
+            Sexp::Atom(token) => match token.kind {
+                TokenKind::Symbol => match token.text {
+                    "true" => Some(Self::Bool(true)),
+                    "false" => Some(Self::Bool(false)),
+                    _ => None,
+                },
+                TokenKind::Numeral => token.text.parse().ok().map(Self::Int),
+                TokenKind::Decimal => Self::decimal(token.text),
+                _ => None,
+            },
+            Sexp::List { elems, .. } => {
+                let (head, args) = elems.split_first()?;
+                match (head.symbol()?, args) {
+                    ("-", [arg]) => match Self::of_sexp(arg)? {
+                        Self::Int(int) => int.checked_neg().map(Self::Int),
+                        Self::Real(num, den) => Some(Self::Real(num.checked_neg()?, den)),
+                        Self::Bool(_) => None,
+                    },
+                    ("/", [num, den]) => {
+                        let (num_num, num_den) = Self::of_sexp(num)?.rational()?;
+                        let (den_num, den_den) = Self::of_sexp(den)?.rational()?;
+                        Self::real(num_num.checked_mul(den_den)?, num_den.checked_mul(den_num)?)
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// SMT-LIB 2 version, the inverse of [`Self::of_sexp`].
+    pub fn to_smt2(self) -> String {
+        let (neg, abs) = match self {
+            Self::Bool(b) => return b.to_string(),
+            Self::Int(int) => (int < 0, int.unsigned_abs().to_string()),
+            Self::Real(num, 1) => (num < 0, format!("{}.0", num.unsigned_abs())),
+            Self::Real(num, den) => (num < 0, format!("(/ {}.0 {}.0)", num.unsigned_abs(), den)),
+        };
+        if neg {
+            format!("(- {})", abs)
+        } else {
+            abs
+        }
+    }
+
+    /// JSON version, rationals are strings `<num>/<den>` to avoid losing precision.
+    pub fn to_json(self) -> serde_json::Value {
+        match self {
+            Self::Bool(b) => b.into(),
+            Self::Int(int) => int.into(),
+            Self::Real(..) => self.to_string().into(),
+        }
+    }
+
+    /// Numerator and denominator of a numeric value.
+    pub fn rational(self) -> Option<(i64, i64)> {
+        match self {
+            Self::Bool(_) => None,
+            Self::Int(int) => Some((int, 1)),
+            Self::Real(num, den) => Some((num, den)),
+        }
+    }
+}
+impl std::fmt::Display for Value {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Bool(b) => b.fmt(fmt),
+            Self::Int(int) => int.fmt(fmt),
+            Self::Real(num, 1) => write!(fmt, "{}.0", num),
+            Self::Real(num, den) => write!(fmt, "{}/{}", num, den),
+        }
+    }
+}
+
+/// A state: the values of the state variables.
+pub type State = BTreeMap<String, Value>;
+
+/// A sequence of states.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub states: Vec<State>,
+}
+impl Trace {
+    /// Constructor.
+    pub fn new(states: Vec<State>) -> Self {
+        Self { states }
+    }
+
+    /// Number of transitions in the trace, `None` if it is empty.
+    pub fn depth(&self) -> Option<usize> {
+        self.states.len().checked_sub(1)
+    }
+
+    /// JSON version, an array of objects mapping variables to values.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.states
+            .iter()
+            .map(|state| {
+                state
+                    .iter()
+                    .map(|(var, value)| (var.clone(), value.to_json()))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            })
+            .collect::<Vec<serde_json::Value>>()
+            .into()
+    }
+}
+impl std::fmt::Display for Trace {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (idx, state) in self.states.iter().enumerate() {
+            writeln!(fmt, "|=| Step {}", idx)?;
+            let width = state.keys().map(|var| var.len()).max().unwrap_or(0);
+            for (var, value) in state {
+                writeln!(fmt, "| {:>width$} = {}", var, value, width = width)?;
+            }
+        }
+        write!(fmt, "|=|")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let (sexps, _) = crate::smt2::parse("true (- 7) 0.25 (/ 3.0 (- 6.0)) (/ 4 2)");
+        let values: Vec<Option<Value>> = sexps.iter().map(Value::of_sexp).collect();
+        assert_eq!(
+            values,
+            vec![
+                Some(Value::Bool(true)),
+                Some(Value::Int(-7)),
+                Some(Value::Real(1, 4)),
+                Some(Value::Real(-1, 2)),
+                Some(Value::Real(2, 1)),
+            ]
+        );
+        for value in values.into_iter().flatten() {
+            assert_eq!(Value::parse(&value.to_string()), Some(value));
+            let smt2 = value.to_smt2();
+            let (sexps, _) = crate::smt2::parse(&smt2);
+            assert_eq!(Value::of_sexp(&sexps[0]), Some(value))
+        }
+        assert_eq!(Value::parse("0.5"), Value::real(1, 2));
+
+        // Malformed or out of range.
+        let (sexps, _) = crate::smt2::parse(
+            "() (-) (/ 1) (/ 9223372036854775807 (/ 1 2)) (/ (/ 1 9223372036854775807) 2)",
+        );
+        assert_eq!(sexps.len(), 5);
+        for sexp in &sexps {
+            assert_eq!(Value::of_sexp(sexp), None)
+        }
+        assert_eq!(Value::parse("cnt"), None);
+    }
+}
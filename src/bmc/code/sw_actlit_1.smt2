@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn bmc
 ; ANCHOR: all
 ; Initial predicate.
 (define-fun init
@@ -87,7 +88,7 @@
 ; ANCHOR: actlit_0_check_sat
 ; Check-sat, assuming `actlit_0` is true.
 (echo "; info: falsification at depth 0?")
-(check-sat-assuming ( actlit_0 ))
+(check-sat-assuming (actlit_0))
 ; ANCHOR_END: actlit_0_check_sat
 ; ANCHOR: deactlit_0
 ; Solver answers `unsat`, we want to unroll the system some more.
@@ -115,7 +116,7 @@
     (not (>= cnt_1 0))
 ))
 (echo "; info: falsification at depth 1?")
-(check-sat-assuming ( actlit_1 ))
+(check-sat-assuming (actlit_1))
 (assert (not actlit_1))
 (echo "; info: making sure assertion at 1 is not active anymore, expecting `sat`")
 (check-sat)
@@ -132,8 +133,8 @@
     (not (>= cnt_2 0))
 ))
 (echo "; info: falsification at depth 2?")
-(check-sat-assuming ( actlit_2 ))
+(check-sat-assuming (actlit_2))
 (assert (not actlit_2))
 (echo "; info: making sure assertion at 2 is not active anymore, expecting `sat`")
 (check-sat)
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
@@ -37,4 +37,4 @@
 (check-sat)
 (get-model)
 ; ANCHOR_END: state_constraints
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
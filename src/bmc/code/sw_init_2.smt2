@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn
 ; ANCHOR: all
 ; ANCHOR: init_def
 (define-fun init
@@ -38,4 +39,4 @@
 
 (check-sat)
 ; ANCHOR_END: state_constraints
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
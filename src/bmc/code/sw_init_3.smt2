@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn
 ; ANCHOR: all
 ; Initial predicate.
 (define-fun init
@@ -95,4 +96,4 @@
 
 ; Is this possible?
 (check-sat)
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn
 ; ANCHOR: all
 ; ANCHOR: trans_def
 (define-fun trans
@@ -83,4 +84,4 @@
 
 (check-sat)
 ; ANCHOR_END: state_constraints
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn base step
 ; ANCHOR: all
 ; Initial predicate.
 (define-fun init
@@ -53,7 +54,6 @@
     (>= |s.cnt| 0)
 )
 
-
 (declare-const start_stop_0 Bool)
 (declare-const reset_0 Bool)
 (declare-const is_counting_0 Bool)
@@ -70,7 +70,8 @@
     (init start_stop_0 reset_0 is_counting_0 cnt_0)
 ))
 (check-sat-assuming (base_actlit))
-
+; The step check must not assume the initial predicate.
+(assert (not base_actlit))
 
 (declare-const start_stop_1 Bool)
 (declare-const reset_1 Bool)
@@ -109,4 +110,4 @@
 (check-sat)
 
 (echo "induction proof complete")
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn base
 ; ANCHOR: all
 ; Initial predicate.
 (define-fun init
@@ -34,4 +35,4 @@
 ; Is there a state that's initial but does not verify `candidate`?
 (check-sat)
 ; ANCHOR_END: main
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
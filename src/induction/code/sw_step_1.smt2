@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn step
 ; ANCHOR: all
 ; Initial predicate.
 (define-fun init
@@ -80,4 +81,4 @@
 ; reach a state falsifying it in one transition?
 (check-sat)
 ; ANCHOR_END: main
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
@@ -2,8 +2,8 @@
 (declare-const y Int)
 
 (assert (and
-	(> x 7)
-	(or (= y (* 2 x)) (= x 11))
+    (> x 7)
+    (or (= y (* 2 x)) (= x 11))
 ))
 
-(check-sat)
\ No newline at end of file
+(check-sat)
@@ -2,9 +2,9 @@
 (declare-const y Int)
 
 (assert (and
-	(> x 7)
-	(or (= y (* 2 x)) (= x 11))
+    (> x 7)
+    (or (= y (* 2 x)) (= x 11))
 ))
 
 (check-sat)
-(get-model)
\ No newline at end of file
+(get-model)
@@ -5,4 +5,4 @@
 (assert (or (= y (* 2 x)) (= x 11)))
 
 (check-sat)
-(get-model)
\ No newline at end of file
+(get-model)
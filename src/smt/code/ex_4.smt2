@@ -6,4 +6,4 @@
 (assert (= (mod y 2) 1))
 
 (check-sat)
-(get-model)
\ No newline at end of file
+(get-model)
@@ -8,4 +8,4 @@
 (assert (not (= (mod x 2) 1)))
 
 (check-sat)
-(get-model)
\ No newline at end of file
+(get-model)
@@ -1,3 +1,4 @@
+; SYSTEM: systems/stopwatch.mkn
 ; ANCHOR: all
 ; ANCHOR: trans_def
 (define-fun trans
@@ -67,4 +68,4 @@
 (check-sat)
 (get-model)
 ; ANCHOR_END: state_constraints
-; ANCHOR_END: all
\ No newline at end of file
+; ANCHOR_END: all
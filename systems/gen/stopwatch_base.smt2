@@ -0,0 +1,82 @@
+; Generated from `systems/stopwatch.mkn` by `manage gen`, do not edit.
+; ANCHOR: all
+; ANCHOR: init_def
+; Initial predicate.
+(define-fun init
+    (
+        (|s.start_stop| Bool)
+        (|s.reset| Bool)
+        (|s.is_counting| Bool)
+        (|s.cnt| Int)
+    )
+    Bool
+
+    (and
+        (= |s.is_counting| |s.start_stop|)
+        (>= |s.cnt| 0)
+        (=> |s.reset| (= |s.cnt| 0))
+    )
+)
+; ANCHOR_END: init_def
+
+; ANCHOR: trans_def
+; Transition relation.
+(define-fun trans
+    (
+        ; "Previous" state.
+        (|s.start_stop| Bool)
+        (|s.reset| Bool)
+        (|s.is_counting| Bool)
+        (|s.cnt| Int)
+        ; "Next" state.
+        (|s'.start_stop| Bool)
+        (|s'.reset| Bool)
+        (|s'.is_counting| Bool)
+        (|s'.cnt| Int)
+    )
+    Bool
+
+    (and
+        (= |s'.is_counting|
+            (ite |s'.start_stop| (not |s.is_counting|) |s.is_counting|)
+        )
+        (= |s'.cnt|
+            (ite |s'.reset| 0 (ite |s'.is_counting| (+ |s.cnt| 1) |s.cnt|))
+        )
+    )
+)
+; ANCHOR_END: trans_def
+
+; ANCHOR: cand_defs
+; Candidate `cnt is positive`.
+(define-fun cand_0
+    (
+        (|s.start_stop| Bool)
+        (|s.reset| Bool)
+        (|s.is_counting| Bool)
+        (|s.cnt| Int)
+    )
+    Bool
+    (>= |s.cnt| 0)
+)
+; ANCHOR_END: cand_defs
+
+; ANCHOR: states_def
+; State 0.
+(declare-const start_stop_0 Bool)
+(declare-const reset_0 Bool)
+(declare-const is_counting_0 Bool)
+(declare-const cnt_0 Int)
+; ANCHOR_END: states_def
+
+; ANCHOR: assert_init
+; State 0 is initial.
+(assert (init start_stop_0 reset_0 is_counting_0 cnt_0))
+; ANCHOR_END: assert_init
+
+; ANCHOR: check
+; Can an initial state falsify the candidates?
+(assert (not (cand_0 start_stop_0 reset_0 is_counting_0 cnt_0)))
+(check-sat)
+; ANCHOR_END: check
+; ANCHOR_END: all
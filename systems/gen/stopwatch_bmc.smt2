@@ -0,0 +1,149 @@
+; Generated from `systems/stopwatch.mkn` by `manage gen`, do not edit.
+; ANCHOR: all
+; ANCHOR: init_def
+; Initial predicate.
+(define-fun init
+    (
+        (|s.start_stop| Bool)
+        (|s.reset| Bool)
+        (|s.is_counting| Bool)
+        (|s.cnt| Int)
+    )
+    Bool
+
+    (and
+        (= |s.is_counting| |s.start_stop|)
+        (>= |s.cnt| 0)
+        (=> |s.reset| (= |s.cnt| 0))
+    )
+)
+; ANCHOR_END: init_def
+
+; ANCHOR: trans_def
+; Transition relation.
+(define-fun trans
+    (
+        ; "Previous" state.
+        (|s.start_stop| Bool)
+        (|s.reset| Bool)
+        (|s.is_counting| Bool)
+        (|s.cnt| Int)
+        ; "Next" state.
+        (|s'.start_stop| Bool)
+        (|s'.reset| Bool)
+        (|s'.is_counting| Bool)
+        (|s'.cnt| Int)
+    )
+    Bool
+
+    (and
+        (= |s'.is_counting|
+            (ite |s'.start_stop| (not |s.is_counting|) |s.is_counting|)
+        )
+        (= |s'.cnt|
+            (ite |s'.reset| 0 (ite |s'.is_counting| (+ |s.cnt| 1) |s.cnt|))
+        )
+    )
+)
+; ANCHOR_END: trans_def
+
+; ANCHOR: cand_defs
+; Candidate `cnt is positive`.
+(define-fun cand_0
+    (
+        (|s.start_stop| Bool)
+        (|s.reset| Bool)
+        (|s.is_counting| Bool)
+        (|s.cnt| Int)
+    )
+    Bool
+    (>= |s.cnt| 0)
+)
+; ANCHOR_END: cand_defs
+
+; ANCHOR: states_def
+; State 0.
+(declare-const start_stop_0 Bool)
+(declare-const reset_0 Bool)
+(declare-const is_counting_0 Bool)
+(declare-const cnt_0 Int)
+; State 1.
+(declare-const start_stop_1 Bool)
+(declare-const reset_1 Bool)
+(declare-const is_counting_1 Bool)
+(declare-const cnt_1 Int)
+; State 2.
+(declare-const start_stop_2 Bool)
+(declare-const reset_2 Bool)
+(declare-const is_counting_2 Bool)
+(declare-const cnt_2 Int)
+; State 3.
+(declare-const start_stop_3 Bool)
+(declare-const reset_3 Bool)
+(declare-const is_counting_3 Bool)
+(declare-const cnt_3 Int)
+; ANCHOR_END: states_def
+
+; ANCHOR: assert_init
+; State 0 is initial.
+(assert (init start_stop_0 reset_0 is_counting_0 cnt_0))
+; ANCHOR_END: assert_init
+
+; ANCHOR: depth_0
+; Falsification at depth 0.
+(declare-const actlit_0 Bool)
+(assert (=> actlit_0 (not (cand_0 start_stop_0 reset_0 is_counting_0 cnt_0))))
+(echo "; info: falsification at depth 0?")
+(check-sat-assuming (actlit_0))
+(assert (not actlit_0))
+; ANCHOR_END: depth_0
+
+; ANCHOR: depth_1
+; Unrolling from 0 to 1.
+(assert
+    (trans
+        start_stop_0 reset_0 is_counting_0 cnt_0 start_stop_1 reset_1
+        is_counting_1 cnt_1
+    )
+)
+; Falsification at depth 1.
+(declare-const actlit_1 Bool)
+(assert (=> actlit_1 (not (cand_0 start_stop_1 reset_1 is_counting_1 cnt_1))))
+(echo "; info: falsification at depth 1?")
+(check-sat-assuming (actlit_1))
+(assert (not actlit_1))
+; ANCHOR_END: depth_1
+
+; ANCHOR: depth_2
+; Unrolling from 1 to 2.
+(assert
+    (trans
+        start_stop_1 reset_1 is_counting_1 cnt_1 start_stop_2 reset_2
+        is_counting_2 cnt_2
+    )
+)
+; Falsification at depth 2.
+(declare-const actlit_2 Bool)
+(assert (=> actlit_2 (not (cand_0 start_stop_2 reset_2 is_counting_2 cnt_2))))
+(echo "; info: falsification at depth 2?")
+(check-sat-assuming (actlit_2))
+(assert (not actlit_2))
+; ANCHOR_END: depth_2
+
+; ANCHOR: depth_3
+; Unrolling from 2 to 3.
+(assert
+    (trans
+        start_stop_2 reset_2 is_counting_2 cnt_2 start_stop_3 reset_3
+        is_counting_3 cnt_3
+    )
+)
+; Falsification at depth 3.
+(declare-const actlit_3 Bool)
+(assert (=> actlit_3 (not (cand_0 start_stop_3 reset_3 is_counting_3 cnt_3))))
+(echo "; info: falsification at depth 3?")
+(check-sat-assuming (actlit_3))
+(assert (not actlit_3))
+; ANCHOR_END: depth_3
+
+; ANCHOR_END: all